@@ -1,2 +1,5 @@
 mod allsorts;
 pub use self::allsorts::*;
+
+mod outline;
+pub use self::outline::*;
@@ -3,7 +3,9 @@ use allsorts::{
     font::MatchingPresentation,
     font_data::{DynamicFontTableProvider, FontData},
     glyph_position,
+    post::PostTable,
     subset::subset,
+    tables::{FontTableProvider, os2::Os2},
     tag,
 };
 use layout::{Error, Features, GlyphPosition, TextPosition, unit::Em};
@@ -18,6 +20,9 @@ use std::{
     collections::hash_map::Entry,
     sync::{Arc, Mutex, RwLock},
 };
+use ttf_parser::Face;
+
+use super::{OutlineSegment, glyph_outline};
 
 const NON_TTC_TABLE: usize = 0;
 
@@ -139,6 +144,15 @@ impl Default for FontCache {
     }
 }
 
+// positions are relative to the baseline, positive upwards
+#[derive(Clone, Copy, Debug)]
+pub struct DecorationMetrics {
+    pub underline_position: Em,
+    pub underline_thickness: Em,
+    pub strikeout_position: Em,
+    pub strikeout_thickness: Em,
+}
+
 unsafe impl Send for Font {}
 unsafe impl Sync for Font {}
 
@@ -174,9 +188,11 @@ impl Font {
         &self,
         text: impl AsRef<str>,
         features: &Features,
+        kerning: bool,
     ) -> Result<TextPosition, Error> {
         self.with_mut(|cached_font| {
-            cached_font.with_font_mut(|font| Self::typeset_inner(font, text.as_ref(), features))
+            cached_font
+                .with_font_mut(|font| Self::typeset_inner(font, text.as_ref(), features, kerning))
         })
     }
 
@@ -184,13 +200,15 @@ impl Font {
         font: &mut allsorts::Font<DynamicFontTableProvider<'_>>,
         text: &str,
         features: &Features,
+        kerning: bool,
     ) -> Result<TextPosition, Error> {
         let features = features.into();
 
         let glyphs = font.map_glyphs(text, tag::LATN, MatchingPresentation::NotRequired);
 
+        // kerning flag covers both GPOS kern feature and legacy kern table
         let shapes = font
-            .shape(glyphs, tag::LATN, None, &features, None, true)
+            .shape(glyphs, tag::LATN, None, &features, None, kerning)
             .unwrap_or_else(|(_, shapes)| shapes);
 
         let positions = glyph_position::GlyphLayout::new(
@@ -240,14 +258,66 @@ impl Font {
         glyph_collector: &mut IndexSet<u16>,
         text: impl AsRef<str>,
         features: &Features,
+        kerning: bool,
     ) -> Result<TextPosition, Error> {
-        let mut positions = self.typeset(text, features)?;
+        let mut positions = self.typeset(text, features, kerning)?;
         for glyph in positions.positions.iter_mut() {
             glyph.set_glyph_index(glyph_collector.insert_full(glyph.glyph_index).0 as u16);
         }
         Ok(positions)
     }
 
+    pub fn reserve_collect(&self, glyph_collector: &mut IndexSet<u16>, text: impl AsRef<str>) {
+        self.with_mut(|cached_font| {
+            cached_font.with_font_mut(|font| {
+                let glyphs =
+                    font.map_glyphs(text.as_ref(), tag::LATN, MatchingPresentation::NotRequired);
+                for glyph in glyphs {
+                    glyph_collector.insert(glyph.glyph_index);
+                }
+            })
+        })
+    }
+
+    pub fn decoration_metrics(&self) -> Result<DecorationMetrics, Error> {
+        self.with(|cached_font| cached_font.with_font(|font| Self::decoration_metrics_inner(font)))
+    }
+
+    fn decoration_metrics_inner(
+        font: &allsorts::Font<DynamicFontTableProvider<'_>>,
+    ) -> Result<DecorationMetrics, Error> {
+        let units_per_em = font
+            .head_table()?
+            .map(|head| head.units_per_em as f64)
+            .unwrap_or(1000.0);
+
+        // fallbacks for fonts without post or OS/2 tables
+        let (mut underline_position, mut underline_thickness) = (-0.1, 0.05);
+        if let Some(data) = font.font_table_provider.table_data(tag::POST)? {
+            let post = ReadScope::new(&data).read::<PostTable<'_>>()?;
+            underline_position = post.header.underline_position as f64 / units_per_em;
+            underline_thickness = post.header.underline_thickness as f64 / units_per_em;
+        }
+
+        let (mut strikeout_position, mut strikeout_thickness) = (0.25, underline_thickness);
+        if let Some(data) = font.font_table_provider.table_data(tag::OS_2)? {
+            let os2 = ReadScope::new(&data).read_dep::<Os2>(data.len())?;
+            strikeout_position = os2.y_strikeout_position as f64 / units_per_em;
+            strikeout_thickness = os2.y_strikeout_size as f64 / units_per_em;
+        }
+
+        Ok(DecorationMetrics {
+            underline_position: Em(underline_position),
+            underline_thickness: Em(underline_thickness),
+            strikeout_position: Em(strikeout_position),
+            strikeout_thickness: Em(strikeout_thickness),
+        })
+    }
+
+    pub fn outline(&self, glyph_index: u16) -> Result<Vec<OutlineSegment>, Error> {
+        self.with(|cached_font| Ok(glyph_outline(cached_font.borrow_face(), glyph_index)))
+    }
+
     pub fn subset(&self, glyph_collector: &IndexSet<u16>) -> Result<Option<Vec<u8>>, Error> {
         self.with(|cached_font| Self::subset_inner(cached_font.borrow_source(), glyph_collector))
     }
@@ -278,16 +348,27 @@ pub struct CachedAllsortsFont {
     #[borrows(source)]
     #[covariant]
     font: allsorts::Font<allsorts::font_data::DynamicFontTableProvider<'this>>,
+    // outlines are read by ttf-parser, the face is parsed along with the font
+    #[borrows(source)]
+    #[covariant]
+    face: Face<'this>,
 }
 
 impl CachedAllsortsFont {
     fn from_source(name: &str, source: FontSource) -> Result<Self, Error> {
-        Self::try_new(source, |source| {
-            let scope = ReadScope::new(source);
-            let font_data = scope.read::<FontData>()?;
-            let provider = font_data.table_provider(NON_TTC_TABLE)?;
-            allsorts::Font::new(provider).map_err(|_| Error::MalformedFont(name.into()))
-        })
+        Self::try_new(
+            source,
+            |source| {
+                let scope = ReadScope::new(source);
+                let font_data = scope.read::<FontData>()?;
+                let provider = font_data.table_provider(NON_TTC_TABLE)?;
+                allsorts::Font::new(provider).map_err(|_| Error::MalformedFont(name.into()))
+            },
+            |source| {
+                Face::parse(source, NON_TTC_TABLE as u32)
+                    .map_err(|error| Error::MalformedFont(error.to_string().into()))
+            },
+        )
     }
 }
 
@@ -341,6 +422,7 @@ mod tests {
                 &mut collector,
                 "Ťg AVA AA ě Tě 012 afa afia",
                 &Features::empty(),
+                true,
             )
             .unwrap();
 
@@ -463,4 +545,42 @@ mod tests {
         ))
         .unwrap();
     }
+
+    #[test]
+    fn kerning() {
+        let fonts = FontCache::new();
+        fonts
+            .add("LatoReg", include_bytes!("../../tests/Lato-Regular.ttf"))
+            .unwrap();
+
+        let font = fonts.get("LatoReg").unwrap();
+
+        let kerned = font.typeset("AVAVA", &Features::empty(), true).unwrap();
+        let unkerned = font.typeset("AVAVA", &Features::empty(), false).unwrap();
+
+        assert!(kerned.width.0 < unkerned.width.0);
+    }
+
+    #[test]
+    fn reserve_collect() {
+        let fonts = FontCache::new();
+        fonts
+            .add("LatoReg", include_bytes!("../../tests/Lato-Regular.ttf"))
+            .unwrap();
+
+        let font = fonts.get("LatoReg").unwrap();
+
+        let mut collector = index_set::new::<u16>();
+        collector.insert(0);
+
+        font.reserve_collect(&mut collector, "0123456789");
+        assert_eq!(collector.len(), 11);
+
+        let positions = font
+            .typeset_collect(&mut collector, "5", &Features::empty(), true)
+            .unwrap();
+
+        assert_eq!(positions.positions[0].glyph_index, 6);
+        assert_eq!(collector.len(), 11);
+    }
 }
@@ -0,0 +1,110 @@
+use layout::Error;
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+// points are in em, relative to the glyph origin, quadratic curves are converted
+// to cubic ones, PDF paths have no quadratic segments
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutlineSegment {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    CurveTo([f32; 6]),
+    Close,
+}
+
+struct OutlineCollector {
+    scale: f32,
+    current: (f32, f32),
+    segments: Vec<OutlineSegment>,
+}
+
+impl OutlineBuilder for OutlineCollector {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.current = (x, y);
+        self.segments
+            .push(OutlineSegment::MoveTo(x * self.scale, y * self.scale));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.current = (x, y);
+        self.segments
+            .push(OutlineSegment::LineTo(x * self.scale, y * self.scale));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x0, y0) = self.current;
+        self.curve_to(
+            x0 + 2.0 / 3.0 * (x1 - x0),
+            y0 + 2.0 / 3.0 * (y1 - y0),
+            x + 2.0 / 3.0 * (x1 - x),
+            y + 2.0 / 3.0 * (y1 - y),
+            x,
+            y,
+        );
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.current = (x, y);
+        self.segments.push(OutlineSegment::CurveTo(
+            [x1, y1, x2, y2, x, y].map(|value| value * self.scale),
+        ));
+    }
+
+    fn close(&mut self) {
+        self.segments.push(OutlineSegment::Close);
+    }
+}
+
+// glyph index is the one of the font, not of a subset; glyphs without outline,
+// e.g. spaces, have no segments; the face is the one parsed with the cached font
+pub fn glyph_outline(face: &Face, glyph_index: u16) -> Vec<OutlineSegment> {
+    let mut collector = OutlineCollector {
+        scale: 1.0 / face.units_per_em() as f32,
+        current: (0.0, 0.0),
+        segments: vec![],
+    };
+    face.outline_glyph(GlyphId(glyph_index), &mut collector);
+
+    collector.segments
+}
+
+// advances of all glyphs of the font in thousandths of em, as PDF font widths
+// have them; glyphs without metrics get the default width of 1000
+pub fn glyph_widths(source: &[u8]) -> Result<Vec<i64>, Error> {
+    let face =
+        Face::parse(source, 0).map_err(|error| Error::MalformedFont(error.to_string().into()))?;
+    let units_per_em = face.units_per_em() as f32;
+
+    Ok((0..face.number_of_glyphs())
+        .map(|glyph_index| {
+            face.glyph_hor_advance(GlyphId(glyph_index))
+                .map(|advance| (advance as f32 * 1000.0 / units_per_em) as i64)
+                .unwrap_or(1000)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use ttf_parser::Face;
+
+    use super::{OutlineSegment, glyph_outline};
+
+    #[test]
+    fn outline() {
+        let source = include_bytes!("../../tests/Lato-Regular.ttf");
+        let face = Face::parse(source, 0).unwrap();
+        let glyph = |c: char| face.glyph_index(c).unwrap().0;
+
+        assert!(glyph_outline(&face, glyph(' ')).is_empty());
+
+        let segments = glyph_outline(&face, glyph('a'));
+        assert!(matches!(segments.first(), Some(OutlineSegment::MoveTo(..))));
+        assert!(matches!(segments.last(), Some(OutlineSegment::Close)));
+        assert!(segments.iter().all(|segment| match segment {
+            OutlineSegment::MoveTo(x, y) | OutlineSegment::LineTo(x, y) =>
+                x.abs() < 2.0 && y.abs() < 2.0,
+            OutlineSegment::CurveTo(points) => points.iter().all(|value| value.abs() < 2.0),
+            OutlineSegment::Close => true,
+        }));
+    }
+}
@@ -1,23 +1,216 @@
+mod annotations;
+
+mod background;
+pub use background::*;
+
+mod barcode;
+pub use barcode::*;
+
+mod chart;
+pub use chart::*;
+
+mod color;
+pub use color::*;
+
+mod columns;
+pub use columns::*;
+
+mod compression;
+pub use compression::*;
+
 mod context;
+pub use context::RenderContext;
 pub(crate) use context::*;
 
+mod continuation;
+pub use continuation::*;
+
+#[cfg(feature = "description")]
+mod description;
+#[cfg(feature = "description")]
+pub use description::*;
+
+mod document_section;
+pub use document_section::*;
+
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "encryption")]
+pub use encryption::*;
+
+mod footnote;
+pub use footnote::*;
+
+mod form;
+pub use form::*;
+
+mod gradient;
+pub use gradient::*;
+
+mod header;
+
+mod html;
+pub use html::*;
+
+mod image;
+pub use image::*;
+
+mod imposition;
+pub use imposition::*;
+
+mod incremental;
+pub use incremental::*;
+
+mod initial_view;
+pub use initial_view::*;
+
+mod layer_state;
+
+mod layers;
+
+mod linearization;
+
+mod link;
+pub use link::*;
+
+mod markup;
+pub use markup::*;
+
+#[cfg(feature = "markdown")]
+mod markdown;
+#[cfg(feature = "markdown")]
+pub use markdown::*;
+
+mod marks;
+pub use marks::*;
+
+mod master_page;
+
+mod merge;
+pub use merge::*;
+
+mod note;
+pub use note::*;
+
+mod optimization;
+pub use optimization::*;
+
+mod outline;
+pub use outline::*;
+
+mod overlay;
+pub use overlay::*;
+
+mod page_break_lines;
+pub use page_break_lines::*;
+
+mod page_decorator;
+pub use page_decorator::*;
+
+mod page_label;
+pub use page_label::*;
+
+mod page_flow;
+
+mod page_numbering;
+pub use page_numbering::*;
+
+mod page_template;
+pub use page_template::*;
+
+mod page_values;
+pub use page_values::*;
+
+mod pagination;
+pub use pagination::*;
+
+mod path;
+pub use path::*;
+
+mod pdf_a;
+pub use pdf_a::*;
+
+mod pdf_version;
+pub use pdf_version::*;
+
+mod precision;
+
+mod print_marks;
+pub use print_marks::*;
+
+mod progress;
+pub use progress::*;
+
+mod render_options;
+pub use render_options::*;
+
 mod renderer;
-use layout::{
-    Rgba,
-    unit::{Mm, Pt, Unit},
-};
-use printpdf::Color;
 pub use renderer::*;
 
-fn from_unit(unit: Unit) -> printpdf::Mm {
-    printpdf::Mm(Mm::from(unit).0 as f32)
-}
+mod resources;
 
-fn from_pt(pt: Pt) -> printpdf::Mm {
-    printpdf::Mm(Mm::from(pt).0 as f32)
-}
+mod rich_text;
+pub use rich_text::*;
+
+mod section;
+pub use section::*;
+
+mod shadow;
+pub use shadow::*;
+
+mod signature;
+pub use signature::*;
+
+mod soft_mask;
+pub use soft_mask::*;
 
-fn from_rgba(color: &Rgba) -> Color {
-    let color = color.into_rgba();
-    Color::Rgb(printpdf::Rgb::new(color.0, color.1, color.2, None))
+mod span;
+pub use span::*;
+
+mod stationery;
+pub use stationery::*;
+
+mod stats;
+pub use stats::*;
+
+mod stroke;
+pub use stroke::*;
+
+mod structure;
+pub use structure::*;
+
+mod svg_context;
+pub use svg_context::*;
+
+mod table;
+pub use table::*;
+
+mod table_grid;
+pub use table_grid::*;
+
+#[cfg(test)]
+mod test_layouts;
+
+mod text;
+pub use text::*;
+
+mod text_dump;
+pub use text_dump::*;
+
+mod toc;
+pub use toc::*;
+
+mod transform;
+pub use transform::*;
+
+mod watermark;
+pub use watermark::*;
+
+mod xmp;
+pub use xmp::*;
+
+use layout::unit::{Mm, Unit};
+
+fn from_unit(unit: Unit) -> printpdf::Mm {
+    printpdf::Mm(Mm::from(unit).0 as f32)
 }
@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+
+use layout::{Error, Rgba};
+use printpdf::{
+    Rect,
+    lopdf::{Dictionary, Document, Object, ObjectId, StringFormat},
+};
+
+use super::{
+    form::default_appearance,
+    resources::{catalog, indirect_streams, pdf_error},
+};
+
+struct Anchor {
+    name: String,
+    page: usize,
+    point: (f32, f32),
+    // exported as a named destination
+    named: bool,
+}
+
+struct PageAnnotation {
+    page: usize,
+    annotation: Dictionary,
+    // anchor resolved into the destination when the document is written
+    target: Option<String>,
+}
+
+// annotations printpdf has no public API for, added to the saved document
+#[derive(Default)]
+pub(crate) struct PageAnnotations {
+    annotations: Vec<PageAnnotation>,
+    anchors: Vec<Anchor>,
+}
+
+impl PageAnnotations {
+    // point is in page coordinates, in pt
+    pub(crate) fn add_anchor(&mut self, name: &str, page: usize, point: (f32, f32), named: bool) {
+        if self.anchors.iter().any(|anchor| anchor.name == name) {
+            tracing::warn!("Anchor {} is already defined.", name);
+            return;
+        }
+        self.anchors.push(Anchor {
+            name: name.to_string(),
+            page,
+            point,
+            named,
+        });
+    }
+
+    pub(crate) fn anchor_pages(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.anchors
+            .iter()
+            .map(|anchor| (anchor.name.as_str(), anchor.page))
+    }
+
+    // link may target an anchor defined later in the document
+    pub(crate) fn add_link(&mut self, page: usize, rect: &Rect, anchor: &str) {
+        let mut annotation = annotation("Link", rect);
+        annotation.set("Border", reals(vec![0.0, 0.0, 0.0]));
+        self.annotations.push(PageAnnotation {
+            page,
+            annotation,
+            target: Some(anchor.to_string()),
+        });
+    }
+
+    pub(crate) fn add(&mut self, page: usize, annotation: Dictionary) {
+        self.annotations.push(PageAnnotation {
+            page,
+            annotation,
+            target: None,
+        });
+    }
+
+    // e.g. of outline items, pages are those of the saved document
+    pub(crate) fn anchor_destination(
+        &self,
+        pages: &BTreeMap<u32, ObjectId>,
+        name: &str,
+    ) -> Option<Vec<Object>> {
+        self.anchors
+            .iter()
+            .find(|anchor| anchor.name == name)
+            .and_then(|anchor| destination(pages, anchor))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.annotations.is_empty() && !self.anchors.iter().any(|anchor| anchor.named)
+    }
+
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let pages = document.get_pages();
+        let page_id = |page: usize| pages.get(&(page as u32 + 1)).copied();
+        let destination = |anchor: &Anchor| destination(&pages, anchor);
+
+        let mut fields = vec![];
+        for page_annotation in self.annotations.iter() {
+            let Some(annotation_page_id) = page_id(page_annotation.page) else {
+                tracing::warn!("Annotation refers to missing page.");
+                continue;
+            };
+
+            let mut annotation = page_annotation.annotation.clone();
+            if let Some(target) = &page_annotation.target {
+                let Some(destination) = self
+                    .anchors
+                    .iter()
+                    .find(|anchor| anchor.name == *target)
+                    .and_then(destination)
+                else {
+                    tracing::warn!("Link refers to missing anchor {}.", target);
+                    continue;
+                };
+                annotation.set("Dest", destination);
+            }
+
+            let field = annotation.get(b"FT").ok().cloned();
+            let annotation = indirect_streams(document, Object::Dictionary(annotation));
+            let annotation_id = document.add_object(annotation);
+            append_annotation(document, annotation_page_id, annotation_id)?;
+            if let Some(field) = field {
+                fields.push((annotation_id, field));
+            }
+        }
+        if !fields.is_empty() {
+            write_form(document, fields)?;
+        }
+
+        // name tree keys have to be sorted
+        let mut named = self
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.named)
+            .filter_map(|anchor| Some((anchor.name.as_str(), destination(anchor)?)))
+            .collect::<Vec<_>>();
+        if !named.is_empty() {
+            named.sort_by(|(name1, _), (name2, _)| name1.as_bytes().cmp(name2.as_bytes()));
+            let names = named
+                .into_iter()
+                .flat_map(|(name, destination)| {
+                    [Object::string_literal(name), Object::Array(destination)]
+                })
+                .collect::<Vec<_>>();
+            write_destinations(document, names)?;
+        }
+        Ok(())
+    }
+}
+
+// the anchor is at the top left corner of the view
+fn destination(pages: &BTreeMap<u32, ObjectId>, anchor: &Anchor) -> Option<Vec<Object>> {
+    let (x, y) = anchor.point;
+    Some(vec![
+        Object::Reference(*pages.get(&(anchor.page as u32 + 1))?),
+        Object::Name(b"XYZ".to_vec()),
+        Object::Real(x),
+        Object::Real(y),
+        Object::Null,
+    ])
+}
+
+pub(crate) fn annotation(subtype: &str, rect: &Rect) -> Dictionary {
+    let mut annotation = Dictionary::new();
+    annotation.set("Type", Object::Name(b"Annot".to_vec()));
+    annotation.set("Subtype", Object::Name(subtype.as_bytes().to_vec()));
+    annotation.set(
+        "Rect",
+        reals(vec![rect.ll.x.0, rect.ll.y.0, rect.ur.x.0, rect.ur.y.0]),
+    );
+    annotation
+}
+
+fn reals(values: Vec<f32>) -> Object {
+    Object::Array(values.into_iter().map(Object::Real).collect())
+}
+
+pub(crate) fn color_array(color: &Rgba) -> Object {
+    let color = color.into_rgba();
+    reals(vec![color.0, color.1, color.2])
+}
+
+// strings outside of ascii are written as utf-16 with byte order mark
+pub(crate) fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+    let bytes = [0xFE, 0xFF]
+        .into_iter()
+        .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+        .collect();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+fn write_form(document: &mut Document, fields: Vec<(ObjectId, Object)>) -> Result<(), Error> {
+    let signature = fields
+        .iter()
+        .any(|(_, field)| matches!(field, Object::Name(name) if name == b"Sig"));
+
+    let mut helvetica = Dictionary::new();
+    helvetica.set("Type", Object::Name(b"Font".to_vec()));
+    helvetica.set("Subtype", Object::Name(b"Type1".to_vec()));
+    helvetica.set("BaseFont", Object::Name(b"Helvetica".to_vec()));
+    helvetica.set("Encoding", Object::Name(b"WinAnsiEncoding".to_vec()));
+    let mut fonts = Dictionary::new();
+    fonts.set("Helv", helvetica);
+    let mut resources = Dictionary::new();
+    resources.set("Font", fonts);
+
+    let mut form = Dictionary::new();
+    form.set(
+        "Fields",
+        fields
+            .into_iter()
+            .map(|(id, _)| Object::Reference(id))
+            .collect::<Vec<_>>(),
+    );
+    form.set("DR", resources);
+    form.set("DA", default_appearance(0.0));
+    // text fields have no appearance streams, viewers generate them
+    form.set("NeedAppearances", true);
+    if signature {
+        form.set("SigFlags", 1);
+    }
+    let form_id = document.add_object(form);
+
+    catalog(document)?.set("AcroForm", Object::Reference(form_id));
+    Ok(())
+}
+
+fn write_destinations(document: &mut Document, names: Vec<Object>) -> Result<(), Error> {
+    let mut tree = Dictionary::new();
+    tree.set("Names", names);
+    let tree_id = document.add_object(tree);
+
+    let catalog = catalog(document)?;
+    match catalog.get_mut(b"Names").and_then(Object::as_dict_mut) {
+        Ok(names) => names.set("Dests", Object::Reference(tree_id)),
+        Err(_) => {
+            let mut names = Dictionary::new();
+            names.set("Dests", Object::Reference(tree_id));
+            catalog.set("Names", names);
+        }
+    }
+    Ok(())
+}
+
+// printpdf writes Annots as a direct array, possibly empty
+fn append_annotation(
+    document: &mut Document,
+    page_id: ObjectId,
+    annotation_id: ObjectId,
+) -> Result<(), Error> {
+    let page = document
+        .get_object(page_id)
+        .and_then(Object::as_dict)
+        .map_err(pdf_error)?;
+    let annotations_id = match page.get(b"Annots") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+
+    let annotations = match annotations_id {
+        Some(id) => document.get_object_mut(id),
+        None => {
+            let page = document
+                .get_object_mut(page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?;
+            if page.get(b"Annots").is_err() {
+                page.set("Annots", Object::Array(vec![]));
+            }
+            page.get_mut(b"Annots")
+        }
+    };
+    annotations
+        .and_then(Object::as_array_mut)
+        .map_err(pdf_error)?
+        .push(Object::Reference(annotation_id));
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use layout::Error;
+use printpdf::lopdf::{Document, Object, Stream};
+
+use super::{
+    Image, ImageFit,
+    stationery::{page_box, stamp_xobject},
+};
+
+const MM_PER_PT: f32 = 25.4 / 72.0;
+
+// image painted under everything else on pages, e.g. the decorative frame of a certificate;
+// it is written once and shared by the pages, placed over their media box by its fit
+#[derive(Clone, Debug)]
+pub struct Background {
+    image: Image,
+    pages: Option<Vec<usize>>,
+}
+
+impl Background {
+    pub fn new(image: Image) -> Self {
+        Self { image, pages: None }
+    }
+
+    // the image covers the whole page, its edges are cut off if proportions differ
+    pub fn from_jpeg(data: Vec<u8>) -> Result<Self, Error> {
+        Ok(Self::new(Image::from_jpeg(data)?.with_fit(ImageFit::Cover)))
+    }
+
+    // pages are numbered from 1, all pages get the background unless set
+    pub fn with_pages(mut self, pages: impl IntoIterator<Item = usize>) -> Self {
+        self.pages = Some(pages.into_iter().collect());
+        self
+    }
+
+    fn on_page(&self, page: usize) -> bool {
+        match &self.pages {
+            Some(pages) => pages.contains(&page),
+            None => true,
+        }
+    }
+
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let mut xobject_id = None;
+        for (page, page_id) in document.get_pages() {
+            if !self.on_page(page as usize) {
+                continue;
+            }
+            let Some([left, bottom, right, top]) = page_box(document, page_id) else {
+                tracing::warn!("Page {page} has no media box, background is not painted.");
+                continue;
+            };
+            let placement = self
+                .image
+                .place((right - left) * MM_PER_PT, (top - bottom) * MM_PER_PT);
+            // the first page decides the resolution, pages are usually of the same size
+            let id = match xobject_id {
+                Some(id) => id,
+                None => {
+                    let stream = Stream::from(self.image.to_xobject(&placement));
+                    *xobject_id.insert(document.add_object(stream))
+                }
+            };
+
+            let width = placement.width / MM_PER_PT;
+            let height = placement.height / MM_PER_PT;
+            let x = left + placement.x / MM_PER_PT;
+            let y = top - placement.y / MM_PER_PT - height;
+            stamp_xobject(
+                document,
+                page_id,
+                "PRBackground",
+                Object::Reference(id),
+                [width, 0.0, 0.0, height, x, y],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use printpdf::lopdf::{Document, Object};
+
+    use crate::{Background, Image, ImageColorSpace, ImageFit};
+
+    use super::super::test_layouts::render_context;
+
+    // matrices the background is painted by, per page
+    fn matrices(document: &Document) -> Vec<Option<Vec<f32>>> {
+        document
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(page_id).unwrap();
+                let index = content.operations.iter().position(|operation| {
+                    operation.operator == "Do"
+                        && operation.operands[0].as_name().ok() == Some(&b"PRBackground"[..])
+                })?;
+                let matrix = &content.operations[index - 1];
+                Some(
+                    matrix
+                        .operands
+                        .iter()
+                        .map(|operand| operand.as_float().unwrap())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn background() {
+        let image = Image::from_raw(2, 3, ImageColorSpace::Gray, vec![128; 6])
+            .unwrap()
+            .with_fit(ImageFit::Cover);
+        let pdf = render_context("Test", 210.0, 3)
+            .with_background(Background::new(image).with_pages([1, 3]))
+            .save_to_bytes()
+            .unwrap();
+        BufWriter::new(File::create("test_background.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let matrices = matrices(&document);
+        assert!(matrices[1].is_none());
+        // the image is written once for all pages
+        assert_eq!(matrices[0], matrices[2]);
+        let images = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| {
+                stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+            })
+            .count();
+        assert_eq!(images, 1);
+
+        // the width covers the page, the height overflows it evenly
+        let matrix = matrices[0].clone().unwrap();
+        assert!((matrix[0] - 595.2756).abs() < 1e-2);
+        assert!((matrix[3] - 892.9134).abs() < 1e-2);
+        assert!(matrix[4].abs() < 1e-2);
+        assert!((matrix[5] + 25.5118).abs() < 1e-2);
+
+        assert!(Background::from_jpeg(vec![0; 16]).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use layout::{
+    Error, Layout, LayoutBox, MeasureContext, RenderContext, Rgba, Stroke, StyleBuilder, Text,
+    hbox, hfill,
+    position::{Offset, Size},
+    unit::{Mm, Pt, Unit},
+    vbox,
+};
+
+// bar and space widths of code 128 symbols, the stop symbol includes the final bar
+const CODE128: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_START_C: usize = 105;
+const CODE128_STOP: usize = 106;
+
+// left-hand odd parity digits of EAN-13, the others are derived from them
+const EAN_L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+    "0110111", "0001011",
+];
+// even parity (G) digits of the left half selected by the first digit
+const EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL",
+    "LGGLGL",
+];
+
+// 1D barcode filling the width it is laid out to, quiet zones included; bars are as high
+// as set, the human-readable text is centered below them
+#[derive(Debug)]
+pub struct Barcode {
+    modules: Vec<bool>,
+    // quiet zones before and after the symbol, in modules
+    quiet_zone: (usize, usize),
+    text: String,
+    height: Unit,
+    color: Rgba,
+    text_style: Option<StyleBuilder>,
+    body: Option<LayoutBox>,
+    offset: Offset,
+    size: Size,
+}
+
+impl Barcode {
+    // printable ASCII, digits of even length are encoded by the denser code set C
+    pub fn code128(data: &str) -> Result<Self, Error> {
+        let digits = data.len() >= 4 && data.len().is_multiple_of(2);
+        let values = if digits && data.bytes().all(|byte| byte.is_ascii_digit()) {
+            let pairs = data.as_bytes().chunks(2);
+            let pairs = pairs.map(|pair| ((pair[0] - b'0') * 10 + pair[1] - b'0') as usize);
+            [CODE128_START_C]
+                .into_iter()
+                .chain(pairs)
+                .collect::<Vec<_>>()
+        } else if data.bytes().all(|byte| (b' '..=b'~').contains(&byte)) && !data.is_empty() {
+            let values = data.bytes().map(|byte| (byte - b' ') as usize);
+            [CODE128_START_B]
+                .into_iter()
+                .chain(values)
+                .collect::<Vec<_>>()
+        } else {
+            return Err(barcode_error("Code 128", data));
+        };
+
+        let checksum = values
+            .iter()
+            .enumerate()
+            .map(|(position, value)| position.max(1) * value)
+            .sum::<usize>()
+            % 103;
+
+        let mut modules = vec![];
+        for value in values.into_iter().chain([checksum, CODE128_STOP]) {
+            for (index, width) in CODE128[value].bytes().enumerate() {
+                let bar = index % 2 == 0;
+                modules.extend(std::iter::repeat_n(bar, (width - b'0') as usize));
+            }
+        }
+        Ok(Self::new(modules, (10, 10), data.to_string()))
+    }
+
+    // 12 digits get the check digit computed, 13 digits have it verified
+    pub fn ean13(data: &str) -> Result<Self, Error> {
+        if !matches!(data.len(), 12 | 13) || !data.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(barcode_error("EAN-13", data));
+        }
+        let mut digits = data
+            .bytes()
+            .map(|byte| (byte - b'0') as usize)
+            .collect::<Vec<_>>();
+        let check = ean13_check_digit(&digits[..12]);
+        match digits.get(12) {
+            Some(digit) if *digit != check => return Err(barcode_error("EAN-13", data)),
+            Some(_) => {}
+            None => digits.push(check),
+        }
+
+        let bits = |pattern: &str| pattern.bytes().map(|bit| bit == b'1').collect::<Vec<_>>();
+        let mut modules = bits("101");
+        for (digit, parity) in digits[1..7].iter().zip(EAN_PARITY[digits[0]].bytes()) {
+            let left = bits(EAN_L[*digit]);
+            match parity {
+                // the right-hand code reversed
+                b'G' => modules.extend(left.iter().rev().map(|bar| !bar)),
+                _ => modules.extend(left),
+            }
+        }
+        modules.extend(bits("01010"));
+        for digit in &digits[7..] {
+            modules.extend(bits(EAN_L[*digit]).iter().map(|bar| !bar));
+        }
+        modules.extend(bits("101"));
+
+        let text = digits.iter().map(|digit| digit.to_string()).collect();
+        Ok(Self::new(modules, (11, 7), text))
+    }
+
+    fn new(modules: Vec<bool>, quiet_zone: (usize, usize), text: String) -> Self {
+        Self {
+            modules,
+            quiet_zone,
+            text,
+            height: Mm(15.0).into(),
+            color: Rgba::black(),
+            text_style: None,
+            body: None,
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+
+    pub fn with_height(mut self, height: impl Into<Unit>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    pub fn with_color(mut self, color: Rgba) -> Self {
+        self.color = color;
+        self
+    }
+
+    // the human-readable text is shown only if its style is set
+    pub fn with_text(mut self, style: StyleBuilder) -> Self {
+        self.text_style = Some(style);
+        self
+    }
+
+    // modules of the symbol including quiet zones
+    pub fn width_in_modules(&self) -> usize {
+        self.quiet_zone.0 + self.modules.len() + self.quiet_zone.1
+    }
+
+    // runs of bars as (first module, number of modules), quiet zones excluded
+    fn bars(&self) -> Vec<(usize, usize)> {
+        let mut bars: Vec<(usize, usize)> = vec![];
+        for (index, bar) in self.modules.iter().enumerate() {
+            match bars.last_mut() {
+                Some((start, width)) if *bar && *start + *width == index => *width += 1,
+                _ if *bar => bars.push((index, 1)),
+                _ => {}
+            }
+        }
+        bars
+    }
+}
+
+impl Layout for Barcode {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        let mut body = vbox().child(vbox().axis_size(self.height));
+        if let Some(style) = &self.text_style {
+            let text = Text::new(&self.text).style(style.clone());
+            body = body.child(hbox().child(hfill(1)).child(text).child(hfill(1)));
+        }
+        body.measure(ctx, size)?;
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        match self.body.as_mut() {
+            Some(body) => body.lay_out(ctx, offset, size),
+            None => Ok(()),
+        }
+    }
+
+    // bars are lines as thick as they are wide, the symbol is kept on one page together
+    // with its text
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+
+        let module = Mm::from(self.size.base_width()).0 / self.width_in_modules() as f64;
+        let top = self.offset.y;
+        let bottom = self.offset.y + self.height;
+        for (start, width) in self.bars() {
+            let center = (self.quiet_zone.0 + start) as f64 + width as f64 / 2.0;
+            let x: Unit = Mm(Mm::from(self.offset.x).0 + center * module).into();
+            let thickness = Pt::from(Mm(width as f64 * module));
+            ctx.line(
+                &Offset::new(x, top),
+                &Offset::new(x, bottom),
+                &Stroke::new(self.color, thickness),
+            );
+        }
+
+        match self.body.as_ref() {
+            Some(body) => body.render(ctx),
+            None => Ok(()),
+        }
+    }
+}
+
+fn ean13_check_digit(digits: &[usize]) -> usize {
+    let sum = digits
+        .iter()
+        .enumerate()
+        .map(|(index, digit)| match index % 2 {
+            0 => *digit,
+            _ => 3 * digit,
+        })
+        .sum::<usize>();
+    (10 - sum % 10) % 10
+}
+
+fn barcode_error(symbology: &str, data: &str) -> Error {
+    Error::PdfWrite(format!("Data {data:?} can not be encoded as {symbology}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, Layout, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::{PdfDocument, lopdf::Document};
+
+    use crate::{Barcode, RenderContext, new_font_cache};
+
+    use super::{CODE128, ean13_check_digit};
+
+    fn modules(barcode: &Barcode) -> String {
+        barcode
+            .modules
+            .iter()
+            .map(|bar| if *bar { '1' } else { '0' })
+            .collect()
+    }
+
+    #[test]
+    fn code128() {
+        assert!(CODE128[..106].iter().all(|symbol| symbol.len() == 6));
+        assert!(CODE128.iter().all(|symbol| {
+            symbol
+                .bytes()
+                .map(|width| (width - b'0') as usize)
+                .sum::<usize>()
+                == if symbol.len() == 7 { 13 } else { 11 }
+        }));
+
+        // start B, 'A', the check symbol (104 + 33) % 103 = 34 and stop
+        let barcode = Barcode::code128("A").unwrap();
+        let bits = modules(&barcode);
+        assert_eq!(bits.len(), 11 * 3 + 13);
+        assert!(bits.starts_with("11010010000"));
+        assert!(bits.ends_with("1100011101011"));
+        assert_eq!(&bits[11..22], "10100011000");
+        assert_eq!(&bits[22..33], "10001011000");
+        assert_eq!(barcode.width_in_modules(), 46 + 20);
+
+        // digits are paired by code set C
+        let barcode = Barcode::code128("123456").unwrap();
+        assert_eq!(barcode.modules.len(), 11 * 5 + 13);
+        assert!(modules(&barcode).starts_with("11010011100"));
+
+        assert!(Barcode::code128("").is_err());
+        assert!(Barcode::code128("tab\t").is_err());
+    }
+
+    #[test]
+    fn ean13() {
+        assert_eq!(ean13_check_digit(&[4, 0, 0, 6, 3, 8, 1, 3, 3, 3, 9, 3]), 1);
+
+        let barcode = Barcode::ean13("400638133393").unwrap();
+        assert_eq!(barcode.text, "4006381333931");
+        let bits = modules(&barcode);
+        assert_eq!(bits.len(), 95);
+        assert!(bits.starts_with("101"));
+        // 0 of odd parity, 0 of even parity selected by the first digit 4
+        assert_eq!(&bits[3..17], "00011010100111");
+        assert_eq!(&bits[45..50], "01010");
+        // right-hand 1
+        assert_eq!(&bits[85..92], "1100110");
+        assert_eq!(barcode.width_in_modules(), 95 + 18);
+
+        assert!(Barcode::ean13("4006381333932").is_err());
+        assert!(Barcode::ean13("40063813339").is_err());
+        assert!(Barcode::ean13("40063813339a").is_err());
+    }
+
+    #[test]
+    fn bars() {
+        let barcode = Barcode::ean13("4006381333931").unwrap();
+        let bars = barcode.bars();
+        assert_eq!(bars[..2], [(0, 1), (2, 1)]);
+        assert_eq!(
+            bars.iter().map(|(_, width)| width).sum::<usize>(),
+            barcode.modules.iter().filter(|bar| **bar).count()
+        );
+
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        let mut barcode = barcode
+            .with_height(Mm(20.0))
+            .with_text(StyleBuilder::default().with_font(Font::new("LatoReg", Pt(8.0), None)));
+        let size = Size::fixed(Mm(37.29), Mm(25.0));
+        barcode.measure(&mut rctx, size.clone()).unwrap();
+        barcode
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(0.0)), size.clone())
+            .unwrap();
+        assert!(barcode.render(&mut rctx).is_ok());
+
+        // the bars fit the page, the text below them does not
+        barcode
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(255.0)), size)
+            .unwrap();
+        assert!(barcode.render(&mut rctx).is_ok());
+        let document = Document::load_mem(&rctx.save_to_bytes().unwrap()).unwrap();
+        let bars = |page: u32| {
+            let page_id = document.get_pages()[&page];
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let bars = content.operations.iter();
+            bars.filter(|operation| operation.operator == "l").count()
+        };
+        let count = barcode.bars().len();
+        assert_eq!((bars(1), bars(2)), (count, count));
+    }
+}
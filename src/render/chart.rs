@@ -0,0 +1,409 @@
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+use layout::{
+    Error, Layout, LayoutBox, MeasureContext, RenderContext, Rgba, Stroke,
+    position::{Offset, Size},
+    unit::{Mm, Pt, Unit},
+    vbox,
+};
+
+use super::{Marks, Path, path::PathPaint};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartSeries {
+    values: Vec<f64>,
+    color: Rgba,
+}
+
+impl ChartSeries {
+    pub fn new(values: impl IntoIterator<Item = f64>, color: Rgba) -> Self {
+        Self {
+            values: values.into_iter().collect(),
+            color,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ChartKind {
+    Bar,
+    Line,
+    Pie,
+}
+
+// chart drawn by paths of the marks, it fills the width it is laid out to and is as high
+// as set; labels and legends are left to layouts around it
+#[derive(Debug)]
+pub struct Chart {
+    kind: ChartKind,
+    series: Vec<ChartSeries>,
+    marks: Marks,
+    height: Unit,
+    axis: Option<Stroke>,
+    line_width: Pt,
+    body: Option<LayoutBox>,
+    offset: Offset,
+    size: Size,
+}
+
+impl Chart {
+    // values of series are grouped by categories, their indices
+    pub fn bar(marks: Marks, series: impl IntoIterator<Item = ChartSeries>) -> Self {
+        Self::new(ChartKind::Bar, marks, series.into_iter().collect())
+    }
+
+    // points are in the middle of categories, the same as bars
+    pub fn line(marks: Marks, series: impl IntoIterator<Item = ChartSeries>) -> Self {
+        Self::new(ChartKind::Line, marks, series.into_iter().collect())
+    }
+
+    // slices go clockwise from the top, values which are not positive are left out
+    pub fn pie(marks: Marks, slices: impl IntoIterator<Item = (f64, Rgba)>) -> Self {
+        let series = slices
+            .into_iter()
+            .map(|(value, color)| ChartSeries::new([value], color))
+            .collect();
+        Self::new(ChartKind::Pie, marks, series)
+    }
+
+    fn new(kind: ChartKind, marks: Marks, series: Vec<ChartSeries>) -> Self {
+        Self {
+            kind,
+            series,
+            marks,
+            height: Mm(60.0).into(),
+            axis: None,
+            line_width: Pt(1.5),
+            body: None,
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+
+    pub fn with_height(mut self, height: impl Into<Unit>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    // the zero line and the left edge of bar and line charts
+    pub fn with_axis(mut self, stroke: Stroke) -> Self {
+        self.axis = Some(stroke);
+        self
+    }
+
+    pub fn with_line_width(mut self, width: Pt) -> Self {
+        self.line_width = width;
+        self
+    }
+
+    // (left, top, width, height) in mm
+    fn area(&self) -> (f64, f64, f64, f64) {
+        (
+            Mm::from(self.offset.x).0,
+            Mm::from(self.offset.y).0,
+            Mm::from(self.size.base_width()).0,
+            Mm::from(self.height).0,
+        )
+    }
+
+    fn categories(&self) -> usize {
+        let values = self.series.iter().map(|series| series.values.len());
+        values.max().unwrap_or_default()
+    }
+
+    // vertical position of the value, the range always includes zero
+    fn scale(&self) -> impl Fn(f64) -> f64 {
+        let values = self.series.iter().flat_map(|series| &series.values);
+        let (low, high) = values.fold((0.0f64, 0.0f64), |(low, high), value| {
+            (low.min(*value), high.max(*value))
+        });
+        let high = if high > low { high } else { low + 1.0 };
+        let (_, top, _, height) = self.area();
+        move |value| top + (high - value) / (high - low) * height
+    }
+
+    pub(crate) fn paths(&self) -> Vec<PathPaint> {
+        let mut paths = match self.kind {
+            ChartKind::Bar => self.bars(),
+            ChartKind::Line => self.lines(),
+            ChartKind::Pie => return self.slices(),
+        };
+        if let Some(axis) = &self.axis {
+            let (left, top, width, height) = self.area();
+            let zero = self.scale()(0.0);
+            let path = Path::new()
+                .move_to(point(left, top))
+                .line_to(point(left, top + height))
+                .move_to(point(left, zero))
+                .line_to(point(left + width, zero));
+            paths.push((path, None, Some(axis.clone())));
+        }
+        paths
+    }
+
+    // bars of a category are side by side, a fifth of the category is the gap between them
+    fn bars(&self) -> Vec<PathPaint> {
+        let (left, _, width, _) = self.area();
+        let scale = self.scale();
+        let category = width / self.categories().max(1) as f64;
+        let bar = category * 0.8 / self.series.len().max(1) as f64;
+        let mut paths = vec![];
+        for (index, series) in self.series.iter().enumerate() {
+            for (position, value) in series.values.iter().enumerate() {
+                let x = left + position as f64 * category + category * 0.1 + index as f64 * bar;
+                let (from, to) = (scale(*value), scale(0.0));
+                let path = Path::new().polygon([
+                    point(x, from),
+                    point(x + bar, from),
+                    point(x + bar, to),
+                    point(x, to),
+                ]);
+                paths.push((path, Some(series.color), None));
+            }
+        }
+        paths
+    }
+
+    fn lines(&self) -> Vec<PathPaint> {
+        let (left, _, width, _) = self.area();
+        let scale = self.scale();
+        let category = width / self.categories().max(1) as f64;
+        let mut paths = vec![];
+        for series in &self.series {
+            let mut points = series.values.iter().enumerate().map(|(position, value)| {
+                point(left + (position as f64 + 0.5) * category, scale(*value))
+            });
+            let Some(first) = points.next() else {
+                continue;
+            };
+            let path = points.fold(Path::new().move_to(first), Path::line_to);
+            let stroke = Stroke::new(series.color, self.line_width);
+            paths.push((path, None, Some(stroke)));
+        }
+        paths
+    }
+
+    // arcs are approximated by cubic curves of a quarter turn at most
+    fn slices(&self) -> Vec<PathPaint> {
+        let (left, top, width, height) = self.area();
+        let (x, y) = (left + width / 2.0, top + height / 2.0);
+        let radius = width.min(height) / 2.0;
+        let slices = self
+            .series
+            .iter()
+            .filter_map(|series| Some((*series.values.first()?, series.color)))
+            .filter(|(value, _)| *value > 0.0)
+            .collect::<Vec<_>>();
+        let total = slices.iter().map(|(value, _)| value).sum::<f64>();
+
+        // angle clockwise from the top
+        let at = |angle: f64| (x + radius * angle.sin(), y - radius * angle.cos());
+        let mut start = 0.0;
+        let mut paths = vec![];
+        for (value, color) in slices {
+            let end = start + value / total * TAU;
+            let (from_x, from_y) = at(start);
+            let mut path = Path::new()
+                .move_to(point(x, y))
+                .line_to(point(from_x, from_y));
+            let turns = ((end - start) / FRAC_PI_2 - 1e-9).ceil().max(1.0) as usize;
+            let step = (end - start) / turns as f64;
+            for turn in 0..turns {
+                let (from, to) = (start + turn as f64 * step, start + (turn + 1) as f64 * step);
+                let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+                let ((from_x, from_y), (to_x, to_y)) = (at(from), at(to));
+                path = path.curve_to(
+                    point(from_x + k * from.cos(), from_y + k * from.sin()),
+                    point(to_x - k * to.cos(), to_y - k * to.sin()),
+                    point(to_x, to_y),
+                );
+            }
+            paths.push((path.close(), Some(color), None));
+            start = end;
+        }
+        paths
+    }
+}
+
+impl Layout for Chart {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        let mut body = vbox().axis_size(self.height);
+        body.measure(ctx, size)?;
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        match self.body.as_mut() {
+            Some(body) => body.lay_out(ctx, offset, size),
+            None => Ok(()),
+        }
+    }
+
+    // the chart is kept on one page, its paths are queued once the page is known and
+    // painted by checking it again
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        if !self.marks.is_wired() {
+            return Err(Error::PdfWrite(
+                "Chart marks are not passed to the render context".into(),
+            ));
+        }
+        ctx.check_page_break(self.offset.y, self.height, false);
+        for (path, fill, stroke) in self.paths() {
+            self.marks.paint(path, fill, stroke);
+        }
+        ctx.check_page_break(self.offset.y, Unit::zero(), false);
+        match self.body.as_ref() {
+            Some(body) => body.render(ctx),
+            None => Ok(()),
+        }
+    }
+}
+
+fn point(x: f64, y: f64) -> Offset {
+    Offset::new(Mm(x), Mm(y))
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Layout, Rgba, Stroke,
+        position::{Offset, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::lopdf::Document;
+
+    use crate::{Chart, ChartSeries, Marks, PathSegment};
+
+    use super::super::test_layouts::render_context;
+
+    fn laid_out(mut chart: Chart) -> Chart {
+        chart.offset = Offset::new(Mm(10.0), Mm(20.0));
+        chart.size = Size::fixed(Mm(100.0), Mm(50.0));
+        chart.with_height(Mm(50.0))
+    }
+
+    fn mm(offset: &Offset) -> (f64, f64) {
+        let round = |value: f64| (value * 1000.0).round() / 1000.0;
+        (round(Mm::from(offset.x).0), round(Mm::from(offset.y).0))
+    }
+
+    #[test]
+    fn bars() {
+        let red = Rgba::from((255, 0, 0, 1.0));
+        let blue = Rgba::from((0, 0, 255, 1.0));
+        let chart = laid_out(
+            Chart::bar(
+                Marks::new(),
+                [
+                    ChartSeries::new([10.0, -5.0], red),
+                    ChartSeries::new([5.0], blue),
+                ],
+            )
+            .with_axis(Stroke::new(Rgba::black(), Pt(0.5))),
+        );
+        let paths = chart.paths();
+        assert_eq!(paths.len(), 4);
+
+        // range -5..10 is 50 mm high, zero is at 20 + 50 * 10 / 15
+        let PathSegment::MoveTo(top_left) = &paths[0].0.segments()[0] else {
+            panic!("bar does not start by a move");
+        };
+        assert_eq!(mm(top_left), (15.0, 20.0));
+        let PathSegment::LineTo(bottom) = &paths[0].0.segments()[2] else {
+            panic!("bar is not a polygon");
+        };
+        assert_eq!(mm(bottom), (35.0, 53.333));
+        // the negative bar goes down from zero
+        let PathSegment::LineTo(bottom) = &paths[1].0.segments()[2] else {
+            panic!("bar is not a polygon");
+        };
+        assert_eq!(mm(bottom), (85.0, 53.333));
+        assert_eq!(paths[2].1, Some(blue));
+        assert!(paths[3].1.is_none() && paths[3].2.is_some());
+    }
+
+    #[test]
+    fn lines_and_slices() {
+        let red = Rgba::from((255, 0, 0, 1.0));
+        let chart = laid_out(Chart::line(
+            Marks::new(),
+            [ChartSeries::new([0.0, 4.0], red), ChartSeries::new([], red)],
+        ));
+        let paths = chart.paths();
+        assert_eq!(paths.len(), 1);
+        let points = paths[0]
+            .0
+            .segments()
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::MoveTo(to) | PathSegment::LineTo(to) => mm(to),
+                _ => panic!("line is not a polyline"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(points, [(35.0, 70.0), (85.0, 20.0)]);
+
+        let chart = laid_out(Chart::pie(
+            Marks::new(),
+            [(3.0, red), (1.0, red), (0.0, red)],
+        ));
+        let paths = chart.paths();
+        assert_eq!(paths.len(), 2);
+        // three quarters of four curves, the rest by one; both start at the center
+        let curves = |index: usize| {
+            paths[index]
+                .0
+                .segments()
+                .iter()
+                .filter(|segment| matches!(segment, PathSegment::CurveTo(..)))
+                .count()
+        };
+        assert_eq!((curves(0), curves(1)), (3, 1));
+        let PathSegment::LineTo(start) = &paths[1].0.segments()[1] else {
+            panic!("slice does not start by a radius");
+        };
+        assert_eq!(mm(start), (35.0, 45.0));
+    }
+
+    #[test]
+    fn chart() {
+        let marks = Marks::new();
+        let mut rctx = render_context("Test", 210.0, 1).with_marks(marks.clone());
+
+        let mut chart = Chart::pie(marks.clone(), [(1.0, Rgba::black())]).with_height(Mm(40.0));
+        let size = Size::fixed(Mm(190.0), Mm(40.0));
+        chart.measure(&mut rctx, size.clone()).unwrap();
+        chart
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(0.0)), size.clone())
+            .unwrap();
+        chart.render(&mut rctx).unwrap();
+        assert!(marks.take().is_empty());
+
+        // the chart past the page end is painted on the next page only
+        chart
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(260.0)), size.clone())
+            .unwrap();
+        chart.render(&mut rctx).unwrap();
+        let document = Document::load_mem(&rctx.save_to_bytes().unwrap()).unwrap();
+        let curves = |page: u32| {
+            let page_id = document.get_pages()[&page];
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let curves = content.operations.iter();
+            curves.filter(|operation| operation.operator == "c").count()
+        };
+        assert_eq!((curves(1), curves(2)), (4, 4));
+
+        // marks not passed to the context would be lost
+        let mut rctx = render_context("Test", 210.0, 1);
+        let mut chart = Chart::pie(Marks::new(), [(1.0, Rgba::black())]);
+        chart.measure(&mut rctx, size).unwrap();
+        assert!(chart.render(&mut rctx).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use layout::{Error, Rgba};
+use printpdf::{
+    Cmyk, Color, Greyscale, Rgb,
+    lopdf::{Dictionary, Document, Object, ObjectId, Stream},
+};
+
+use super::resources::{indirect_dictionary, pdf_error};
+
+// colors of the layout are RGB, they are converted to the model on output,
+// alpha is ignored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorModel {
+    #[default]
+    Rgb,
+    Cmyk,
+    Gray,
+}
+
+impl ColorModel {
+    pub(crate) fn color(&self, color: &Rgba) -> Color {
+        let components = self.components(color);
+        match self {
+            ColorModel::Rgb => {
+                Color::Rgb(Rgb::new(components[0], components[1], components[2], None))
+            }
+            ColorModel::Cmyk => Color::Cmyk(Cmyk::new(
+                components[0],
+                components[1],
+                components[2],
+                components[3],
+                None,
+            )),
+            ColorModel::Gray => Color::Greyscale(Greyscale::new(components[0], None)),
+        }
+    }
+
+    // naive conversion without a color profile, pure gray goes to the black channel only
+    pub(crate) fn components(&self, color: &Rgba) -> Vec<f32> {
+        let (red, green, blue, _) = color.into_rgba();
+        match self {
+            ColorModel::Rgb => vec![red, green, blue],
+            ColorModel::Cmyk => {
+                let black = 1.0 - red.max(green).max(blue);
+                if black >= 1.0 {
+                    return vec![0.0, 0.0, 0.0, 1.0];
+                }
+                let ink = |component: f32| (1.0 - component - black) / (1.0 - black);
+                vec![ink(red), ink(green), ink(blue), black]
+            }
+            ColorModel::Gray => vec![0.299 * red + 0.587 * green + 0.114 * blue],
+        }
+    }
+
+    pub(crate) fn color_space(&self) -> &'static [u8] {
+        match self {
+            ColorModel::Rgb => b"DeviceRGB",
+            ColorModel::Cmyk => b"DeviceCMYK",
+            ColorModel::Gray => b"DeviceGray",
+        }
+    }
+}
+
+// Profile replaces the device color space of its model on all pages, so fills, strokes,
+// shadings and images in that color space are all color managed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IccProfile {
+    color_model: ColorModel,
+    data: Vec<u8>,
+}
+
+impl IccProfile {
+    pub fn new(color_model: ColorModel, data: Vec<u8>) -> Self {
+        Self { color_model, data }
+    }
+
+    pub fn color_model(&self) -> ColorModel {
+        self.color_model
+    }
+
+    // returns the id of the profile stream
+    pub(crate) fn write(&self, document: &mut Document) -> Result<ObjectId, Error> {
+        let (default_name, components) = match self.color_model {
+            ColorModel::Rgb => (&b"DefaultRGB"[..], 3),
+            ColorModel::Cmyk => (&b"DefaultCMYK"[..], 4),
+            ColorModel::Gray => (&b"DefaultGray"[..], 1),
+        };
+
+        let mut dictionary = Dictionary::new();
+        dictionary.set("N", components);
+        dictionary.set(
+            "Alternate",
+            Object::Name(self.color_model.color_space().to_vec()),
+        );
+        let mut stream = Stream::new(dictionary, self.data.clone());
+        // profiles compress well, failure just keeps the stream as is
+        let _ = stream.compress();
+        let profile_id = document.add_object(stream);
+        let color_space = vec![
+            Object::Name(b"ICCBased".to_vec()),
+            Object::Reference(profile_id),
+        ];
+
+        for page_id in document.get_pages().into_values() {
+            let resources_id = indirect_dictionary(document, page_id, b"Resources")?;
+            let color_spaces_id = indirect_dictionary(document, resources_id, b"ColorSpace")?;
+            document
+                .get_object_mut(color_spaces_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?
+                .set(default_name, color_space.clone());
+        }
+        Ok(profile_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::Rgba;
+
+    use super::ColorModel;
+
+    #[test]
+    fn components() {
+        let black = Rgba::black();
+        assert_eq!(ColorModel::Cmyk.components(&black), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(ColorModel::Gray.components(&black), [0.0]);
+
+        let red = Rgba::from((255, 0, 0, 1.0));
+        assert_eq!(ColorModel::Cmyk.components(&red), [0.0, 1.0, 1.0, 0.0]);
+
+        let gray = Rgba::from((128, 128, 128, 1.0));
+        let cmyk = ColorModel::Cmyk.components(&gray);
+        assert_eq!(&cmyk[..3], [0.0, 0.0, 0.0]);
+        assert!((cmyk[3] - 0.498).abs() < 0.001);
+    }
+}
@@ -0,0 +1,235 @@
+use layout::{
+    Error, Layout, MeasureContext, RenderContext,
+    position::{Offset, Size},
+    unit::{Mm, Unit},
+};
+
+use super::{Mark, Marks};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ColumnMark {
+    // content offset, count of columns, distance of their left edges and height of the
+    // content flowed
+    Begin(Unit, usize, Unit, Unit),
+    // content offset the content after the columns continues from
+    End(Unit),
+}
+
+// columns being flowed, offsets are of the page content except the end
+#[derive(Clone, Debug)]
+pub(crate) struct ColumnFlow {
+    pub(crate) count: usize,
+    pub(crate) pitch: Unit,
+    // content offset the flowed content ends at
+    pub(crate) end: Unit,
+    pub(crate) index: usize,
+    // top of the columns, bottom of the deepest column left and the end of page content
+    pub(crate) top: Unit,
+    pub(crate) bottom: Unit,
+    pub(crate) page_end: Unit,
+}
+
+pub(crate) fn column_width(width: Unit, count: usize, gap: Unit) -> Unit {
+    let count = count.max(1) as f64;
+    let (width, gap) = (Mm::from(width).0, Mm::from(gap).0);
+    Mm(((width - gap * (count - 1.0)) / count).max(0.0)).into()
+}
+
+// begins and ends of columns are taken over by the render context, which moves content
+// between columns
+impl Marks {
+    // the layout is laid out in one column, which is flowed into the columns of balanced
+    // heights, the last column of a page takes the rest if lines do not split evenly
+    pub fn columns(
+        &self,
+        count: usize,
+        gap: impl Into<Unit>,
+        layout: impl Layout + 'static,
+    ) -> ColumnLayout {
+        ColumnLayout {
+            layout: Box::new(layout),
+            count: count.max(1),
+            gap: gap.into(),
+            marks: self.clone(),
+            width: Unit::zero(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ColumnLayout {
+    layout: Box<dyn Layout>,
+    count: usize,
+    gap: Unit,
+    marks: Marks,
+    width: Unit,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for ColumnLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.width = column_width(size.base_width(), self.count, self.gap);
+        self.layout
+            .measure(ctx, Size::fixed(self.width, size.base_height()))
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout
+            .lay_out(ctx, offset, Size::fixed(self.width, size.base_height()))
+    }
+
+    // content after the columns continues below the deepest one
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        let height = self.size.base_height();
+        self.marks.push(Mark::Column(ColumnMark::Begin(
+            self.offset.y,
+            self.count,
+            self.width + self.gap,
+            height,
+        )));
+        ctx.check_page_break(self.offset.y, Unit::zero(), false);
+        self.layout.render(ctx)?;
+        self.marks
+            .push(Mark::Column(ColumnMark::End(self.offset.y + height)));
+        ctx.check_page_break(self.offset.y + height, Unit::zero(), false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Axis, Font, Layout, LayoutBox, MeasureContext, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt, Unit},
+    };
+    use printpdf::{PdfDocument, lopdf::Document};
+
+    use crate::{Marks, RenderContext, new_font_cache, render::test_layouts::Lines};
+
+    use super::column_width;
+
+    #[test]
+    fn widths() {
+        let width = |width: f64, count, gap: f64| {
+            Mm::from(column_width(Mm(width).into(), count, Mm(gap).into())).0
+        };
+        assert!((width(190.0, 2, 10.0) - 90.0).abs() < 1e-9);
+        assert!((width(190.0, 3, 5.0) - 60.0).abs() < 1e-9);
+        assert!((width(190.0, 0, 5.0) - 190.0).abs() < 1e-9);
+        assert_eq!(
+            column_width(Mm(10.0).into(), 3, Mm(10.0).into()),
+            Unit::zero()
+        );
+    }
+
+    #[test]
+    fn columns() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let mut layout = marks.columns(2, Mm(10.0), LayoutBox::new(Axis::Vertical));
+        let size = Size::fixed(Mm(190.0), Mm(400.0));
+        layout.measure(&mut rctx, size.clone()).unwrap();
+        layout
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(0.0)), size)
+            .unwrap();
+        layout.render(&mut rctx).unwrap();
+
+        // both marks are taken over with the page break checks
+        assert!(marks.take().is_empty());
+        assert!(rctx.save_to_bytes().is_ok());
+    }
+
+    #[test]
+    fn flow() {
+        let fonts = new_font_cache();
+        fonts
+            .add(
+                "LatoReg",
+                include_bytes!("../../tests/Lato-Regular.ttf").as_ref(),
+            )
+            .unwrap();
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_marks(marks.clone());
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let text = rctx.typeset(&style, "Line").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        // 20 lines of 200 mm are balanced into two columns 100 mm apart
+        let lines = Lines::new(text, 20);
+        let mut layout = marks.columns(2, Mm(10.0), lines);
+        let size = Size::fixed(Mm(190.0), Mm(200.0));
+        layout.measure(&mut rctx, size.clone()).unwrap();
+        layout
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(0.0)), size)
+            .unwrap();
+        layout.render(&mut rctx).unwrap();
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 1);
+        // origins of texts, the first move of a text section
+        let content = document.get_and_decode_page_content(pages[&1]).unwrap();
+        let mut origin = false;
+        let mut origins = vec![];
+        for operation in content.operations.iter() {
+            match operation.operator.as_str() {
+                "BT" => origin = true,
+                "Td" if origin => {
+                    origins.push((
+                        operation.operands[0].as_float().unwrap(),
+                        operation.operands[1].as_float().unwrap(),
+                    ));
+                    origin = false;
+                }
+                _ => (),
+            }
+        }
+        assert_eq!(origins.len(), 20);
+        let left = Pt::from(Mm(10.0)).0 as f32;
+        let pitch = Pt::from(Mm(100.0)).0 as f32;
+        let column = |x: f32| ((x - left) / pitch).round() as usize;
+        let columns = origins.iter().map(|(x, _)| column(*x)).collect::<Vec<_>>();
+        assert_eq!(columns.iter().filter(|column| **column == 0).count(), 10);
+        assert_eq!(columns.iter().filter(|column| **column == 1).count(), 10);
+        // the second column starts at the top of the first one
+        assert!((origins[0].1 - origins[10].1).abs() < 0.01);
+    }
+}
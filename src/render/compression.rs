@@ -0,0 +1,62 @@
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use layout::Error;
+use printpdf::lopdf::{Document, Object};
+
+use super::resources::pdf_error;
+
+// the filter entry makes tiny streams grow, they are kept uncompressed
+const FILTER_OVERHEAD: usize = 19;
+
+// compression of streams of the saved document; streams written uncompressed, e.g. by the
+// debug build of printpdf or by post-processing, are compressed, compressed ones are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    // streams are decompressed, e.g. to read content streams when debugging; images keep
+    // their encoding
+    None,
+    // level from 0 (fastest) to 9 (smallest)
+    Flate(u32),
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::Flate(6)
+    }
+}
+
+impl Compression {
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let level = match *self {
+            Compression::None => {
+                document.decompress();
+                return Ok(());
+            }
+            Compression::Flate(level) => flate2::Compression::new(level.min(9)),
+        };
+
+        for object in document.objects.values_mut() {
+            let Object::Stream(stream) = object else {
+                continue;
+            };
+            if !stream.allows_compression || stream.dict.has(b"Filter") {
+                continue;
+            }
+            let compressed = deflate(&stream.content, level)?;
+            if compressed.len() + FILTER_OVERHEAD < stream.content.len() {
+                stream
+                    .dict
+                    .set("Filter", Object::Name(b"FlateDecode".to_vec()));
+                stream.set_content(compressed);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn deflate(content: &[u8], level: flate2::Compression) -> Result<Vec<u8>, Error> {
+    let mut encoder = ZlibEncoder::new(vec![], level);
+    encoder.write_all(content).map_err(pdf_error)?;
+    encoder.finish().map_err(pdf_error)
+}
@@ -1,25 +1,64 @@
 use std::{borrow::Borrow, sync::Arc};
 
 use layout::{
-    Error, Features, NewPageOptions, Rgba, Stroke, Style, TextPosition,
+    Error, Features, Font, Layout, NewPageOptions, Rgba, Stroke, Style, TextPosition,
     position::{Offset, Quad, Size},
-    unit::{FillPerMille, Unit},
+    unit::{Em, FillPerMille, Mm, Pt, Unit},
 };
 use printpdf::{
-    Color, IndirectFontRef, PdfDocumentReference, PdfLayerIndex, PdfLayerReference, PdfPageIndex,
-    PdfPageReference, Point, Polygon, Rgb, path::PaintMode,
+    Actions, BorderArray, ColorArray, CurTransMat, ImageTransform, IndirectFontRef, LinkAnnotation,
+    OffsetDateTime, PdfDocumentReference, PdfLayerIndex, PdfLayerReference, PdfPageIndex,
+    PdfPageReference, Point, Polygon, Rect, TextMatrix,
+    lopdf::{self, Dictionary, Object, StringFormat, content::Operation},
+    path::PaintMode,
 };
 use rtext::index_set::{self, IndexSet};
 use smol_str::{SmolStr, ToSmolStr};
 
-use crate::font::FontCache;
+use crate::font::{DecorationMetrics, FontCache, OutlineSegment, glyph_widths};
 
-use super::{from_pt, from_rgba, from_unit};
+#[cfg(feature = "encryption")]
+use super::Encryption;
+use super::{
+    Background, CancellationToken, ColorModel, Compression, Continuation, ContinuationText, Dash,
+    FontStats, FormField, Gradient, IccProfile, Image, Imposition, InitialView, Mark, Marks,
+    Markup, Note, Optimization, Outline, Overlay, PageBreakOptions, PageDecorator, PageInfo,
+    PageLabel, PageNumbering, PageTemplate, PageTemplates, PageValues, Path, PdfALevel, PdfVersion,
+    PrintMarks, QuarterTurn, RenderOptions, RenderProgress, RenderStats, SectionMark, Shadow,
+    Signature, SoftMask, SpanMark, Stationery, StrokeMark, StrokeStyle, StructureElement,
+    StructureMark, StructureMarks, TableGrid, TableOfContents, TextDecoration, TextFill, TextMode,
+    TocEntry, Transform, Watermark, WatermarkContent, XmpMetadata,
+    annotations::{PageAnnotations, text_string},
+    columns::{ColumnFlow, ColumnMark},
+    footnote::{
+        FOOTNOTE_GAP, FOOTNOTE_SEPARATOR, FootnoteLine, FootnoteMark, break_lines, lines_height,
+    },
+    from_unit,
+    header::{HeaderMark, RepeatedHeader},
+    layer_state::LayerState,
+    layers::{flatten_layers, merge_layers, stack_layers},
+    linearization::linearize,
+    master_page::{MASTER_PAGE_LAYER, stamp_master_pages},
+    overlay::anchor_offset,
+    page_break_lines::line_count,
+    page_decorator::{PageAddedCallback, PageCallback},
+    page_flow::{LinePages, reserve_page_break, split_line},
+    page_label::write_page_labels,
+    page_values::{PAGE_VALUE_CHARS, PageSlotRuns, has_page_slot, substitute_page_values},
+    pdf_a::{print_annotations, validate, write_output_intent},
+    precision::{real, round, round_content},
+    resources::{PageResources, catalog, pdf_error},
+    stroke::stroke_thickness,
+    structure::{StructureTree, identify_pdf_ua, marked_content},
+};
 
 struct RenderFont {
     name: SmolStr,
     glyph_collector: IndexSet<u16>,
     font_ref: Option<IndirectFontRef>,
+    subset_size: usize,
+    // widths of the subset glyphs as written to the font dictionary
+    widths: Vec<i64>,
 }
 
 impl RenderFont {
@@ -31,13 +70,21 @@ impl RenderFont {
             name: name.to_smolstr(),
             glyph_collector: collector,
             font_ref: None,
+            subset_size: 0,
+            widths: vec![],
         }
     }
 }
 
-pub struct RenderFonts {
+pub(crate) struct RenderFonts {
     fonts: FontCache,
     render_fonts: Vec<RenderFont>,
+    kerning: bool,
+    font_kerning: Vec<(SmolStr, bool)>,
+    // glyphs are painted as paths, fonts are not embedded
+    text_outlines: bool,
+    // subsets are written, glyphs not collected before are missing in them
+    completed: bool,
 }
 
 impl RenderFonts {
@@ -45,33 +92,112 @@ impl RenderFonts {
         Self {
             fonts,
             render_fonts: vec![],
+            kerning: true,
+            font_kerning: vec![],
+            text_outlines: false,
+            completed: false,
         }
     }
 
-    pub fn typeset(
-        &mut self,
-        font_name: &str,
-        text: &str,
-        features: &Features,
-    ) -> Result<TextPosition, Error> {
-        let glyph_collector = match self
-            .render_fonts
+    pub fn set_kerning(&mut self, kerning: bool) {
+        self.kerning = kerning;
+    }
+
+    pub fn set_font_kerning(&mut self, font_name: impl ToSmolStr, kerning: bool) {
+        let font_name = font_name.to_smolstr();
+        match self
+            .font_kerning
             .iter_mut()
-            .find(|render_font| render_font.name == font_name)
+            .find(|(name, _)| *name == font_name)
+        {
+            Some((_, font_kerning)) => *font_kerning = kerning,
+            None => self.font_kerning.push((font_name, kerning)),
+        }
+    }
+
+    pub fn set_text_outlines(&mut self, text_outlines: bool) {
+        self.text_outlines = text_outlines;
+    }
+
+    fn kerning(&self, font_name: &str) -> bool {
+        self.font_kerning
+            .iter()
+            .find(|(name, _)| name == font_name)
+            .map(|(_, kerning)| *kerning)
+            .unwrap_or(self.kerning)
+    }
+
+    fn glyph_collector(&mut self, font_name: &str) -> &mut IndexSet<u16> {
+        match self
+            .render_fonts
+            .iter()
+            .position(|render_font| render_font.name == font_name)
         {
-            Some(font) => &mut font.glyph_collector,
+            Some(index) => &mut self.render_fonts[index].glyph_collector,
             None => {
                 self.render_fonts.push(RenderFont::new(font_name));
                 &mut self.render_fonts.last_mut().unwrap().glyph_collector
             }
-        };
+        }
+    }
+
+    pub fn typeset(
+        &mut self,
+        font_name: &str,
+        text: &str,
+        features: &Features,
+    ) -> Result<TextPosition, Error> {
+        self.typeset_with(font_name, text, features, None)
+    }
 
-        self.fonts
-            .get(font_name)?
-            .typeset_collect(glyph_collector, text, features)
+    // kerning of the run overrides the one of the font
+    pub(crate) fn typeset_with(
+        &mut self,
+        font_name: &str,
+        text: &str,
+        features: &Features,
+        kerning: Option<bool>,
+    ) -> Result<TextPosition, Error> {
+        let kerning = kerning.unwrap_or_else(|| self.kerning(font_name));
+        let font = self.fonts.get(font_name)?;
+
+        let check = self.completed && !self.text_outlines;
+        let collector = self.glyph_collector(font_name);
+        if !check {
+            return font.typeset_collect(collector, text, features, kerning);
+        }
+
+        // the subset is written, so glyphs missing in it are not collected
+        let mut checked = collector.clone();
+        let position = font.typeset_collect(&mut checked, text, features, kerning)?;
+        if checked.len() > collector.len() {
+            return Err(Error::PdfWrite(
+                format!("Glyphs of '{text}' are not reserved before fonts are completed").into(),
+            ));
+        }
+        Ok(position)
+    }
+
+    // Glyphs are added to the subset ahead of typesetting, so their subset ids
+    // don't depend on which text gets rendered first.
+    pub fn reserve_glyphs(
+        &mut self,
+        font_name: &str,
+        chars: impl IntoIterator<Item = char>,
+    ) -> Result<(), Error> {
+        let font = self.fonts.get(font_name)?;
+        let text = chars.into_iter().collect::<String>();
+
+        font.reserve_collect(self.glyph_collector(font_name), text);
+
+        Ok(())
     }
 
     pub fn complete_and_write(&mut self, document: &PdfDocumentReference) -> Result<(), Error> {
+        self.completed = true;
+        if self.text_outlines {
+            return Ok(());
+        }
         for render_font in self.render_fonts.iter_mut() {
             let subsetted_font = self
                 .fonts
@@ -81,6 +207,8 @@ impl RenderFonts {
                 Some(subsetted_font) => subsetted_font,
                 None => continue,
             };
+            render_font.subset_size = subsetted_font.len();
+            render_font.widths = glyph_widths(&subsetted_font)?;
             let reader = std::io::Cursor::new(subsetted_font);
             render_font.font_ref = Some(
                 document
@@ -92,6 +220,75 @@ impl RenderFonts {
         Ok(())
     }
 
+    fn stats(&self) -> Vec<FontStats> {
+        self.render_fonts
+            .iter()
+            .map(|render_font| FontStats {
+                name: render_font.name.clone(),
+                glyphs: render_font.glyph_collector.len(),
+                subset_size: render_font.subset_size,
+            })
+            .collect()
+    }
+
+    // subset id of the space glyph, if it was typeset with the font
+    fn space_glyph(&self, font_name: &str) -> Option<u16> {
+        let font = self.fonts.get(font_name).ok()?;
+        let space = font.typeset(" ", &Features::default(), false).ok()?;
+        let glyph = space.positions.first()?.glyph_index;
+        self.render_fonts
+            .iter()
+            .find(|render_font| render_font.name == font_name)?
+            .glyph_collector
+            .get_index_of(&glyph)
+            .map(|index| index as u16)
+    }
+
+    // width of the glyph of the subset id in thousandths of em, once fonts are written
+    fn glyph_width(&self, font_name: &str, glyph: u16) -> Option<i64> {
+        self.render_fonts
+            .iter()
+            .find(|render_font| render_font.name == font_name)?
+            .widths
+            .get(glyph as usize)
+            .copied()
+    }
+
+    // outline of the glyph of the subset id, glyphs are collected when typeset
+    pub(crate) fn glyph_outline(&self, font_name: &str, glyph: u16) -> Option<Vec<OutlineSegment>> {
+        let glyph = *self
+            .render_fonts
+            .iter()
+            .find(|render_font| render_font.name == font_name)?
+            .glyph_collector
+            .get_index(glyph as usize)?;
+        match self
+            .fonts
+            .get(font_name)
+            .and_then(|font| font.outline(glyph))
+        {
+            Ok(outline) => Some(outline),
+            Err(error) => {
+                tracing::warn!("Glyph outline not available: {:?}", error);
+                None
+            }
+        }
+    }
+
+    pub fn decoration_metrics(&self, font_name: &str) -> Option<DecorationMetrics> {
+        match self
+            .fonts
+            .get(font_name)
+            .and_then(|font| font.decoration_metrics())
+        {
+            Ok(metrics) => Some(metrics),
+            Err(error) => {
+                tracing::warn!("Text decoration metrics not available: {:?}", error);
+                None
+            }
+        }
+    }
+
     pub fn get_font_ref<B>(&self, name: &B) -> Option<&IndirectFontRef>
     where
         B: Borrow<str> + ?Sized,
@@ -108,21 +305,147 @@ impl RenderFonts {
     }
 }
 
+// graphics state changes which are kept until the scope is closed, even across pages
+enum GraphicsScope {
+    Opacity(f32),
+    Clip(Path),
+    Transform(Transform),
+    SoftMask(SoftMask),
+    // text clip can not be repeated on a new page
+    TextClip,
+    // explicitly saved state
+    State,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum LinkTarget {
+    Uri(String),
+    Anchor(String),
+}
+
 pub struct RenderContext {
     fonts: RenderFonts,
 
     document: PdfDocumentReference,
     page: PdfPageReference,
     layer: PdfLayerReference,
+    // layer of the page the content goes to unless a named layer is set
+    base_layer: PdfLayerReference,
+    layer_name: Option<String>,
+    // named layers of the current page, created on the first use
+    layers: Vec<(String, PdfLayerReference)>,
+    // layers of the same name on different pages are merged into one when saved
+    merge_layers: bool,
+    // layers are stacked by z-index when saved
+    layer_z_indexes: Vec<(String, i32)>,
+    page_number: usize,
+    resources: PageResources,
+    annotations: PageAnnotations,
 
     page_margin: Quad,
     page_size: Size,
+    // sizes of pages, with the page a size starts on
+    page_sizes: Vec<(usize, Size)>,
     page_start: Option<Offset>,
     page_end: Option<Offset>,
 
     style: Arc<Style>,
+    color_model: ColorModel,
+    precision: Option<u8>,
+    compression: Option<Compression>,
+    optimization: Option<Optimization>,
+    linearization: bool,
+    icc_profiles: Vec<IccProfile>,
+    print_marks: Option<PrintMarks>,
+    xmp_metadata: Option<XmpMetadata>,
+    pdf_a: Option<PdfALevel>,
+    pdf_version: Option<PdfVersion>,
+    #[cfg(feature = "encryption")]
+    encryption: Option<Encryption>,
+    signature: Option<Signature>,
+    landscape_rotation: bool,
+    // inner margins are at the right of even pages, binding offset widens them
+    mirrored_margins: bool,
+    binding_offset: Unit,
+    // pages with portrait media box, displayed rotated
+    rotated_pages: Vec<usize>,
+    stroke_style: StrokeStyle,
+    // styles of the enclosing elements
+    stroke_styles: Vec<StrokeStyle>,
+    graphics_scopes: Vec<GraphicsScope>,
+    // state of the layer content is written to, and the states saved by graphics scopes
+    layer_state: LayerState,
+    layer_states: Vec<LayerState>,
+    text_rotation: f32,
+    text_mode: TextMode,
+    text_decoration: TextDecoration,
+    baseline_shift: Em,
+    // text runs are stretched to the width
+    text_width: Option<Unit>,
+    link: Option<LinkTarget>,
+    markups: Vec<Markup>,
+    marks: Marks,
+    // headers of the content rendered, the innermost one is repeated on new pages
+    repeated_headers: Vec<RepeatedHeader>,
+    columns: Option<ColumnFlow>,
+    // columns begun in columns are flowed in the enclosing ones
+    column_nesting: usize,
+    // lines of notes of footnotes of the current page, space is reserved for them above
+    // the bottom margin
+    footnotes: Vec<FootnoteLine>,
+    // link and decoration enclosing the spans begun
+    spans: Vec<(Option<LinkTarget>, TextDecoration)>,
+    watermark: Option<Watermark>,
+    overlays: Vec<Overlay>,
+    master_page: Option<Box<dyn Layout>>,
+    page_templates: Option<PageTemplates>,
+    // margin of pages without template, or with the template keeping it
+    document_margin: Quad,
+    page_callbacks: Vec<PageCallback>,
+    page_added_callbacks: Vec<PageAddedCallback>,
+    // of the document section rendered, in addition to those of the document
+    section_page_callbacks: Vec<PageCallback>,
+    page_numbering: Option<PageNumbering>,
+    // sections with the pages they start on, for running headers and tables of contents
+    sections: Vec<TocEntry>,
+    outline: Option<Outline>,
+    // the outline is generated from marked sections, unless it is set; hierarchical marks
+    // of headings turn it on
+    section_outline: bool,
+    // tagged documents get a structure tree, layouts tag their content through the marks
+    structure: Option<StructureTree>,
+    structure_marks: StructureMarks,
+    // conformance to PDF/UA is claimed in the metadata
+    pdf_ua: bool,
+    // layers of figures begun, their marked content ends where it begun
+    figure_layers: Vec<Option<PdfLayerReference>>,
+    language: Option<String>,
+    initial_view: Option<InitialView>,
+    stationery: Option<Stationery>,
+    background: Option<Background>,
+    imposition: Option<Imposition>,
+    // pages numbering restarts on, with the number
+    page_number_restarts: Vec<(usize, usize)>,
+    // pages label ranges start on, with the label
+    page_labels: Vec<(usize, PageLabel)>,
+    // values substituted into page value slots of typeset text, known from the previous pass
+    page_values: Option<PageValues>,
+    // a slot was substituted since reset, e.g. when an overlay is measured
+    page_values_used: bool,
+    // text with the current page slot, typeset again when it is rendered
+    page_slot_runs: PageSlotRuns,
+    continuation: Option<Continuation>,
+    fonts_completed: bool,
+    // pages are broken and counted, content is not painted
+    dry_run: bool,
     debug_frame: bool,
     debug_page_breaks: bool,
+    debug_text_metrics: bool,
+    progress: Option<Box<dyn Fn(RenderProgress)>>,
+    cancellation: Option<CancellationToken>,
+    // images painted and bytes of their data
+    images: usize,
+    image_bytes: usize,
 
     page_break_reservations: Vec<bool>, // bool = avoid break
 }
@@ -143,14 +466,92 @@ impl RenderContext {
             fonts: RenderFonts::new(fonts),
             document,
             page,
+            base_layer: layer.clone(),
             layer,
+            layer_name: None,
+            layers: vec![],
+            merge_layers: false,
+            layer_z_indexes: vec![],
+            page_number: 0,
+            resources: PageResources::default(),
+            annotations: PageAnnotations::default(),
             page_margin: margin,
+            page_sizes: vec![(0, size.clone())],
             page_size: size,
             page_start: None,
             page_end: None,
             style: Style::new_default(),
+            color_model: ColorModel::Rgb,
+            precision: None,
+            compression: None,
+            optimization: None,
+            linearization: false,
+            icc_profiles: vec![],
+            print_marks: None,
+            xmp_metadata: None,
+            pdf_a: None,
+            pdf_version: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            signature: None,
+            landscape_rotation: false,
+            mirrored_margins: false,
+            binding_offset: Unit::zero(),
+            rotated_pages: vec![],
+            stroke_style: StrokeStyle::Solid,
+            stroke_styles: vec![],
+            graphics_scopes: vec![],
+            layer_state: LayerState::initial(),
+            layer_states: vec![],
+            text_rotation: 0.0,
+            text_mode: TextMode::Fill,
+            text_decoration: TextDecoration::empty(),
+            baseline_shift: Em(0.0),
+            text_width: None,
+            link: None,
+            markups: vec![],
+            marks: Marks::default(),
+            repeated_headers: vec![],
+            columns: None,
+            column_nesting: 0,
+            footnotes: vec![],
+            spans: vec![],
+            watermark: None,
+            overlays: vec![],
+            master_page: None,
+            page_templates: None,
+            document_margin: Quad::empty(),
+            page_callbacks: vec![],
+            page_added_callbacks: vec![],
+            section_page_callbacks: vec![],
+            page_numbering: None,
+            sections: vec![],
+            outline: None,
+            section_outline: false,
+            structure: None,
+            structure_marks: StructureMarks::default(),
+            pdf_ua: false,
+            figure_layers: vec![],
+            language: None,
+            initial_view: None,
+            stationery: None,
+            background: None,
+            imposition: None,
+            page_number_restarts: vec![],
+            page_labels: vec![],
+            page_values: None,
+            page_values_used: false,
+            page_slot_runs: PageSlotRuns::default(),
+            continuation: None,
+            fonts_completed: false,
+            dry_run: false,
             debug_frame: false,
             debug_page_breaks: false,
+            debug_text_metrics: false,
+            progress: None,
+            cancellation: None,
+            images: 0,
+            image_bytes: 0,
             page_break_reservations: vec![],
         };
         render_context.set_page_offsets(Unit::from(0));
@@ -158,316 +559,6146 @@ impl RenderContext {
         render_context
     }
 
-    pub fn with_debug_frame(mut self, debug_frame: bool) -> Self {
-        self.debug_frame = debug_frame;
+    pub fn with_progress(mut self, progress: impl Fn(RenderProgress) + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
         self
     }
 
-    pub fn with_debug_page_breaks(mut self, debug_page_breaks: bool) -> Self {
-        self.debug_page_breaks = debug_page_breaks;
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
         self
     }
 
-    pub fn complete_fonts(&mut self) -> Result<(), Error> {
-        self.fonts.complete_and_write(&self.document)
+    pub(crate) fn report(&self, progress: RenderProgress) {
+        if let Some(report) = &self.progress {
+            report(progress);
+        }
     }
 
-    pub fn save_to_bytes(self) -> Result<Vec<u8>, Error> {
-        self.document
-            .save_to_bytes()
-            .map_err(|error| Error::PdfWrite(error.to_string().into()))
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
     }
 
-    fn page_content_offset(&self, content_offset: &Offset) -> Offset {
-        match &self.page_start {
-            Some(page_start) => content_offset - page_start,
-            None => content_offset.clone(),
+    // phases are timed by the renderer
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            pages: self.page_number + 1,
+            fonts: self.fonts.stats(),
+            images: self.images,
+            image_bytes: self.image_bytes,
+            phases: vec![],
         }
     }
 
-    fn swap_y(&self, page_position: &Offset) -> Offset {
-        Offset::new(
-            page_position.x,
-            self.page_size.base_height() - page_position.y,
-        )
+    pub(crate) fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
     }
 
-    fn new_page(&mut self, margin: Option<&Quad>, size: Option<&Size>) {
-        if let Some(margin) = margin {
-            self.page_margin = margin.clone();
-        }
-        if let Some(size) = size {
-            self.page_size = size.clone();
+    // every option is replaced, those not set in the options are cleared
+    pub(crate) fn with_options(mut self, options: &RenderOptions) -> Self {
+        self.debug_frame = options.debug_frame;
+        self.debug_page_breaks = options.debug_page_breaks;
+        self.debug_text_metrics = options.debug_text_metrics;
+        self.fonts.set_kerning(options.kerning);
+        self.fonts.set_text_outlines(options.text_outlines);
+        self.color_model = options.color_model;
+        self.icc_profiles = vec![];
+        for icc_profile in options.icc_profiles.iter() {
+            self = self.with_icc_profile(icc_profile.clone());
         }
+        self.precision = options.precision;
+        self.compression = options.compression;
+        self.optimization = options.optimization;
+        self.linearization = options.linearization;
+        self.print_marks = options.print_marks.clone();
+        self.landscape_rotation = options.landscape_rotation;
+        self.mirrored_margins = options.mirrored_margins;
+        self.binding_offset = options.binding_offset;
+        self
+    }
 
-        self.page_start = None;
-        self.page_end = None;
-
-        let (page, layer) = self.document.add_page(
-            from_unit(self.page_size.base_width()),
-            from_unit(self.page_size.base_height()),
-            "default",
-        );
-
-        self.page = self.document.get_page(page);
-        self.layer = self.page.get_layer(layer);
+    pub fn with_debug_frame(mut self, debug_frame: bool) -> Self {
+        self.debug_frame = debug_frame;
+        self
     }
 
-    fn check_page_break(
-        &mut self,
-        content_offset: impl Into<Unit>,
-        content_height: impl Into<Unit>,
-        reserve_content_height: bool,
-    ) -> bool {
-        if Some(&true) == self.page_break_reservations.last() {
-            if reserve_content_height {
-                // keep stack symmetrical to pop(), which is unconditional
-                self.page_break_reservations.push(true);
-            }
-            return false;
-        }
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.fonts.set_kerning(kerning);
+        self
+    }
 
-        let content_offset = content_offset.into();
-        let content_height = content_height.into();
+    pub fn with_font_kerning(mut self, font_name: impl ToSmolStr, kerning: bool) -> Self {
+        self.fonts.set_font_kerning(font_name, kerning);
+        self
+    }
 
-        let check_page_break = if reserve_content_height {
-            let fits_into_page =
-                content_height <= self.page_size.base_height() - self.page_margin.height();
-            self.page_break_reservations.push(fits_into_page);
-            fits_into_page
-        } else {
-            true
-        };
+    // text is not searchable nor selectable, but the document has no fonts
+    pub fn with_text_outlines(mut self, text_outlines: bool) -> Self {
+        self.fonts.set_text_outlines(text_outlines);
+        self
+    }
 
-        let mut new_page = false;
-        if let Some(page_end) = &self.page_end {
-            if !check_page_break {
-                tracing::debug!(
-                    "Page OVERFLOWN at offset {content_offset:?}, content height {content_height:?}, page end {:?}",
-                    page_end.y
-                );
-            } else if content_offset + content_height > page_end.y {
-                if self.debug_page_breaks {
-                    tracing::debug!(
-                        "Page BREAK at offset {content_offset:?}, content height {content_height:?}, page end {:?}",
-                        page_end.y
-                    );
-                }
+    pub fn with_debug_page_breaks(mut self, debug_page_breaks: bool) -> Self {
+        self.debug_page_breaks = debug_page_breaks;
+        self
+    }
 
-                self.new_page(None, None);
-                new_page = true;
-            } else if self.debug_page_breaks {
-                tracing::debug!(
-                    "Page fill CHECK at offset {content_offset:?}, content height {content_height:?}, page end {:?}",
-                    page_end.y
-                );
-            }
-        }
+    // baseline, ascent and descent of every text run, with ticks at glyph advances
+    pub fn with_debug_text_metrics(mut self, debug_text_metrics: bool) -> Self {
+        self.debug_text_metrics = debug_text_metrics;
+        self
+    }
 
-        if self.page_start.is_none() {
-            self.set_page_offsets(content_offset);
-        }
+    // all colors painted are converted to the model, e.g. to CMYK for offset printing
+    pub fn with_color_model(mut self, color_model: ColorModel) -> Self {
+        self.color_model = color_model;
+        self
+    }
 
-        new_page
+    // coordinates are rounded to the decimals (in pt) when saved, equal positions computed
+    // differently then render the same, and the output gets smaller
+    pub fn with_precision(mut self, decimals: u8) -> Self {
+        self.precision = Some(decimals);
+        self
     }
 
-    fn set_page_offsets(&mut self, content_offset: Unit) {
-        let page_start = Offset::new(Unit::zero(), content_offset);
+    // streams are left as printpdf and post-processing write them unless set
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
 
-        let mut page_end = page_start.clone();
-        page_end.x_advance(self.page_size.base_width() - self.page_margin.width());
-        page_end.y_advance(self.page_size.base_height() - self.page_margin.height());
+    // runs when the document is saved, after everything else is written to it
+    pub fn with_optimization(mut self, optimization: Optimization) -> Self {
+        self.optimization = Some(optimization);
+        self
+    }
 
-        self.page_start = Some(page_start);
-        self.page_end = Some(page_end);
+    // the first page is written first with hints to the rest, so viewers display it before
+    // the whole file is downloaded; it does not go with encryption
+    pub fn with_linearization(mut self, linearization: bool) -> Self {
+        self.linearization = linearization;
+        self
     }
 
-    fn line(&self, content_points: &[&Offset]) {
-        let line_points = content_points.iter().map(|point| {
-            let position = self.swap_y(point);
-            (
-                Point::new(from_unit(position.x), from_unit(position.y)),
-                false,
-            )
-        });
+    // one profile per color model, the later one replaces the former
+    pub fn with_icc_profile(mut self, icc_profile: IccProfile) -> Self {
+        self.icc_profiles
+            .retain(|profile| profile.color_model() != icc_profile.color_model());
+        self.icc_profiles.push(icc_profile);
+        self
+    }
 
-        let mut polygon = Polygon::from_iter(line_points);
-        polygon.mode = PaintMode::Stroke;
+    pub fn with_print_marks(mut self, print_marks: PrintMarks) -> Self {
+        self.print_marks = Some(print_marks);
+        self
+    }
 
-        self.layer.add_polygon(polygon);
+    // landscape pages are added with the portrait media box and turned by the page
+    // rotation, so they print on the same paper as the rest, instead of swapped sizes
+    pub fn with_landscape_rotation(mut self, landscape_rotation: bool) -> Self {
+        self.landscape_rotation = landscape_rotation;
+        self
     }
-}
 
-impl layout::MeasureContext for RenderContext {
-    fn style(&self) -> &Style {
-        self.style.as_ref()
+    // for duplex printing, left and right margins of odd pages are inner and outer ones,
+    // even pages have them swapped
+    pub fn with_mirrored_margins(mut self, mirrored_margins: bool) -> Self {
+        self.mirrored_margins = mirrored_margins;
+        self
     }
 
-    fn typeset(&mut self, style: &Style, text: &str) -> Result<TextPosition, Error> {
-        let font = style.font().merge(self.style.font());
-        if let Some(name) = font.name()
-            && font.size().is_some()
-        {
-            self.fonts
-                .typeset(name, text, &font.features().cloned().unwrap_or_default())
-        } else {
-            Err(Error::UnknownFont("Font name or size is undefined".into()))
-        }
+    // added to the inner margin of every page, e.g. for the binding; content of pages is
+    // narrower by the offset
+    pub fn with_binding_offset(mut self, binding_offset: impl Into<Unit>) -> Self {
+        self.binding_offset = binding_offset.into();
+        self
     }
-}
 
-impl layout::RenderContext for RenderContext {
-    fn debug_frame(&mut self, content_position: &Offset, size: &Size) {
-        if self.debug_frame {
-            let content_position = self.page_content_offset(content_position);
-            let top_left = self.page_margin.offset(&content_position);
-            let bottom_right = &top_left + size;
+    // document info, the title is set when the document is created
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.document = self.document.with_author(author);
+        self
+    }
 
-            let points = [
-                &top_left,
-                &Offset::new(bottom_right.x, top_left.y),
-                &bottom_right,
-                &Offset::new(top_left.x, bottom_right.y),
-                &top_left,
-            ];
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.document = self.document.with_subject(subject);
+        self
+    }
 
-            self.layer
-                .set_outline_color(from_rgba(&Rgba::from((240, 240, 240, 1.0))));
-            self.layer.set_outline_thickness(0.25);
+    // keywords are joined by commas
+    pub fn with_keywords(mut self, keywords: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let keywords = keywords
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<String>>();
+        self.document = self.document.with_keywords(keywords);
+        self
+    }
 
-            RenderContext::line(self, &points);
-        }
+    // application the document was created by
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.document = self.document.with_creator(creator);
+        self
     }
 
-    fn check_page_break(&mut self, offset: Unit, height: Unit, reserve_height: bool) -> bool {
-        RenderContext::check_page_break(self, offset, height, reserve_height)
+    // both dates are the current time unless set
+    pub fn with_creation_date(mut self, date: OffsetDateTime) -> Self {
+        self.document = self.document.with_creation_date(date);
+        self
     }
 
-    fn release_page_break_reservation(&mut self) {
-        if self.page_break_reservations.pop().is_none() {
-            tracing::warn!("Page break reservation released when not defined.");
-        }
+    pub fn with_modification_date(mut self, date: OffsetDateTime) -> Self {
+        self.document = self.document.with_mod_date(date);
+        self
     }
 
-    fn new_page(&mut self, options: Option<NewPageOptions>) {
-        RenderContext::new_page(
-            self,
-            options.as_ref().and_then(|options| options.margin.as_ref()),
-            options.as_ref().and_then(|options| options.size.as_ref()),
-        );
+    pub fn with_xmp_metadata(mut self, xmp_metadata: XmpMetadata) -> Self {
+        self.xmp_metadata = Some(xmp_metadata);
+        self
     }
 
-    fn line(&mut self, from: &Offset, to: &Offset, stroke: &Stroke) {
-        self.check_page_break(from.y, 0, false);
+    // the document is identified as conforming to the profile, the output intent is the
+    // icc profile of the color model; saving fails if a feature the profile forbids is used
+    pub fn with_pdf_a(mut self, level: PdfALevel) -> Self {
+        self.pdf_a = Some(level);
+        self
+    }
 
-        let from = self.page_content_offset(from);
-        let from = self.page_margin.offset(&from);
+    // layers are flattened below 1.5, transparency fails saving below 1.4
+    pub fn with_pdf_version(mut self, version: PdfVersion) -> Self {
+        self.pdf_version = Some(version);
+        self
+    }
 
-        let to = self.page_content_offset(to);
-        let to = self.page_margin.offset(&to);
+    // the version is raised to the one of the encryption method, unless it is selected
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption(mut self, encryption: Encryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
 
-        self.layer.set_outline_color(from_rgba(stroke.color()));
-        self.layer
-            .set_outline_thickness(stroke.thickness().0 as f32);
+    // the document is signed into the signature form field of the name, once saved
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
 
-        RenderContext::line(self, &[&from, &to]);
+    pub fn reserve_glyphs(
+        &mut self,
+        font_name: &str,
+        chars: impl IntoIterator<Item = char>,
+    ) -> Result<(), Error> {
+        self.fonts.reserve_glyphs(font_name, chars)
     }
 
-    fn text(
+    // strokes drawn by the closure are in the style, e.g. lines, rects and paths drawn
+    // directly; layouts get it by strokes of the marks
+    pub fn with_stroke_style<R>(
         &mut self,
-        content_position: &Offset,
-        style: &Style,
-        text: &TextPosition,
-        position_is_baseline: bool,
-    ) {
-        if text.positions.is_empty() {
+        stroke_style: StrokeStyle,
+        render: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        self.take_stroke_mark(StrokeMark::Begin(stroke_style));
+        let result = render(self);
+        self.take_stroke_mark(StrokeMark::End);
+        result
+    }
+
+    fn take_stroke_mark(&mut self, mark: StrokeMark) {
+        match mark {
+            StrokeMark::Begin(stroke_style) => {
+                let outer = std::mem::replace(&mut self.stroke_style, stroke_style);
+                self.stroke_styles.push(outer);
+            }
+            StrokeMark::End => {
+                self.stroke_style = self.stroke_styles.pop().unwrap_or_default();
+            }
+        }
+    }
+
+    fn stroke_dash(&self, stroke: &Stroke) -> Option<Dash> {
+        self.stroke_style.dash(stroke.thickness())
+    }
+
+    fn apply_dash(&self, stroke: &Stroke) {
+        if let Some(dash) = self.stroke_dash(stroke) {
+            for operation in dash.operations() {
+                self.layer.add_operation(operation);
+            }
+        }
+    }
+
+    // everything rendered by the closure is painted semi-transparent,
+    // alpha of nested scopes multiplies; alpha of colors, e.g. of borders and text of
+    // styles, multiplies it as well, colors of zero alpha are not visible
+    pub fn with_opacity<R>(&mut self, alpha: f32, render: impl FnOnce(&mut Self) -> R) -> R {
+        self.push_graphics_scope(GraphicsScope::Opacity(alpha.clamp(0.0, 1.0)));
+        let result = render(self);
+        self.pop_graphics_scope();
+        result
+    }
+
+    // text rendered by the closure is rotated counterclockwise around its position
+    pub fn with_text_rotation<R>(
+        &mut self,
+        degrees: f32,
+        render: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let rotation = std::mem::replace(&mut self.text_rotation, degrees);
+        let result = render(self);
+        self.text_rotation = rotation;
+        result
+    }
+
+    // text rendered by the closure uses the mode, with TextMode::Clip the content
+    // rendered after the text in the closure is clipped by its glyphs
+    pub fn with_text_mode<R>(&mut self, mode: TextMode, render: impl FnOnce(&mut Self) -> R) -> R {
+        let clip = matches!(mode, TextMode::Clip);
+        if clip {
+            self.push_graphics_scope(GraphicsScope::TextClip);
+        }
+
+        let mode = std::mem::replace(&mut self.text_mode, mode);
+        let result = render(self);
+        self.text_mode = mode;
+
+        if clip {
+            self.pop_graphics_scope();
+        }
+        result
+    }
+
+    // text rendered by the closure gets rules positioned by font metrics
+    pub fn with_text_decoration<R>(
+        &mut self,
+        decoration: TextDecoration,
+        render: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let decoration = std::mem::replace(&mut self.text_decoration, decoration);
+        let result = render(self);
+        self.text_decoration = decoration;
+        result
+    }
+
+    // text rendered by the closure is raised by the shift relative to its font size,
+    // negative shift lowers it, e.g. for subscripts
+    pub fn with_baseline_shift<R>(&mut self, shift: Em, render: impl FnOnce(&mut Self) -> R) -> R {
+        let shift = std::mem::replace(&mut self.baseline_shift, shift);
+        let result = render(self);
+        self.baseline_shift = shift;
+        result
+    }
+
+    // text runs rendered by the closure are justified to the width, by wider spaces
+    // between words, or by spacing of letters if there is a single word only
+    pub fn with_text_width<R>(
+        &mut self,
+        width: impl Into<Unit>,
+        render: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let width = self.text_width.replace(width.into());
+        let result = render(self);
+        self.text_width = width;
+        result
+    }
+
+    // text rendered by the closure is marked up, nested markups are all applied
+    pub fn with_markup<R>(&mut self, markup: Markup, render: impl FnOnce(&mut Self) -> R) -> R {
+        self.markups.push(markup);
+        let result = render(self);
+        self.markups.pop();
+        result
+    }
+
+    // spans, links, paths, footnotes, columns, page breaks and headers queued to the marks by
+    // layouts are taken over in the order they were queued
+    pub fn with_marks(mut self, marks: Marks) -> Self {
+        marks.wire();
+        self.marks = marks;
+        self
+    }
+
+    fn take_marks(&mut self, marks: Vec<Mark>) {
+        for mark in marks {
+            match mark {
+                Mark::Span(mark) => self.take_span_mark(mark),
+                Mark::Path((path, fill, stroke)) => {
+                    if !self.dry_run {
+                        self.path(&path, fill.as_ref(), stroke.as_ref());
+                    }
+                }
+                Mark::Footnote(mark) => self.take_footnote_mark(mark),
+                Mark::Link((offset, size, target)) => {
+                    if !self.dry_run {
+                        self.box_link(&offset, &size, &target);
+                    }
+                }
+                Mark::Column(mark) => self.take_column_mark(mark),
+                Mark::Header(HeaderMark::Begin(header)) => self.repeated_headers.push(header),
+                Mark::Header(HeaderMark::End) => {
+                    self.repeated_headers.pop();
+                }
+                Mark::Stroke(mark) => self.take_stroke_mark(mark),
+                Mark::Section(mark) => self.take_section_mark(mark),
+                // options of a page break not checked are stale
+                Mark::PageBreak(_) => (),
+            }
+        }
+    }
+
+    // the marker at the offset goes to a new page with its note if both do not fit
+    fn take_footnote_mark(&mut self, (offset, height, note): FootnoteMark) {
+        let width = from_unit(self.page_size.base_width() - self.margin_width())
+            .into_pt()
+            .0;
+        let lines = break_lines(&note, width, |style, position| {
+            self.text_extent(style, position)
+        });
+        let reserved = |footnotes: &[FootnoteLine]| match footnotes.is_empty() {
+            true => lines_height(&lines) + FOOTNOTE_GAP.into(),
+            false => lines_height(&lines),
+        };
+
+        self.check_page_break(offset, height + reserved(&self.footnotes), false);
+        let reserved = reserved(&self.footnotes);
+        if let Some(page_end) = self.page_end.as_mut() {
+            page_end.y = page_end.y - reserved;
+        }
+        if let Some(columns) = self.columns.as_mut() {
+            columns.page_end = columns.page_end - reserved;
+        }
+        self.footnotes.extend(lines);
+    }
+
+    // the page starts with the header above the content at the offset; a header that does
+    // not fit into the page itself is not repeated on pages it breaks to
+    fn repeat_header(&mut self, content_offset: Unit) {
+        let Some(header) = self.repeated_headers.last().cloned() else {
+            return;
+        };
+        let Ok(mut layout) = header.layout.try_borrow_mut() else {
+            return;
+        };
+
+        let height = header.size.base_height();
+        if let Some(page_start) = self.page_start.as_mut() {
+            page_start.y = page_start.y - height;
+        }
+        if let Some(page_end) = self.page_end.as_mut() {
+            page_end.y = page_end.y - height;
+        }
+
+        let offset = Offset::new(header.offset.x, content_offset - height);
+        let result = layout
+            .lay_out(self, offset, header.size.clone())
+            .and_then(|_| layout.render(self));
+        if let Err(error) = result {
+            tracing::warn!("Header not repeated: {:?}", error);
+        }
+    }
+
+    // columns flow content of their layouts side by side
+    fn take_column_mark(&mut self, mark: ColumnMark) {
+        match mark {
+            ColumnMark::Begin(content_offset, count, pitch, height) => {
+                self.column_nesting += 1;
+                if self.column_nesting > 1 {
+                    return;
+                }
+                let (Some(page_start), Some(page_end)) = (&self.page_start, &self.page_end) else {
+                    return;
+                };
+                let top = content_offset - page_start.y;
+                self.columns = Some(ColumnFlow {
+                    count,
+                    pitch,
+                    end: content_offset + height,
+                    index: 0,
+                    top,
+                    bottom: top,
+                    page_end: page_end.y - page_start.y,
+                });
+                self.set_column(content_offset);
+            }
+            ColumnMark::End(content_offset) => {
+                self.column_nesting = self.column_nesting.saturating_sub(1);
+                if self.column_nesting == 0 {
+                    self.end_columns(content_offset);
+                }
+            }
+        }
+    }
+
+    // content at the offset goes to the top of the column of the index, the column ends
+    // when the rest of the content is balanced over columns left, the last one takes the
+    // rest of the page
+    fn set_column(&mut self, content_offset: Unit) {
+        let Some(columns) = &self.columns else {
+            return;
+        };
+        let mm = |unit: Unit| Mm::from(unit).0;
+        let page_start = Offset::new(
+            Mm(-mm(columns.pitch) * columns.index as f64),
+            content_offset - columns.top,
+        );
+        let page_end = page_start.y + columns.page_end;
+        let left = (columns.count - columns.index) as f64;
+        let balanced = Unit::from(Mm(
+            mm(content_offset) + (mm(columns.end) - mm(content_offset)).max(0.0) / left
+        ));
+        let end = match columns.index + 1 < columns.count && balanced < page_end {
+            true => balanced,
+            false => page_end,
+        };
+
+        self.page_start = Some(page_start);
+        if let Some(page_end) = self.page_end.as_mut() {
+            page_end.y = end;
+        }
+    }
+
+    // content at the offset did not fit into the column, returns false for the last one
+    fn next_column(&mut self, content_offset: Unit) -> bool {
+        let Some(columns) = self.columns.as_mut() else {
+            return false;
+        };
+        if columns.index + 1 >= columns.count {
+            return false;
+        }
+        if let Some(page_start) = &self.page_start {
+            let bottom = content_offset - page_start.y;
+            if bottom > columns.bottom {
+                columns.bottom = bottom;
+            }
+        }
+        columns.index += 1;
+        self.set_column(content_offset);
+        true
+    }
+
+    // content after the columns continues below the deepest of them
+    fn end_columns(&mut self, content_offset: Unit) {
+        let Some(columns) = self.columns.take() else {
+            return;
+        };
+        let Some(page_start) = &self.page_start else {
+            return;
+        };
+        let mut bottom = content_offset - page_start.y;
+        if columns.bottom > bottom {
+            bottom = columns.bottom;
+        }
+
+        let page_start = Offset::new(Unit::zero(), content_offset - bottom);
+        if let Some(page_end) = self.page_end.as_mut() {
+            page_end.y = page_start.y + columns.page_end;
+        }
+        self.page_start = Some(page_start);
+    }
+
+    // notes go above the bottom margin of the page being finished, below a separator rule
+    // of a third of the content width
+    fn paint_footnotes(&mut self) {
+        let footnotes = std::mem::take(&mut self.footnotes);
+        if footnotes.is_empty() || self.dry_run || !self.fonts_completed {
+            return;
+        }
+
+        let left = self.margin_offset(&Offset::zero());
+        let width = self.page_size.base_width() - self.margin_width();
+        let bottom = left.y + self.page_size.base_height() - self.page_margin.height();
+        let mut top = bottom - lines_height(&footnotes);
+        let mm = |unit: Unit| Mm::from(unit).0;
+
+        self.with_page_position(|rctx| {
+            let rule = Unit::from(Mm(mm(top) - mm(FOOTNOTE_GAP.into()) / 2.0));
+            let stroke = Stroke::new(Rgba::black(), FOOTNOTE_SEPARATOR);
+            rctx.with_color_alpha(None, Some(stroke.color()), |rctx| {
+                rctx.paint_line(
+                    &Offset::new(left.x, rule),
+                    &Offset::new(Mm(mm(left.x) + mm(width) / 3.0), rule),
+                    &stroke,
+                )
+            });
+            for line in footnotes.iter() {
+                let baseline = top + Pt(line.ascent as f64).into();
+                for (x, word) in line.words.iter() {
+                    let offset = Offset::new(left.x + Pt(*x as f64).into(), baseline);
+                    layout::RenderContext::text(rctx, &offset, &line.style, word, true);
+                }
+                top = top + Pt(line.height as f64).into();
+            }
+        });
+    }
+
+    fn take_span_mark(&mut self, mark: SpanMark) {
+        match mark {
+            SpanMark::Begin(span) => {
+                self.spans.push((self.link.clone(), self.text_decoration));
+                if let Some(link) = span.link {
+                    self.link = Some(link);
+                }
+                if let Some(decoration) = span.decoration {
+                    self.text_decoration = decoration;
+                }
+            }
+            SpanMark::End => {
+                if let Some((link, decoration)) = self.spans.pop() {
+                    self.link = link;
+                    self.text_decoration = decoration;
+                }
+            }
+        }
+    }
+
+    // content rendered by the closure is positioned relative to the physical page, e.g. fold
+    // marks or address windows; margins and the content flow are ignored, no page breaks are made
+    pub fn with_page_position<R>(&mut self, render: impl FnOnce(&mut Self) -> R) -> R {
+        let page_start = self.page_start.replace(Offset::zero());
+        let page_end = self.page_end.take();
+        let page_margin = std::mem::replace(&mut self.page_margin, Quad::empty());
+        let mirrored_margins = std::mem::replace(&mut self.mirrored_margins, false);
+        let binding_offset = std::mem::replace(&mut self.binding_offset, Unit::zero());
+        let result = render(self);
+        self.page_start = page_start;
+        self.page_end = page_end;
+        self.page_margin = page_margin;
+        self.mirrored_margins = mirrored_margins;
+        self.binding_offset = binding_offset;
+        result
+    }
+
+    // content rendered goes to the named layer, viewers can toggle layers (optional
+    // content groups) of the same name on all pages at once
+    pub fn with_layer<R>(&mut self, name: &str, render: impl FnOnce(&mut Self) -> R) -> R {
+        let layer_name = self.layer_name.replace(name.to_string());
+        self.switch_layer();
+        let result = render(self);
+        self.layer_name = layer_name;
+        self.switch_layer();
+        result
+    }
+
+    // content of layers is stacked by z-index, so it can be rendered in any order, e.g.
+    // a background after the content above it; layers of the same z-index are stacked
+    // as created, page content has z-index 0, overlays and debug output are on top
+    pub fn set_layer_z_index(&mut self, name: &str, z_index: i32) {
+        match self
+            .layer_z_indexes
+            .iter_mut()
+            .find(|(existing, _)| existing == name)
+        {
+            Some((_, existing)) => *existing = z_index,
+            None => self.layer_z_indexes.push((name.to_string(), z_index)),
+        }
+    }
+
+    // content rendered by the closure goes to the layer of the z-index
+    pub fn with_z_index<R>(&mut self, z_index: i32, render: impl FnOnce(&mut Self) -> R) -> R {
+        let name = format!("z-index {z_index}");
+        self.set_layer_z_index(&name, z_index);
+        self.with_layer(&name, render)
+    }
+
+    // open graphics scopes are closed in the layer left and reopened in the other one
+    fn switch_layer(&mut self) {
+        let scopes = self.graphics_scopes.len();
+        for _ in 0..scopes {
+            self.layer.restore_graphics_state();
+        }
+        // the layer may have been used before, its state is not known
+        self.layer_state = LayerState::default();
+        self.layer_states.fill(LayerState::default());
+
+        self.layer = match self.layer_name.clone() {
+            Some(name) => self.named_layer(&name),
+            None => self.base_layer.clone(),
+        };
+
+        for index in 0..scopes {
+            self.layer.save_graphics_state();
+            self.apply_graphics_scope(index);
+        }
+    }
+
+    // e.g. page furniture painted while content is clipped or transformed; open scopes are
+    // closed for the closure and reopened after it
+    fn outside_graphics_scopes<R>(&mut self, paint: impl FnOnce(&mut Self) -> R) -> R {
+        let scopes = self.graphics_scopes.len();
+        for _ in 0..scopes {
+            self.layer.restore_graphics_state();
+        }
+        if let Some(layer_state) = self.layer_states.first() {
+            self.layer_state = layer_state.clone();
+        }
+
+        let result = paint(self);
+
+        // the closure painted in the state the scopes started from
+        self.layer_states.fill(self.layer_state.clone());
+        for index in 0..scopes {
+            self.layer.save_graphics_state();
+            self.apply_graphics_scope(index);
+        }
+        result
+    }
+
+    fn named_layer(&mut self, name: &str) -> PdfLayerReference {
+        self.merge_layers = true;
+        if let Some((_, layer)) = self.layers.iter().find(|(existing, _)| existing == name) {
+            return layer.clone();
+        }
+
+        let layer = self.page.add_layer(name);
+        if let Some(matrix) = self.page_rotation() {
+            layer.set_ctm(CurTransMat::Raw(matrix));
+        }
+        self.layers.push((name.to_string(), layer.clone()));
+        layer
+    }
+
+    // content outside of the path is not painted until pop_clip
+    pub fn push_clip(&mut self, clip: &Path) {
+        self.push_graphics_scope(GraphicsScope::Clip(clip.clone()));
+    }
+
+    pub fn pop_clip(&mut self) {
+        if matches!(self.graphics_scopes.last(), Some(GraphicsScope::Clip(_))) {
+            self.pop_graphics_scope();
+        } else {
+            tracing::warn!("Clip popped when not pushed.");
+        }
+    }
+
+    // content is transformed until pop_transform, page breaks are checked
+    // with untransformed positions
+    pub fn push_transform(&mut self, transform: &Transform) {
+        self.push_graphics_scope(GraphicsScope::Transform(transform.clone()));
+    }
+
+    pub fn pop_transform(&mut self) {
+        if matches!(
+            self.graphics_scopes.last(),
+            Some(GraphicsScope::Transform(_))
+        ) {
+            self.pop_graphics_scope();
+        } else {
+            tracing::warn!("Transform popped when not pushed.");
+        }
+    }
+
+    // content is masked until pop_soft_mask
+    pub fn push_soft_mask(&mut self, soft_mask: &SoftMask) {
+        self.push_graphics_scope(GraphicsScope::SoftMask(soft_mask.clone()));
+    }
+
+    pub fn pop_soft_mask(&mut self) {
+        if matches!(
+            self.graphics_scopes.last(),
+            Some(GraphicsScope::SoftMask(_))
+        ) {
+            self.pop_graphics_scope();
+        } else {
+            tracing::warn!("Soft mask popped when not pushed.");
+        }
+    }
+
+    // changes done until restore_state, e.g. by layer operations of user extensions, do not
+    // leak to content rendered later
+    pub fn save_state(&mut self) {
+        self.push_graphics_scope(GraphicsScope::State);
+    }
+
+    pub fn restore_state(&mut self) {
+        if let Some(GraphicsScope::State) = self.graphics_scopes.last() {
+            self.pop_graphics_scope();
+        } else {
+            tracing::warn!("State restored when not saved.");
+        }
+    }
+
+    fn push_graphics_scope(&mut self, scope: GraphicsScope) {
+        self.graphics_scopes.push(scope);
+        self.layer_states.push(self.layer_state.clone());
+        self.layer.save_graphics_state();
+        self.apply_graphics_scope(self.graphics_scopes.len() - 1);
+    }
+
+    fn pop_graphics_scope(&mut self) {
+        self.layer.restore_graphics_state();
+        self.graphics_scopes.pop();
+        if let Some(layer_state) = self.layer_states.pop() {
+            self.layer_state = layer_state;
+        }
+    }
+
+    fn apply_graphics_scope(&mut self, index: usize) {
+        match &self.graphics_scopes[index] {
+            GraphicsScope::Opacity(_) => {
+                let alpha = scopes_opacity(&self.graphics_scopes[..=index]);
+                self.apply_opacity(alpha);
+            }
+            GraphicsScope::Clip(path) => {
+                for operation in path.operations(|point| self.page_point(point)) {
+                    self.layer.add_operation(operation);
+                }
+                self.layer
+                    .add_operation(Operation::new(path.clip_operator(), vec![]));
+                self.layer.add_operation(Operation::new("n", vec![]));
+            }
+            GraphicsScope::Transform(transform) => {
+                let matrix = transform.matrix(|point| self.page_point(point));
+                self.layer.set_ctm(CurTransMat::Raw(matrix));
+            }
+            GraphicsScope::SoftMask(soft_mask) => {
+                let state = soft_mask.graphics_state(
+                    |point| self.page_point(point),
+                    from_unit(self.page_size.base_width()).into_pt().0,
+                    from_unit(self.page_size.base_height()).into_pt().0,
+                );
+                match state {
+                    Ok(state) => {
+                        let state = self.add_resource("ExtGState", state);
+                        self.layer.add_operation(Operation::new(
+                            "gs",
+                            vec![Object::Name(state.into_bytes())],
+                        ));
+                    }
+                    Err(error) => tracing::warn!("Soft mask not applied: {:?}", error),
+                }
+            }
+            GraphicsScope::TextClip | GraphicsScope::State => {}
+        }
+    }
+
+    fn apply_opacity(&mut self, alpha: f32) {
+        self.apply_alpha(alpha, alpha);
+    }
+
+    fn apply_alpha(&mut self, fill: f32, stroke: f32) {
+        let state = self.resources.add_shared(
+            self.page_number,
+            "ExtGState",
+            format!("alpha {fill} {stroke}"),
+            || {
+                let mut state = Dictionary::new();
+                state.set("Type", Object::Name(b"ExtGState".to_vec()));
+                state.set("CA", Object::Real(stroke));
+                state.set("ca", Object::Real(fill));
+                state.into()
+            },
+        );
+        self.layer
+            .add_operation(Operation::new("gs", vec![Object::Name(state.into_bytes())]));
+    }
+
+    // alpha of fill and stroke colors, e.g. of the style, is applied by the graphics state
+    // within the opacity of open scopes, the element is painted in its own state then
+    fn with_color_alpha<R>(
+        &mut self,
+        fill: Option<&Rgba>,
+        stroke: Option<&Rgba>,
+        render: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let alpha = |color: Option<&Rgba>| color.map(|color| color.into_rgba().3).unwrap_or(1.0);
+        let (fill, stroke) = (alpha(fill), alpha(stroke));
+        if fill >= 1.0 && stroke >= 1.0 {
+            return render(self);
+        }
+
+        let opacity = scopes_opacity(&self.graphics_scopes);
+        let layer_state = self.layer_state.clone();
+        self.layer.save_graphics_state();
+        self.apply_alpha(fill * opacity, stroke * opacity);
+        let result = render(self);
+        self.layer.restore_graphics_state();
+        self.layer_state = layer_state;
+        result
+    }
+
+    // text watermarks need their glyphs, so they have to be set before fonts are completed;
+    // pages created from then on get the watermark, including the current one
+    pub fn set_watermark(&mut self, watermark: Option<Watermark>) -> Result<(), Error> {
+        self.watermark = None;
+        let Some(mut watermark) = watermark else {
+            return Ok(());
+        };
+
+        if let WatermarkContent::Text {
+            text,
+            style,
+            position,
+        } = watermark.content_mut()
+        {
+            if self.fonts_completed {
+                return Err(Error::PdfWrite(
+                    "Text watermark must be set before fonts are completed".into(),
+                ));
+            }
+            *position = Some(layout::MeasureContext::typeset(self, style, text)?);
+        }
+
+        self.watermark = Some(watermark);
+        if self.fonts_completed {
+            self.stamp_watermark();
+        }
+        Ok(())
+    }
+
+    fn stamp_watermark(&mut self) {
+        if self.dry_run {
+            return;
+        }
+        let Some(watermark) = self.watermark.take() else {
             return;
+        };
+
+        let layer_state = self.layer_state.clone();
+        self.layer.save_graphics_state();
+        if watermark.opacity() < 1.0 {
+            self.apply_opacity(watermark.opacity());
+        }
+        // origin is moved to the page center, content is centered around it
+        self.layer.set_ctm(CurTransMat::TranslateRotate(
+            printpdf::Mm(from_unit(self.page_size.base_width()).0 / 2.0).into_pt(),
+            printpdf::Mm(from_unit(self.page_size.base_height()).0 / 2.0).into_pt(),
+            watermark.rotation(),
+        ));
+
+        match watermark.content() {
+            WatermarkContent::Text {
+                style,
+                position: Some(position),
+                ..
+            } => {
+                let (width, ascent, depth) = self.text_extent(style, position);
+                self.paint_plain_text((-width / 2.0, -(ascent - depth) / 2.0), style, position);
+            }
+            WatermarkContent::Image { image, size } => {
+                let width = from_unit(size.base_width());
+                let height = from_unit(size.base_height());
+                self.paint_image(
+                    printpdf::Mm(-width.0 / 2.0),
+                    printpdf::Mm(height.0 / 2.0),
+                    width,
+                    height,
+                    image,
+                );
+            }
+            _ => {}
+        }
+        self.layer.restore_graphics_state();
+        self.layer_state = layer_state;
+
+        self.watermark = Some(watermark);
+    }
+
+    // width, ascent and depth (both from the baseline) of typeset text, in pt
+    fn text_extent(&self, style: &Style, position: &TextPosition) -> (f32, f32, f32) {
+        let font = style.font().merge(self.style.font());
+        let font_size = font.size().map(|size| *size as f32).unwrap_or(0.0);
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0) as f32;
+        (
+            position.width.0 as f32 * font_size * font_scaling,
+            position.ascent().0 as f32 * font_size,
+            position.depth.0 as f32 * font_size,
+        )
+    }
+
+    // text state of the current scope does not apply, e.g. to watermarks
+    fn paint_plain_text(&mut self, origin: (f32, f32), style: &Style, position: &TextPosition) {
+        let text_rotation = std::mem::replace(&mut self.text_rotation, 0.0);
+        let text_mode = std::mem::replace(&mut self.text_mode, TextMode::Fill);
+        let text_decoration = std::mem::replace(&mut self.text_decoration, TextDecoration::empty());
+        let baseline_shift = std::mem::replace(&mut self.baseline_shift, Em(0.0));
+        let text_width = self.text_width.take();
+
+        self.paint_text(origin, style, position);
+
+        self.text_rotation = text_rotation;
+        self.text_mode = text_mode;
+        self.text_decoration = text_decoration;
+        self.baseline_shift = baseline_shift;
+        self.text_width = text_width;
+    }
+
+    // continuation texts are typeset here, so it has to be set before fonts are completed
+    pub fn set_continuation(&mut self, continuation: Option<Continuation>) -> Result<(), Error> {
+        self.continuation = None;
+        let Some(mut continuation) = continuation else {
+            return Ok(());
+        };
+
+        if self.fonts_completed {
+            return Err(Error::PdfWrite(
+                "Continuation must be set before fonts are completed".into(),
+            ));
+        }
+        for ContinuationText {
+            text,
+            style,
+            position,
+        } in continuation.texts_mut()
+        {
+            *position = Some(layout::MeasureContext::typeset(self, style, text)?);
+        }
+
+        self.continuation = Some(continuation);
+        Ok(())
+    }
+
+    // overlays are measured with their glyphs, so they have to be added before fonts
+    // are completed; they are painted on every page, including the current one, overlays
+    // with page value slots are measured again for every page
+    pub fn add_overlay(&mut self, mut overlay: Overlay) -> Result<(), Error> {
+        if self.fonts_completed {
+            return Err(Error::PdfWrite(
+                "Overlay must be added before fonts are completed".into(),
+            ));
+        }
+        let size = overlay.size().clone();
+        self.page_values_used = false;
+        overlay.layout_mut().measure(self, size)?;
+        overlay.set_page_dependent(self.page_values_used);
+        self.overlays.push(overlay);
+        Ok(())
+    }
+
+    // the master page, e.g. a letterhead with a logo and margin lines, is laid out over the
+    // whole page and painted under the content of every page; it is rendered once, so its
+    // text can not have page value slots, and every page refers to it
+    pub fn set_master_page(&mut self, mut layout: Box<dyn Layout>) -> Result<(), Error> {
+        if self.fonts_completed {
+            return Err(Error::PdfWrite(
+                "Master page must be set before fonts are completed".into(),
+            ));
+        }
+        self.page_values_used = false;
+        let size = self.page_size.clone();
+        layout.measure(self, size)?;
+        if self.page_values_used {
+            return Err(Error::PdfWrite(
+                "Master page must not have page value slots".into(),
+            ));
+        }
+        self.master_page = Some(layout);
+        Ok(())
+    }
+
+    // rendered once for every page size to a scratch page after the last page, scratch
+    // pages are moved to forms when the document is saved; returns scratch pages with
+    // pages of their size
+    fn render_master_pages(&mut self) -> Result<Vec<(usize, Vec<usize>)>, Error> {
+        let Some(mut layout) = self.master_page.take() else {
+            return Ok(vec![]);
+        };
+        if !self.fonts_completed {
+            return Err(Error::PdfWrite(
+                "Master page is rendered after fonts are completed".into(),
+            ));
+        }
+
+        let mut sizes: Vec<(Size, Vec<usize>)> = vec![];
+        for page in 0..=self.page_number {
+            let size = self
+                .page_sizes
+                .iter()
+                .rev()
+                .find(|(start, _)| *start <= page)
+                .map_or(&self.page_size, |(_, size)| size)
+                .clone();
+            match sizes.iter_mut().find(|(existing, _)| *existing == size) {
+                Some((_, pages)) => pages.push(page),
+                None => sizes.push((size, vec![page])),
+            }
         }
 
-        let font = style.font().merge(self.style.font());
-        if font.name().is_none() || font.size().is_none() {
-            tracing::warn!("Try to typeset text without defined font");
-            return;
-        }
-        let font_size = font.size().unwrap();
-        let font_scaling = font
-            .scaling()
-            .as_ref()
-            .map(FillPerMille::scaling)
-            .unwrap_or(1.0);
+        let page_number = self.page_number;
+        let page_size = self.page_size.clone();
+        let rotated_pages = self.rotated_pages.clone();
+        let graphics_scopes = std::mem::take(&mut self.graphics_scopes);
+        // content of the form is an artifact of tagged documents
+        let structure = self.structure.take();
+        let mut master_pages = vec![];
+        let mut result = Ok(());
+        for (size, pages) in sizes {
+            self.scratch_page(&size);
+            result = self.with_page_position(|rctx| {
+                layout
+                    .lay_out(rctx, Offset::zero(), size)
+                    .and_then(|_| layout.render(rctx))
+            });
+            if result.is_err() {
+                break;
+            }
+            master_pages.push((self.page_number, pages));
+        }
+        self.page_number = page_number;
+        self.page_size = page_size;
+        self.rotated_pages = rotated_pages;
+        self.graphics_scopes = graphics_scopes;
+        self.structure = structure;
+        result.map(|_| master_pages)
+    }
+
+    // the page is not a page of the document, only the master page layer is rendered to it
+    fn scratch_page(&mut self, size: &Size) {
+        self.page_size = size.clone();
+        let (width, height) = (from_unit(size.base_width()), from_unit(size.base_height()));
+        let rotated = self.landscape_rotation && width > height;
+        let (page, layer) = match rotated {
+            true => self.document.add_page(height, width, MASTER_PAGE_LAYER),
+            false => self.document.add_page(width, height, MASTER_PAGE_LAYER),
+        };
+
+        self.page = self.document.get_page(page);
+        self.layer = self.page.get_layer(layer);
+        self.base_layer = self.layer.clone();
+        self.layers.clear();
+        self.layer_name = None;
+        self.page_number += 1;
+        self.layer_state = LayerState::initial();
+        self.layer_states.clear();
+        if rotated {
+            self.rotated_pages.push(self.page_number);
+        }
+        if let Some(matrix) = self.page_rotation() {
+            self.layer.set_ctm(CurTransMat::Raw(matrix));
+        }
+    }
+
+    // backgrounds are measured with their glyphs, so templates have to be set before fonts
+    // are completed, and before content is rendered, as the template of the current page
+    // applies to it; templates of next pages apply on page breaks; content is laid out
+    // once, so margins of templates must keep the width of the document content
+    pub fn set_page_templates(&mut self, mut templates: PageTemplates) -> Result<(), Error> {
+        if self.fonts_completed {
+            return Err(Error::PdfWrite(
+                "Page templates must be set before fonts are completed".into(),
+            ));
+        }
+        let width = self.page_margin.width();
+        if templates
+            .templates_mut()
+            .filter_map(|template| template.margin())
+            .any(|margin| margin.width() != width)
+        {
+            return Err(Error::PdfWrite(
+                "Margins of page templates must keep the content width".into(),
+            ));
+        }
+        let size = self.page_size.clone();
+        for template in templates.templates_mut() {
+            if let Some(background) = template.background_mut() {
+                background.measure(self, size.clone())?;
+            }
+        }
+
+        self.document_margin = self.page_margin.clone();
+        if let Some(margin) = templates
+            .template(self.page_number)
+            .and_then(PageTemplate::margin)
+        {
+            self.page_margin = margin.clone();
+            if let Some(page_start) = self.page_start.as_ref().map(|page_start| page_start.y) {
+                self.set_page_offsets(page_start);
+            }
+        }
+        self.page_templates = Some(templates);
+        Ok(())
+    }
+
+    // the background of the template of the page goes under its content and watermark
+    fn paint_page_background(&mut self) {
+        if self.dry_run || !self.fonts_completed {
+            return;
+        }
+        let Some(mut templates) = self.page_templates.take() else {
+            return;
+        };
+
+        if let Some(background) = templates
+            .template_mut(self.page_number)
+            .and_then(PageTemplate::background_mut)
+        {
+            let size = self.page_size.clone();
+            let result = self.with_page_position(|rctx| {
+                background
+                    .lay_out(rctx, Offset::zero(), size)
+                    .and_then(|_| background.render(rctx))
+            });
+            if let Err(error) = result {
+                tracing::warn!("Page background not rendered: {:?}", error);
+            }
+        }
+        self.page_templates = Some(templates);
+    }
+
+    // callbacks run for every page, when it is finished, and get the page number; they
+    // decorate it in their own layer, below overlays
+    pub fn on_page(&mut self, callback: impl FnMut(&mut PageDecorator, usize) + 'static) {
+        self.page_callbacks.push(Box::new(callback));
+    }
+
+    // callbacks run for every page, when it is added, before its content is rendered, e.g.
+    // serial numbers; they draw in the layer of page decorations
+    pub fn on_page_added(&mut self, callback: impl FnMut(&mut PageDecorator, &PageInfo) + 'static) {
+        self.page_added_callbacks.push(Box::new(callback));
+    }
+
+    fn page_added(&mut self) {
+        if self.page_added_callbacks.is_empty() || self.dry_run || !self.fonts_completed {
+            return;
+        }
+        let mut callbacks = std::mem::take(&mut self.page_added_callbacks);
+        let decoration_layer = self.named_layer("decoration");
+        let layer = std::mem::replace(&mut self.layer, decoration_layer);
+        let layer_state = std::mem::take(&mut self.layer_state);
+        let info = PageInfo {
+            index: self.page_number,
+            size: self.page_size.clone(),
+            margin: self.page_margin.clone(),
+        };
+        let content_offset = self.margin_offset(&Offset::zero());
+
+        self.with_page_position(|rctx| {
+            let mut decorator = PageDecorator::new(rctx, info.margin.clone(), content_offset);
+            for callback in callbacks.iter_mut() {
+                callback(&mut decorator, &info);
+            }
+        });
+
+        self.layer = layer;
+        self.layer_state = layer_state;
+        self.page_added_callbacks = callbacks;
+    }
+
+    fn decorate_page(&mut self) {
+        if (self.page_callbacks.is_empty()
+            && self.section_page_callbacks.is_empty()
+            && self.page_numbering.is_none()
+            && self.page_templates.is_none())
+            || !self.fonts_completed
+        {
+            return;
+        }
+        let mut callbacks = std::mem::take(&mut self.page_callbacks);
+        let mut section_callbacks = std::mem::take(&mut self.section_page_callbacks);
+        let mut templates = self.page_templates.take();
+        let decoration_layer = self.named_layer("decoration");
+        let layer = std::mem::replace(&mut self.layer, decoration_layer);
+        let layer_state = std::mem::take(&mut self.layer_state);
+        let page_margin = self.page_margin.clone();
+        let content_offset = self.margin_offset(&Offset::zero());
+        let page = self.page_number + 1;
+
+        self.with_page_position(|rctx| {
+            rctx.paint_page_number();
+            let mut decorator = PageDecorator::new(rctx, page_margin, content_offset);
+            for callback in callbacks.iter_mut().chain(section_callbacks.iter_mut()) {
+                callback(&mut decorator, page);
+            }
+            if let Some(decoration) = templates
+                .as_mut()
+                .and_then(|templates| templates.template_mut(page - 1))
+                .and_then(PageTemplate::decoration_mut)
+            {
+                decoration(&mut decorator, page);
+            }
+        });
+
+        self.layer = layer;
+        self.layer_state = layer_state;
+        self.page_callbacks = callbacks;
+        self.section_page_callbacks = section_callbacks;
+        self.page_templates = templates;
+    }
+
+    // the section starts at the position, e.g. of its heading, levels start at 1; page
+    // callbacks get titles of the sections on their page, a table of contents lists them
+    pub fn mark_section(
+        &mut self,
+        title: impl Into<String>,
+        level: usize,
+        content_position: &Offset,
+    ) {
+        let anchor = format!("section {}", self.sections.len() + 1);
+        self.add_anchor(&anchor, content_position, false);
+        self.sections.push(TocEntry {
+            title: title.into(),
+            level,
+            page: self.page_number + 1,
+            anchor,
+        });
+        if let Some(structure) = self.structure.as_mut() {
+            structure.heading(level);
+        }
+    }
+
+    fn take_section_mark(&mut self, section: SectionMark) {
+        self.section_outline |= section.numbered;
+        self.mark_section(
+            section.title,
+            section.level,
+            &Offset::new(Unit::zero(), section.offset),
+        );
+    }
+
+    pub fn toc_entries(&self) -> Vec<TocEntry> {
+        self.sections.clone()
+    }
+
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
+        self.outline = outline;
+    }
+
+    pub fn with_section_outline(mut self, section_outline: bool) -> Self {
+        self.section_outline = section_outline;
+        self
+    }
+
+    // text is tagged as paragraphs, or headings of sections marked, unless it is tagged by
+    // elements; content tagged by neither is marked as artifact
+    pub fn with_tagged_pdf(mut self, tagged: bool) -> Self {
+        self.structure = tagged.then(StructureTree::new);
+        self.pdf_ua &= tagged;
+        self
+    }
+
+    // tagged document claiming PDF/UA, saving fails unless it has the language, a title
+    // and alternate text of all figures
+    pub fn with_pdf_ua(mut self, pdf_ua: bool) -> Self {
+        if pdf_ua && self.structure.is_none() {
+            self.structure = Some(StructureTree::new());
+        }
+        self.pdf_ua = pdf_ua;
+        self
+    }
+
+    // natural language of the document, e.g. en-US, required by accessibility
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn with_initial_view(mut self, initial_view: InitialView) -> Self {
+        self.initial_view = Some(initial_view);
+        self
+    }
+
+    // pages of the stationery are painted under the content of pages
+    pub fn with_stationery(mut self, stationery: Stationery) -> Self {
+        self.stationery = Some(stationery);
+        self
+    }
+
+    // the background image is painted under stationery and the content of pages
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    // finished pages are placed onto sheets, print marks are added to the sheets
+    pub fn with_imposition(mut self, imposition: Imposition) -> Self {
+        self.imposition = Some(imposition);
+        self
+    }
+
+    // elements made by the marks tag content of layouts
+    pub fn with_structure_marks(mut self, structure_marks: StructureMarks) -> Self {
+        self.structure_marks = structure_marks;
+        self
+    }
+
+    // content rendered by the closure is tagged as the element, or as its descendants
+    pub fn with_structure<R>(
+        &mut self,
+        element: StructureElement,
+        render: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        self.take_structure_marks();
+        self.begin_structure(element);
+        let result = render(self);
+        self.end_structure();
+        result
+    }
+
+    // content of a figure is marked on the layer it begins on, content on following
+    // pages is left to artifacts
+    fn begin_structure(&mut self, element: StructureElement) {
+        let Some(structure) = self.structure.as_mut() else {
+            return;
+        };
+        let figure = element.is_figure() && !structure.in_figure();
+        structure.begin(element);
+        let layer = figure.then(|| {
+            let (tag, mcid) = structure.mark_element(self.page_number);
+            self.layer.add_operation(marked_content(&tag, mcid));
+            self.layer.clone()
+        });
+        self.figure_layers.push(layer);
+    }
+
+    fn end_structure(&mut self) {
+        let Some(structure) = self.structure.as_mut() else {
+            return;
+        };
+        structure.end();
+        if let Some(Some(layer)) = self.figure_layers.pop() {
+            layer.add_operation(Operation::new("EMC", vec![]));
+        }
+    }
+
+    fn take_structure_marks(&mut self) {
+        for mark in self.structure_marks.take() {
+            match mark {
+                StructureMark::Begin(element) => self.begin_structure(element),
+                StructureMark::End => self.end_structure(),
+            }
+        }
+    }
+
+    fn outline(&self) -> Option<Outline> {
+        match &self.outline {
+            Some(outline) => Some(outline.clone()),
+            None if self.section_outline => Some(Outline::from_entries(&self.sections)),
+            None => None,
+        }
+        .filter(|outline| !outline.is_empty())
+    }
+
+    // dictionary-style: the first or the last section marked on the current page, or
+    // the section continuing from previous pages if none is marked on it
+    pub(crate) fn section(&self, first: bool) -> Option<&str> {
+        let mut on_page = self
+            .sections
+            .iter()
+            .filter(|section| section.page == self.page_number + 1);
+        let marked = match first {
+            true => on_page.next(),
+            false => on_page.next_back(),
+        };
+        marked
+            .or_else(|| {
+                self.sections
+                    .iter()
+                    .rev()
+                    .find(|section| section.page <= self.page_number)
+            })
+            .map(|section| section.title.as_str())
+    }
+
+    // glyphs of the numbers are reserved, so numbering has to be set before fonts are completed
+    pub fn set_page_numbering(&mut self, numbering: Option<PageNumbering>) -> Result<(), Error> {
+        self.page_numbering = None;
+        let Some(numbering) = numbering else {
+            return Ok(());
+        };
+
+        if self.fonts_completed {
+            return Err(Error::PdfWrite(
+                "Page numbering must be set before fonts are completed".into(),
+            ));
+        }
+        self.reserve_page_numbering(&numbering)?;
+        self.page_numbering = Some(numbering);
+        Ok(())
+    }
+
+    pub(crate) fn reserve_page_numbering(
+        &mut self,
+        numbering: &PageNumbering,
+    ) -> Result<(), Error> {
+        let font = numbering.style().font().merge(self.style.font());
+        let Some(name) = font.name() else {
+            return Err(Error::UnknownFont("Font name is undefined".into()));
+        };
+        let chars = numbering.literal_chars() + PAGE_VALUE_CHARS;
+        self.fonts.reserve_glyphs(name, chars.chars())
+    }
+
+    // numbering reserved before, returns the one replaced
+    pub(crate) fn switch_page_numbering(
+        &mut self,
+        numbering: Option<PageNumbering>,
+    ) -> Option<PageNumbering> {
+        std::mem::replace(&mut self.page_numbering, numbering)
+    }
+
+    pub(crate) fn set_section_page_callbacks(&mut self, callbacks: Vec<PageCallback>) {
+        self.section_page_callbacks = callbacks;
+    }
+
+    // a new page of the setup, or the current page if nothing is rendered on it yet, e.g.
+    // the first page of the document
+    pub(crate) fn set_page_setup(&mut self, margin: &Quad, size: &Size, current_page: bool) {
+        if !current_page {
+            self.new_page(Some(margin), Some(size));
+            return;
+        }
+
+        self.page_margin = margin.clone();
+        self.page_size = size.clone();
+        self.set_page_offsets(Unit::zero());
+        self.page_sizes
+            .retain(|(page, _)| *page != self.page_number);
+        self.page_sizes.push((self.page_number, size.clone()));
+
+        let width = from_unit(size.base_width()).into_pt().0;
+        let height = from_unit(size.base_height()).into_pt().0;
+        let rotated = self.landscape_rotation && width > height;
+        let (width, height) = match rotated {
+            true => (height, width),
+            false => (width, height),
+        };
+        let page_box = || {
+            Object::Array(vec![
+                Object::Integer(0),
+                Object::Integer(0),
+                Object::Real(width),
+                Object::Real(height),
+            ])
+        };
+        let mut boxes = Dictionary::new();
+        for key in ["MediaBox", "TrimBox", "CropBox"] {
+            boxes.set(key, page_box());
+        }
+        self.page.extend_with(boxes);
+
+        self.rotated_pages.retain(|page| *page != self.page_number);
+        if rotated {
+            self.rotated_pages.push(self.page_number);
+        }
+        if let Some(matrix) = self.page_rotation() {
+            self.layer.set_ctm(CurTransMat::Raw(matrix));
+        }
+    }
+
+    // the current page gets the number, following pages continue from it, e.g. a section
+    pub fn restart_page_numbering(&mut self, number: usize) {
+        self.page_number_restarts
+            .retain(|(page, _)| *page != self.page_number);
+        self.page_number_restarts.push((self.page_number, number));
+    }
+
+    // viewers label the current page and following ones by the label, e.g. a section
+    pub fn set_page_label(&mut self, label: PageLabel) {
+        self.page_labels
+            .retain(|(page, _)| *page != self.page_number);
+        self.page_labels.push((self.page_number, label));
+    }
+
+    fn numbered_page(&self, start: usize) -> usize {
+        let (page, number) = self
+            .page_number_restarts
+            .last()
+            .copied()
+            .unwrap_or((0, start));
+        number + self.page_number - page
+    }
+
+    fn paint_page_number(&mut self) {
+        let Some(numbering) = self.page_numbering.take() else {
+            return;
+        };
+
+        let pages = self.page_values.as_ref().map(PageValues::pages);
+        let text = numbering.format(self.numbered_page(numbering.start()), pages);
+        match layout::MeasureContext::typeset(self, numbering.style(), &text) {
+            Ok(position) => {
+                let (width, ascent, depth) = self.text_extent(numbering.style(), &position);
+                let size = Size::fixed(Pt(width as f64), Pt((ascent + depth.abs()) as f64));
+                let offset = anchor_offset(
+                    numbering.anchor(),
+                    numbering.inset(),
+                    &self.page_size,
+                    &size,
+                );
+                layout::RenderContext::text(self, &offset, numbering.style(), &position, false);
+            }
+            Err(error) => tracing::warn!("Page number not rendered: {:?}", error),
+        }
+
+        self.page_numbering = Some(numbering);
+    }
+
+    // overlays are painted over the content of the page being finished, in their own layer
+    fn stamp_overlays(&mut self) {
+        if self.overlays.is_empty() || !self.fonts_completed {
+            return;
+        }
+        let mut overlays = std::mem::take(&mut self.overlays);
+        let overlay_layer = self.named_layer("overlay");
+        let layer = std::mem::replace(&mut self.layer, overlay_layer);
+        let layer_state = std::mem::take(&mut self.layer_state);
+
+        self.with_page_position(|rctx| {
+            for overlay in overlays.iter_mut() {
+                let offset = overlay.offset(&rctx.page_size);
+                let size = overlay.size().clone();
+                let result = match overlay.page_dependent() {
+                    true => overlay.layout_mut().measure(rctx, size.clone()),
+                    false => Ok(()),
+                }
+                .and_then(|_| overlay.layout_mut().lay_out(rctx, offset, size))
+                .and_then(|_| overlay.layout().render(rctx));
+                if let Err(error) = result {
+                    tracing::warn!("Overlay not rendered: {:?}", error);
+                }
+            }
+        });
+
+        self.layer = layer;
+        self.layer_state = layer_state;
+        self.overlays = overlays;
+    }
+
+    // bottom text goes below the content of the current page, top text above it
+    fn paint_continuation(&mut self, bottom: bool) {
+        let Some(continuation) = self.continuation.take() else {
+            return;
+        };
+        let text = match bottom {
+            true => continuation.bottom(),
+            false => continuation.top(),
+        };
+
+        if self.fonts_completed
+            && !self.dry_run
+            && let Some(ContinuationText {
+                style,
+                position: Some(position),
+                ..
+            }) = text
+        {
+            let (width, ascent, depth) = self.text_extent(style, position);
+            let top_left = self.margin_offset(&Offset::zero());
+            let bottom_right = Offset::new(
+                top_left.x + self.page_size.base_width() - self.margin_width(),
+                top_left.y + self.page_size.base_height() - self.page_margin.height(),
+            );
+            let (top_left, bottom_right) = (self.swap_y(&top_left), self.swap_y(&bottom_right));
+
+            let origin = match bottom {
+                true => (
+                    from_unit(bottom_right.x).into_pt().0 - width,
+                    from_unit(bottom_right.y).into_pt().0 - ascent,
+                ),
+                false => (
+                    from_unit(top_left.x).into_pt().0,
+                    from_unit(top_left.y).into_pt().0 + depth,
+                ),
+            };
+            self.outside_graphics_scopes(|rctx| rctx.paint_plain_text(origin, style, position));
+        }
+
+        self.continuation = Some(continuation);
+    }
+
+    // values of {pages}, {page:name} and {ref:name} slots, text typeset before they are set
+    // keeps ? or reserved digits
+    pub fn set_page_values(&mut self, page_values: Option<PageValues>) {
+        self.page_values = page_values;
+    }
+
+    fn page_slot_text(&mut self, font: &Font, text: &TextPosition) -> Option<TextPosition> {
+        let name = font.name()?;
+        let (template, kerning) = self.page_slot_runs.find(name, text)?;
+        let substituted = substitute_page_values(
+            template,
+            Some(self.page_number + 1),
+            self.page_values.as_ref(),
+        )?;
+        let features = font.features().cloned().unwrap_or_default();
+        match self
+            .fonts
+            .typeset_with(name, &substituted, &features, kerning)
+        {
+            Ok(position) => Some(position),
+            Err(error) => {
+                tracing::warn!("Page slot not substituted: {:?}", error);
+                None
+            }
+        }
+    }
+
+    // page count and pages of anchors rendered so far
+    pub fn page_values(&self) -> PageValues {
+        self.annotations.anchor_pages().fold(
+            PageValues::new(self.page_number + 1),
+            |values, (name, page)| values.with_anchor(name, page + 1),
+        )
+    }
+
+    pub fn complete_fonts(&mut self) -> Result<(), Error> {
+        // nothing is painted by the dry run, fonts are not subset
+        if !self.dry_run {
+            self.fonts.complete_and_write(&self.document)?;
+        }
+        self.fonts_completed = true;
+        self.paint_page_background();
+        self.stamp_watermark();
+        self.page_added();
+        Ok(())
+    }
+
+    pub fn save_to_bytes(mut self) -> Result<Vec<u8>, Error> {
+        self.take_structure_marks();
+        while !self.figure_layers.is_empty() {
+            self.end_structure();
+        }
+        self.paint_footnotes();
+        self.decorate_page();
+        self.stamp_overlays();
+        let master_pages = self.render_master_pages()?;
+
+        let outline = self.outline();
+        let pdf = self
+            .document
+            .save_to_bytes()
+            .map_err(|error| Error::PdfWrite(error.to_string().into()))?;
+        #[cfg(feature = "encryption")]
+        let encryption = self.encryption.take();
+        #[cfg(not(feature = "encryption"))]
+        let encryption: Option<()> = None;
+        if self.resources.is_empty()
+            && self.annotations.is_empty()
+            && self.icc_profiles.is_empty()
+            && self.precision.is_none()
+            && self.compression.is_none()
+            && self.optimization.is_none()
+            && !self.linearization
+            && self.print_marks.is_none()
+            && self.xmp_metadata.is_none()
+            && self.pdf_a.is_none()
+            && self.pdf_version.is_none()
+            && encryption.is_none()
+            && self.signature.is_none()
+            && self.rotated_pages.is_empty()
+            && !self.merge_layers
+            && self.layer_z_indexes.is_empty()
+            && outline.is_none()
+            && self.structure.is_none()
+            && self.language.is_none()
+            && self.page_labels.is_empty()
+            && self.initial_view.is_none()
+            && self.stationery.is_none()
+            && self.background.is_none()
+            && self.imposition.is_none()
+            && master_pages.is_empty()
+        {
+            return Ok(pdf);
+        }
+
+        let mut document = lopdf::Document::load_mem(&pdf).map_err(pdf_error)?;
+        if self.merge_layers {
+            merge_layers(&mut document)?;
+        }
+        if !self.layer_z_indexes.is_empty() {
+            stack_layers(&mut document, |name| {
+                layer_z_index(&self.layer_z_indexes, name)
+            })?;
+        }
+        self.resources.write(&mut document)?;
+        // scratch pages are removed, resources of them are written before
+        if !master_pages.is_empty() {
+            stamp_master_pages(&mut document, &master_pages)?;
+        }
+        if let Some(stationery) = &self.stationery {
+            stationery.write(&mut document)?;
+        }
+        if let Some(background) = &self.background {
+            background.write(&mut document)?;
+        }
+        self.annotations.write(&mut document)?;
+        if let Some(outline) = outline {
+            let pages = document.get_pages();
+            outline.write(&mut document, |name| {
+                self.annotations.anchor_destination(&pages, name)
+            })?;
+        }
+        if let Some(structure) = &self.structure {
+            structure.write(&mut document)?;
+        }
+        if let Some(language) = &self.language {
+            catalog(&mut document)?.set("Lang", text_string(language));
+        }
+        if !self.page_labels.is_empty() {
+            write_page_labels(&mut document, &self.page_labels)?;
+        }
+        if let Some(initial_view) = &self.initial_view {
+            let pages = document.get_pages();
+            initial_view.write(&mut document, |name| {
+                self.annotations.anchor_destination(&pages, name)
+            })?;
+        }
+        let mut output_profile = None;
+        for icc_profile in self.icc_profiles.iter() {
+            let profile_id = icc_profile.write(&mut document)?;
+            if icc_profile.color_model() == self.color_model {
+                output_profile = Some(profile_id);
+            }
+        }
+        if let Some(decimals) = self.precision {
+            round_content(&mut document, decimals)?;
+        }
+        if let Some(imposition) = &self.imposition {
+            imposition.write(&mut document, &self.rotated_pages)?;
+            // sheets are not rotated, pages placed onto them are
+            self.rotated_pages.clear();
+        }
+        if let Some(print_marks) = &self.print_marks {
+            print_marks.write(&mut document)?;
+        }
+        let mut xmp_metadata = match self.pdf_a {
+            Some(level) => Some(level.identify(self.xmp_metadata.take().unwrap_or_default())),
+            None => self.xmp_metadata.take(),
+        };
+        // imposed sheets drop the structure
+        if self.pdf_ua && self.imposition.is_none() {
+            let xmp = xmp_metadata.unwrap_or_default();
+            let mut missing = vec![];
+            if self.language.is_none() {
+                missing.push("language");
+            }
+            if !xmp.has_title(&document) {
+                missing.push("title");
+            }
+            if let Some(structure) = &self.structure
+                && structure.has_figures_without_alt_text()
+            {
+                missing.push("alternate text of figures");
+            }
+            if !missing.is_empty() {
+                return Err(Error::PdfWrite(
+                    format!("PDF/UA document misses {}", missing.join(", ")).into(),
+                ));
+            }
+            xmp_metadata = Some(identify_pdf_ua(xmp));
+        }
+        if let Some(xmp_metadata) = xmp_metadata {
+            xmp_metadata.write(&mut document)?;
+        }
+        if let Some(version) = self.pdf_version {
+            version.apply(&mut document)?;
+        }
+        if let Some(level) = self.pdf_a {
+            if !level.allows_optional_content() {
+                flatten_layers(&mut document)?;
+            }
+            let Some(profile_id) = output_profile else {
+                return Err(Error::PdfWrite(
+                    "PDF/A requires an ICC profile of the color model".into(),
+                ));
+            };
+            write_output_intent(&mut document, profile_id, self.color_model)?;
+            print_annotations(&mut document);
+            validate(&document, level)?;
+        }
+        let pages = document.get_pages();
+        for page in self.rotated_pages.iter() {
+            if let Some(page_id) = pages.get(&(*page as u32 + 1)) {
+                document
+                    .get_object_mut(*page_id)
+                    .and_then(Object::as_dict_mut)
+                    .map_err(pdf_error)?
+                    .set("Rotate", 90);
+            }
+        }
+        if let Some(optimization) = self.optimization {
+            optimization.write(&mut document)?;
+        }
+        // streams are encrypted, so they are compressed before
+        if let Some(compression) = self.compression {
+            compression.write(&mut document)?;
+        }
+        let signature = self.signature.take();
+        if let Some(signature) = &signature {
+            signature.prepare(&mut document)?;
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(encryption) = encryption {
+            if let Some(level) = self.pdf_a {
+                return Err(Error::PdfWrite(
+                    format!("{} forbids encryption", level.name()).into(),
+                ));
+            }
+            // objects are renumbered when linearized, their numbers are a part of keys
+            if self.linearization {
+                return Err(Error::PdfWrite(
+                    "Encrypted document can not be linearized".into(),
+                ));
+            }
+            let version = encryption.method().version();
+            match self.pdf_version {
+                Some(selected) if selected < version => {
+                    return Err(Error::PdfWrite(
+                        format!(
+                            "PDF {} does not support {:?} encryption",
+                            selected.header(),
+                            encryption.method()
+                        )
+                        .into(),
+                    ));
+                }
+                Some(_) => {}
+                None if document.version.as_str() < version.header() => {
+                    document.version = version.header().to_string();
+                }
+                None => {}
+            }
+            encryption.write(&mut document)?;
+        }
+
+        let pdf = match self.linearization {
+            true => linearize(&mut document)?,
+            false => {
+                let mut pdf = vec![];
+                document.save_to(&mut pdf).map_err(pdf_error)?;
+                pdf
+            }
+        };
+        match signature {
+            Some(signature) => signature.sign(pdf),
+            None => Ok(pdf),
+        }
+    }
+
+    // returns the name of the resource on the current page
+    fn add_resource(&mut self, category: &'static str, object: impl Into<Object>) -> String {
+        self.resources.add(self.page_number, category, object)
+    }
+
+    pub(crate) fn page_size(&self) -> &Size {
+        &self.page_size
+    }
+
+    fn page_content_offset(&self, content_offset: &Offset) -> Offset {
+        match &self.page_start {
+            Some(page_start) => content_offset - page_start,
+            None => content_offset.clone(),
+        }
+    }
+
+    fn swap_y(&self, page_position: &Offset) -> Offset {
+        Offset::new(
+            page_position.x,
+            self.page_size.base_height() - page_position.y,
+        )
+    }
+
+    fn new_page(&mut self, margin: Option<&Quad>, size: Option<&Size>) {
+        self.paint_footnotes();
+        if let Some(margin) = margin {
+            self.page_margin = margin.clone();
+            self.document_margin = margin.clone();
+        } else if let Some(templates) = &self.page_templates {
+            self.page_margin = templates
+                .template(self.page_number + 1)
+                .and_then(PageTemplate::margin)
+                .unwrap_or(&self.document_margin)
+                .clone();
+        }
+        if let Some(size) = size {
+            self.page_size = size.clone();
+        }
+
+        self.page_start = None;
+        self.page_end = None;
+
+        if self.dry_run {
+            self.page_number += 1;
+            self.report(RenderProgress::Pages(self.page_number + 1));
+            return;
+        }
+
+        // graphics state does not cross pages, open scopes are closed and reopened
+        let scopes = self.graphics_scopes.len();
+        for _ in 0..scopes {
+            self.layer.restore_graphics_state();
+        }
+        self.decorate_page();
+        self.stamp_overlays();
+
+        let (width, height) = (
+            from_unit(self.page_size.base_width()),
+            from_unit(self.page_size.base_height()),
+        );
+        let rotated = self.landscape_rotation && width > height;
+        let (page, layer) = match rotated {
+            true => self.document.add_page(height, width, "default"),
+            false => self.document.add_page(width, height, "default"),
+        };
+
+        self.page = self.document.get_page(page);
+        self.layer = self.page.get_layer(layer);
+        self.base_layer = self.layer.clone();
+        self.layers.clear();
+        self.page_number += 1;
+        self.layer_state = LayerState::initial();
+        self.layer_states.fill(LayerState::initial());
+        self.report(RenderProgress::Pages(self.page_number + 1));
+        if let Some(size) = size {
+            self.page_sizes.push((self.page_number, size.clone()));
+        }
+
+        if rotated {
+            self.rotated_pages.push(self.page_number);
+        }
+        if let Some(matrix) = self.page_rotation() {
+            self.layer.set_ctm(CurTransMat::Raw(matrix));
+        }
+
+        // fonts are completed before any content is rendered
+        if self.fonts_completed {
+            self.paint_page_background();
+            self.stamp_watermark();
+            self.page_added();
+        }
+
+        if let Some(name) = self.layer_name.clone() {
+            self.layer = self.named_layer(&name);
+        }
+
+        for index in 0..scopes {
+            self.layer.save_graphics_state();
+            self.apply_graphics_scope(index);
+        }
+    }
+
+    // content of the region is not split by page breaks, a new page is started before
+    // the region if it does not fit into the rest of the page, regions can be nested;
+    // returns true if the new page was started
+    pub fn begin_keep_together(
+        &mut self,
+        content_offset: impl Into<Unit>,
+        content_height: impl Into<Unit>,
+    ) -> bool {
+        self.check_page_break(content_offset, content_height, true)
+    }
+
+    pub fn end_keep_together(&mut self) {
+        if self.page_break_reservations.pop().is_none() {
+            tracing::warn!("Keep together ended when not begun.");
+        }
+    }
+
+    // the page break check of the content, constrained by the options; content of lines
+    // of the same height is broken by pages so that neither too few of them are left before
+    // the break, nor moved after it, the page ends before the first line moved; returns
+    // true if the new page was started
+    pub fn check_page_break_with(
+        &mut self,
+        content_offset: impl Into<Unit>,
+        content_height: impl Into<Unit>,
+        reserve_content_height: bool,
+        options: Option<&PageBreakOptions>,
+    ) -> bool {
+        let content_offset = content_offset.into();
+        let content_height = content_height.into();
+        // lines kept together are not broken
+        let (line_height, page_break_lines) = match options.and_then(|options| options.lines) {
+            Some(lines)
+                if !reserve_content_height
+                    && Some(&true) != self.page_break_reservations.last() =>
+            {
+                lines
+            }
+            _ => {
+                return self.check_page_break(
+                    content_offset,
+                    content_height,
+                    reserve_content_height,
+                );
+            }
+        };
+        let Some(page_end) = self.page_end.as_ref().map(|page_end| page_end.y) else {
+            return false;
+        };
+
+        let lines = line_count(content_height, line_height);
+        let mm = |unit: Unit| Mm::from(unit).0;
+        let fitting = match mm(line_height) > 0.0 {
+            true => ((mm(page_end) - mm(content_offset)) / mm(line_height) + 1e-9)
+                .floor()
+                .max(0.0) as usize,
+            false => lines,
+        };
+        match page_break_lines.lines_before_break(lines, fitting) {
+            None => false,
+            Some(0) => self.check_page_break(
+                content_offset,
+                page_end - content_offset + line_height,
+                false,
+            ),
+            Some(before) => {
+                if let Some(page_end) = self.page_end.as_mut() {
+                    page_end.y = Mm(mm(content_offset) + mm(line_height) * before as f64).into();
+                }
+                false
+            }
+        }
+    }
+
+    fn check_page_break(
+        &mut self,
+        content_offset: impl Into<Unit>,
+        content_height: impl Into<Unit>,
+        reserve_content_height: bool,
+    ) -> bool {
+        let content_offset = content_offset.into();
+        let content_height = content_height.into();
+        let Some(check_page_break) = reserve_page_break(
+            &mut self.page_break_reservations,
+            content_height,
+            self.page_size.base_height() - self.page_margin.height(),
+            reserve_content_height,
+        ) else {
+            return false;
+        };
+
+        let mut new_page = false;
+        if let Some(page_end) = &self.page_end {
+            if !check_page_break {
+                tracing::debug!(
+                    "Page OVERFLOWN at offset {content_offset:?}, content height {content_height:?}, page end {:?}",
+                    page_end.y
+                );
+            } else if content_offset + content_height > page_end.y && self.is_cancelled() {
+                tracing::debug!("Page break skipped, rendering is cancelled");
+            } else if content_offset + content_height > page_end.y {
+                if self.debug_page_breaks && !self.dry_run {
+                    tracing::debug!(
+                        "Page BREAK at offset {content_offset:?}, content height {content_height:?}, page end {:?}",
+                        page_end.y
+                    );
+
+                    // content which did not fit would start here
+                    let width = page_end.x;
+                    let left = self.margin_offset(
+                        &self.page_content_offset(&Offset::new(Unit::zero(), content_offset)),
+                    );
+                    let right = Offset::new(left.x + width, left.y);
+                    self.debug_line(&[&left, &right], &Rgba::from((244, 67, 54, 1.0)));
+                }
+
+                if !self.next_column(content_offset) {
+                    self.paint_continuation(true);
+                    self.new_page(None, None);
+                    self.paint_continuation(false);
+                }
+                new_page = true;
+            } else if self.debug_page_breaks {
+                tracing::debug!(
+                    "Page fill CHECK at offset {content_offset:?}, content height {content_height:?}, page end {:?}",
+                    page_end.y
+                );
+            }
+        }
+
+        if self.page_start.is_none() {
+            self.set_page_offsets(content_offset);
+            self.repeat_header(content_offset);
+        }
+
+        new_page
+    }
+
+    // top left corner of content of the page, e.g. moved by the outer margin of even pages
+    // of mirrored margins
+    fn margin_offset(&self, content_position: &Offset) -> Offset {
+        let mut offset = self.page_margin.offset(content_position);
+        match self.mirrored_margins && self.page_number % 2 == 1 {
+            true => {
+                let left = self.page_margin.offset(&Offset::zero()).x;
+                offset.x_advance(self.page_margin.width() - left - left);
+            }
+            false => offset.x_advance(self.binding_offset),
+        }
+        offset
+    }
+
+    fn margin_width(&self) -> Unit {
+        self.page_margin.width() + self.binding_offset
+    }
+
+    fn set_page_offsets(&mut self, content_offset: Unit) {
+        let page_start = Offset::new(Unit::zero(), content_offset);
+
+        let mut page_end = page_start.clone();
+        page_end.x_advance(self.page_size.base_width() - self.margin_width());
+        page_end.y_advance(self.page_size.base_height() - self.page_margin.height());
+
+        self.page_start = Some(page_start);
+        self.page_end = Some(page_end);
+
+        // columns continue at the top of the new page
+        if let Some(columns) = self.columns.as_mut() {
+            columns.index = 0;
+            columns.top = Unit::zero();
+            columns.bottom = Unit::zero();
+            columns.page_end = self.page_size.base_height() - self.page_margin.height();
+            self.set_column(content_offset);
+        }
+    }
+
+    fn paint_line(&mut self, from: &Offset, to: &Offset, stroke: &Stroke) {
+        if self.stroke_style != StrokeStyle::Double {
+            self.paint_rule(from, to, stroke);
+            return;
+        }
+
+        // rules are shifted to both sides of the line
+        let mm = |unit: Unit| Mm::from(unit).0;
+        let (dx, dy) = (mm(to.x) - mm(from.x), mm(to.y) - mm(from.y));
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            return;
+        }
+        let (rule, shift) = double_rule(stroke);
+        let (shift_x, shift_y) = (-dy / length * mm(shift), dx / length * mm(shift));
+        for side in [-1.0, 1.0] {
+            let shifted = |point: &Offset| {
+                Offset::new(
+                    Mm(mm(point.x) + side * shift_x),
+                    Mm(mm(point.y) + side * shift_y),
+                )
+            };
+            self.paint_rule(&shifted(from), &shifted(to), &rule);
+        }
+    }
+
+    fn paint_rule(&mut self, from: &Offset, to: &Offset, stroke: &Stroke) {
+        let from = self.page_content_offset(from);
+        let from = self.margin_offset(&from);
+
+        let to = self.page_content_offset(to);
+        let to = self.margin_offset(&to);
+
+        let dashed = self.stroke_dash(stroke).is_some();
+        let layer_state = self.layer_state.clone();
+        if dashed {
+            self.layer.save_graphics_state();
+            self.apply_dash(stroke);
+        }
+
+        self.layer_state
+            .set_outline(&self.layer, self.color_model, stroke);
+
+        RenderContext::line(self, &[&from, &to]);
+
+        if dashed {
+            self.layer.restore_graphics_state();
+            self.layer_state = layer_state;
+        }
+    }
+
+    fn line(&self, content_points: &[&Offset]) {
+        self.line_on(&self.layer, content_points);
+    }
+
+    fn line_on(&self, layer: &PdfLayerReference, content_points: &[&Offset]) {
+        let line_points = content_points.iter().map(|point| {
+            let position = self.swap_y(point);
+            (
+                Point::new(from_unit(position.x), from_unit(position.y)),
+                false,
+            )
+        });
+
+        let mut polygon = Polygon::from_iter(line_points);
+        polygon.mode = PaintMode::Stroke;
+
+        layer.add_polygon(polygon);
+    }
+
+    // debug output goes to a separate layer, viewers can hide it
+    fn debug_layer(&mut self, color: &Rgba, thickness: f32) -> PdfLayerReference {
+        let layer = self.named_layer("debug");
+        if self.layer_name.as_deref() == Some("debug") {
+            self.layer_state.forget_outline();
+        }
+        layer.set_outline_color(self.color_model.color(color));
+        layer.set_outline_thickness(thickness);
+        layer
+    }
+
+    fn debug_line(&mut self, content_points: &[&Offset], color: &Rgba) {
+        let layer = self.debug_layer(color, 0.25);
+        self.line_on(&layer, content_points);
+    }
+
+    // origin is the start of the baseline in page coordinates (pt), guides follow text rotation
+    fn debug_text_metrics(&mut self, origin: (f32, f32), style: &Style, text: &TextPosition) {
+        let font = style.font().merge(self.style.font());
+        let Some(font_size) = font.size().map(|size| *size as f32) else {
+            return;
+        };
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0) as f32;
+        let width = text.width.0 as f32 * font_size * font_scaling;
+        let ascent = text.ascent().0 as f32 * font_size;
+        let depth = -(text.depth.0 as f32) * font_size;
+
+        let (sin, cos) = self.text_rotation.to_radians().sin_cos();
+        let point = |x: f32, y: f32| {
+            (
+                Point::new(
+                    printpdf::Pt(origin.0 + x * cos - y * sin).into(),
+                    printpdf::Pt(origin.1 + x * sin + y * cos).into(),
+                ),
+                false,
+            )
+        };
+        let stroke = |layer: &PdfLayerReference, from: (f32, f32), to: (f32, f32)| {
+            let mut polygon = Polygon::from_iter([point(from.0, from.1), point(to.0, to.1)]);
+            polygon.mode = PaintMode::Stroke;
+            layer.add_polygon(polygon);
+        };
+
+        let layer = self.debug_layer(&Rgba::from((255, 152, 0, 1.0)), 0.1);
+        let mut advance = 0.0;
+        stroke(&layer, (advance, depth), (advance, ascent));
+        for position in text.positions.iter() {
+            advance += position.h_advance.0 as f32 * font_size * font_scaling;
+            stroke(&layer, (advance, depth), (advance, ascent));
+        }
+
+        let layer = self.debug_layer(&Rgba::from((76, 175, 80, 1.0)), 0.1);
+        stroke(&layer, (0.0, ascent), (width, ascent));
+        stroke(&layer, (0.0, depth), (width, depth));
+
+        let layer = self.debug_layer(&Rgba::from((33, 150, 243, 1.0)), 0.25);
+        stroke(&layer, (0.0, 0.0), (width, 0.0));
+    }
+
+    // annotations are not affected by the graphics state, their rectangle is the bounding box
+    // of the corners (in pt) transformed by the open transform scopes
+    fn annotation_rect(&self, corners: [(f32, f32); 4]) -> Rect {
+        let corners = corners.map(|corner| self.transformed_point(corner));
+        bounding_rect(corners)
+    }
+
+    fn add_markup(&mut self, corners: [(f32, f32); 4], markup: &Markup) {
+        let corners = corners.map(|corner| self.transformed_point(corner));
+        let annotation = markup.annotation(&bounding_rect(corners), corners);
+        self.annotations.add(self.page_number, annotation);
+    }
+
+    pub fn markup(&mut self, content_position: &Offset, size: &Size, markup: &Markup) {
+        self.check_page_break(content_position.y, size.base_height(), false);
+
+        let rect = self.page_rect(content_position, size);
+        self.add_markup(rect_corners(&rect), markup);
+    }
+
+    // page point (in pt) where it ends up with the open transform scopes
+    fn transformed_point(&self, point: (f32, f32)) -> (f32, f32) {
+        let transform = |(x, y): (f32, f32), matrix: [f32; 6]| {
+            (
+                matrix[0] * x + matrix[2] * y + matrix[4],
+                matrix[1] * x + matrix[3] * y + matrix[5],
+            )
+        };
+        let point = self
+            .graphics_scopes
+            .iter()
+            .rev()
+            .fold(point, |point, scope| match scope {
+                GraphicsScope::Transform(transform_scope) => transform(
+                    point,
+                    transform_scope.matrix(|point| self.page_point(point)),
+                ),
+                _ => point,
+            });
+        match self.page_rotation() {
+            Some(matrix) => transform(point, matrix),
+            None => point,
+        }
+    }
+
+    // landscape content of a rotated page is turned into its portrait media box,
+    // the page rotation turns it back when displayed
+    fn page_rotation(&self) -> Option<[f32; 6]> {
+        if self.rotated_pages.last() != Some(&self.page_number) {
+            return None;
+        }
+        let height = from_unit(self.page_size.base_height()).into_pt().0;
+        Some([0.0, 1.0, -1.0, 0.0, height, 0.0])
+    }
+
+    fn add_link(&mut self, rect: Rect, target: &LinkTarget) {
+        match target {
+            LinkTarget::Uri(uri) => {
+                self.layer.add_link_annotation(LinkAnnotation::new(
+                    rect,
+                    Some(BorderArray::Solid([0.0, 0.0, 0.0])),
+                    Some(ColorArray::Transparent),
+                    Actions::uri(uri.to_string()),
+                    None,
+                ));
+            }
+            LinkTarget::Anchor(anchor) => {
+                self.annotations.add_link(self.page_number, &rect, anchor)
+            }
+        }
+    }
+
+    fn box_link(&mut self, content_position: &Offset, size: &Size, target: &LinkTarget) {
+        self.check_page_break(content_position.y, size.base_height(), false);
+
+        let rect = self.page_rect(content_position, size);
+        let rect = self.annotation_rect(rect_corners(&rect));
+        self.add_link(rect, target);
+    }
+
+    pub fn link(&mut self, content_position: &Offset, size: &Size, uri: &str) {
+        self.box_link(content_position, size, &LinkTarget::Uri(uri.to_string()));
+    }
+
+    pub fn internal_link(&mut self, content_position: &Offset, size: &Size, anchor: &str) {
+        self.box_link(
+            content_position,
+            size,
+            &LinkTarget::Anchor(anchor.to_string()),
+        );
+    }
+
+    // internal links jump to the page the position lands on, with the position at the top left
+    pub fn anchor(&mut self, name: &str, content_position: &Offset) {
+        self.add_anchor(name, content_position, false);
+    }
+
+    // anchor which is also reachable from outside, e.g. by #nameddest=name in the url
+    pub fn destination(&mut self, name: &str, content_position: &Offset) {
+        self.add_anchor(name, content_position, true);
+    }
+
+    // field keeps its rectangle when the content is transformed, it can not be rotated
+    pub fn form_field(&mut self, content_position: &Offset, size: &Size, field: &FormField) {
+        self.check_page_break(content_position.y, size.base_height(), false);
+
+        let rect = self.page_rect(content_position, size);
+        let rect = self.annotation_rect(rect_corners(&rect));
+        self.annotations
+            .add(self.page_number, field.annotation(&rect));
+    }
+
+    // icon of the note has its top left corner at the position
+    pub fn note(&mut self, content_position: &Offset, note: &Note) {
+        const ICON_SIZE: f32 = 20.0;
+
+        self.check_page_break(content_position.y, Unit::zero(), false);
+
+        let (left, top) = self.transformed_point(self.page_point(content_position));
+        let rect = Rect::new(
+            printpdf::Pt(left).into(),
+            printpdf::Pt(top - ICON_SIZE).into(),
+            printpdf::Pt(left + ICON_SIZE).into(),
+            printpdf::Pt(top).into(),
+        );
+        self.annotations
+            .add(self.page_number, note.annotation(&rect));
+    }
+
+    fn add_anchor(&mut self, name: &str, content_position: &Offset, named: bool) {
+        self.check_page_break(content_position.y, Unit::zero(), false);
+
+        let point = self.transformed_point(self.page_point(content_position));
+        self.annotations
+            .add_anchor(name, self.page_number, point, named);
+    }
+
+    fn page_rect(&self, content_position: &Offset, size: &Size) -> Rect {
+        let content_position = self.page_content_offset(content_position);
+        let top_left = self.swap_y(&self.margin_offset(&content_position));
+        let left = from_unit(top_left.x);
+        let top = from_unit(top_left.y);
+
+        Rect::new(
+            left,
+            top - from_unit(size.base_height()),
+            left + from_unit(size.base_width()),
+            top,
+        )
+    }
+
+    pub fn rect(
+        &mut self,
+        content_position: &Offset,
+        size: &Size,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        self.rounded_rect(content_position, size, Unit::zero(), fill, stroke);
+    }
+
+    // borders of layout styles are stroked by the layout line by line and stay square,
+    // boxes with rounded corners are drawn by this, table grids have their own radius
+    pub fn rounded_rect(
+        &mut self,
+        content_position: &Offset,
+        size: &Size,
+        radius: impl Into<Unit>,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        if fill.is_none() && stroke.is_none() {
+            return;
+        }
+
+        self.check_page_break(content_position.y, size.base_height(), false);
+        let radius = radius.into();
+        self.with_color_alpha(fill, stroke.map(Stroke::color), |rctx| {
+            rctx.paint_rounded_rect(content_position, size, radius, fill, stroke)
+        });
+    }
+
+    // drawn behind the box, so call it before the box background
+    pub fn shadow(
+        &mut self,
+        content_position: &Offset,
+        size: &Size,
+        radius: impl Into<Unit>,
+        shadow: &Shadow,
+    ) {
+        self.check_page_break(content_position.y, size.base_height(), false);
+
+        let layers = shadow.layers(content_position, size, radius.into());
+        self.with_opacity(shadow.layer_alpha(), |rctx| {
+            for (position, size, radius) in layers.iter() {
+                rctx.paint_rounded_rect(position, size, *radius, Some(shadow.color()), None);
+            }
+        });
+    }
+
+    fn paint_rounded_rect(
+        &self,
+        content_position: &Offset,
+        size: &Size,
+        radius: Unit,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        let Some(stroke) = stroke.filter(|_| self.stroke_style == StrokeStyle::Double) else {
+            self.paint_rect_shape(content_position, size, radius, fill, stroke);
+            return;
+        };
+
+        // rules outside and inside of the single stroke, square corners are kept
+        self.paint_rect_shape(content_position, size, radius, fill, None);
+        let (rule, shift) = double_rule(stroke);
+        for shift in [shift, Unit::zero() - shift] {
+            let position = Offset::new(content_position.x - shift, content_position.y - shift);
+            let size = Size::fixed(
+                size.base_width() + shift + shift,
+                size.base_height() + shift + shift,
+            );
+            let radius = match radius > Unit::zero() {
+                true if radius + shift > Unit::zero() => radius + shift,
+                _ => Unit::zero(),
+            };
+            self.paint_rect_shape(&position, &size, radius, None, Some(&rule));
+        }
+    }
+
+    fn paint_rect_shape(
+        &self,
+        content_position: &Offset,
+        size: &Size,
+        radius: Unit,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        let mode = match (fill, stroke) {
+            (Some(_), Some(_)) => PaintMode::FillStroke,
+            (Some(_), None) => PaintMode::Fill,
+            (None, Some(_)) => PaintMode::Stroke,
+            (None, None) => return,
+        };
+
+        let rect = self.page_rect(content_position, size).with_mode(mode);
+        let radius = from_unit(radius).into_pt().0;
+
+        self.layer.save_graphics_state();
+        if let Some(fill) = fill {
+            self.layer.set_fill_color(self.color_model.color(fill));
+        }
+        if let Some(stroke) = stroke {
+            self.layer
+                .set_outline_color(self.color_model.color(stroke.color()));
+            self.layer.set_outline_thickness(stroke_thickness(stroke));
+            self.apply_dash(stroke);
+        }
+        if radius > 0.0 {
+            let mut polygon = Polygon::from_iter(rounded_rect_points(&rect, radius));
+            polygon.mode = mode;
+            self.layer.add_polygon(polygon);
+        } else {
+            self.layer.add_rect(rect);
+        }
+        self.layer.restore_graphics_state();
+    }
+
+    // page coordinates in pt, origin at the bottom left page corner
+    fn page_point(&self, content_position: &Offset) -> (f32, f32) {
+        let content_position = self.page_content_offset(content_position);
+        let position = self.swap_y(&self.margin_offset(&content_position));
+        (
+            from_unit(position.x).into_pt().0,
+            from_unit(position.y).into_pt().0,
+        )
+    }
+
+    pub fn path(&mut self, path: &Path, fill: Option<&Rgba>, stroke: Option<&Stroke>) {
+        let Some(paint) = path.paint_operator(fill.is_some(), stroke.is_some()) else {
+            return;
+        };
+
+        let Some((top, bottom)) = path.v_extent() else {
+            return;
+        };
+        self.check_page_break(top, bottom - top, false);
+
+        self.with_color_alpha(fill, stroke.map(Stroke::color), |rctx| {
+            rctx.paint_path(path, paint, fill, stroke)
+        });
+    }
+
+    fn paint_path(
+        &self,
+        path: &Path,
+        paint: &'static str,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        self.layer.save_graphics_state();
+        if let Some(fill) = fill {
+            self.layer.set_fill_color(self.color_model.color(fill));
+        }
+        if let Some(stroke) = stroke {
+            self.layer
+                .set_outline_color(self.color_model.color(stroke.color()));
+            self.layer.set_outline_thickness(stroke_thickness(stroke));
+            self.apply_dash(stroke);
+        }
+        for operation in path.operations(|point| self.page_point(point)) {
+            self.layer.add_operation(operation);
+        }
+        self.layer.add_operation(Operation::new(paint, vec![]));
+        self.layer.restore_graphics_state();
+    }
+
+    // lines of a text block are rendered one by one, so a page break can come between
+    // any two of them instead of before the block, shaped texts spanning lines are split
+    // into them; line height defaults to the advance of each line, returns the offset
+    // below the last line
+    pub fn text_block(
+        &mut self,
+        content_position: &Offset,
+        style: &Style,
+        lines: &[TextPosition],
+        line_height: Option<Unit>,
+    ) -> Unit {
+        let font_size = style
+            .font()
+            .merge(self.style.font())
+            .size()
+            .unwrap_or(Pt(0.0));
+
+        let mut offset = content_position.y;
+        for (h_offset, advance, line) in lines.iter().flat_map(text_lines) {
+            let position = Offset::new(content_position.x + (h_offset * font_size).into(), offset);
+            layout::RenderContext::text(self, &position, style, &line, false);
+            offset = offset + line_height.unwrap_or_else(|| (advance * font_size).into());
+        }
+        offset
+    }
+
+    // glyphs clip the fill painted over the box of the text, e.g. a gradient
+    // of a display heading
+    pub fn fill_text(
+        &mut self,
+        content_position: &Offset,
+        style: &Style,
+        text: &TextPosition,
+        position_is_baseline: bool,
+        fill: &TextFill,
+    ) {
+        let Some(font_size) = style.font().merge(self.style.font()).size() else {
+            tracing::warn!("Try to typeset text without defined font");
+            return;
+        };
+
+        let top = match position_is_baseline {
+            true => content_position.y - (text.ascent() * font_size).into(),
+            false => content_position.y,
+        };
+        let top_left = Offset::new(content_position.x, top);
+        let size = Size::fixed(
+            self.text_width
+                .unwrap_or_else(|| (text.width * font_size).into()),
+            text.height * font_size,
+        );
+        // text and its fill have to stay on the same page
+        self.check_page_break(top, size.base_height(), false);
+
+        self.with_text_mode(TextMode::Clip, |rctx| {
+            layout::RenderContext::text(rctx, content_position, style, text, position_is_baseline);
+            match fill {
+                TextFill::Gradient(gradient) => {
+                    rctx.gradient(&Path::rect(&top_left, &size), gradient)
+                }
+                TextFill::Image(image) => rctx.image(&top_left, &size, image),
+            }
+        });
+    }
+
+    // sub-layout turned into the box, e.g. a sideways table header; it is measured and laid
+    // out with width and height of the box swapped, and never broken across pages
+    pub fn rotated_layout(
+        &mut self,
+        layout: &mut dyn Layout,
+        content_position: &Offset,
+        size: &Size,
+        turn: QuarterTurn,
+    ) -> Result<(), Error> {
+        let turned_size = Size::fixed(size.base_height(), size.base_width());
+        layout.measure(self, turned_size.clone())?;
+
+        self.check_page_break(content_position.y, size.base_height(), true);
+        let result = layout
+            .lay_out(self, content_position.clone(), turned_size)
+            .and_then(|_| {
+                self.push_transform(&turn.transform(content_position, size));
+                let result = layout.render(self);
+                self.pop_transform();
+                result
+            });
+        layout::RenderContext::release_page_break_reservation(self);
+        result
+    }
+
+    // rows, typeset before, are painted from the position down and link to their sections;
+    // returns the offset below the last row
+    pub fn table_of_contents(
+        &mut self,
+        content_position: &Offset,
+        width: Unit,
+        toc: &TableOfContents,
+    ) -> Unit {
+        let style = toc.style().clone();
+        let row_height = Unit::from(Pt(toc.row_height(&self.style) as f64));
+        let pt = |unit: Unit| from_unit(unit).into_pt().0;
+        let (leader_width, _, _) =
+            match layout::MeasureContext::typeset(self, &style, &toc.leader().to_string()) {
+                Ok(leader) => self.text_extent(&style, &leader),
+                Err(_) => (0.0, 0.0, 0.0),
+            };
+
+        let mut y = content_position.y;
+        for (entry, title, page) in toc.rows() {
+            self.check_page_break(y, row_height, false);
+
+            let left = content_position.x + toc.indent(entry.level);
+            let (title_width, _, _) = self.text_extent(&style, title);
+            let (page_width, _, _) = self.text_extent(&style, page);
+            let right = content_position.x + width - Unit::from(Pt(page_width as f64));
+            layout::RenderContext::text(self, &Offset::new(left, y), &style, title, false);
+            layout::RenderContext::text(self, &Offset::new(right, y), &style, page, false);
+
+            // leaders keep a gap of one leader from the title and the page number
+            let gap = pt(right) - pt(left) - title_width - 2.0 * leader_width;
+            if leader_width > 0.0 && gap > 0.0 {
+                let leaders = toc
+                    .leader()
+                    .to_string()
+                    .repeat((gap / leader_width).floor() as usize);
+                if let Ok(leaders) = layout::MeasureContext::typeset(self, &style, &leaders) {
+                    let (leaders_width, _, _) = self.text_extent(&style, &leaders);
+                    let x = right - Unit::from(Pt((leader_width + leaders_width) as f64));
+                    layout::RenderContext::text(self, &Offset::new(x, y), &style, &leaders, false);
+                }
+            }
+
+            self.internal_link(
+                &Offset::new(content_position.x, y),
+                &Size::fixed(width, row_height),
+                &entry.anchor,
+            );
+            y = y + row_height;
+        }
+        y
+    }
+
+    pub fn table_grid(&mut self, content_position: &Offset, grid: &TableGrid) {
+        let lines = grid.lines(content_position);
+        if lines.is_empty() {
+            return;
+        }
+        self.check_page_break(content_position.y, grid.height(), false);
+
+        self.layer.save_graphics_state();
+        // projecting caps close corners where lines meet
+        self.layer
+            .add_operation(Operation::new("J", vec![Object::Integer(2)]));
+        // strokes of different alpha set their own, so none of them is left over
+        let opacity = scopes_opacity(&self.graphics_scopes);
+        let translucent = lines
+            .iter()
+            .any(|(stroke, _)| stroke.color().into_rgba().3 < 1.0);
+        for (stroke, path) in lines {
+            if translucent {
+                self.apply_alpha(opacity, stroke.color().into_rgba().3 * opacity);
+            }
+            self.layer
+                .set_outline_color(self.color_model.color(stroke.color()));
+            self.layer.set_outline_thickness(stroke_thickness(&stroke));
+            // dashes of the stroke style follow the thickness of each stroke
+            self.apply_dash(&stroke);
+            for operation in path.operations(|point| self.page_point(point)) {
+                self.layer.add_operation(operation);
+            }
+            self.layer.add_operation(Operation::new("S", vec![]));
+        }
+        self.layer.restore_graphics_state();
+    }
+
+    pub fn gradient(&mut self, path: &Path, gradient: &Gradient) {
+        let Some((top, bottom)) = path.v_extent() else {
+            return;
+        };
+        self.check_page_break(top, bottom - top, false);
+
+        let Some(shading) = gradient.shading(|point| self.page_point(point), self.color_model)
+        else {
+            return;
+        };
+        let shading = self.add_resource("Shading", shading);
+
+        // the path clips the shading, which itself covers the whole clipping area
+        self.layer.save_graphics_state();
+        for operation in path.operations(|point| self.page_point(point)) {
+            self.layer.add_operation(operation);
+        }
+        self.layer
+            .add_operation(Operation::new(path.clip_operator(), vec![]));
+        self.layer.add_operation(Operation::new("n", vec![]));
+        self.layer.add_operation(Operation::new(
+            "sh",
+            vec![Object::Name(shading.into_bytes())],
+        ));
+        self.layer.restore_graphics_state();
+    }
+
+    pub fn image(&mut self, content_position: &Offset, size: &Size, image: &Image) {
+        self.check_page_break(content_position.y, size.base_height(), false);
+
+        let content_position = self.page_content_offset(content_position);
+        let top_left = self.swap_y(&self.margin_offset(&content_position));
+        let left = from_unit(top_left.x);
+        let top = from_unit(top_left.y);
+        let width = from_unit(size.base_width());
+        let height = from_unit(size.base_height());
+
+        self.paint_image(left, top, width, height, image);
+    }
+
+    // left and top are in page coordinates
+    fn paint_image(
+        &mut self,
+        left: printpdf::Mm,
+        top: printpdf::Mm,
+        width: printpdf::Mm,
+        height: printpdf::Mm,
+        image: &Image,
+    ) {
+        let placement = image.place(width.0, height.0);
+        let xobject = image.to_xobject(&placement);
+        self.images += 1;
+        self.image_bytes += xobject.image_data.len();
+
+        // 72 dpi maps one pixel onto one point, scale then gives the placed size
+        let dpi = 72.0;
+        let transform = ImageTransform {
+            translate_x: Some(printpdf::Mm(left.0 + placement.x)),
+            translate_y: Some(printpdf::Mm(top.0 - placement.y - placement.height)),
+            rotate: None,
+            scale_x: Some(printpdf::Mm(placement.width).into_pt().0 / xobject.width.0 as f32),
+            scale_y: Some(printpdf::Mm(placement.height).into_pt().0 / xobject.height.0 as f32),
+            dpi: Some(dpi),
+        };
+
+        if placement.clip {
+            self.layer.save_graphics_state();
+            self.layer.add_rect(
+                Rect::new(left, top - height, left + width, top).with_mode(PaintMode::Clip),
+            );
+        }
+
+        match image.decode() {
+            // the stream is embedded by the page resources to carry the decode array
+            Some(decode) => {
+                let left = printpdf::Mm(left.0 + placement.x).into_pt().0;
+                let bottom = printpdf::Mm(top.0 - placement.y - placement.height)
+                    .into_pt()
+                    .0;
+                let width = printpdf::Mm(placement.width).into_pt().0;
+                let height = printpdf::Mm(placement.height).into_pt().0;
+                let mut stream = lopdf::Stream::from(xobject);
+                stream.dict.set("Decode", decode);
+                let name = self.add_resource("XObject", stream);
+
+                self.layer.save_graphics_state();
+                self.layer.add_operation(Operation::new(
+                    "cm",
+                    [width, 0.0, 0.0, height, left, bottom]
+                        .map(Object::Real)
+                        .to_vec(),
+                ));
+                self.layer
+                    .add_operation(Operation::new("Do", vec![Object::Name(name.into_bytes())]));
+                self.layer.restore_graphics_state();
+            }
+            None => printpdf::Image::from(xobject).add_to_layer(self.layer.clone(), transform),
+        }
+
+        if placement.clip {
+            self.layer.restore_graphics_state();
+        }
+    }
+
+    // origin is the start of the baseline in page coordinates, in pt
+    fn paint_text(&mut self, origin: (f32, f32), style: &Style, text: &TextPosition) {
+        let font = style.font().merge(self.style.font());
+        if font.name().is_none() || font.size().is_none() {
+            tracing::warn!("Try to typeset text without defined font");
+            return;
+        }
+        let font_size = font.size().unwrap();
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0);
+
+        let (word_spacing, letter_spacing, stretch) = self.justification(
+            font.name().unwrap(),
+            text,
+            *(text.width * font_size * font_scaling),
+        );
+        let gaps = text.positions.len().saturating_sub(1);
+        let space = match word_spacing > 0.0 {
+            true => self.fonts.space_glyph(font.name().unwrap()),
+            false => None,
+        };
+        let spacing = |index: usize, glyph: u16| {
+            let mut spacing = 0.0;
+            if index < gaps {
+                spacing += letter_spacing;
+                if Some(glyph) == space {
+                    spacing += word_spacing;
+                }
+            }
+            spacing
+        };
+
+        if self.fonts.text_outlines {
+            self.paint_outlines(origin, style, font.name().unwrap(), text, spacing);
+            self.paint_text_decoration(origin, style, font.name().unwrap(), text, stretch);
+            return;
+        }
+
+        let font_ref = self.fonts.get_font_ref(font.name().unwrap()).unwrap();
+        // justified glyphs advance by their font widths and the character spacing,
+        // glyphs moved vertically are positioned one by one
+        let widths =
+            match stretch > 0.0
+                && text.positions.iter().all(|position| {
+                    position.v_offset.is_zero() && position.v_advance_rest().is_zero()
+                }) {
+                true => text
+                    .positions
+                    .iter()
+                    .map(|position| {
+                        self.fonts
+                            .glyph_width(font.name().unwrap(), position.glyph_index)
+                    })
+                    .collect::<Option<Vec<_>>>(),
+                false => None,
+            };
+
+        // text state is a part of the graphics state, it is not isolated, so only
+        // its changes are written; the clip of clipping text has to outlive the text anyway
+        self.layer.begin_text_section();
+        self.layer_state.set_fill_color(
+            &self.layer,
+            self.color_model,
+            style.color().unwrap_or(&Rgba::black()),
+        );
+        self.layer_state.set_font(
+            &self.layer,
+            font.name().unwrap(),
+            font_ref,
+            *font_size as f32,
+        );
+        if let Some(stroke) = self.text_mode.stroke() {
+            self.layer_state
+                .set_outline(&self.layer, self.color_model, stroke);
+        }
+        self.layer_state
+            .set_rendering_mode(&self.layer, self.text_mode.rendering_mode());
+        self.layer_state
+            .set_text_scaling(&self.layer, 100.0 * font_scaling as f32);
+
+        let layer = &self.layer;
+        if self.text_rotation == 0.0 {
+            layer.set_text_cursor(printpdf::Pt(origin.0).into(), printpdf::Pt(origin.1).into());
+        } else {
+            layer.set_text_matrix(TextMatrix::TranslateRotate(
+                printpdf::Pt(origin.0),
+                printpdf::Pt(origin.1),
+                self.text_rotation,
+            ));
+        }
+
+        if let Some(widths) = widths {
+            // TJ adjustments are in thousandths of em, the character spacing is scaled
+            // horizontally like glyph widths; runs of the same spacing share a TJ
+            let mut elements = vec![];
+            for (index, (position, width)) in text.positions.iter().zip(widths).enumerate() {
+                if index < gaps {
+                    let character_spacing =
+                        (spacing(index, position.glyph_index) / font_scaling) as f32;
+                    if !elements.is_empty()
+                        && self.layer_state.character_spacing() != Some(character_spacing)
+                    {
+                        layer.add_operation(Operation::new(
+                            "TJ",
+                            vec![Object::Array(std::mem::take(&mut elements))],
+                        ));
+                    }
+                    self.layer_state
+                        .set_character_spacing(layer, character_spacing);
+                }
+                if !position.h_offset.is_zero() {
+                    elements.push(Object::Real((-position.h_offset.0 * 1000.0) as f32));
+                }
+                elements.push(Object::String(
+                    position.glyph_index.to_be_bytes().to_vec(),
+                    StringFormat::Hexadecimal,
+                ));
+                let adjustment = width as f64 - position.h_advance_rest().0 * 1000.0;
+                if adjustment != 0.0 {
+                    elements.push(Object::Real(adjustment as f32));
+                }
+            }
+            layer.add_operation(Operation::new("TJ", vec![Object::Array(elements)]));
+            layer.end_text_section();
+
+            self.paint_text_decoration(origin, style, font.name().unwrap(), text, stretch);
+            return;
+        }
+
+        // pen position is kept unrounded and every move goes to its rounded value,
+        // so rounding errors do not accumulate along the run
+        let mut pen = (0.0, 0.0);
+        let mut cursor = (0.0, 0.0);
+        let mut move_by = |h: Pt, v: Pt| {
+            pen = (pen.0 + *h, pen.1 + *v);
+            let target = (round(pen.0, self.precision), round(pen.1, self.precision));
+            layer.add_operation(Operation::new(
+                "Td",
+                vec![
+                    real(target.0 - cursor.0, self.precision),
+                    real(target.1 - cursor.1, self.precision),
+                ],
+            ));
+            cursor = target;
+        };
+
+        for (index, position) in text.positions.iter().enumerate() {
+            let h_offset = position.h_offset;
+            let v_offset = position.v_offset;
+            if !h_offset.is_zero() || !v_offset.is_zero() {
+                move_by(h_offset * font_size * font_scaling, v_offset * font_size);
+            }
+
+            layer.write_codepoints([position.glyph_index]);
+
+            move_by(
+                Pt(*(position.h_advance_rest() * font_size * font_scaling)
+                    + spacing(index, position.glyph_index)),
+                position.v_advance_rest() * font_size,
+            );
+        }
+
+        layer.end_text_section();
+
+        self.paint_text_decoration(origin, style, font.name().unwrap(), text, stretch);
+    }
+
+    // glyphs are filled or stroked as paths, in the same positions as text would be
+    fn paint_outlines(
+        &mut self,
+        origin: (f32, f32),
+        style: &Style,
+        font_name: &str,
+        text: &TextPosition,
+        spacing: impl Fn(usize, u16) -> f64,
+    ) {
+        let font = style.font().merge(self.style.font());
+        let font_size = font.size().unwrap();
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0);
+
+        // paths are transformed here, a cm would have to be restored before clipping
+        let [a, b, c, d, e, f]: [f32; 6] = CurTransMat::TranslateRotate(
+            printpdf::Pt(origin.0),
+            printpdf::Pt(origin.1),
+            self.text_rotation,
+        )
+        .into();
+        let (h_scale, v_scale) = ((*font_size * font_scaling) as f32, *font_size as f32);
+
+        let mut operations = vec![];
+        let mut pen = (0.0, 0.0);
+        for (index, position) in text.positions.iter().enumerate() {
+            let glyph_origin = (
+                pen.0 + *(position.h_offset * font_size * font_scaling),
+                pen.1 + *(position.v_offset * font_size),
+            );
+            let point = |x: f32, y: f32| {
+                let x = glyph_origin.0 as f32 + x * h_scale;
+                let y = glyph_origin.1 as f32 + y * v_scale;
+                [a * x + c * y + e, b * x + d * y + f].map(Object::Real)
+            };
+            for segment in self
+                .fonts
+                .glyph_outline(font_name, position.glyph_index)
+                .unwrap_or_default()
+            {
+                operations.push(match segment {
+                    OutlineSegment::MoveTo(x, y) => Operation::new("m", point(x, y).to_vec()),
+                    OutlineSegment::LineTo(x, y) => Operation::new("l", point(x, y).to_vec()),
+                    OutlineSegment::CurveTo([x1, y1, x2, y2, x, y]) => {
+                        Operation::new("c", [point(x1, y1), point(x2, y2), point(x, y)].concat())
+                    }
+                    OutlineSegment::Close => Operation::new("h", vec![]),
+                });
+            }
+
+            pen = (
+                pen.0
+                    + *(position.h_advance * font_size * font_scaling)
+                    + spacing(index, position.glyph_index),
+                pen.1 + *(position.v_advance * font_size),
+            );
+        }
+
+        let paint = match self.text_mode {
+            TextMode::Fill => vec!["f"],
+            TextMode::Stroke(_) => vec!["S"],
+            TextMode::FillStroke(_) => vec!["B"],
+            TextMode::Invisible => return,
+            TextMode::Clip => vec!["W", "n"],
+        };
+        // like with text, the state is kept and the clip outlives the text
+        self.layer_state.set_fill_color(
+            &self.layer,
+            self.color_model,
+            style.color().unwrap_or(&Rgba::black()),
+        );
+        if let Some(stroke) = self.text_mode.stroke() {
+            self.layer_state
+                .set_outline(&self.layer, self.color_model, stroke);
+        }
+        for operation in operations {
+            self.layer.add_operation(operation);
+        }
+        for operator in paint {
+            self.layer.add_operation(Operation::new(operator, vec![]));
+        }
+    }
+
+    fn paint_text_decoration(
+        &self,
+        origin: (f32, f32),
+        style: &Style,
+        font_name: &str,
+        text: &TextPosition,
+        stretch: f64,
+    ) {
+        let font = style.font().merge(self.style.font());
+        let font_size = font.size().unwrap();
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0);
+
+        if !self.text_decoration.is_empty()
+            && let Some(metrics) = self.fonts.decoration_metrics(font_name)
+        {
+            let width = (*(text.width * font_size * font_scaling) + stretch) as f32;
+            let font_size = *font_size as f32;
+
+            // rules are drawn in text space, so they follow rotated text
+            self.layer.save_graphics_state();
+            self.layer.set_ctm(CurTransMat::TranslateRotate(
+                printpdf::Pt(origin.0),
+                printpdf::Pt(origin.1),
+                self.text_rotation,
+            ));
+            self.layer.set_fill_color(
+                self.color_model
+                    .color(style.color().unwrap_or(&Rgba::black())),
+            );
+            for (center, thickness) in self.text_decoration.rules(&metrics, text.ascent()) {
+                let thickness = thickness.0 as f32 * font_size;
+                let bottom = center.0 as f32 * font_size - thickness / 2.0;
+                self.layer.add_operation(Operation::new(
+                    "re",
+                    vec![
+                        Object::Real(0.0),
+                        Object::Real(bottom),
+                        Object::Real(width),
+                        Object::Real(thickness),
+                    ],
+                ));
+            }
+            self.layer.add_operation(Operation::new("f", vec![]));
+            self.layer.restore_graphics_state();
+        }
+    }
+
+    // extra space (in pt) after word spaces and after every glyph, and the total
+    // stretch; Tw would apply to one-byte codes of spaces only, not to two-byte glyph
+    // ids of embedded fonts, so word spaces get the character spacing instead
+    fn justification(&self, font_name: &str, text: &TextPosition, width: f64) -> (f64, f64, f64) {
+        let Some(text_width) = self.text_width else {
+            return (0.0, 0.0, 0.0);
+        };
+        let stretch = from_unit(text_width).into_pt().0 as f64 - width;
+        let gaps = text.positions.len().saturating_sub(1);
+        if stretch <= 0.0 || gaps == 0 {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let space = self.fonts.space_glyph(font_name);
+        let spaces = text.positions[..gaps]
+            .iter()
+            .filter(|position| Some(position.glyph_index) == space)
+            .count();
+        match spaces {
+            0 => (0.0, stretch / gaps as f64, stretch),
+            spaces => (stretch / spaces as f64, 0.0, stretch),
+        }
+    }
+
+    // SVG is converted to a form XObject of PDF vector operators, scaled to fit the box
+    #[cfg(feature = "svg")]
+    pub fn svg(&mut self, content_position: &Offset, size: &Size, svg: &str) -> Result<(), Error> {
+        let svg =
+            printpdf::Svg::parse(svg).map_err(|error| Error::PdfWrite(error.to_string().into()))?;
+        if svg.width.0 == 0 || svg.height.0 == 0 {
+            return Ok(());
+        }
+
+        self.check_page_break(content_position.y, size.base_height(), false);
+
+        let content_position = self.page_content_offset(content_position);
+        let top_left = self.swap_y(&self.margin_offset(&content_position));
+        let left = from_unit(top_left.x).into_pt();
+        let top = from_unit(top_left.y).into_pt();
+        let width = from_unit(size.base_width()).into_pt();
+        let height = from_unit(size.base_height()).into_pt();
+
+        // svg2pdf bounding box is in points
+        let placement = super::ImageFit::Contain.place(
+            width.0,
+            height.0,
+            svg.width.0 as f32,
+            svg.height.0 as f32,
+        );
+
+        let transform = printpdf::SvgTransform {
+            translate_x: Some(printpdf::Pt(left.0 + placement.x)),
+            translate_y: Some(printpdf::Pt(top.0 - placement.y - placement.height)),
+            rotate: None,
+            scale_x: Some(placement.width / svg.width.0 as f32),
+            scale_y: Some(placement.height / svg.height.0 as f32),
+            dpi: Some(72.0),
+        };
+        svg.add_to_layer(&self.layer, transform);
+
+        Ok(())
+    }
+}
+
+// shaped text moving its pen down spans lines, it is split after every glyph moving
+// to the next line; lines come with the horizontal pen offset they start at and
+// the advance to the next line, the last one advances by its height
+fn text_lines(text: &TextPosition) -> Vec<(Em, Em, TextPosition)> {
+    if text
+        .positions
+        .iter()
+        .all(|position| position.v_advance.is_zero())
+    {
+        return vec![(Em(0.0), text.height, text.clone())];
+    }
+
+    let travel = -text
+        .positions
+        .iter()
+        .map(|position| position.v_advance.0)
+        .sum::<f64>();
+    let line = |positions, width| TextPosition {
+        width: Em(width),
+        height: Em(text.height.0 - travel),
+        depth: text.depth,
+        positions,
+    };
+
+    let mut lines = vec![];
+    let (mut pen, mut start, mut width) = (0.0, 0.0, 0.0);
+    let mut positions = vec![];
+    for position in text.positions.iter() {
+        positions.push(position.clone());
+        pen += position.h_advance.0;
+        if position.v_advance.is_zero() {
+            width += position.h_advance.0;
+            continue;
+        }
+        lines.push((
+            Em(start),
+            Em(-position.v_advance.0),
+            line(std::mem::take(&mut positions), width),
+        ));
+        (start, width) = (pen, 0.0);
+    }
+    if !positions.is_empty() {
+        lines.push((
+            Em(start),
+            Em(text.height.0 - travel),
+            line(positions, width),
+        ));
+    }
+    lines
+}
+
+// cubic Bezier handle length approximating a quarter circle of radius 1
+pub(crate) const KAPPA: f32 = 0.552_284_8;
+
+// rule of a double stroke, a third of its thickness, and distance of its middle
+// from the middle of the stroke
+fn double_rule(stroke: &Stroke) -> (Stroke, Unit) {
+    let third = Pt(stroke.thickness().0 / 3.0);
+    (Stroke::new(*stroke.color(), third), third.into())
+}
+
+fn layer_z_index(z_indexes: &[(String, i32)], name: &[u8]) -> i32 {
+    let z_index = z_indexes
+        .iter()
+        .find(|(existing, _)| existing.as_bytes() == name);
+    match (z_index, name) {
+        (Some((_, z_index)), _) => *z_index,
+        (None, b"overlay" | b"debug") => i32::MAX,
+        (None, _) => 0,
+    }
+}
+
+fn scopes_opacity(scopes: &[GraphicsScope]) -> f32 {
+    scopes
+        .iter()
+        .map(|scope| match scope {
+            GraphicsScope::Opacity(alpha) => *alpha,
+            _ => 1.0,
+        })
+        .product()
+}
+
+// counterclockwise from the bottom left corner, in pt
+fn rect_corners(rect: &Rect) -> [(f32, f32); 4] {
+    let (left, bottom) = (rect.ll.x.0, rect.ll.y.0);
+    let (right, top) = (rect.ur.x.0, rect.ur.y.0);
+    [(left, bottom), (right, bottom), (right, top), (left, top)]
+}
+
+fn bounding_rect(corners: [(f32, f32); 4]) -> Rect {
+    let (xs, ys) = (corners.map(|(x, _)| x), corners.map(|(_, y)| y));
+    Rect::new(
+        printpdf::Pt(xs.into_iter().fold(f32::MAX, f32::min)).into(),
+        printpdf::Pt(ys.into_iter().fold(f32::MAX, f32::min)).into(),
+        printpdf::Pt(xs.into_iter().fold(f32::MIN, f32::max)).into(),
+        printpdf::Pt(ys.into_iter().fold(f32::MIN, f32::max)).into(),
+    )
+}
+
+fn rounded_rect_points(rect: &Rect, radius: f32) -> Vec<(Point, bool)> {
+    let (left, bottom, right, top) = (rect.ll.x.0, rect.ll.y.0, rect.ur.x.0, rect.ur.y.0);
+    let radius = radius.min((right - left) / 2.0).min((top - bottom) / 2.0);
+    let handle = radius * (1.0 - KAPPA);
+
+    let point = |x: f32, y: f32, bezier: bool| {
+        (
+            Point {
+                x: printpdf::Pt(x),
+                y: printpdf::Pt(y),
+            },
+            bezier,
+        )
+    };
+
+    // clockwise from top left, every corner is start, two handles, end
+    vec![
+        point(left, top - radius, true),
+        point(left, top - handle, true),
+        point(left + handle, top, false),
+        point(left + radius, top, false),
+        point(right - radius, top, true),
+        point(right - handle, top, true),
+        point(right, top - handle, false),
+        point(right, top - radius, false),
+        point(right, bottom + radius, true),
+        point(right, bottom + handle, true),
+        point(right - handle, bottom, false),
+        point(right - radius, bottom, false),
+        point(left + radius, bottom, true),
+        point(left + handle, bottom, true),
+        point(left, bottom + handle, false),
+        point(left, bottom + radius, false),
+    ]
+}
+
+impl layout::MeasureContext for RenderContext {
+    fn style(&self) -> &Style {
+        self.style.as_ref()
+    }
+
+    fn typeset(&mut self, style: &Style, text: &str) -> Result<TextPosition, Error> {
+        let font = style.font().merge(self.style.font());
+        if let Some(name) = font.name()
+            && font.size().is_some()
+        {
+            // digits are reserved, slots may be typeset again when fonts are completed
+            let substituted = substitute_page_values(text, None, self.page_values.as_ref());
+            if substituted.is_some() {
+                self.fonts.reserve_glyphs(name, PAGE_VALUE_CHARS.chars())?;
+                self.page_values_used = true;
+            }
+            let features = font.features().cloned().unwrap_or_default();
+            let kerning = self.marks.kerning();
+            let position = self.fonts.typeset_with(
+                name,
+                substituted.as_deref().unwrap_or(text),
+                &features,
+                kerning,
+            )?;
+            if has_page_slot(text) {
+                self.page_slot_runs.record(name, &position, text, kerning);
+            }
+            Ok(position)
+        } else {
+            Err(Error::UnknownFont("Font name or size is undefined".into()))
+        }
+    }
+}
+
+impl LinePages for RenderContext {
+    fn page_end(&self) -> Option<Unit> {
+        self.page_end.as_ref().map(|page_end| page_end.y)
+    }
+
+    fn break_page(&mut self, content_offset: Unit, content_height: Unit) -> bool {
+        RenderContext::check_page_break(self, content_offset, content_height, false)
+    }
+}
+
+impl layout::RenderContext for RenderContext {
+    fn debug_frame(&mut self, content_position: &Offset, size: &Size) {
+        self.take_structure_marks();
+        let marks = self.marks.take();
+        self.take_marks(marks);
+        if self.debug_frame && !self.dry_run {
+            let content_position = self.page_content_offset(content_position);
+            let top_left = self.margin_offset(&content_position);
+            let bottom_right = &top_left + size;
+
+            let points = [
+                &top_left,
+                &Offset::new(bottom_right.x, top_left.y),
+                &bottom_right,
+                &Offset::new(top_left.x, bottom_right.y),
+                &top_left,
+            ];
+
+            self.debug_line(&points, &Rgba::from((240, 240, 240, 1.0)));
+        }
+    }
+
+    fn check_page_break(&mut self, offset: Unit, height: Unit, reserve_height: bool) -> bool {
+        let marks = self.marks.take();
+        let options = marks.iter().rev().find_map(|mark| match mark {
+            Mark::PageBreak(options) => Some(options.clone()),
+            _ => None,
+        });
+        let new_page = self.check_page_break_with(offset, height, reserve_height, options.as_ref());
+        self.take_structure_marks();
+        self.take_marks(marks);
+        new_page
+    }
+
+    fn release_page_break_reservation(&mut self) {
+        if self.page_break_reservations.pop().is_none() {
+            tracing::warn!("Page break reservation released when not defined.");
+        }
+    }
+
+    fn new_page(&mut self, options: Option<NewPageOptions>) {
+        self.take_structure_marks();
+        let marks = self.marks.take();
+        self.take_marks(marks);
+        RenderContext::new_page(
+            self,
+            options.as_ref().and_then(|options| options.margin.as_ref()),
+            options.as_ref().and_then(|options| options.size.as_ref()),
+        );
+    }
+
+    fn line(&mut self, from: &Offset, to: &Offset, stroke: &Stroke) {
+        self.take_structure_marks();
+        let marks = self.marks.take();
+        self.take_marks(marks);
+
+        split_line(self, from, to, |rctx, from, to| {
+            if !rctx.dry_run {
+                rctx.with_color_alpha(None, Some(stroke.color()), |rctx| {
+                    rctx.paint_line(from, to, stroke)
+                });
+            }
+        });
+    }
+
+    fn text(
+        &mut self,
+        content_position: &Offset,
+        style: &Style,
+        text: &TextPosition,
+        position_is_baseline: bool,
+    ) {
+        self.take_structure_marks();
+        let marks = self.marks.take();
+        self.take_marks(marks);
+        if text.positions.is_empty() {
+            return;
+        }
+
+        let font = style.font().merge(self.style.font());
+        if font.name().is_none() || font.size().is_none() {
+            tracing::warn!("Try to typeset text without defined font");
+            return;
+        }
+        let font_size = font.size().unwrap();
+
+        self.check_page_break(content_position.y, text.height * font_size, false);
+        if self.dry_run {
+            return;
+        }
+        // the page is known once it is checked
+        let page_slot_text = self.page_slot_text(&font, text);
+        let text = page_slot_text.as_ref().unwrap_or(text);
+
+        let content_position = self.page_content_offset(content_position);
+        let mut page_position = self.margin_offset(&content_position);
+        let anchor = self.swap_y(&page_position);
+        if !position_is_baseline {
+            page_position.y_advance(text.ascent() * font.size().unwrap());
+        }
+        let page_position = self.swap_y(&page_position);
+
+        // baseline is below the anchor in rotated text space
+        let angle = self.text_rotation.to_radians();
+        let drop = from_unit(anchor.y - page_position.y).into_pt().0
+            - self.baseline_shift.0 as f32 * *font_size as f32;
+        let origin = (
+            from_unit(anchor.x).into_pt().0 + drop * angle.sin(),
+            from_unit(anchor.y).into_pt().0 - drop * angle.cos(),
+        );
+
+        let marked = match self.structure.as_mut() {
+            Some(structure) if !structure.in_figure() => {
+                Some(structure.mark_text(self.page_number))
+            }
+            _ => None,
+        };
+        if let Some((tag, mcid)) = &marked {
+            self.layer.add_operation(marked_content(tag, *mcid));
+        }
+        let stroke = self.text_mode.stroke().map(|stroke| *stroke.color());
+        self.with_color_alpha(style.color(), stroke.as_ref(), |rctx| {
+            rctx.paint_text(origin, style, text)
+        });
+        if marked.is_some() {
+            self.layer.add_operation(Operation::new("EMC", vec![]));
+        }
+        if self.debug_text_metrics {
+            self.debug_text_metrics(origin, style, text);
+        }
+
+        if self.link.is_some() || !self.markups.is_empty() {
+            let font_size = *font_size as f32;
+            let font_scaling = font
+                .scaling()
+                .as_ref()
+                .map(FillPerMille::scaling)
+                .unwrap_or(1.0) as f32;
+            let width = text.width.0 as f32 * font_size * font_scaling;
+            let width = width
+                + self
+                    .justification(font.name().unwrap(), text, width as f64)
+                    .2 as f32;
+            let ascent = text.ascent().0 as f32 * font_size;
+            let depth = -(text.depth.0 as f32) * font_size;
+
+            let (sin, cos) = angle.sin_cos();
+            let corner =
+                |x: f32, y: f32| (origin.0 + x * cos - y * sin, origin.1 + x * sin + y * cos);
+            let corners = [
+                corner(0.0, depth),
+                corner(width, depth),
+                corner(width, ascent),
+                corner(0.0, ascent),
+            ];
+
+            if let Some(target) = self.link.clone() {
+                let rect = self.annotation_rect(corners);
+                self.add_link(rect, &target);
+            }
+            for markup in self.markups.clone() {
+                self.add_markup(corners, &markup);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        fs::File,
+        io::{BufWriter, Write},
+        rc::Rc,
+    };
+
+    use layout::{
+        Axis, Border, Error, Features, Font, GlyphPosition, LayoutBox, MeasureContext,
+        RenderContext as _, Rgba, Stroke, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Em, Mm, Pt, Unit},
+    };
+    use printpdf::{PdfDocument, lopdf::Object};
+
+    use crate::{
+        CancellationToken, ColorModel, Compression, Continuation, Dash, FillRule, FormField,
+        Gradient, IccProfile, Image, ImageColorSpace, ImageFit, Marks, Markup, Note, NoteIcon,
+        PageAnchor, PageNumbering, PageTemplate, PageTemplates, PageValues, Path, PdfALevel,
+        PdfVersion, PrintMarks, QuarterTurn, RenderOptions, RenderProgress, Shadow, SoftMask,
+        StrokeStyle, TableGrid, TextDecoration, TextFill, TextMode, Transform, Watermark,
+        new_font_cache,
+    };
+
+    use super::{Mark, RenderContext, StrokeMark};
+
+    #[test]
+    fn render_context() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::empty(),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_debug_frame(true)
+        .with_debug_text_metrics(true);
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new(
+                "LatoReg",
+                Pt(36.0),
+                Some(Features::empty().pnum()),
+            ))
+            .build();
+
+        let text1 = rctx
+            .typeset(&style, "Fimfifárumík 12115 jgenealogie")
+            .unwrap();
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new(
+                "LatoReg",
+                Pt(36.0),
+                Some(Features::empty().tnum().smcp()),
+            ))
+            .build();
+
+        let text2 = rctx
+            .typeset(&style, "Fimfifárumík 12115 jgenealogie")
+            .unwrap();
+
+        rctx.set_watermark(Some(
+            Watermark::text("DRAFT", style.clone())
+                .with_rotation(45.0)
+                .with_opacity(0.2),
+        ))
+        .unwrap();
+
+        rctx.complete_fonts().unwrap();
+
+        rctx.text(&Offset::new(Mm(20.0), Mm(20.0)), &style, &text1, true);
+
+        rctx.text(&Offset::new(Mm(20.0), Mm(40.0)), &style, &text2, true);
+
+        rctx.with_text_rotation(90.0, |rctx| {
+            rctx.text(&Offset::new(Mm(20.0), Mm(250.0)), &style, &text1, false)
+        });
+        rctx.with_text_rotation(45.0, |rctx| {
+            rctx.text(&Offset::new(Mm(40.0), Mm(250.0)), &style, &text2, false)
+        });
+
+        rctx.with_text_mode(
+            TextMode::Stroke(Stroke::new(Rgba::black(), Pt(0.5))),
+            |rctx| rctx.text(&Offset::new(Mm(20.0), Mm(80.0)), &style, &text1, false),
+        );
+        rctx.with_text_mode(TextMode::Clip, |rctx| {
+            rctx.text(&Offset::new(Mm(20.0), Mm(100.0)), &style, &text1, false);
+            rctx.gradient(
+                &Path::rect(
+                    &Offset::new(Mm(20.0), Mm(100.0)),
+                    &Size::fixed(Mm(170.0), Mm(15.0)),
+                ),
+                &Gradient::linear(
+                    Offset::new(Mm(20.0), Mm(100.0)),
+                    Offset::new(Mm(190.0), Mm(100.0)),
+                )
+                .with_stop(0.0, Rgba::from((13, 71, 161, 1.0)))
+                .with_stop(1.0, Rgba::from((244, 67, 54, 1.0))),
+            );
+        });
+        rctx.with_text_mode(TextMode::Invisible, |rctx| {
+            rctx.text(&Offset::new(Mm(20.0), Mm(120.0)), &style, &text1, false)
+        });
+        rctx.with_text_decoration(
+            TextDecoration::empty().underline().strikethrough(),
+            |rctx| rctx.text(&Offset::new(Mm(20.0), Mm(140.0)), &style, &text1, false),
+        );
+        rctx.with_text_decoration(TextDecoration::empty().underline(), |rctx| {
+            rctx.text(&Offset::new(Mm(20.0), Mm(180.0)), &style, &text1, false)
+        });
+        rctx.with_markup(Markup::highlight(Rgba::from((255, 235, 59, 1.0))), |rctx| {
+            rctx.with_markup(Markup::squiggly(Rgba::from((244, 67, 54, 1.0))), |rctx| {
+                rctx.text(&Offset::new(Mm(20.0), Mm(200.0)), &style, &text2, false)
+            })
+        });
+        rctx.with_baseline_shift(Em(0.33), |rctx| {
+            rctx.text(&Offset::new(Mm(150.0), Mm(140.0)), &style, &text1, false)
+        });
+        rctx.with_baseline_shift(Em(-0.2), |rctx| {
+            rctx.text(&Offset::new(Mm(150.0), Mm(160.0)), &style, &text1, false)
+        });
+        rctx.with_text_width(Mm(170.0), |rctx| {
+            rctx.with_text_decoration(TextDecoration::empty().underline(), |rctx| {
+                rctx.text(&Offset::new(Mm(20.0), Mm(220.0)), &style, &text1, false)
+            })
+        });
+        rctx.with_text_rotation(90.0, |rctx| {
+            rctx.with_text_decoration(TextDecoration::empty().overline(), |rctx| {
+                rctx.text(&Offset::new(Mm(60.0), Mm(250.0)), &style, &text1, false)
+            })
+        });
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new(
+                "LatoReg",
+                Pt(18.0),
+                Some(Features::empty().pnum()),
+            ))
+            .build();
+        rctx.text(&Offset::new(Mm(20.0), Mm(60.0)), &style, &text2, true);
+        let style = StyleBuilder::default()
+            .with_font(Font::new(
+                "LatoReg",
+                Pt(12.0),
+                Some(Features::empty().pnum()),
+            ))
+            .build();
+        rctx.text(
+            &Offset::new(Mm(20.0) + text2.width * Pt(18.0), Mm(60.0)),
+            &style,
+            &text2,
+            true,
+        );
+
+        rctx.document
+            .save(&mut BufWriter::new(
+                File::create("test_fonts_r.pdf").unwrap(),
+            ))
+            .unwrap();
+    }
+
+    #[test]
+    fn rect() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(190.0), Mm(10.0));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            Some(&Rgba::from((240, 240, 240, 1.0))),
+            None,
+        );
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(20.0)),
+            &size,
+            Some(&Rgba::from((255, 230, 128, 1.0))),
+            Some(&Stroke::new(Rgba::from((135, 135, 135, 1.0)), Pt(0.5))),
+        );
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(40.0)),
+            &size,
+            None,
+            Some(&Stroke::new(Rgba::black(), Pt(1.0))),
+        );
+        let dash = StrokeStyle::Pattern(Dash::dashed(Mm(3.0), Mm(1.5)));
+        rctx.with_stroke_style(dash, |rctx| {
+            layout::RenderContext::line(
+                rctx,
+                &Offset::new(Mm(0.0), Mm(55.0)),
+                &Offset::new(Mm(190.0), Mm(55.0)),
+                &Stroke::new(Rgba::black(), Pt(0.5)),
+            )
+        });
+        let dots = StrokeStyle::Pattern(Dash::dotted(Pt(3.0)));
+        rctx.with_stroke_style(dots, |rctx| {
+            layout::RenderContext::line(
+                rctx,
+                &Offset::new(Mm(0.0), Mm(57.0)),
+                &Offset::new(Mm(190.0), Mm(57.0)),
+                &Stroke::new(Rgba::black(), Pt(1.0)),
+            )
+        });
+
+        rctx.path(
+            &Path::new()
+                .move_to(Offset::new(Mm(100.0), Mm(60.0)))
+                .line_to(Offset::new(Mm(140.0), Mm(60.0)))
+                .line_to(Offset::new(Mm(140.0), Mm(55.0)))
+                .line_to(Offset::new(Mm(150.0), Mm(65.0)))
+                .line_to(Offset::new(Mm(140.0), Mm(75.0)))
+                .line_to(Offset::new(Mm(140.0), Mm(70.0)))
+                .line_to(Offset::new(Mm(100.0), Mm(70.0)))
+                .close(),
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        rctx.path(
+            &Path::new()
+                .move_to(Offset::new(Mm(160.0), Mm(60.0)))
+                .curve_to(
+                    Offset::new(Mm(155.0), Mm(60.0)),
+                    Offset::new(Mm(160.0), Mm(70.0)),
+                    Offset::new(Mm(155.0), Mm(70.0)),
+                )
+                .curve_to(
+                    Offset::new(Mm(160.0), Mm(70.0)),
+                    Offset::new(Mm(155.0), Mm(80.0)),
+                    Offset::new(Mm(160.0), Mm(80.0)),
+                ),
+            None,
+            Some(&Stroke::new(Rgba::black(), Pt(1.0))),
+        );
+
+        rctx.path(
+            &Path::new()
+                .polygon([
+                    Offset::new(Mm(170.0), Mm(60.0)),
+                    Offset::new(Mm(190.0), Mm(60.0)),
+                    Offset::new(Mm(190.0), Mm(80.0)),
+                    Offset::new(Mm(170.0), Mm(80.0)),
+                ])
+                .polygon([
+                    Offset::new(Mm(175.0), Mm(65.0)),
+                    Offset::new(Mm(185.0), Mm(65.0)),
+                    Offset::new(Mm(185.0), Mm(75.0)),
+                    Offset::new(Mm(175.0), Mm(75.0)),
+                ])
+                .with_fill_rule(FillRule::EvenOdd),
+            Some(&Rgba::from((244, 67, 54, 1.0))),
+            None,
+        );
+
+        rctx.push_transform(
+            &Transform::around(Offset::new(Mm(150.0), Mm(120.0)))
+                .with_rotation(30.0)
+                .with_scale(0.5, 0.5),
+        );
+        rctx.rect(
+            &Offset::new(Mm(130.0), Mm(110.0)),
+            &Size::fixed(Mm(40.0), Mm(20.0)),
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        rctx.pop_transform();
+
+        let faded = Offset::new(Mm(0.0), Mm(110.0));
+        rctx.push_soft_mask(&SoftMask::gradient(
+            Gradient::linear(faded.clone(), Offset::new(Mm(0.0), Mm(130.0)))
+                .with_stop(0.0, Rgba::from((255, 255, 255, 1.0)))
+                .with_stop(1.0, Rgba::black()),
+        ));
+        rctx.rect(
+            &faded,
+            &Size::fixed(Mm(90.0), Mm(20.0)),
+            Some(&Rgba::from((244, 67, 54, 1.0))),
+            None,
+        );
+        rctx.pop_soft_mask();
+
+        rctx.shadow(
+            &Offset::new(Mm(0.0), Mm(60.0)),
+            &Size::fixed(Mm(90.0), Mm(40.0)),
+            Mm(2.0),
+            &Shadow::new(Rgba::from((0, 0, 0, 0.3)))
+                .with_offset(Offset::new(Mm(1.0), Mm(1.5)))
+                .with_blur(Mm(3.0)),
+        );
+        rctx.rounded_rect(
+            &Offset::new(Mm(0.0), Mm(60.0)),
+            &Size::fixed(Mm(90.0), Mm(40.0)),
+            Mm(2.0),
+            Some(&Rgba::from((250, 250, 250, 1.0))),
+            Some(&Stroke::new(Rgba::from((200, 200, 200, 1.0)), Pt(0.5))),
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_rect.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn gradient() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let position = Offset::new(Mm(0.0), Mm(0.0));
+        let size = Size::fixed(Mm(190.0), Mm(30.0));
+        rctx.gradient(
+            &Path::rect(&position, &size),
+            &Gradient::linear(position.clone(), Offset::new(Mm(190.0), Mm(0.0)))
+                .with_stop(0.0, Rgba::from((13, 71, 161, 1.0)))
+                .with_stop(0.6, Rgba::from((66, 165, 245, 1.0)))
+                .with_stop(1.0, Rgba::from((255, 255, 255, 1.0))),
+        );
+
+        let position = Offset::new(Mm(70.0), Mm(50.0));
+        rctx.gradient(
+            &Path::rect(&position, &Size::fixed(Mm(50.0), Mm(50.0))),
+            &Gradient::radial(Offset::new(Mm(95.0), Mm(75.0)), Mm(25.0))
+                .with_stop(0.0, Rgba::from((255, 235, 59, 1.0)))
+                .with_stop(1.0, Rgba::from((244, 67, 54, 1.0))),
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        assert!(
+            pdf.windows(b"/ShadingType".len())
+                .any(|window| window == b"/ShadingType")
+        );
+
+        BufWriter::new(File::create("test_gradient.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn stroke_style() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let stroke = Stroke::new(Rgba::black(), Pt(1.5));
+        let line = |rctx: &mut RenderContext, y: f64| {
+            layout::RenderContext::line(
+                rctx,
+                &Offset::new(Mm(0.0), Mm(y)),
+                &Offset::new(Mm(190.0), Mm(y)),
+                &stroke,
+            )
+        };
+
+        // strokes of an element are in its style, the style of the enclosing one is kept
+        marks.push(Mark::Stroke(StrokeMark::Begin(StrokeStyle::Dashed)));
+        line(&mut rctx, 10.0);
+        rctx.with_stroke_style(StrokeStyle::Double, |rctx| {
+            line(rctx, 20.0);
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(30.0)),
+                &Size::fixed(Mm(190.0), Mm(20.0)),
+                Some(&Rgba::from((240, 240, 240, 1.0))),
+                Some(&stroke),
+            );
+        });
+        assert_eq!(rctx.stroke_style, StrokeStyle::Dashed);
+        marks.push(Mark::Stroke(StrokeMark::End));
+        line(&mut rctx, 60.0);
+        assert_eq!(rctx.stroke_style, StrokeStyle::Solid);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let count = |operator: &str| {
+            content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == operator)
+                .count()
+        };
+        // dashed line, two rules of the double one, the solid one, rect fill and two rules
+        // of its stroke
+        assert_eq!(count("d"), 1);
+        assert_eq!(count("m"), 4);
+        assert_eq!(count("re"), 3);
+
+        BufWriter::new(File::create("test_stroke_style.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn opacity() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(60.0), Mm(60.0));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        rctx.with_opacity(0.5, |rctx| {
+            rctx.rect(
+                &Offset::new(Mm(30.0), Mm(30.0)),
+                &size,
+                Some(&Rgba::from((244, 67, 54, 1.0))),
+                None,
+            );
+            rctx.with_opacity(0.5, |rctx| {
+                rctx.rect(
+                    &Offset::new(Mm(60.0), Mm(60.0)),
+                    &size,
+                    Some(&Rgba::from((255, 235, 59, 1.0))),
+                    None,
+                );
+            });
+
+            rctx.push_clip(&Path::rect(
+                &Offset::new(Mm(0.0), Mm(0.0)),
+                &Size::fixed(Mm(30.0), Mm(30.0)),
+            ));
+            layout::RenderContext::new_page(rctx, None);
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(0.0)),
+                &size,
+                Some(&Rgba::from((244, 67, 54, 1.0))),
+                None,
+            );
+            rctx.pop_clip();
+        });
+
+        rctx.save_state();
+        rctx.layer
+            .set_fill_color(printpdf::Color::Rgb(printpdf::Rgb::new(
+                1.0, 0.0, 0.0, None,
+            )));
+        let dash = StrokeStyle::Pattern(Dash::dashed(Mm(2.0), Mm(1.0)));
+        rctx.with_stroke_style(dash, |rctx| {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(100.0)),
+                &size,
+                None,
+                Some(&Stroke::new(Rgba::black(), Pt(1.0))),
+            )
+        });
+        rctx.restore_state();
+        assert_eq!(rctx.stroke_style, StrokeStyle::Solid);
+        rctx.restore_state();
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        assert!(pdf.windows(b"/ca".len()).any(|window| window == b"/ca"));
+
+        BufWriter::new(File::create("test_opacity.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn color_alpha() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(60.0), Mm(60.0));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            Some(&Rgba::from((13, 71, 161, 0.5))),
+            Some(&Stroke::new(Rgba::black(), Pt(2.0))),
+        );
+        rctx.with_opacity(0.5, |rctx| {
+            layout::RenderContext::line(
+                rctx,
+                &Offset::new(Mm(0.0), Mm(80.0)),
+                &Offset::new(Mm(100.0), Mm(80.0)),
+                &Stroke::new(Rgba::from((244, 67, 54, 0.5)), Pt(2.0)),
+            );
+        });
+        // a transparent border is not painted
+        layout::RenderContext::line(
+            &mut rctx,
+            &Offset::new(Mm(0.0), Mm(90.0)),
+            &Offset::new(Mm(100.0), Mm(90.0)),
+            &Stroke::new(Rgba::from((135, 235, 64, 0.0)), Pt(1.0)),
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let alphas = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            // states are direct objects of the page resources
+            .flat_map(|dictionary| {
+                dictionary
+                    .iter()
+                    .filter_map(|(_, value)| value.as_dict().ok())
+            })
+            .filter(|state| state.get(b"Type").and_then(Object::as_name).ok() == Some(b"ExtGState"))
+            .map(|state| {
+                let alpha = |key: &[u8]| state.get(key).unwrap().as_float().unwrap();
+                (alpha(b"ca"), alpha(b"CA"))
+            })
+            .collect::<Vec<_>>();
+        // fill only, scope opacity and the stroke within it
+        assert!(alphas.contains(&(0.5, 1.0)));
+        assert!(alphas.contains(&(0.5, 0.5)));
+        assert!(alphas.contains(&(0.5, 0.25)));
+        assert!(alphas.contains(&(1.0, 0.0)));
+
+        BufWriter::new(File::create("test_color_alpha.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn watermark() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let image = Image::from_raw(2, 2, ImageColorSpace::Gray, vec![0, 255, 255, 0]).unwrap();
+        rctx.set_watermark(Some(
+            Watermark::image(image, Size::fixed(Mm(100.0), Mm(100.0)))
+                .with_rotation(30.0)
+                .with_opacity(0.1),
+        ))
+        .unwrap();
+        rctx.complete_fonts().unwrap();
+
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(60.0), Mm(60.0)),
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        layout::RenderContext::new_page(&mut rctx, None);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        for page_id in pages.values() {
+            let content = document.get_and_decode_page_content(*page_id).unwrap();
+            assert!(
+                content
+                    .operations
+                    .iter()
+                    .any(|operation| operation.operator == "Do")
+            );
+        }
+
+        BufWriter::new(File::create("test_watermark.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn adobe_cmyk_image() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        // SOI, APP14 Adobe, SOF0 64x48 with 4 components, EOI
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E];
+        jpeg.extend(b"Adobe");
+        jpeg.extend([0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x14, 0x08, 0x00, 0x30, 0x00, 0x40, 0x04]);
+        jpeg.extend([
+            0x01, 0x11, 0x00, 0x02, 0x11, 0x00, 0x03, 0x11, 0x00, 0x04, 0x11, 0x00,
+        ]);
+        jpeg.extend([0xFF, 0xD9]);
+        let image = Image::from_jpeg(jpeg).unwrap();
+        rctx.image(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(64.0), Mm(48.0)),
+            &image,
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let page_id = document.get_pages()[&1];
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        assert!(
+            content
+                .operations
+                .iter()
+                .any(|operation| operation.operator == "Do")
+        );
+        let xobject = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .find(|stream| stream.dict.type_is(b"XObject"))
+            .unwrap();
+        let decode = xobject
+            .dict
+            .get(b"Decode")
+            .and_then(Object::as_array)
+            .unwrap()
+            .iter()
+            .map(|value| value.as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(decode, vec![1, 0, 1, 0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn continuation() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(9.0), None))
+            .build();
+        rctx.set_continuation(Some(
+            Continuation::new()
+                .with_bottom("Continues on the next page", style.clone())
+                .with_top("Continued from the previous page", style),
+        ))
+        .unwrap();
+        rctx.complete_fonts().unwrap();
+
+        let size = Size::fixed(Mm(170.0), Mm(200.0));
+        rctx.rect(
+            &Offset::zero(),
+            &size,
+            Some(&Rgba::from((240, 240, 240, 1.0))),
+            None,
+        );
+        // the page is broken within the scope, texts are painted outside of it
+        rctx.with_opacity(0.5, |rctx| {
+            rctx.check_page_break(Mm(200.0), Mm(200.0), false);
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(200.0)),
+                &size,
+                Some(&Rgba::from((240, 240, 240, 1.0))),
+                None,
+            );
+        });
+        // explicit page break is not marked
+        layout::RenderContext::new_page(&mut rctx, None);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 3);
+        let texts = pages
+            .values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(*page_id).unwrap();
+                content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == "BT")
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec![1, 1, 0]);
+        // the layer keeps its own state, scopes are within it
+        for page_id in pages.values().take(2) {
+            let content = document.get_and_decode_page_content(*page_id).unwrap();
+            let mut depth = -1;
+            for operation in content.operations.iter() {
+                match operation.operator.as_str() {
+                    "q" => depth += 1,
+                    "Q" => depth -= 1,
+                    "BT" => assert_eq!(depth, 0),
+                    _ => (),
+                }
+            }
+        }
+
+        BufWriter::new(File::create("test_continuation.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn text_outlines() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_text_outlines(true);
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(24.0), None))
+            .with_color(Rgba::from((13, 71, 161, 1.0)))
+            .build();
+        let text = rctx.typeset(&style, "Outlined text").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        rctx.text(&Offset::new(Mm(0.0), Mm(20.0)), &style, &text, true);
+        rctx.with_text_rotation(30.0, |rctx| {
+            rctx.with_text_mode(
+                TextMode::Stroke(Stroke::new(Rgba::black(), Pt(0.5))),
+                |rctx| rctx.text(&Offset::new(Mm(20.0), Mm(120.0)), &style, &text, true),
+            )
+        });
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let count = |operator: &str| {
+            content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == operator)
+                .count()
+        };
+        assert_eq!(count("BT"), 0);
+        assert_eq!(count("f"), 1);
+        assert_eq!(count("S"), 1);
+        assert!(!String::from_utf8_lossy(&pdf).contains("/FontFile2"));
+
+        BufWriter::new(File::create("test_text_outlines.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn justification() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let words = rctx.typeset(&style, "Justified words").unwrap();
+        let letters = rctx.typeset(&style, "Letters").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        rctx.with_text_width(Mm(120.0), |rctx| {
+            rctx.text(&Offset::new(Mm(0.0), Mm(20.0)), &style, &words, true);
+            rctx.text(&Offset::new(Mm(0.0), Mm(40.0)), &style, &letters, true);
+        });
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let operators = content
+            .operations
+            .iter()
+            .map(|operation| operation.operator.as_str())
+            .filter(|operator| matches!(*operator, "BT" | "Td" | "Tc" | "TJ" | "Tj" | "ET"))
+            .collect::<Vec<_>>();
+        // the space of the words gets the word spacing, letters share one spacing
+        assert_eq!(
+            operators,
+            vec![
+                "BT", "Td", "TJ", "Tc", "TJ", "Tc", "TJ", "ET", "BT", "Td", "Tc", "TJ", "ET"
+            ]
+        );
+        let spacings = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "Tc")
+            .map(|operation| operation.operands[0].as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert!(spacings[0] > 0.0);
+        assert_eq!(spacings[1], 0.0);
+        assert!(spacings[2] > 0.0 && spacings[2] < spacings[0]);
+    }
+
+    #[test]
+    fn keep_together() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        // region fits into the page, but not into its rest
+        assert!(!rctx.check_page_break(Mm(0.0), Mm(200.0), false));
+        assert!(rctx.begin_keep_together(Mm(200.0), Mm(150.0)));
+        assert!(!rctx.begin_keep_together(Mm(200.0), Mm(100.0)));
+        assert!(!rctx.check_page_break(Mm(300.0), Mm(50.0), false));
+        rctx.end_keep_together();
+        rctx.end_keep_together();
+
+        // region higher than the page is split as usual
+        assert!(!rctx.begin_keep_together(Mm(350.0), Mm(400.0)));
+        assert!(rctx.check_page_break(Mm(400.0), Mm(100.0), false));
+        rctx.end_keep_together();
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        assert_eq!(document.get_pages().len(), 3);
+    }
+
+    #[test]
+    fn text_block() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let lines = (1..=30)
+            .map(|line| rctx.typeset(&style, &format!("Line {line}")).unwrap())
+            .collect::<Vec<_>>();
+        rctx.complete_fonts().unwrap();
+
+        // block starts in the middle of the page, its first lines stay there
+        let bottom = rctx.text_block(
+            &Offset::new(Mm(0.0), Mm(150.0)),
+            &style,
+            &lines,
+            Some(Mm(10.0).into()),
+        );
+        assert_eq!(bottom, Mm(450.0).into());
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        let texts = pages
+            .values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(*page_id).unwrap();
+                content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == "BT")
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec![11, 19]);
+
+        BufWriter::new(File::create("test_text_block.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn text_block_lines() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let line = rctx.typeset(&style, "Line").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        // the last glyph of every line but the last one moves the pen to the start
+        // of the next line, 2 em below
+        let mut text = line.clone();
+        text.positions.clear();
+        for index in 0..30 {
+            text.positions.extend(line.positions.iter().cloned());
+            if index < 29 {
+                let last = text.positions.pop().unwrap();
+                text.positions.push(GlyphPosition::new(
+                    None,
+                    last.glyph_index,
+                    Em(last.h_advance.0 - line.width.0),
+                    Em(-2.0),
+                    last.h_offset,
+                    last.v_offset,
+                ));
+            }
+        }
+        text.height = Em(line.height.0 + 29.0 * 2.0);
+
+        let bottom = rctx.text_block(&Offset::new(Mm(0.0), Mm(150.0)), &style, &[text], None);
+        let expected = Pt::from(Unit::from(Mm(150.0))).0 + (29.0 * 2.0 + line.height.0) * 12.0;
+        assert!((Pt::from(bottom).0 - expected).abs() < 0.001);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        let texts = pages
+            .values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(*page_id).unwrap();
+                content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == "BT")
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts.iter().sum::<usize>(), 30);
+        assert!(texts[0] > 0 && texts[1] > 0);
+    }
+
+    #[test]
+    fn fill_text() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(48.0), None))
+            .build();
+        let text = rctx.typeset(&style, "Heading").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        rctx.fill_text(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &style,
+            &text,
+            false,
+            &TextFill::Gradient(
+                Gradient::linear(
+                    Offset::new(Mm(0.0), Mm(0.0)),
+                    Offset::new(Mm(80.0), Mm(0.0)),
+                )
+                .with_stop(0.0, Rgba::from((13, 71, 161, 1.0)))
+                .with_stop(1.0, Rgba::from((244, 67, 54, 1.0))),
+            ),
+        );
+        let image = Image::from_raw(2, 2, ImageColorSpace::Gray, vec![0, 255, 255, 0]).unwrap();
+        rctx.fill_text(
+            &Offset::new(Mm(0.0), Mm(60.0)),
+            &style,
+            &text,
+            true,
+            &TextFill::Image(image.with_fit(ImageFit::Stretch)),
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let count = |operator: &str| {
+            content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == operator)
+                .count()
+        };
+        // clip mode is set again, the state of the first text is restored with its scope
+        assert_eq!(count("Tr"), 2);
+        assert_eq!(count("sh"), 1);
+        assert_eq!(count("Do"), 1);
+        assert_eq!(count("q"), count("Q"));
+
+        BufWriter::new(File::create("test_fill_text.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn page_position() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        // content flow is on the second page
+        rctx.check_page_break(Mm(300.0), Mm(10.0), false);
+        // fold mark at 105 mm from the top of the page
+        rctx.with_page_position(|rctx| {
+            rctx.check_page_break(Mm(400.0), Mm(10.0), false);
+            layout::RenderContext::line(
+                rctx,
+                &Offset::new(Mm(0.0), Mm(105.0)),
+                &Offset::new(Mm(5.0), Mm(105.0)),
+                &Stroke::new(Rgba::black(), Pt(0.25)),
+            );
+        });
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(300.0)),
+            &Size::fixed(Mm(10.0), Mm(10.0)),
+            Some(&Rgba::black()),
+            None,
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        let content = document.get_and_decode_page_content(pages[&2]).unwrap();
+        let start = content
+            .operations
+            .iter()
+            .find(|operation| operation.operator == "m")
+            .unwrap();
+        let (x, y) = (
+            start.operands[0].as_float().unwrap(),
+            start.operands[1].as_float().unwrap(),
+        );
+        assert!(x.abs() < 0.01);
+        assert!((y - printpdf::Mm(297.0 - 105.0).into_pt().0).abs() < 0.01);
+    }
+
+    #[test]
+    fn split_line() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        // vertical rule over three pages, drawn upwards
+        let stroke = Stroke::new(Rgba::black(), Pt(1.0));
+        layout::RenderContext::line(
+            &mut rctx,
+            &Offset::new(Mm(20.0), Mm(600.0)),
+            &Offset::new(Mm(20.0), Mm(100.0)),
+            &stroke,
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 3);
+        for page_id in pages.values() {
+            let content = document.get_and_decode_page_content(*page_id).unwrap();
+            let lines = content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == "m")
+                .count();
+            assert_eq!(lines, 1);
+        }
+
+        BufWriter::new(File::create("test_split_line.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn table_grid() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let thin = Stroke::new(Rgba::from((135, 135, 135, 1.0)), Pt(0.5));
+        let grid = TableGrid::new([Mm(40.0), Mm(60.0), Mm(60.0)], [Mm(10.0); 4])
+            .with_border(Some(thin))
+            .with_outer_border(Stroke::new(Rgba::black(), Pt(1.5)))
+            .with_cell_border(2, 1, Stroke::new(Rgba::from((244, 67, 54, 1.0)), Pt(1.0)));
+        rctx.table_grid(&Offset::new(Mm(0.0), Mm(0.0)), &grid);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let page_id = document.get_pages()[&1];
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let strokes = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "S")
+            .count();
+        assert_eq!(strokes, 3);
+
+        BufWriter::new(File::create("test_table_grid.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn print_marks() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_print_marks(
+            PrintMarks::new()
+                .with_bleed(Mm(5.0))
+                .with_registration_marks(true)
+                .with_color_bars(true),
+        );
+
+        // bleeds over the trim box
+        rctx.rect(
+            &Offset::new(Mm(-15.0), Mm(-15.0)),
+            &Size::fixed(Mm(220.0), Mm(50.0)),
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let page_id = document.get_pages()[&1];
+        let page = document.get_dictionary(page_id).unwrap();
+        let trim_box = page.get(b"TrimBox").unwrap().as_array().unwrap();
+        let media_box = page.get(b"MediaBox").unwrap().as_array().unwrap();
+        assert_eq!(trim_box[0].as_float().unwrap(), 0.0);
+        assert!(media_box[0].as_float().unwrap() < -14.0);
+        assert!(media_box[2].as_float().unwrap() > trim_box[2].as_float().unwrap());
+
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let operators = content
+            .operations
+            .iter()
+            .map(|operation| operation.operator.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operators
+                .iter()
+                .filter(|operator| **operator == "q")
+                .count(),
+            operators
+                .iter()
+                .filter(|operator| **operator == "Q")
+                .count()
+        );
+        assert_eq!(
+            operators
+                .iter()
+                .filter(|operator| **operator == "k")
+                .count(),
+            10
+        );
+
+        BufWriter::new(File::create("test_print_marks.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn landscape_rotation() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_landscape_rotation(true);
+
+        let landscape = Size::fixed(Mm(297.0), Mm(210.0));
+        for size in [
+            None,
+            Some(landscape),
+            Some(Size::fixed(Mm(210.0), Mm(297.0))),
+        ] {
+            if size.is_some() {
+                rctx.new_page(None, size.as_ref());
+            }
+            // wide table spans the content width
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(0.0)),
+                &Size::fixed(Mm(277.0), Mm(20.0)),
+                Some(&Rgba::from((13, 71, 161, 1.0))),
+                None,
+            );
+            rctx.link(
+                &Offset::new(Mm(0.0), Mm(0.0)),
+                &Size::fixed(Mm(277.0), Mm(20.0)),
+                "https://example.com",
+            );
+        }
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        for (number, page_id) in pages {
+            let page = document.get_dictionary(page_id).unwrap();
+            let media_box = page.get(b"MediaBox").unwrap().as_array().unwrap();
+            assert!(media_box[2].as_float().unwrap() < media_box[3].as_float().unwrap());
+            let rotation = page.get(b"Rotate").and_then(Object::as_i64).unwrap_or(0);
+            assert_eq!(rotation == 90, number == 2);
+
+            // link on the rotated page is taller than wide in the media box
+            let annotation = page.get(b"Annots").unwrap().as_array().unwrap()[0]
+                .as_reference()
+                .unwrap();
+            let rect = document
+                .get_dictionary(annotation)
+                .unwrap()
+                .get(b"Rect")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|value| value.as_float().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(rect[2] - rect[0] < rect[3] - rect[1], number == 2);
+        }
+
+        BufWriter::new(File::create("test_landscape_rotation.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn mirrored_margins() {
+        let lefts = |mirrored: bool| {
+            let (document, page, layer) =
+                PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+            let mut rctx = RenderContext::new(
+                document,
+                page,
+                layer,
+                Quad::square(Mm(10.0)),
+                Size::fixed(Mm(210.0), Mm(297.0)),
+                new_font_cache(),
+            )
+            .with_mirrored_margins(mirrored)
+            .with_binding_offset(Mm(5.0));
+            let decorated = Rc::new(RefCell::new(vec![]));
+            rctx.on_page({
+                let decorated = decorated.clone();
+                move |decorator, _| {
+                    decorated
+                        .borrow_mut()
+                        .push(Mm::from(decorator.content_offset().x).0.round())
+                }
+            });
+            rctx.complete_fonts().unwrap();
+            for page in 0..3 {
+                if page > 0 {
+                    layout::RenderContext::new_page(&mut rctx, None);
+                }
+                rctx.link(
+                    &Offset::new(Mm(0.0), Mm(0.0)),
+                    &Size::fixed(Mm(20.0), Mm(20.0)),
+                    "https://example.com",
+                );
+            }
+
+            let pdf = rctx.save_to_bytes().unwrap();
+            let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+            let links = document
+                .get_pages()
+                .into_values()
+                .map(|page_id| {
+                    let page = document.get_dictionary(page_id).unwrap();
+                    let annotation = page.get(b"Annots").unwrap().as_array().unwrap()[0]
+                        .as_reference()
+                        .unwrap();
+                    let rect = document
+                        .get_dictionary(annotation)
+                        .and_then(|annotation| annotation.get(b"Rect"))
+                        .and_then(Object::as_array)
+                        .unwrap();
+                    (rect[0].as_float().unwrap() / 72.0 * 25.4).round() as f64
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(links, *decorated.borrow());
+            links
+        };
+
+        assert_eq!(lefts(false), vec![15.0, 15.0, 15.0]);
+        assert_eq!(lefts(true), vec![15.0, 10.0, 15.0]);
+    }
+
+    #[test]
+    fn progress() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let reported = Rc::new(RefCell::new(vec![]));
+        let cancellation = CancellationToken::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_progress({
+            let reported = reported.clone();
+            move |progress| reported.borrow_mut().push(progress)
+        })
+        .with_cancellation(cancellation.clone());
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for offset in [0.0, 200.0, 400.0] {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+        }
+        assert_eq!(
+            *reported.borrow(),
+            vec![RenderProgress::Pages(2), RenderProgress::Pages(3)]
+        );
+
+        // the rest overflows the last page
+        cancellation.cancel();
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(600.0)),
+            &size,
+            Some(&Rgba::black()),
+            None,
+        );
+        assert!(rctx.is_cancelled());
+        assert_eq!(reported.borrow().len(), 2);
+        assert_eq!(rctx.page_number, 2);
+    }
+
+    #[test]
+    fn page_values() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for offset in [0.0, 200.0, 400.0] {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+        }
+        rctx.anchor("summary", &Offset::new(Mm(0.0), Mm(500.0)));
+
+        assert_eq!(
+            rctx.page_values(),
+            PageValues::new(3).with_anchor("summary", 3)
+        );
+    }
+
+    #[test]
+    fn page_slots() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), Some(Features::default())))
+            .build();
+        let font = style.font().clone();
+        let measured = rctx.typeset(&style, "Page {page}").unwrap();
+        let expected = rctx.typeset(&style, "Page 2").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        // the slot is substituted by the page the text is rendered to
+        rctx.text(&Offset::new(Mm(0.0), Mm(300.0)), &style, &measured, false);
+        assert_eq!(rctx.page_number, 1);
+        let rendered = rctx.page_slot_text(&font, &measured).unwrap();
+        assert_eq!(rendered.positions, expected.positions);
+        assert!(rctx.page_slot_text(&font, &expected).is_none());
+    }
+
+    #[test]
+    fn decorator_glyphs() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        // text of decorations is typeset after fonts are completed
+        let results = Rc::new(RefCell::new(vec![]));
+        rctx.on_page({
+            let results = results.clone();
+            move |decorator, _| {
+                let style = StyleBuilder::default()
+                    .with_font(Font::new("LatoReg", Pt(10.0), Some(Features::default())))
+                    .build();
+                for text in ["Draft", "Final", "Final"] {
+                    let result = decorator.text(&Offset::zero(), &style, text);
+                    results.borrow_mut().push(result.is_ok());
+                }
+            }
+        });
+        rctx.reserve_glyphs("LatoReg", "Draft".chars()).unwrap();
+        rctx.complete_fonts().unwrap();
+        rctx.save_to_bytes().unwrap();
+
+        // glyphs of the second one would be missing in the subset, also when typeset again
+        assert_eq!(*results.borrow(), vec![true, false, false]);
+    }
+
+    #[test]
+    fn on_page() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let decorated = Rc::new(RefCell::new(vec![]));
+        rctx.on_page({
+            let decorated = decorated.clone();
+            move |decorator, page| {
+                decorated.borrow_mut().push(page);
+                // footer rule in the bottom margin
+                let left = decorator.page_margin().offset(&Offset::zero()).x;
+                let y = decorator.page_size().base_height() - Unit::from(Mm(5.0));
+                decorator.line(
+                    &Offset::new(left, y),
+                    &Offset::new(Mm(200.0), y),
+                    &Stroke::new(Rgba::black(), Pt(0.5)),
+                );
+            }
+        });
+        rctx.complete_fonts().unwrap();
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for offset in [0.0, 200.0, 400.0] {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+        }
+        assert_eq!(*decorated.borrow(), vec![1, 2]);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        assert_eq!(*decorated.borrow(), vec![1, 2, 3]);
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        for page_id in document.get_pages().into_values() {
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let lines = content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == "l")
+                .count();
+            assert_eq!(lines, 1);
+        }
+
+        BufWriter::new(File::create("test_on_page.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn on_page_added() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let added = Rc::new(RefCell::new(vec![]));
+        rctx.on_page_added({
+            let added = added.clone();
+            move |decorator, info| {
+                added.borrow_mut().push(info.clone());
+                // serial number box in the top margin
+                decorator.rect(
+                    &Offset::new(Mm(180.0), Mm(2.0)),
+                    &Size::fixed(Mm(20.0), Mm(5.0)),
+                    Some(&Rgba::black()),
+                    None,
+                );
+            }
+        });
+        // pages are added once fonts are completed
+        assert!(added.borrow().is_empty());
+        rctx.complete_fonts().unwrap();
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for offset in [0.0, 200.0, 400.0] {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+        }
+        let added = added.borrow();
+        assert_eq!(
+            added.iter().map(|info| info.index).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert!(added.iter().all(|info| {
+            info.size == Size::fixed(Mm(210.0), Mm(297.0)) && info.margin == Quad::square(Mm(10.0))
+        }));
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        for page_id in document.get_pages().into_values() {
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let rects = content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == "re")
+                .count();
+            assert!(rects >= 2);
+        }
+    }
+
+    #[test]
+    fn page_templates() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        // content laid out at the document width would not fit
+        let templates = PageTemplates::new()
+            .with_first(PageTemplate::new().with_margin(Quad::square(Mm(40.0))));
+        assert!(rctx.set_page_templates(templates).is_err());
+
+        let decorated = Rc::new(RefCell::new(vec![]));
+        let templates = PageTemplates::new()
+            .with_first(PageTemplate::new().with_margin(Quad::square(Mm(10.0))))
+            .with_even(PageTemplate::new().with_decoration({
+                let decorated = decorated.clone();
+                move |_, page| decorated.borrow_mut().push(page)
+            }));
+        rctx.set_page_templates(templates).unwrap();
+        assert_eq!(rctx.page_margin, Quad::square(Mm(10.0)));
+        rctx.complete_fonts().unwrap();
+        assert!(rctx.set_page_templates(PageTemplates::new()).is_err());
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for offset in [0.0, 200.0, 400.0] {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+        }
+        // pages after the first one have the margin of the document
+        assert_eq!(rctx.page_margin, Quad::square(Mm(10.0)));
+
+        rctx.save_to_bytes().unwrap();
+        assert_eq!(*decorated.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn page_numbering() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(10.0), Some(Features::default())))
+            .build();
+        rctx.set_page_numbering(Some(
+            PageNumbering::new("– %p –", style).with_anchor(PageAnchor::BottomRight),
+        ))
+        .unwrap();
+        rctx.complete_fonts().unwrap();
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for offset in [0.0, 200.0, 400.0] {
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+            if offset == 200.0 {
+                rctx.restart_page_numbering(1);
+            }
+        }
+        assert_eq!(rctx.numbered_page(1), 2);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        for page_id in document.get_pages().into_values() {
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let texts = content
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == "BT")
+                .count();
+            assert_eq!(texts, 1);
+        }
+
+        BufWriter::new(File::create("test_page_numbering.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn running_header() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let headers = Rc::new(RefCell::new(vec![]));
+        rctx.on_page({
+            let headers = headers.clone();
+            move |decorator, _| {
+                let first = decorator.first_section().map(str::to_string);
+                let last = decorator.last_section().map(str::to_string);
+                headers.borrow_mut().push((first, last));
+            }
+        });
+        rctx.complete_fonts().unwrap();
+
+        let size = Size::fixed(Mm(190.0), Mm(100.0));
+        for (offset, title) in [(0.0, "A"), (120.0, "B"), (300.0, "C"), (600.0, "")] {
+            if !title.is_empty() {
+                rctx.mark_section(title, 1, &Offset::new(Mm(0.0), Mm(offset)));
+            }
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(offset)),
+                &size,
+                Some(&Rgba::black()),
+                None,
+            );
+        }
+        rctx.save_to_bytes().unwrap();
+
+        let section = |title: &str| Some(title.to_string());
+        assert_eq!(
+            *headers.borrow(),
+            vec![
+                (section("A"), section("B")),
+                (section("C"), section("C")),
+                (section("C"), section("C")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rotated_layout() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        rctx.complete_fonts().unwrap();
+
+        let mut header = LayoutBox::new(Axis::Vertical)
+            .style(StyleBuilder::new().with_border(Border::h(Stroke::new(Rgba::black(), Pt(1.0)))));
+        rctx.rotated_layout(
+            &mut header,
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(10.0), Mm(40.0)),
+            QuarterTurn::Left,
+        )
+        .unwrap();
+        // the box does not fit to the rest of the page, the content goes to the next one
+        rctx.rotated_layout(
+            &mut header,
+            &Offset::new(Mm(0.0), Mm(260.0)),
+            &Size::fixed(Mm(10.0), Mm(40.0)),
+            QuarterTurn::Right,
+        )
+        .unwrap();
+        assert!(rctx.page_break_reservations.is_empty());
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        let rotations = pages
+            .values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(*page_id).unwrap();
+                content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == "cm")
+                    .map(|operation| operation.operands[1].as_float().unwrap().round())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // sine of the rotation
+        assert_eq!(rotations, vec![vec![1.0], vec![-1.0]]);
+
+        BufWriter::new(File::create("test_rotated_layout.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn z_index() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let position = Offset::new(Mm(0.0), Mm(0.0));
+        let rect = |rctx: &mut RenderContext, width: f64| {
+            rctx.rect(
+                &position,
+                &Size::fixed(Mm(width), Mm(20.0)),
+                Some(&Rgba::from((13, 71, 161, 1.0))),
+                None,
+            )
+        };
+        rect(&mut rctx, 30.0);
+        rctx.with_z_index(1, |rctx| rect(rctx, 10.0));
+        rctx.with_z_index(-1, |rctx| rect(rctx, 50.0));
+        rctx.with_layer("artwork", |rctx| rect(rctx, 20.0));
+        rctx.set_layer_z_index("artwork", -1);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let widths = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "re")
+            .map(|operation| {
+                let width = operation.operands[2].as_float().unwrap();
+                (width / 72.0 * 25.4).round()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(widths, vec![50.0, 20.0, 30.0, 10.0]);
+
+        BufWriter::new(File::create("test_z_index.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn layers() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(60.0), Mm(200.0));
+        for offset in [Mm(0.0), Mm(200.0)] {
+            let position = Offset::new(Mm(0.0), offset);
+            rctx.with_layer("artwork", |rctx| {
+                rctx.rect(
+                    &position,
+                    &size,
+                    Some(&Rgba::from((13, 71, 161, 1.0))),
+                    None,
+                );
+                rctx.with_layer("notes", |rctx| {
+                    rctx.rect(
+                        &position,
+                        &size,
+                        None,
+                        Some(&Stroke::new(Rgba::black(), Pt(1.0))),
+                    )
+                });
+            });
+            rctx.rect(
+                &position,
+                &size,
+                None,
+                Some(&Stroke::new(Rgba::black(), Pt(0.5))),
+            );
+        }
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        assert_eq!(document.get_pages().len(), 2);
+        let layers = document
+            .catalog()
+            .unwrap()
+            .get(b"OCProperties")
+            .and_then(Object::as_dict)
+            .and_then(|properties| properties.get(b"OCGs"))
+            .and_then(Object::as_array)
+            .unwrap();
+        let mut names = layers
+            .iter()
+            .map(|layer| {
+                let layer = document
+                    .get_dictionary(layer.as_reference().unwrap())
+                    .unwrap();
+                layer.get(b"Name").unwrap().as_str().unwrap().to_vec()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![b"artwork".to_vec(), b"default".to_vec(), b"notes".to_vec()]
+        );
+
+        BufWriter::new(File::create("test_layers.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn link() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(60.0), Mm(20.0));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        rctx.link(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            "mailto:invoice@example.com",
+        );
+
+        rctx.push_transform(&Transform::around(Offset::new(Mm(0.0), Mm(40.0))).with_rotation(90.0));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(40.0)),
+            &size,
+            Some(&Rgba::from((244, 67, 54, 1.0))),
+            None,
+        );
+        rctx.link(
+            &Offset::new(Mm(0.0), Mm(40.0)),
+            &size,
+            "https://example.com",
+        );
+        rctx.pop_transform();
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let page_id = *document.get_pages().values().next().unwrap();
+        let page = document.get_dictionary(page_id).unwrap();
+        let annotations = page.get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annotations.len(), 2);
+
+        // rotated link is taller than wide
+        let rotated = annotations
+            .iter()
+            .map(|annotation| {
+                document
+                    .get_dictionary(annotation.as_reference().unwrap())
+                    .unwrap()
+            })
+            .find(|annotation| {
+                let action = annotation.get(b"A").unwrap().as_dict().unwrap();
+                action.get(b"URI").unwrap().as_str().unwrap() == b"https://example.com"
+            })
+            .unwrap();
+        let rect = rotated
+            .get(b"Rect")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect::<Vec<_>>();
+        assert!(rect[3] - rect[1] > rect[2] - rect[0]);
+
+        BufWriter::new(File::create("test_link.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn internal_link() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
 
-        self.check_page_break(content_position.y, text.height * font_size, false);
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
 
-        let content_position = self.page_content_offset(content_position);
-        let mut page_position = self.page_margin.offset(&content_position);
-        if !position_is_baseline {
-            page_position.y_advance(text.ascent() * font.size().unwrap());
-        }
-        let page_position = self.swap_y(&page_position);
+        let size = Size::fixed(Mm(60.0), Mm(20.0));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        rctx.internal_link(&Offset::new(Mm(0.0), Mm(0.0)), &size, "section-3");
+        rctx.internal_link(&Offset::new(Mm(0.0), Mm(30.0)), &size, "missing");
+        rctx.note(
+            &Offset::new(Mm(70.0), Mm(0.0)),
+            &Note::new("Položka zkrácena")
+                .with_author("pdf_render")
+                .with_icon(NoteIcon::Note)
+                .with_color(Rgba::from((255, 235, 59, 1.0))),
+        );
 
-        let font_ref = self.fonts.get_font_ref(font.name().unwrap()).unwrap();
+        layout::RenderContext::new_page(&mut rctx, None);
+        rctx.anchor("section-3", &Offset::new(Mm(0.0), Mm(100.0)));
+        rctx.destination("chapter-2", &Offset::new(Mm(0.0), Mm(150.0)));
+        rctx.destination("chapter-1", &Offset::new(Mm(0.0), Mm(0.0)));
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(100.0)),
+            &size,
+            Some(&Rgba::from((244, 67, 54, 1.0))),
+            None,
+        );
 
-        let layer = &self.layer;
-        layer.begin_text_section();
-        if let Some(color) = style.color()
-            && *color != Rgba::black()
-        {
-            let color = color.into_rgba();
-            layer.set_fill_color(Color::Rgb(Rgb::new(color.0, color.1, color.2, None)));
-        }
-        layer.set_font(font_ref, *font.size().unwrap() as f32);
-        layer.set_text_cursor(from_unit(page_position.x), from_unit(page_position.y));
-        layer.set_text_scaling(100.0 * font_scaling as f32);
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        let (first_page_id, second_page_id) = (pages[&1], pages[&2]);
 
-        for position in text.positions.iter() {
-            let h_offset = position.h_offset;
-            let v_offset = position.v_offset;
-            if !h_offset.is_zero() || !v_offset.is_zero() {
-                let h_offset = h_offset * font_size * font_scaling;
-                let v_offset = v_offset * font_size;
-                layer.set_text_cursor(from_pt(h_offset), from_pt(v_offset));
-            }
+        let page = document.get_dictionary(first_page_id).unwrap();
+        let annotations = page.get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annotations.len(), 2);
+        let note = document
+            .get_dictionary(annotations[1].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(note.get(b"Subtype").unwrap().as_name().unwrap(), b"Text");
+        assert!(
+            note.get(b"Contents")
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .starts_with(&[0xFE, 0xFF])
+        );
+        let link = document
+            .get_dictionary(annotations[0].as_reference().unwrap())
+            .unwrap();
+        let destination = link.get(b"Dest").unwrap().as_array().unwrap();
+        assert_eq!(destination[0].as_reference().unwrap(), second_page_id);
 
-            layer.write_codepoints([position.glyph_index]);
+        let catalog = document.catalog().unwrap();
+        let names = catalog.get(b"Names").unwrap().as_dict().unwrap();
+        let tree = document
+            .get_dictionary(names.get(b"Dests").unwrap().as_reference().unwrap())
+            .unwrap();
+        let names = tree.get(b"Names").unwrap().as_array().unwrap();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[0].as_str().unwrap(), b"chapter-1");
+        assert_eq!(names[2].as_str().unwrap(), b"chapter-2");
 
-            let h_advance = position.h_advance_rest() * font_size * font_scaling;
-            let v_advance = position.v_advance_rest() * font_size;
+        BufWriter::new(File::create("test_internal_link.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
 
-            layer.set_text_cursor(from_pt(h_advance), from_pt(v_advance));
-        }
+    #[test]
+    fn markup() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
 
-        layer.set_text_scaling(100.0);
-        if let Some(color) = style.color()
-            && *color != Rgba::black()
-        {
-            layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        let size = Size::fixed(Mm(60.0), Mm(10.0));
+        rctx.markup(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &size,
+            &Markup::highlight(Rgba::from((255, 235, 59, 1.0)))
+                .with_contents("Total does not match items")
+                .with_author("QA"),
+        );
+        rctx.markup(
+            &Offset::new(Mm(0.0), Mm(20.0)),
+            &size,
+            &Markup::underline(Rgba::from((244, 67, 54, 1.0))),
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let page_id = *document.get_pages().values().next().unwrap();
+        let page = document.get_dictionary(page_id).unwrap();
+        let annotations = page.get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annotations.len(), 2);
+
+        let highlight = document
+            .get_dictionary(annotations[0].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(
+            highlight.get(b"Subtype").unwrap().as_name().unwrap(),
+            b"Highlight"
+        );
+        let quad = highlight
+            .get(b"QuadPoints")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect::<Vec<_>>();
+        // top left, top right, bottom left, bottom right
+        assert_eq!(quad.len(), 8);
+        assert!(quad[1] > quad[5]);
+        assert!(quad[2] > quad[0]);
+
+        BufWriter::new(File::create("test_markup.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn form_fields() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+
+        rctx.form_field(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(80.0), Mm(8.0)),
+            &FormField::text("name")
+                .with_value("Jan Novák")
+                .with_max_length(40)
+                .with_required(true),
+        );
+        rctx.form_field(
+            &Offset::new(Mm(0.0), Mm(12.0)),
+            &Size::fixed(Mm(5.0), Mm(5.0)),
+            &FormField::check_box("agree").with_checked(true),
+        );
+        layout::RenderContext::new_page(&mut rctx, None);
+        rctx.form_field(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(80.0), Mm(20.0)),
+            &FormField::signature("signature").with_tooltip("Customer signature"),
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let catalog = document.catalog().unwrap();
+        let form = document
+            .get_dictionary(catalog.get(b"AcroForm").unwrap().as_reference().unwrap())
+            .unwrap();
+        let fields = form.get(b"Fields").unwrap().as_array().unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(form.get(b"SigFlags").unwrap().as_i64().unwrap(), 1);
+
+        let check_box = document
+            .get_dictionary(fields[1].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(check_box.get(b"AS").unwrap().as_name().unwrap(), b"Yes");
+        let appearances = check_box.get(b"AP").unwrap().as_dict().unwrap();
+        let states = appearances.get(b"N").unwrap().as_dict().unwrap();
+        assert!(states.get(b"Yes").unwrap().as_reference().is_ok());
+
+        BufWriter::new(File::create("test_form_fields.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn icc_profile() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        // not a real profile, only its embedding is checked
+        let profile = vec![0; 128];
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_color_model(ColorModel::Cmyk)
+        .with_icc_profile(IccProfile::new(ColorModel::Cmyk, profile));
+
+        rctx.rect(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(60.0), Mm(60.0)),
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
+        layout::RenderContext::new_page(&mut rctx, None);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        for page_id in document.get_pages().into_values() {
+            let page = document.get_dictionary(page_id).unwrap();
+            let resources = document
+                .get_dictionary(page.get(b"Resources").unwrap().as_reference().unwrap())
+                .unwrap();
+            let color_spaces = document
+                .get_dictionary(
+                    resources
+                        .get(b"ColorSpace")
+                        .unwrap()
+                        .as_reference()
+                        .unwrap(),
+                )
+                .unwrap();
+            let color_space = color_spaces
+                .get(b"DefaultCMYK")
+                .unwrap()
+                .as_array()
+                .unwrap();
+            assert_eq!(color_space[0].as_name().unwrap(), b"ICCBased");
         }
-        layer.end_text_section();
+
+        let page_id = document.get_pages()[&1];
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        assert!(
+            content
+                .operations
+                .iter()
+                .any(|operation| operation.operator == "k")
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{fs::File, io::BufWriter};
+    #[test]
+    fn precision() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
 
-    use layout::{
-        Features, Font, MeasureContext, RenderContext as _, StyleBuilder,
-        position::{Offset, Quad, Size},
-        unit::{Mm, Pt},
-    };
-    use printpdf::PdfDocument;
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_precision(1);
 
-    use crate::new_font_cache;
+        rctx.rect(
+            &Offset::new(Mm(0.123), Mm(0.456)),
+            &Size::fixed(Mm(60.789), Mm(60.0)),
+            Some(&Rgba::from((13, 71, 161, 1.0))),
+            None,
+        );
 
-    use super::RenderContext;
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let page_id = document.get_pages()[&1];
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let rect = content
+            .operations
+            .iter()
+            .find(|operation| operation.operator == "re")
+            .unwrap();
+        for operand in rect.operands.iter() {
+            let value = operand.as_float().unwrap();
+            assert!((value * 10.0 - (value * 10.0).round()).abs() < 0.001);
+        }
+    }
 
     #[test]
-    fn render_context() {
-        let fonts = new_font_cache();
+    fn options() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
 
-        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
-        fonts.add("LatoReg", font_bin).unwrap();
+        let rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_precision(1)
+        .with_compression(Compression::None)
+        .with_options(&RenderOptions::new().with_debug_frame(true));
+
+        assert!(rctx.debug_frame);
+        assert_eq!(rctx.precision, None);
+        assert_eq!(rctx.compression, None);
+    }
+
+    #[test]
+    fn compression() {
+        let pdf = |compression| {
+            let (document, page, layer) =
+                PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+            let mut rctx = RenderContext::new(
+                document,
+                page,
+                layer,
+                Quad::square(Mm(10.0)),
+                Size::fixed(Mm(210.0), Mm(297.0)),
+                new_font_cache(),
+            )
+            .with_compression(compression);
+            for row in 0..20 {
+                rctx.rect(
+                    &Offset::new(Mm(0.0), Mm(row as f64 * 10.0)),
+                    &Size::fixed(Mm(60.0), Mm(5.0)),
+                    Some(&Rgba::black()),
+                    None,
+                );
+            }
+            rctx.save_to_bytes().unwrap()
+        };
+        let filters = |pdf: &[u8]| {
+            let document = printpdf::lopdf::Document::load_mem(pdf).unwrap();
+            let page_id = document.get_pages()[&1];
+            let rects = document
+                .get_and_decode_page_content(page_id)
+                .unwrap()
+                .operations
+                .iter()
+                .filter(|operation| operation.operator == "re")
+                .count();
+            assert_eq!(rects, 20);
+            document
+                .get_page_contents(page_id)
+                .into_iter()
+                .map(|id| {
+                    document
+                        .get_object(id)
+                        .unwrap()
+                        .as_stream()
+                        .unwrap()
+                        .dict
+                        .has(b"Filter")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let compressed = pdf(Compression::Flate(9));
+        let uncompressed = pdf(Compression::None);
+        assert!(filters(&compressed).iter().all(|filter| *filter));
+        assert!(filters(&uncompressed).iter().all(|filter| !*filter));
+        assert!(compressed.len() < uncompressed.len());
+    }
 
+    #[test]
+    fn debug_layer() {
         let (document, page, layer) =
             PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
 
@@ -475,68 +6706,251 @@ mod tests {
             document,
             page,
             layer,
-            Quad::empty(),
+            Quad::square(Mm(10.0)),
             Size::fixed(Mm(210.0), Mm(297.0)),
-            fonts,
+            new_font_cache(),
         )
-        .with_debug_frame(true);
+        .with_debug_frame(true)
+        .with_debug_page_breaks(true);
 
-        let style = StyleBuilder::default()
-            .with_font(Font::new(
-                "LatoReg",
-                Pt(36.0),
-                Some(Features::empty().pnum()),
-            ))
-            .build();
+        let size = Size::fixed(Mm(60.0), Mm(200.0));
+        for offset in [Mm(0.0), Mm(200.0)] {
+            let position = Offset::new(Mm(0.0), offset);
+            layout::RenderContext::check_page_break(
+                &mut rctx,
+                offset.into(),
+                Mm(200.0).into(),
+                false,
+            );
+            layout::RenderContext::debug_frame(&mut rctx, &position, &size);
+            rctx.rect(
+                &position,
+                &size,
+                Some(&Rgba::from((13, 71, 161, 1.0))),
+                None,
+            );
+        }
 
-        let text1 = rctx
-            .typeset(&style, "Fimfifárumík 12115 jgenealogie")
+        let pdf = rctx.save_to_bytes().unwrap();
+        std::fs::write("test-debug-layer.pdf", &pdf).unwrap();
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        assert_eq!(document.get_pages().len(), 2);
+        let layers = document
+            .catalog()
+            .unwrap()
+            .get(b"OCProperties")
+            .and_then(printpdf::lopdf::Object::as_dict)
+            .and_then(|properties| properties.get(b"OCGs"))
+            .and_then(printpdf::lopdf::Object::as_array)
             .unwrap();
+        let mut names = layers
+            .iter()
+            .map(|layer| {
+                let layer = document
+                    .get_dictionary(layer.as_reference().unwrap())
+                    .unwrap();
+                layer.get(b"Name").unwrap().as_str().unwrap().to_vec()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec![b"debug".to_vec(), b"default".to_vec()]);
+    }
 
-        let style = StyleBuilder::default()
-            .with_font(Font::new(
-                "LatoReg",
-                Pt(36.0),
-                Some(Features::empty().tnum().smcp()),
-            ))
-            .build();
+    #[test]
+    fn document_info() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
 
-        let text2 = rctx
-            .typeset(&style, "Fimfifárumík 12115 jgenealogie")
+        let rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_author("Jan Novák")
+        .with_subject("Report")
+        .with_keywords(["quarterly", "sales"])
+        .with_creator("pdf_render")
+        .with_creation_date(printpdf::OffsetDateTime::UNIX_EPOCH);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_document_info.pdf").unwrap())
+            .write_all(&pdf)
             .unwrap();
 
-        rctx.complete_fonts().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let info = document
+            .trailer
+            .get(b"Info")
+            .and_then(printpdf::lopdf::Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        let value = |key: &[u8]| info.get(key).unwrap().as_str().unwrap().to_vec();
+        assert_eq!(value(b"Subject"), b"Report");
+        assert_eq!(value(b"Keywords"), b"quarterly,sales");
+        assert_eq!(value(b"Creator"), b"pdf_render");
+        assert!(value(b"CreationDate").starts_with(b"D:1970"));
+    }
 
-        rctx.text(&Offset::new(Mm(20.0), Mm(20.0)), &style, &text1, true);
+    #[test]
+    fn pdf_a() {
+        let context = |level: PdfALevel| {
+            let (document, page, layer) =
+                PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+            let mut rctx = RenderContext::new(
+                document,
+                page,
+                layer,
+                Quad::square(Mm(10.0)),
+                Size::fixed(Mm(210.0), Mm(297.0)),
+                new_font_cache(),
+            )
+            .with_pdf_a(level);
+            rctx.with_opacity(0.5, |rctx| {
+                rctx.rect(
+                    &Offset::new(Mm(0.0), Mm(0.0)),
+                    &Size::fixed(Mm(60.0), Mm(60.0)),
+                    Some(&Rgba::from((244, 67, 54, 1.0))),
+                    None,
+                );
+            });
+            rctx
+        };
 
-        rctx.text(&Offset::new(Mm(20.0), Mm(40.0)), &style, &text2, true);
+        assert!(context(PdfALevel::A2b).save_to_bytes().is_err());
 
-        let style = StyleBuilder::default()
-            .with_font(Font::new(
-                "LatoReg",
-                Pt(18.0),
-                Some(Features::empty().pnum()),
-            ))
-            .build();
-        rctx.text(&Offset::new(Mm(20.0), Mm(60.0)), &style, &text2, true);
-        let style = StyleBuilder::default()
-            .with_font(Font::new(
-                "LatoReg",
-                Pt(12.0),
-                Some(Features::empty().pnum()),
-            ))
-            .build();
-        rctx.text(
-            &Offset::new(Mm(20.0) + text2.width * Pt(18.0), Mm(60.0)),
-            &style,
-            &text2,
-            true,
+        // not a real profile, only the output intent is checked
+        let profile = IccProfile::new(ColorModel::Rgb, vec![0; 128]);
+        let Err(Error::PdfWrite(message)) = context(PdfALevel::A1b)
+            .with_icc_profile(profile.clone())
+            .save_to_bytes()
+        else {
+            panic!("transparency is not reported");
+        };
+        assert_eq!(message, "PDF/A-1b forbids transparency");
+
+        let pdf = context(PdfALevel::A2b)
+            .with_icc_profile(profile)
+            .save_to_bytes()
+            .unwrap();
+        BufWriter::new(File::create("test_pdf_a.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let catalog = document.catalog().unwrap();
+        let intents = catalog
+            .get(b"OutputIntents")
+            .and_then(printpdf::lopdf::Object::as_array)
+            .unwrap();
+        let intent = intents[0].as_dict().unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFA1");
+        let metadata = catalog
+            .get(b"Metadata")
+            .and_then(printpdf::lopdf::Object::as_reference)
+            .and_then(|id| document.get_object(id))
+            .and_then(printpdf::lopdf::Object::as_stream)
+            .unwrap();
+        let packet = String::from_utf8_lossy(&metadata.content);
+        assert!(packet.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(packet.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+    }
+
+    #[test]
+    fn pdf_version() {
+        let context = |version: PdfVersion, alpha: f32| {
+            let (document, page, layer) =
+                PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+            let mut rctx = RenderContext::new(
+                document,
+                page,
+                layer,
+                Quad::square(Mm(10.0)),
+                Size::fixed(Mm(210.0), Mm(297.0)),
+                new_font_cache(),
+            )
+            .with_pdf_version(version);
+            rctx.with_layer("annotations", |rctx| {
+                rctx.with_opacity(alpha, |rctx| {
+                    rctx.rect(
+                        &Offset::new(Mm(0.0), Mm(0.0)),
+                        &Size::fixed(Mm(60.0), Mm(60.0)),
+                        Some(&Rgba::from((244, 67, 54, 1.0))),
+                        None,
+                    );
+                });
+            });
+            rctx
+        };
+
+        let Err(Error::PdfWrite(message)) = context(PdfVersion::V1_3, 0.5).save_to_bytes() else {
+            panic!("transparency is not refused");
+        };
+        assert_eq!(message, "PDF 1.3 does not support transparency");
+
+        let pdf = context(PdfVersion::V1_4, 0.5).save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_pdf_version.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        assert!(!document.catalog().unwrap().has(b"OCProperties"));
+
+        let pdf = context(PdfVersion::V1_7, 1.0).save_to_bytes().unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.7"));
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        assert!(document.catalog().unwrap().has(b"OCProperties"));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
         );
 
-        rctx.document
-            .save(&mut BufWriter::new(
-                File::create("test_fonts_r.pdf").unwrap(),
-            ))
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <rect x="10" y="10" width="80" height="30" fill="#f44336"/>
+        </svg>"##;
+        let size = Size::fixed(Mm(100.0), Mm(50.0));
+        rctx.svg(&Offset::new(Mm(0.0), Mm(0.0)), &size, svg)
+            .unwrap();
+        // the picture past the page end is moved to the next page as a whole
+        rctx.svg(&Offset::new(Mm(0.0), Mm(250.0)), &size, svg)
+            .unwrap();
+        assert!(rctx.svg(&Offset::zero(), &size, "<svg").is_err());
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        for page_id in pages.into_values() {
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let count = |operator: &str| {
+                content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == operator)
+                    .count()
+            };
+            // vector operators are in the form painted, nothing is rasterized
+            assert_eq!(count("Do"), 1);
+            assert_eq!(count("BI"), 0);
+        }
+
+        BufWriter::new(File::create("test_svg.pdf").unwrap())
+            .write_all(&pdf)
             .unwrap();
     }
 }
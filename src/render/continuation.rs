@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use layout::{Style, TextPosition};
+
+pub(crate) struct ContinuationText {
+    pub(crate) text: String,
+    pub(crate) style: Arc<Style>,
+    // typeset when the continuation is set on the render context
+    pub(crate) position: Option<TextPosition>,
+}
+
+impl ContinuationText {
+    fn new(text: impl Into<String>, style: impl Into<Arc<Style>>) -> Self {
+        Self {
+            text: text.into(),
+            style: style.into(),
+            position: None,
+        }
+    }
+}
+
+// painted when content does not fit and check_page_break moves it to a new page,
+// explicitly requested new pages are not marked
+#[derive(Default)]
+pub struct Continuation {
+    bottom: Option<ContinuationText>,
+    top: Option<ContinuationText>,
+}
+
+impl Continuation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // below the content of the page being left, aligned to the right
+    pub fn with_bottom(mut self, text: impl Into<String>, style: impl Into<Arc<Style>>) -> Self {
+        self.bottom = Some(ContinuationText::new(text, style));
+        self
+    }
+
+    // above the content of the new page, aligned to the left
+    pub fn with_top(mut self, text: impl Into<String>, style: impl Into<Arc<Style>>) -> Self {
+        self.top = Some(ContinuationText::new(text, style));
+        self
+    }
+
+    pub(crate) fn bottom(&self) -> Option<&ContinuationText> {
+        self.bottom.as_ref()
+    }
+
+    pub(crate) fn top(&self) -> Option<&ContinuationText> {
+        self.top.as_ref()
+    }
+
+    pub(crate) fn texts_mut(&mut self) -> impl Iterator<Item = &mut ContinuationText> {
+        self.bottom.iter_mut().chain(self.top.iter_mut())
+    }
+}
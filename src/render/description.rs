@@ -0,0 +1,355 @@
+use layout::{
+    Axis, Error, Font, Layout, LayoutBox, Rgba, StyleBuilder, Text, hfill,
+    position::{Quad, Size},
+    unit::{Mm, Pt},
+    vfill,
+};
+use serde::Deserialize;
+
+use crate::font::FontCache;
+
+use super::{RenderError, Renderer};
+
+#[derive(Debug)]
+pub enum DescriptionError {
+    // not valid JSON or YAML, or not matching the schema
+    Parse(String),
+    // e.g. a malformed color
+    InvalidValue(String),
+    Render(RenderError),
+}
+
+impl From<Error> for DescriptionError {
+    fn from(error: Error) -> Self {
+        DescriptionError::Render(error.into())
+    }
+}
+
+impl From<RenderError> for DescriptionError {
+    fn from(error: RenderError) -> Self {
+        DescriptionError::Render(error)
+    }
+}
+
+impl std::fmt::Display for DescriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DescriptionError::Parse(message) => {
+                write!(f, "Invalid document description: {message}")
+            }
+            DescriptionError::InvalidValue(message) => write!(f, "{message}"),
+            DescriptionError::Render(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for DescriptionError {}
+
+// document submitted as data, e.g. JSON or YAML posted to a service wrapping the renderer;
+// fonts are referred to by names of the font cache, lengths are in mm, font sizes in pt
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentDescription {
+    #[serde(default)]
+    pub title: String,
+    // width and height, A4 unless set
+    #[serde(default)]
+    pub page_size: Option<[f64; 2]>,
+    #[serde(default)]
+    pub page_margin: Option<f64>,
+    pub body: ElementDescription,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ElementDescription {
+    Hbox {
+        #[serde(default)]
+        size: Option<f64>,
+        #[serde(default)]
+        style: Option<StyleDescription>,
+        #[serde(default)]
+        children: Vec<ElementDescription>,
+    },
+    Vbox {
+        #[serde(default)]
+        size: Option<f64>,
+        #[serde(default)]
+        style: Option<StyleDescription>,
+        #[serde(default)]
+        children: Vec<ElementDescription>,
+    },
+    Text {
+        text: String,
+        #[serde(default)]
+        style: Option<StyleDescription>,
+    },
+    // free space shared by weights of fills of the box
+    Hfill {
+        #[serde(default = "default_weight")]
+        weight: u32,
+    },
+    Vfill {
+        #[serde(default = "default_weight")]
+        weight: u32,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StyleDescription {
+    #[serde(default)]
+    pub font: Option<FontDescription>,
+    // #rrggbb or #rrggbbaa
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub padding: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FontDescription {
+    pub name: String,
+    pub size: f64,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl DocumentDescription {
+    pub fn from_json(json: &str) -> Result<Self, DescriptionError> {
+        serde_json::from_str(json).map_err(|error| DescriptionError::Parse(error.to_string()))
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self, DescriptionError> {
+        serde_yaml::from_str(yaml).map_err(|error| DescriptionError::Parse(error.to_string()))
+    }
+
+    pub fn page_size(&self) -> Size {
+        let [width, height] = self.page_size.unwrap_or([210.0, 297.0]);
+        Size::fixed(Mm(width), Mm(height))
+    }
+
+    pub fn page_margin(&self) -> Quad {
+        match self.page_margin {
+            Some(margin) => Quad::square(Mm(margin)),
+            None => Quad::empty(),
+        }
+    }
+
+    // e.g. to set options before the body is rendered
+    pub fn renderer(&self, fonts: FontCache) -> Renderer {
+        Renderer::new(&self.title, self.page_margin(), self.page_size(), fonts)
+    }
+
+    pub fn layout(&self) -> Result<Box<dyn Layout>, DescriptionError> {
+        self.body.layout()
+    }
+
+    pub fn render(&self, fonts: FontCache) -> Result<Vec<u8>, DescriptionError> {
+        Ok(self.renderer(fonts).render_layout(self.layout()?)?)
+    }
+}
+
+impl ElementDescription {
+    pub fn layout(&self) -> Result<Box<dyn Layout>, DescriptionError> {
+        Ok(match self {
+            Self::Hbox {
+                size,
+                style,
+                children,
+            } => Box::new(layout_box(Axis::Horizontal, *size, style, children)?),
+            Self::Vbox {
+                size,
+                style,
+                children,
+            } => Box::new(layout_box(Axis::Vertical, *size, style, children)?),
+            Self::Text { text, style } => Box::new(layout_text(text, style)?),
+            Self::Hfill { weight } => Box::new(hfill(*weight)),
+            Self::Vfill { weight } => Box::new(vfill(*weight)),
+        })
+    }
+
+    // children are added by their types, boxes take them by value
+    fn add_to(&self, parent: LayoutBox) -> Result<LayoutBox, DescriptionError> {
+        Ok(match self {
+            Self::Hbox {
+                size,
+                style,
+                children,
+            } => parent.child(layout_box(Axis::Horizontal, *size, style, children)?),
+            Self::Vbox {
+                size,
+                style,
+                children,
+            } => parent.child(layout_box(Axis::Vertical, *size, style, children)?),
+            Self::Text { text, style } => parent.child(layout_text(text, style)?),
+            Self::Hfill { weight } => parent.child(hfill(*weight)),
+            Self::Vfill { weight } => parent.child(vfill(*weight)),
+        })
+    }
+}
+
+fn layout_box(
+    axis: Axis,
+    size: Option<f64>,
+    style: &Option<StyleDescription>,
+    children: &[ElementDescription],
+) -> Result<LayoutBox, DescriptionError> {
+    let mut layout_box = LayoutBox::new(axis);
+    if let Some(size) = size {
+        layout_box = layout_box.axis_size(Mm(size));
+    }
+    if let Some(style) = style {
+        layout_box = layout_box.style(style.builder()?);
+    }
+    for child in children {
+        layout_box = child.add_to(layout_box)?;
+    }
+    Ok(layout_box)
+}
+
+fn layout_text(text: &str, style: &Option<StyleDescription>) -> Result<Text, DescriptionError> {
+    let mut layout = Text::new(text);
+    if let Some(style) = style {
+        layout = layout.style(style.builder()?);
+    }
+    Ok(layout)
+}
+
+impl StyleDescription {
+    fn builder(&self) -> Result<StyleBuilder, DescriptionError> {
+        let mut builder = StyleBuilder::default();
+        if let Some(font) = &self.font {
+            builder = builder.with_font(Font::new(&font.name, Pt(font.size), None));
+        }
+        if let Some(color) = &self.color {
+            builder = builder.with_color(parse_color(color)?);
+        }
+        if let Some(padding) = self.padding {
+            builder = builder.with_padding(Quad::square(Mm(padding)));
+        }
+        Ok(builder)
+    }
+}
+
+fn parse_color(color: &str) -> Result<Rgba, DescriptionError> {
+    let invalid = || DescriptionError::InvalidValue(format!("Invalid color {color}"));
+    let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let component = |index: usize| {
+        u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).map_err(|_| invalid())
+    };
+    let alpha = match hex.len() {
+        8 => component(3)? as f32 / 255.0,
+        _ => 1.0,
+    };
+    Ok(Rgba::from((
+        component(0)?,
+        component(1)?,
+        component(2)?,
+        alpha,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::Rgba;
+    use printpdf::lopdf::{Document, Object};
+
+    use crate::{DescriptionError, DocumentDescription, ElementDescription, new_font_cache};
+
+    use super::parse_color;
+
+    const JSON: &str = r##"{
+        "title": "Job",
+        "page_size": [148, 210],
+        "page_margin": 10,
+        "body": {
+            "type": "vbox",
+            "children": [
+                {
+                    "type": "text",
+                    "text": "Title",
+                    "style": { "font": { "name": "LatoReg", "size": 18 }, "color": "#0d47a1" }
+                },
+                { "type": "vfill" },
+                { "type": "hbox", "size": 20, "children": [{ "type": "hfill", "weight": 2 }] }
+            ]
+        }
+    }"##;
+
+    const YAML: &str = r##"
+title: Job
+page_size: [148, 210]
+page_margin: 10
+body:
+  type: vbox
+  children:
+    - type: text
+      text: Title
+      style:
+        font: { name: LatoReg, size: 18 }
+        color: "#0d47a1"
+    - type: vfill
+    - type: hbox
+      size: 20
+      children:
+        - type: hfill
+          weight: 2
+"##;
+
+    #[test]
+    fn description() {
+        let description = DocumentDescription::from_json(JSON).unwrap();
+        assert_eq!(DocumentDescription::from_yaml(YAML).unwrap(), description);
+        let ElementDescription::Vbox { children, .. } = &description.body else {
+            panic!("body is not a vbox");
+        };
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[1], ElementDescription::Vfill { weight: 1 });
+
+        let fonts = new_font_cache();
+        fonts
+            .add("LatoReg", include_bytes!("../../tests/Lato-Regular.ttf"))
+            .unwrap();
+        let pdf = description.render(fonts).unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let width = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"MediaBox"))
+            .and_then(Object::as_array)
+            .map(|media_box| media_box[2].as_float().unwrap().round())
+            .unwrap();
+        assert_eq!(width, 420.0);
+
+        assert!(matches!(
+            DocumentDescription::from_json(r#"{"body": {"type": "table"}}"#),
+            Err(DescriptionError::Parse(_))
+        ));
+        assert!(matches!(
+            DocumentDescription::from_yaml("body: { type: hfill }\nx: 1"),
+            Err(DescriptionError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            parse_color("#ff8000").unwrap(),
+            Rgba::from((255, 128, 0, 1.0))
+        );
+        assert_eq!(
+            parse_color("#00000080").unwrap(),
+            Rgba::from((0, 0, 0, 128.0 / 255.0))
+        );
+        assert!(parse_color("ff8000").is_err());
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("#gg8000").is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use layout::{
+    Layout,
+    position::{Quad, Size},
+};
+
+use super::{PageDecorator, PageNumbering, page_decorator::PageCallback};
+
+// part of a document rendered from its own layout, it starts on a new page of its own
+// setup; setup not set is the one of the renderer
+pub struct DocumentSection {
+    pub(crate) layout: Box<dyn Layout>,
+    page_margin: Option<Quad>,
+    page_size: Option<Size>,
+    landscape: bool,
+    // none keeps numbering of the document, some none turns it off
+    pub(crate) page_numbering: Option<Option<PageNumbering>>,
+    pub(crate) page_callbacks: Vec<PageCallback>,
+}
+
+impl DocumentSection {
+    pub fn new(layout: impl Layout + 'static) -> Self {
+        Self::from_box(Box::new(layout))
+    }
+
+    pub(crate) fn from_box(layout: Box<dyn Layout>) -> Self {
+        Self {
+            layout,
+            page_margin: None,
+            page_size: None,
+            landscape: false,
+            page_numbering: None,
+            page_callbacks: vec![],
+        }
+    }
+
+    pub fn with_page_margin(mut self, page_margin: Quad) -> Self {
+        self.page_margin = Some(page_margin);
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: Size) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    // the longer side of the page is horizontal
+    pub fn with_landscape(mut self, landscape: bool) -> Self {
+        self.landscape = landscape;
+        self
+    }
+
+    // pages of the section are numbered from the start of the numbering, none leaves them
+    // without numbers
+    pub fn with_page_numbering(mut self, page_numbering: Option<PageNumbering>) -> Self {
+        self.page_numbering = Some(page_numbering);
+        self
+    }
+
+    // e.g. headers and footers of the section, in addition to those of the renderer
+    pub fn with_page_callback(
+        mut self,
+        callback: impl FnMut(&mut PageDecorator, usize) + 'static,
+    ) -> Self {
+        self.page_callbacks.push(Box::new(callback));
+        self
+    }
+
+    pub(crate) fn has_page_setup(&self) -> bool {
+        self.page_margin.is_some() || self.page_size.is_some() || self.landscape
+    }
+
+    pub(crate) fn page_margin(&self, default: &Quad) -> Quad {
+        self.page_margin.clone().unwrap_or_else(|| default.clone())
+    }
+
+    pub(crate) fn page_size(&self, default: &Size) -> Size {
+        let size = self.page_size.clone().unwrap_or_else(|| default.clone());
+        match self.landscape && size.base_width() < size.base_height() {
+            true => Size::fixed(size.base_height(), size.base_width()),
+            false => size,
+        }
+    }
+}
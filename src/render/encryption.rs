@@ -0,0 +1,517 @@
+use aes::{
+    Aes128, Aes256, Block,
+    cipher::{BlockEncrypt, KeyInit},
+};
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use super::{
+    PdfVersion,
+    resources::{catalog, pdf_error},
+};
+
+const PADDING: [u8; 32] = [
+    0x28, 0xbf, 0x4e, 0x5e, 0x4e, 0x75, 0x8a, 0x41, 0x64, 0x00, 0x4e, 0x56, 0xff, 0xfa, 0x01, 0x08,
+    0x2e, 0x2e, 0x00, 0xb6, 0xd0, 0x68, 0x3e, 0x80, 0x2f, 0x0c, 0xa9, 0xfe, 0x64, 0x53, 0x69, 0x7a,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncryptionMethod {
+    // standard security handler revision 4, pdf 1.6
+    Aes128,
+    // standard security handler revision 6, pdf 2.0, read by pdf 1.7 viewers with the
+    // adobe extension level 8
+    Aes256,
+}
+
+impl EncryptionMethod {
+    pub(crate) fn version(self) -> PdfVersion {
+        match self {
+            EncryptionMethod::Aes128 => PdfVersion::V1_6,
+            EncryptionMethod::Aes256 => PdfVersion::V1_7,
+        }
+    }
+}
+
+// operations viewers allow without the owner password, all are allowed unless denied
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Permissions {
+    printing: bool,
+    modifying: bool,
+    copying: bool,
+    annotating: bool,
+    form_filling: bool,
+    assembling: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            printing: true,
+            modifying: true,
+            copying: true,
+            annotating: true,
+            form_filling: true,
+            assembling: true,
+        }
+    }
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_printing(mut self, printing: bool) -> Self {
+        self.printing = printing;
+        self
+    }
+
+    pub fn with_modifying(mut self, modifying: bool) -> Self {
+        self.modifying = modifying;
+        self
+    }
+
+    // copying of text and graphics, extraction for accessibility is always allowed
+    pub fn with_copying(mut self, copying: bool) -> Self {
+        self.copying = copying;
+        self
+    }
+
+    pub fn with_annotating(mut self, annotating: bool) -> Self {
+        self.annotating = annotating;
+        self
+    }
+
+    pub fn with_form_filling(mut self, form_filling: bool) -> Self {
+        self.form_filling = form_filling;
+        self
+    }
+
+    // inserting, rotating and deleting pages
+    pub fn with_assembling(mut self, assembling: bool) -> Self {
+        self.assembling = assembling;
+        self
+    }
+
+    // bits of the P entry, reserved bits are set
+    fn flags(&self) -> u32 {
+        let mut flags = 0xffff_f0c0 | 1 << 9;
+        for (allowed, bits) in [
+            (self.printing, 1 << 2 | 1 << 11),
+            (self.modifying, 1 << 3),
+            (self.copying, 1 << 4),
+            (self.annotating, 1 << 5),
+            (self.form_filling, 1 << 8),
+            (self.assembling, 1 << 10),
+        ] {
+            if allowed {
+                flags |= bits;
+            }
+        }
+        flags
+    }
+}
+
+// the document opens with the user password, an empty one opens it without asking; the
+// owner password lifts the permissions
+pub struct Encryption {
+    owner_password: String,
+    user_password: String,
+    method: EncryptionMethod,
+    permissions: Permissions,
+}
+
+impl Encryption {
+    pub fn new(
+        owner_password: impl Into<String>,
+        user_password: impl Into<String>,
+        method: EncryptionMethod,
+    ) -> Self {
+        Self {
+            owner_password: owner_password.into(),
+            user_password: user_password.into(),
+            method,
+            permissions: Permissions::default(),
+        }
+    }
+
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub(crate) fn method(&self) -> EncryptionMethod {
+        self.method
+    }
+
+    // strings and streams of all objects are encrypted, so it has to be the last change
+    // of the document
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let (dictionary, key) = match self.method {
+            EncryptionMethod::Aes128 => self.standard_aes128(document)?,
+            EncryptionMethod::Aes256 => self.standard_aes256()?,
+        };
+
+        if self.method == EncryptionMethod::Aes256 {
+            let mut adobe = Dictionary::new();
+            adobe.set("BaseVersion", Object::Name(b"1.7".to_vec()));
+            adobe.set("ExtensionLevel", 8);
+            let mut extensions = Dictionary::new();
+            extensions.set("ADBE", adobe);
+            catalog(document)?.set("Extensions", extensions);
+        }
+
+        let ids = document.objects.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            let object_key = self.object_key(&key, id);
+            let cipher = Cipher::new(self.method, &object_key)?;
+            if let Some(object) = document.objects.get_mut(&id) {
+                encrypt_object(&cipher, object)?;
+            }
+        }
+
+        let encrypt_id = document.add_object(dictionary);
+        document
+            .trailer
+            .set("Encrypt", Object::Reference(encrypt_id));
+        Ok(())
+    }
+
+    fn object_key(&self, key: &[u8], (number, generation): ObjectId) -> Vec<u8> {
+        match self.method {
+            EncryptionMethod::Aes128 => {
+                let mut context = md5::Context::new();
+                context.consume(key);
+                context.consume(&number.to_le_bytes()[..3]);
+                context.consume(&generation.to_le_bytes()[..2]);
+                context.consume(b"sAlT");
+                context.compute().0.to_vec()
+            }
+            EncryptionMethod::Aes256 => key.to_vec(),
+        }
+    }
+
+    // algorithms 2, 3 and 5 of the standard security handler, revision 4
+    fn standard_aes128(&self, document: &Document) -> Result<(Dictionary, Vec<u8>), Error> {
+        let file_id = document
+            .trailer
+            .get(b"ID")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|id| id.first())
+            .and_then(|id| id.as_str().ok())
+            .ok_or_else(|| Error::PdfWrite("Encryption requires the document ID".into()))?
+            .to_vec();
+        let flags = self.permissions.flags();
+
+        let owner_password = match self.owner_password.is_empty() {
+            true => &self.user_password,
+            false => &self.owner_password,
+        };
+        let mut owner_key = md5::compute(padded(owner_password)).0;
+        for _ in 0..50 {
+            owner_key = md5::compute(owner_key).0;
+        }
+        let owner = rc4_rounds(&owner_key, &padded(&self.user_password));
+
+        let mut context = md5::Context::new();
+        context.consume(padded(&self.user_password));
+        context.consume(&owner);
+        context.consume(flags.to_le_bytes());
+        context.consume(&file_id);
+        let mut key = context.compute().0;
+        for _ in 0..50 {
+            key = md5::compute(key).0;
+        }
+
+        let mut context = md5::Context::new();
+        context.consume(PADDING);
+        context.consume(&file_id);
+        let mut user = rc4_rounds(&key, &context.compute().0);
+        user.extend_from_slice(&PADDING[..16]);
+
+        let mut dictionary = self.dictionary(4, 128, b"AESV2", 16);
+        dictionary.set("O", Object::String(owner, StringFormat::Hexadecimal));
+        dictionary.set("U", Object::String(user, StringFormat::Hexadecimal));
+        Ok((dictionary, key.to_vec()))
+    }
+
+    // algorithms 8, 9 and 10 of the standard security handler, revision 6
+    fn standard_aes256(&self) -> Result<(Dictionary, Vec<u8>), Error> {
+        let key = random::<32>()?;
+        let flags = self.permissions.flags();
+        let zero_iv = [0; 16];
+
+        let user_password = truncated(&self.user_password);
+        let [validation_salt, key_salt] = [random::<8>()?, random::<8>()?];
+        let mut user = hash_r6(user_password, &validation_salt, &[])?;
+        user.extend_from_slice(&validation_salt);
+        user.extend_from_slice(&key_salt);
+        let user_key = hash_r6(user_password, &key_salt, &[])?;
+        let user_encrypted = Cipher::new(EncryptionMethod::Aes256, &user_key)?.cbc(zero_iv, &key);
+
+        // an empty owner password would open the document without the user one
+        let owner_password = match self.owner_password.is_empty() {
+            true => truncated(&self.user_password),
+            false => truncated(&self.owner_password),
+        };
+        let [validation_salt, key_salt] = [random::<8>()?, random::<8>()?];
+        let mut owner = hash_r6(owner_password, &validation_salt, &user)?;
+        owner.extend_from_slice(&validation_salt);
+        owner.extend_from_slice(&key_salt);
+        let owner_key = hash_r6(owner_password, &key_salt, &user)?;
+        let owner_encrypted = Cipher::new(EncryptionMethod::Aes256, &owner_key)?.cbc(zero_iv, &key);
+
+        let mut permissions = flags.to_le_bytes().to_vec();
+        permissions.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, b'T', b'a', b'd', b'b']);
+        permissions.extend_from_slice(&random::<4>()?);
+        let permissions = Cipher::new(EncryptionMethod::Aes256, &key)?.cbc(zero_iv, &permissions);
+
+        let mut dictionary = self.dictionary(6, 256, b"AESV3", 32);
+        dictionary.set("V", 5);
+        for (name, value) in [
+            ("O", owner),
+            ("U", user),
+            ("OE", owner_encrypted),
+            ("UE", user_encrypted),
+            ("Perms", permissions),
+        ] {
+            dictionary.set(name, Object::String(value, StringFormat::Hexadecimal));
+        }
+        Ok((dictionary, key.to_vec()))
+    }
+
+    fn dictionary(&self, revision: i64, length: i64, method: &[u8], key_length: i64) -> Dictionary {
+        let mut filter = Dictionary::new();
+        filter.set("Type", Object::Name(b"CryptFilter".to_vec()));
+        filter.set("CFM", Object::Name(method.to_vec()));
+        filter.set("AuthEvent", Object::Name(b"DocOpen".to_vec()));
+        filter.set("Length", key_length);
+        let mut filters = Dictionary::new();
+        filters.set("StdCF", filter);
+
+        let mut dictionary = Dictionary::new();
+        dictionary.set("Filter", Object::Name(b"Standard".to_vec()));
+        dictionary.set("V", 4);
+        dictionary.set("R", revision);
+        dictionary.set("Length", length);
+        dictionary.set("CF", filters);
+        dictionary.set("StmF", Object::Name(b"StdCF".to_vec()));
+        dictionary.set("StrF", Object::Name(b"StdCF".to_vec()));
+        dictionary.set("P", self.permissions.flags() as i32 as i64);
+        dictionary.set("EncryptMetadata", true);
+        dictionary
+    }
+}
+
+enum Cipher {
+    Aes128(Aes128),
+    Aes256(Aes256),
+}
+
+impl Cipher {
+    fn new(method: EncryptionMethod, key: &[u8]) -> Result<Self, Error> {
+        Ok(match method {
+            EncryptionMethod::Aes128 => {
+                Cipher::Aes128(Aes128::new_from_slice(key).map_err(pdf_error)?)
+            }
+            EncryptionMethod::Aes256 => {
+                Cipher::Aes256(Aes256::new_from_slice(key).map_err(pdf_error)?)
+            }
+        })
+    }
+
+    // cbc mode, data is a multiple of the block size
+    fn cbc(&self, iv: [u8; 16], data: &[u8]) -> Vec<u8> {
+        let mut previous = iv;
+        let mut encrypted = Vec::with_capacity(data.len());
+        for chunk in data.chunks(16) {
+            let mut block = [0; 16];
+            for (index, byte) in chunk.iter().enumerate() {
+                block[index] = byte ^ previous[index];
+            }
+            let mut block = Block::clone_from_slice(&block);
+            match self {
+                Cipher::Aes128(cipher) => cipher.encrypt_block(&mut block),
+                Cipher::Aes256(cipher) => cipher.encrypt_block(&mut block),
+            }
+            previous.copy_from_slice(block.as_slice());
+            encrypted.extend_from_slice(&previous);
+        }
+        encrypted
+    }
+
+    // random initialization vector followed by the data padded by pkcs#7
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let padding = 16 - data.len() % 16;
+        let mut padded = data.to_vec();
+        padded.resize(data.len() + padding, padding as u8);
+        let iv = random::<16>()?;
+        let mut encrypted = iv.to_vec();
+        encrypted.extend(self.cbc(iv, &padded));
+        Ok(encrypted)
+    }
+}
+
+fn encrypt_object(cipher: &Cipher, object: &mut Object) -> Result<(), Error> {
+    match object {
+        Object::String(text, format) => {
+            *text = cipher.encrypt(text)?;
+            *format = StringFormat::Hexadecimal;
+        }
+        Object::Array(array) => {
+            for object in array.iter_mut() {
+                encrypt_object(cipher, object)?;
+            }
+        }
+        Object::Dictionary(dictionary) => {
+            // contents of signatures are not encrypted
+            let signature = dictionary
+                .get(b"Type")
+                .and_then(Object::as_name)
+                .is_ok_and(|name| name == b"Sig");
+            for (key, object) in dictionary.iter_mut() {
+                if !(signature && key == b"Contents") {
+                    encrypt_object(cipher, object)?;
+                }
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, object) in stream.dict.iter_mut() {
+                encrypt_object(cipher, object)?;
+            }
+            let content = cipher.encrypt(&stream.content)?;
+            stream.set_content(content);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn padded(password: &str) -> [u8; 32] {
+    let mut padded = PADDING;
+    let password = password.as_bytes();
+    let length = password.len().min(32);
+    padded[..length].copy_from_slice(&password[..length]);
+    padded[length..].copy_from_slice(&PADDING[..32 - length]);
+    padded
+}
+
+fn truncated(password: &str) -> &[u8] {
+    let password = password.as_bytes();
+    &password[..password.len().min(127)]
+}
+
+// rc4 with the key, then 19 times with the key xored by the round number
+fn rc4_rounds(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut data = rc4(key, data);
+    for round in 1..=19u8 {
+        let key = key.iter().map(|byte| byte ^ round).collect::<Vec<_>>();
+        data = rc4(&key, &data);
+    }
+    data
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: [u8; 256] = std::array::from_fn(|index| index as u8);
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+// algorithm 2.B, user data is the U entry when hashing the owner password
+fn hash_r6(password: &[u8], salt: &[u8], user: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut hash = Sha256::digest([password, salt, user].concat()).to_vec();
+    let mut round = 0;
+    loop {
+        let sequence = [password, &hash, user].concat().repeat(64);
+        let mut iv = [0; 16];
+        iv.copy_from_slice(&hash[16..32]);
+        let encrypted = Cipher::new(EncryptionMethod::Aes128, &hash[..16])?.cbc(iv, &sequence);
+        let remainder = encrypted[..16].iter().map(|byte| *byte as u32).sum::<u32>() % 3;
+        hash = match remainder {
+            0 => Sha256::digest(&encrypted).to_vec(),
+            1 => Sha384::digest(&encrypted).to_vec(),
+            _ => Sha512::digest(&encrypted).to_vec(),
+        };
+        round += 1;
+        if round >= 64 && *encrypted.last().unwrap_or(&0) as u32 <= round - 32 {
+            break;
+        }
+    }
+    hash.truncate(32);
+    Ok(hash)
+}
+
+fn random<const N: usize>() -> Result<[u8; N], Error> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).map_err(pdf_error)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::lopdf::{Document, Object, StringFormat, encryption::get_encryption_key};
+
+    use super::{Encryption, EncryptionMethod, Permissions, hash_r6, padded, rc4};
+
+    #[test]
+    fn standard_aes128() {
+        // key and U entry of revision 4 are computed as by revision 3 the decoder knows
+        let mut document = Document::with_version("1.6");
+        document.trailer.set(
+            "ID",
+            vec![
+                Object::String(b"0123456789abcdef".to_vec(), StringFormat::Literal),
+                Object::String(b"0123456789abcdef".to_vec(), StringFormat::Literal),
+            ],
+        );
+        let encryption = Encryption::new("owner", "user", EncryptionMethod::Aes128)
+            .with_permissions(Permissions::new().with_printing(false).with_copying(false));
+        let (mut dictionary, key) = encryption.standard_aes128(&document).unwrap();
+        assert_eq!(dictionary.get(b"P").unwrap().as_i64().unwrap(), -2072);
+
+        dictionary.set("V", 2);
+        dictionary.set("R", 3);
+        let encrypt_id = document.add_object(dictionary);
+        document
+            .trailer
+            .set("Encrypt", Object::Reference(encrypt_id));
+        assert_eq!(get_encryption_key(&document, "user", true).unwrap(), key);
+        assert!(get_encryption_key(&document, "owner", true).is_err());
+
+        assert_eq!(
+            rc4(b"Key", b"Plaintext"),
+            [0xbb, 0xf3, 0x16, 0xe8, 0xd9, 0x40, 0xaf, 0x0a, 0xd3]
+        );
+        assert_eq!(padded("")[..4], [0x28, 0xbf, 0x4e, 0x5e]);
+    }
+
+    #[test]
+    fn standard_aes256_empty_owner() {
+        let encryption = Encryption::new("", "user", EncryptionMethod::Aes256);
+        let (dictionary, _) = encryption.standard_aes256().unwrap();
+        let entry = |name: &[u8]| dictionary.get(name).unwrap().as_str().unwrap().to_vec();
+        let (owner, user) = (entry(b"O"), entry(b"U"));
+
+        // algorithm 12, the owner password is validated against the O entry
+        let is_owner = |password: &[u8]| {
+            hash_r6(password, &owner[32..40], &user[..48]).unwrap() == owner[..32]
+        };
+        assert!(!is_owner(b""));
+        assert!(is_owner(b"user"));
+    }
+}
@@ -0,0 +1,312 @@
+use std::sync::Arc;
+
+use layout::{
+    Error, Layout, MeasureContext, RenderContext, Style, TextPosition,
+    position::{Offset, Size},
+    unit::{Pt, Unit},
+};
+
+use super::{Mark, Marks};
+
+// space above the notes of a page, the separator rule is drawn in its middle
+pub(crate) const FOOTNOTE_GAP: Pt = Pt(8.0);
+pub(crate) const FOOTNOTE_SEPARATOR: Pt = Pt(0.5);
+
+// words of the note are typeset when the footnote is measured, so their glyphs are subset,
+// lines are broken by the render context to the width of the page content
+#[derive(Clone, Debug)]
+pub(crate) struct FootnoteText {
+    style: Arc<Style>,
+    words: Vec<TextPosition>,
+    space: TextPosition,
+}
+
+// words with their x offsets in pt, the height and ascent of the line
+#[derive(Clone, Debug)]
+pub(crate) struct FootnoteLine {
+    pub(crate) style: Arc<Style>,
+    pub(crate) words: Vec<(f32, TextPosition)>,
+    pub(crate) ascent: f32,
+    pub(crate) height: f32,
+}
+
+// offset and height of the marker, and the note
+pub(crate) type FootnoteMark = (Unit, Unit, FootnoteText);
+
+// width, ascent and depth of typeset text, in pt
+pub(crate) fn break_lines(
+    note: &FootnoteText,
+    width: f32,
+    extent: impl Fn(&Style, &TextPosition) -> (f32, f32, f32),
+) -> Vec<FootnoteLine> {
+    let (space, _, _) = extent(&note.style, &note.space);
+    let mut lines = vec![];
+    let mut line = FootnoteLine {
+        style: note.style.clone(),
+        words: vec![],
+        ascent: 0.0,
+        height: 0.0,
+    };
+    let mut x = 0.0;
+    for word in note.words.iter() {
+        let (word_width, ascent, depth) = extent(&note.style, word);
+        if !line.words.is_empty() && x + space + word_width > width {
+            let style = line.style.clone();
+            lines.push(std::mem::replace(
+                &mut line,
+                FootnoteLine {
+                    style,
+                    words: vec![],
+                    ascent: 0.0,
+                    height: 0.0,
+                },
+            ));
+            x = 0.0;
+        }
+        if !line.words.is_empty() {
+            x += space;
+        }
+        line.words.push((x, word.clone()));
+        line.ascent = line.ascent.max(ascent);
+        line.height = line.height.max(ascent + depth.abs());
+        x += word_width;
+    }
+    if !line.words.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+pub(crate) fn lines_height(lines: &[FootnoteLine]) -> Unit {
+    Pt(lines.iter().map(|line| line.height as f64).sum()).into()
+}
+
+// notes are taken over by the render context, which reserves space for them at the
+// bottom of the page
+impl Marks {
+    // the marker is typeset in the body, e.g. a superscript number, the note goes to the
+    // bottom of the page the marker is rendered on
+    pub fn footnote(
+        &self,
+        marker: impl Layout + 'static,
+        note: impl Into<String>,
+        style: impl Into<Arc<Style>>,
+    ) -> FootnoteLayout {
+        FootnoteLayout {
+            marker: Box::new(marker),
+            note: note.into(),
+            style: style.into(),
+            text: None,
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FootnoteLayout {
+    marker: Box<dyn Layout>,
+    note: String,
+    style: Arc<Style>,
+    text: Option<FootnoteText>,
+    marks: Marks,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for FootnoteLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        let words = self
+            .note
+            .split_whitespace()
+            .map(|word| ctx.typeset(&self.style, word))
+            .collect::<Result<Vec<_>, _>>()?;
+        // a note without words has no lines
+        self.text = match words.is_empty() {
+            true => None,
+            false => Some(FootnoteText {
+                style: self.style.clone(),
+                words,
+                space: ctx.typeset(&self.style, " ")?,
+            }),
+        };
+        self.marker.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.marker.lay_out(ctx, offset, size)
+    }
+
+    // the marker moves to the next page if it does not fit with its note
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        if let Some(text) = &self.text {
+            self.marks.push(Mark::Footnote((
+                self.offset.y,
+                self.size.base_height(),
+                text.clone(),
+            )));
+        }
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+        self.marker.render(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Axis, Font, Layout, LayoutBox, MeasureContext, RenderContext as _, Style, StyleBuilder,
+        TextPosition,
+        position::{Offset, Quad, Size},
+        unit::{Em, Mm, Pt},
+    };
+    use printpdf::{PdfDocument, lopdf::Document};
+
+    use crate::{Marks, RenderContext, new_font_cache};
+
+    use super::{FootnoteText, break_lines};
+
+    fn word(width: f64) -> TextPosition {
+        TextPosition {
+            width: Em(width),
+            height: Em(1.2),
+            depth: Em(-0.2),
+            positions: vec![],
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let note = FootnoteText {
+            style: Style::new_default(),
+            words: vec![word(30.0), word(40.0), word(20.0), word(50.0)],
+            space: word(5.0),
+        };
+        let extent = |_: &Style, position: &TextPosition| {
+            (
+                position.width.0 as f32,
+                position.ascent().0 as f32 * 10.0,
+                position.depth.0 as f32 * 10.0,
+            )
+        };
+
+        let lines = break_lines(&note, 100.0, extent);
+        let offsets = lines
+            .iter()
+            .map(|line| line.words.iter().map(|(x, _)| *x).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![vec![0.0, 35.0, 80.0], vec![0.0]]);
+        assert!((lines[0].ascent - 14.0).abs() < 1e-4);
+        assert!((lines[0].height - 16.0).abs() < 1e-4);
+
+        // a word wider than the page is not broken
+        assert_eq!(break_lines(&note, 10.0, extent).len(), 4);
+    }
+
+    #[test]
+    fn footnotes() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let mut layout = marks.footnote(LayoutBox::new(Axis::Vertical), "", Style::new_default());
+        let size = Size::fixed(Mm(5.0), Mm(5.0));
+        layout.measure(&mut rctx, size.clone()).unwrap();
+        layout
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(100.0)), size)
+            .unwrap();
+        layout.render(&mut rctx).unwrap();
+
+        // nothing is queued for a note without words
+        assert!(marks.take().is_empty());
+        assert!(rctx.save_to_bytes().is_ok());
+    }
+
+    #[test]
+    fn notes() {
+        let fonts = new_font_cache();
+        fonts
+            .add(
+                "LatoReg",
+                include_bytes!("../../tests/Lato-Regular.ttf").as_ref(),
+            )
+            .unwrap();
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_marks(marks.clone());
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let mut layout = marks.footnote(
+            LayoutBox::new(Axis::Vertical),
+            "Note at the foot of the page",
+            style.clone(),
+        );
+        let size = Size::fixed(Mm(5.0), Mm(5.0));
+        layout.measure(&mut rctx, size.clone()).unwrap();
+        layout
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(100.0)), size)
+            .unwrap();
+        let text = rctx.typeset(&style, "Body").unwrap();
+        rctx.complete_fonts().unwrap();
+
+        layout.render(&mut rctx).unwrap();
+        // the text would fit above the bottom margin, but not above the note
+        rctx.text(&Offset::new(Mm(0.0), Mm(270.0)), &style, &text, false);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        // baselines of texts, the first move of a text section goes to its origin
+        let texts = |page: u32| {
+            let content = document.get_and_decode_page_content(pages[&page]).unwrap();
+            let mut origin = false;
+            let mut baselines = vec![];
+            for operation in content.operations.iter() {
+                match operation.operator.as_str() {
+                    "BT" => origin = true,
+                    "Td" if origin => {
+                        baselines.push(operation.operands[1].as_float().unwrap());
+                        origin = false;
+                    }
+                    _ => (),
+                }
+            }
+            baselines
+        };
+        // words of the note stand just above the bottom margin of 28.35 pt
+        let notes = texts(1);
+        assert!(!notes.is_empty());
+        assert!(notes.iter().all(|y| *y > 28.35 && *y < 40.0));
+        assert_eq!(texts(2).len(), 1);
+    }
+}
@@ -0,0 +1,228 @@
+use printpdf::{
+    Rect,
+    lopdf::{
+        Dictionary, Object, Stream,
+        content::{Content, Operation},
+    },
+};
+
+use super::annotations::{annotation, text_string};
+
+const FIELD_READ_ONLY: i64 = 1;
+const FIELD_REQUIRED: i64 = 1 << 1;
+const TEXT_MULTILINE: i64 = 1 << 12;
+
+#[derive(Clone, Debug, PartialEq)]
+enum FormFieldKind {
+    Text {
+        value: String,
+        multiline: bool,
+        max_length: Option<usize>,
+    },
+    CheckBox {
+        checked: bool,
+    },
+    Signature,
+}
+
+// interactive field of the document form, filled in a viewer
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormField {
+    name: String,
+    kind: FormFieldKind,
+    tooltip: Option<String>,
+    required: bool,
+    read_only: bool,
+    font_size: f32,
+}
+
+impl FormField {
+    pub fn text(name: impl Into<String>) -> Self {
+        Self::new(
+            name,
+            FormFieldKind::Text {
+                value: String::new(),
+                multiline: false,
+                max_length: None,
+            },
+        )
+    }
+
+    pub fn check_box(name: impl Into<String>) -> Self {
+        Self::new(name, FormFieldKind::CheckBox { checked: false })
+    }
+
+    pub fn signature(name: impl Into<String>) -> Self {
+        Self::new(name, FormFieldKind::Signature)
+    }
+
+    fn new(name: impl Into<String>, kind: FormFieldKind) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            tooltip: None,
+            required: false,
+            read_only: false,
+            font_size: 10.0,
+        }
+    }
+
+    // ignored by other than text fields
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        if let FormFieldKind::Text { value: current, .. } = &mut self.kind {
+            *current = value.into();
+        }
+        self
+    }
+
+    pub fn with_multiline(mut self, multiline: bool) -> Self {
+        if let FormFieldKind::Text {
+            multiline: current, ..
+        } = &mut self.kind
+        {
+            *current = multiline;
+        }
+        self
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        if let FormFieldKind::Text {
+            max_length: current,
+            ..
+        } = &mut self.kind
+        {
+            *current = Some(max_length);
+        }
+        self
+    }
+
+    // ignored by other than check boxes
+    pub fn with_checked(mut self, checked: bool) -> Self {
+        if let FormFieldKind::CheckBox { checked: current } = &mut self.kind {
+            *current = checked;
+        }
+        self
+    }
+
+    pub fn with_tooltip(mut self, tooltip: impl Into<String>) -> Self {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+
+    pub fn with_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    // in pt, 0.0 lets the viewer fit the text into the field
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size.max(0.0);
+        self
+    }
+
+    // field merged with its widget annotation
+    pub(crate) fn annotation(&self, rect: &Rect) -> Dictionary {
+        let mut widget = annotation("Widget", rect);
+        widget.set("T", text_string(&self.name));
+        if let Some(tooltip) = &self.tooltip {
+            widget.set("TU", text_string(tooltip));
+        }
+        widget.set("F", 4);
+
+        let mut flags = 0;
+        if self.read_only {
+            flags |= FIELD_READ_ONLY;
+        }
+        if self.required {
+            flags |= FIELD_REQUIRED;
+        }
+
+        let mut characteristics = Dictionary::new();
+        characteristics.set("BC", vec![Object::Real(0.5); 3]);
+
+        match &self.kind {
+            FormFieldKind::Text {
+                value,
+                multiline,
+                max_length,
+            } => {
+                widget.set("FT", Object::Name(b"Tx".to_vec()));
+                widget.set("V", text_string(value));
+                widget.set("DA", default_appearance(self.font_size));
+                if *multiline {
+                    flags |= TEXT_MULTILINE;
+                }
+                if let Some(max_length) = max_length {
+                    widget.set("MaxLen", *max_length as i64);
+                }
+            }
+            FormFieldKind::CheckBox { checked } => {
+                let state = Object::Name(if *checked {
+                    b"Yes".to_vec()
+                } else {
+                    b"Off".to_vec()
+                });
+                widget.set("FT", Object::Name(b"Btn".to_vec()));
+                widget.set("V", state.clone());
+                widget.set("AS", state);
+                widget.set("AP", check_box_appearance(rect));
+            }
+            FormFieldKind::Signature => {
+                widget.set("FT", Object::Name(b"Sig".to_vec()));
+            }
+        }
+
+        widget.set("Ff", flags);
+        widget.set("MK", characteristics);
+        widget
+    }
+}
+
+// Helvetica is added to the form resources, viewers use it to generate text appearances
+pub(crate) fn default_appearance(font_size: f32) -> Object {
+    Object::string_literal(format!("/Helv {font_size} Tf 0 g"))
+}
+
+// both states, with a check mark drawn by lines, so it does not need any font
+fn check_box_appearance(rect: &Rect) -> Dictionary {
+    let width = rect.ur.x.0 - rect.ll.x.0;
+    let height = rect.ur.y.0 - rect.ll.y.0;
+    let point = |x: f32, y: f32| vec![Object::Real(x * width), Object::Real(y * height)];
+
+    let operations = vec![
+        Operation::new("w", vec![Object::Real(width.min(height) / 10.0)]),
+        Operation::new("J", vec![Object::Integer(1)]),
+        Operation::new("j", vec![Object::Integer(1)]),
+        Operation::new("m", point(0.2, 0.5)),
+        Operation::new("l", point(0.4, 0.25)),
+        Operation::new("l", point(0.8, 0.8)),
+        Operation::new("S", vec![]),
+    ];
+    let appearance = |operations: Vec<Operation>| {
+        let mut form = Dictionary::new();
+        form.set("Type", Object::Name(b"XObject".to_vec()));
+        form.set("Subtype", Object::Name(b"Form".to_vec()));
+        form.set(
+            "BBox",
+            vec![0.0, 0.0, width, height]
+                .into_iter()
+                .map(Object::Real)
+                .collect::<Vec<_>>(),
+        );
+        let content = Content { operations }.encode().unwrap_or_default();
+        Object::Stream(Stream::new(form, content))
+    };
+
+    let mut states = Dictionary::new();
+    states.set("Yes", appearance(operations));
+    states.set("Off", appearance(vec![]));
+
+    let mut appearances = Dictionary::new();
+    appearances.set("N", states);
+    appearances
+}
@@ -0,0 +1,175 @@
+use layout::{Rgba, position::Offset, unit::Unit};
+use printpdf::lopdf::{Dictionary, Object};
+
+use super::{ColorModel, from_unit};
+
+#[derive(Clone, Debug, PartialEq)]
+enum GradientGeometry {
+    Linear { from: Offset, to: Offset },
+    Radial { center: Offset, radius: Unit },
+}
+
+// Geometry is in content coordinates, same as offsets of laid out elements
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    geometry: GradientGeometry,
+    stops: Vec<(f32, Rgba)>,
+}
+
+impl Gradient {
+    pub fn linear(from: Offset, to: Offset) -> Self {
+        Self {
+            geometry: GradientGeometry::Linear { from, to },
+            stops: vec![],
+        }
+    }
+
+    pub fn radial(center: Offset, radius: impl Into<Unit>) -> Self {
+        Self {
+            geometry: GradientGeometry::Radial {
+                center,
+                radius: radius.into(),
+            },
+            stops: vec![],
+        }
+    }
+
+    // position goes from 0.0 at the start to 1.0 at the end of the gradient,
+    // alpha of the color is ignored
+    pub fn with_stop(mut self, position: f32, color: Rgba) -> Self {
+        let position = position.clamp(0.0, 1.0);
+        let index = self
+            .stops
+            .iter()
+            .position(|(stop, _)| *stop > position)
+            .unwrap_or(self.stops.len());
+        self.stops.insert(index, (position, color));
+        self
+    }
+
+    pub(crate) fn shading<F>(&self, page_point: F, color_model: ColorModel) -> Option<Dictionary>
+    where
+        F: Fn(&Offset) -> (f32, f32),
+    {
+        let function = self.function(color_model)?;
+
+        let (shading_type, coordinates) = match &self.geometry {
+            GradientGeometry::Linear { from, to } => {
+                let (x0, y0) = page_point(from);
+                let (x1, y1) = page_point(to);
+                (2, vec![x0, y0, x1, y1])
+            }
+            GradientGeometry::Radial { center, radius } => {
+                let (x, y) = page_point(center);
+                let radius = from_unit(*radius).into_pt().0;
+                (3, vec![x, y, 0.0, x, y, radius])
+            }
+        };
+
+        let mut shading = Dictionary::new();
+        shading.set("ShadingType", shading_type);
+        shading.set(
+            "ColorSpace",
+            Object::Name(color_model.color_space().to_vec()),
+        );
+        shading.set("Coords", reals(coordinates));
+        shading.set("Function", function);
+        shading.set("Extend", vec![Object::Boolean(true), Object::Boolean(true)]);
+        Some(shading)
+    }
+
+    // interpolation between neighbouring stops, stitched together if there are more of them
+    fn function(&self, color_model: ColorModel) -> Option<Dictionary> {
+        let (first, last) = (self.stops.first()?, self.stops.last()?);
+
+        let mut stops = self
+            .stops
+            .iter()
+            .map(|(position, color)| (*position, color))
+            .collect::<Vec<_>>();
+        if first.0 > 0.0 {
+            stops.insert(0, (0.0, &first.1));
+        }
+        if last.0 < 1.0 {
+            stops.push((1.0, &last.1));
+        }
+
+        let mut functions = stops
+            .windows(2)
+            .map(|pair| interpolation(pair[0].1, pair[1].1, color_model))
+            .collect::<Vec<_>>();
+        if functions.len() == 1 {
+            return functions.pop();
+        }
+
+        let bounds = stops[1..stops.len() - 1]
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        let encode = functions.iter().flat_map(|_| [0.0, 1.0]).collect();
+
+        let mut stitching = Dictionary::new();
+        stitching.set("FunctionType", 3);
+        stitching.set("Domain", reals(vec![0.0, 1.0]));
+        stitching.set(
+            "Functions",
+            functions.into_iter().map(Object::from).collect::<Vec<_>>(),
+        );
+        stitching.set("Bounds", reals(bounds));
+        stitching.set("Encode", reals(encode));
+        Some(stitching)
+    }
+}
+
+fn interpolation(from: &Rgba, to: &Rgba, color_model: ColorModel) -> Dictionary {
+    let components = |color: &Rgba| reals(color_model.components(color));
+
+    let mut function = Dictionary::new();
+    function.set("FunctionType", 2);
+    function.set("Domain", reals(vec![0.0, 1.0]));
+    function.set("C0", components(from));
+    function.set("C1", components(to));
+    function.set("N", 1);
+    function
+}
+
+fn reals(values: Vec<f32>) -> Object {
+    Object::Array(values.into_iter().map(Object::Real).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{Rgba, position::Offset, unit::Mm};
+
+    use super::{ColorModel, Gradient};
+
+    #[test]
+    fn stops() {
+        let gradient = Gradient::linear(
+            Offset::new(Mm(0.0), Mm(0.0)),
+            Offset::new(Mm(100.0), Mm(0.0)),
+        )
+        .with_stop(1.0, Rgba::black())
+        .with_stop(0.0, Rgba::from((255, 255, 255, 1.0)));
+
+        let shading = gradient.shading(|_| (0.0, 0.0), ColorModel::Rgb).unwrap();
+        let function = shading.get(b"Function").unwrap().as_dict().unwrap();
+        assert_eq!(function.get(b"FunctionType").unwrap().as_i64().unwrap(), 2);
+        let c1 = function.get(b"C1").unwrap().as_array().unwrap();
+        assert!(
+            c1.iter()
+                .all(|component| component.as_f32().unwrap() == 0.0)
+        );
+
+        let gradient = gradient.with_stop(0.25, Rgba::from((255, 0, 0, 1.0)));
+        let shading = gradient.shading(|_| (0.0, 0.0), ColorModel::Rgb).unwrap();
+        let function = shading.get(b"Function").unwrap().as_dict().unwrap();
+        assert_eq!(function.get(b"FunctionType").unwrap().as_i64().unwrap(), 3);
+        let bounds = function.get(b"Bounds").unwrap().as_array().unwrap();
+        assert_eq!(bounds.len(), 1);
+        assert_eq!(bounds[0].as_f32().unwrap(), 0.25);
+
+        let empty = Gradient::radial(Offset::new(Mm(0.0), Mm(0.0)), Mm(10.0));
+        assert!(empty.shading(|_| (0.0, 0.0), ColorModel::Rgb).is_none());
+    }
+}
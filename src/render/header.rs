@@ -0,0 +1,93 @@
+use std::{cell::RefCell, rc::Rc};
+
+use layout::{
+    Layout,
+    position::{Offset, Size},
+};
+
+use super::{Mark, Marks};
+
+pub(crate) type SharedLayout = Rc<RefCell<Box<dyn Layout>>>;
+
+// header laid out again at the top of every page the content it heads continues on, at
+// the offset and of the size it was laid out to first
+#[derive(Clone, Debug)]
+pub(crate) struct RepeatedHeader {
+    pub(crate) layout: SharedLayout,
+    pub(crate) offset: Offset,
+    pub(crate) size: Size,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum HeaderMark {
+    Begin(RepeatedHeader),
+    End,
+}
+
+// headers layouts repeat, e.g. rows of table headers, are taken over by the render
+// context, which replays the innermost one when it starts a new page
+impl Marks {
+    // the header is measured, it is repeated until the end
+    pub(crate) fn begin_header(&self, layout: SharedLayout, offset: Offset, size: Size) {
+        self.push(Mark::Header(HeaderMark::Begin(RepeatedHeader {
+            layout,
+            offset,
+            size,
+        })));
+    }
+
+    pub(crate) fn end_header(&self) {
+        self.push(Mark::Header(HeaderMark::End));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use layout::{
+        Axis, Layout, LayoutBox,
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::PdfDocument;
+
+    use crate::{Marks, RenderContext, new_font_cache};
+
+    use super::{HeaderMark, Mark};
+
+    #[test]
+    fn headers() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let header: Box<dyn Layout> = Box::new(LayoutBox::new(Axis::Vertical));
+        let size = Size::fixed(Mm(190.0), Mm(10.0));
+        marks.begin_header(Rc::new(RefCell::new(header)), Offset::zero(), size);
+        marks.end_header();
+        let queued = marks.take();
+        assert!(matches!(
+            queued.as_slice(),
+            [
+                Mark::Header(HeaderMark::Begin(_)),
+                Mark::Header(HeaderMark::End)
+            ]
+        ));
+
+        queued.into_iter().for_each(|mark| marks.push(mark));
+        layout::RenderContext::check_page_break(&mut rctx, Mm(10.0).into(), Mm(0.0).into(), false);
+        assert!(marks.take().is_empty());
+        assert!(rctx.save_to_bytes().is_ok());
+    }
+}
@@ -0,0 +1,627 @@
+use layout::LayoutBox;
+
+use super::{
+    RichTextStyles,
+    rich_text::{Block, Cells, Lines, Run, push_run},
+};
+
+// HTML subset of paragraphs, headings, line breaks, lists, preformatted text, tables,
+// bold, italic and underlined text and links converted into layout elements, e.g.
+// descriptions stored by a CMS; other tags are dropped, their text is kept
+pub struct HtmlConverter {
+    styles: RichTextStyles,
+}
+
+impl HtmlConverter {
+    // links and underlines need the marks of the renderer set to the styles
+    pub fn new(styles: RichTextStyles) -> Self {
+        Self { styles }
+    }
+
+    pub fn convert(&self, html: &str) -> LayoutBox {
+        self.styles.layout(&parse(html))
+    }
+}
+
+// cells of a table row and whether all of them are header cells
+struct HtmlRow {
+    cells: Cells,
+    header: bool,
+}
+
+#[derive(Default)]
+struct HtmlTable {
+    header: Cells,
+    rows: Vec<Cells>,
+    row: Option<HtmlRow>,
+    // the cell open is a header one
+    cell: Option<bool>,
+}
+
+#[derive(Default)]
+struct HtmlParser {
+    // blocks of the document and of list items being parsed
+    containers: Vec<Vec<Block>>,
+    // items of lists, the last one is open until the next item or the end of the list
+    lists: Vec<(Option<u64>, Vec<Vec<Block>>, bool)>,
+    lines: Lines,
+    line: Vec<Run>,
+    strong: usize,
+    emphasis: usize,
+    underline: usize,
+    // anchors without href are kept to be closed
+    links: Vec<Option<String>>,
+    // tables being parsed, the last one is the innermost
+    tables: Vec<HtmlTable>,
+    // text of the preformatted element open, kept as it is
+    preformatted: Option<String>,
+    // content of script and style elements
+    skipped: usize,
+}
+
+impl HtmlParser {
+    fn push_text(&mut self, text: &str) {
+        if self.skipped > 0 {
+            return;
+        }
+        if let Some(preformatted) = self.preformatted.as_mut() {
+            preformatted.push_str(&decode(text));
+            return;
+        }
+        // whitespace is collapsed, other than non-breaking spaces
+        let mut collapsed = String::with_capacity(text.len());
+        for char in decode(text).chars() {
+            match char.is_ascii_whitespace() {
+                true if !collapsed.ends_with(' ') => collapsed.push(' '),
+                true => {}
+                false => collapsed.push(char),
+            }
+        }
+        let line_start = self.line.last().is_none_or(|last| last.text.ends_with(' '));
+        let text = match line_start {
+            true => collapsed.trim_start_matches(' '),
+            false => &collapsed,
+        };
+        if text.is_empty() {
+            return;
+        }
+        let run = Run {
+            text: text.into(),
+            emphasis: self.emphasis > 0,
+            strong: self.strong > 0,
+            underline: self.underline > 0,
+            link: self.links.iter().rev().flatten().next().cloned(),
+            ..Run::default()
+        };
+        push_run(&mut self.line, run);
+    }
+
+    fn break_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.lines.push(line);
+    }
+
+    // trailing spaces and empty lines are dropped
+    fn take_lines(&mut self) -> Lines {
+        if !self.line.is_empty() {
+            self.break_line();
+        }
+        let mut lines = std::mem::take(&mut self.lines);
+        for line in lines.iter_mut() {
+            if let Some(last) = line.last_mut() {
+                last.text.truncate(last.text.trim_end_matches(' ').len());
+            }
+            line.retain(|run| !run.text.is_empty());
+        }
+        while lines.last().is_some_and(Vec::is_empty) {
+            lines.pop();
+        }
+        lines
+    }
+
+    fn push_paragraph(&mut self) {
+        // paragraphs of a table cell are its lines
+        if self.in_cell() {
+            if !self.line.is_empty() {
+                self.break_line();
+            }
+            return;
+        }
+        let lines = self.take_lines();
+        if !lines.is_empty()
+            && let Some(container) = self.containers.last_mut()
+        {
+            container.push(Block::Paragraph(lines));
+        }
+    }
+
+    fn push_block(&mut self, block: Block) {
+        self.push_paragraph();
+        if let Some(container) = self.containers.last_mut() {
+            container.push(block);
+        }
+    }
+
+    fn end_item(&mut self) {
+        if let Some((_, _, open)) = self.lists.last_mut()
+            && *open
+        {
+            *open = false;
+            self.push_paragraph();
+            let item = self.containers.pop().unwrap_or_default();
+            if let Some((_, items, _)) = self.lists.last_mut() {
+                items.push(item);
+            }
+        }
+    }
+
+    fn end_list(&mut self) {
+        self.end_item();
+        if let Some((start, items, _)) = self.lists.pop() {
+            self.push_block(Block::List(start, items));
+        }
+    }
+
+    fn in_cell(&self) -> bool {
+        self.tables.last().is_some_and(|table| table.cell.is_some())
+    }
+
+    fn push_heading(&mut self, level: usize) {
+        if self.in_cell() {
+            return self.push_paragraph();
+        }
+        let lines = self.take_lines();
+        if !lines.is_empty()
+            && let Some(container) = self.containers.last_mut()
+        {
+            container.push(Block::Heading(level, lines));
+        }
+    }
+
+    // a line break after the start tag is not the content
+    fn end_preformatted(&mut self) {
+        if let Some(text) = self.preformatted.take() {
+            let text = text.strip_prefix('\n').unwrap_or(&text);
+            let text = text.strip_suffix('\n').unwrap_or(text);
+            self.push_block(Block::Preformatted(
+                text.split('\n').map(str::to_string).collect(),
+            ));
+        }
+    }
+
+    // lines of the cell are joined into one
+    fn end_cell(&mut self) {
+        let Some(header) = self.tables.last_mut().and_then(|table| table.cell.take()) else {
+            return;
+        };
+        let mut cell = vec![];
+        for (index, line) in self.take_lines().into_iter().enumerate() {
+            if index > 0 {
+                push_run(
+                    &mut cell,
+                    Run {
+                        text: " ".into(),
+                        ..Run::default()
+                    },
+                );
+            }
+            line.into_iter().for_each(|run| push_run(&mut cell, run));
+        }
+        if let Some(row) = self.tables.last_mut().and_then(|table| table.row.as_mut()) {
+            row.header &= header;
+            row.cells.push(cell);
+        }
+    }
+
+    // the first row of header cells only is the header of the table
+    fn end_row(&mut self) {
+        self.end_cell();
+        let Some(table) = self.tables.last_mut() else {
+            return;
+        };
+        if let Some(row) = table.row.take()
+            && !row.cells.is_empty()
+        {
+            match row.header && table.header.is_empty() && table.rows.is_empty() {
+                true => table.header = row.cells,
+                false => table.rows.push(row.cells),
+            }
+        }
+    }
+
+    fn start_cell(&mut self, header: bool) {
+        self.end_cell();
+        self.push_paragraph();
+        if let Some(table) = self.tables.last_mut() {
+            table.row.get_or_insert(HtmlRow {
+                cells: vec![],
+                header: true,
+            });
+            table.cell = Some(header);
+        }
+    }
+
+    fn end_table(&mut self) {
+        self.end_row();
+        if let Some(table) = self.tables.pop() {
+            self.push_block(Block::Table(table.header, table.rows));
+        }
+    }
+
+    fn tag(&mut self, tag: &str) {
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name_end = tag
+            .find(|char: char| !char.is_ascii_alphanumeric())
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        match closing {
+            true => self.end(&name),
+            false => self.start(&name, &tag[name_end..]),
+        }
+    }
+
+    fn start(&mut self, name: &str, attributes: &str) {
+        if self.preformatted.is_some() {
+            return;
+        }
+        match name {
+            "b" | "strong" => self.strong += 1,
+            "i" | "em" => self.emphasis += 1,
+            "u" => self.underline += 1,
+            "a" => self.links.push(attribute(attributes, "href")),
+            "br" => self.break_line(),
+            "p" | "div" => self.push_paragraph(),
+            "ul" | "ol" => {
+                self.push_paragraph();
+                let start = (name == "ol").then(|| {
+                    attribute(attributes, "start")
+                        .and_then(|start| start.trim().parse().ok())
+                        .unwrap_or(1)
+                });
+                self.lists.push((start, vec![], false));
+            }
+            "li" => {
+                self.end_item();
+                match self.lists.last_mut() {
+                    Some((_, _, open)) => {
+                        *open = true;
+                        self.containers.push(vec![]);
+                    }
+                    None => self.push_paragraph(),
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.push_paragraph(),
+            "pre" => {
+                self.push_paragraph();
+                self.preformatted = Some(String::new());
+            }
+            "table" => {
+                self.push_paragraph();
+                self.tables.push(HtmlTable::default());
+            }
+            "tr" => self.end_row(),
+            "th" | "td" => self.start_cell(name == "th"),
+            "script" | "style" => self.skipped += 1,
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.preformatted.is_some() {
+            if name == "pre" {
+                self.end_preformatted();
+            }
+            return;
+        }
+        match name {
+            "b" | "strong" => self.strong = self.strong.saturating_sub(1),
+            "i" | "em" => self.emphasis = self.emphasis.saturating_sub(1),
+            "u" => self.underline = self.underline.saturating_sub(1),
+            "a" => {
+                self.links.pop();
+            }
+            "p" | "div" => self.push_paragraph(),
+            "ul" | "ol" => self.end_list(),
+            "li" => self.end_item(),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.push_heading(usize::from(name.as_bytes()[1] - b'0'))
+            }
+            "table" => self.end_table(),
+            "tr" => self.end_row(),
+            "th" | "td" => self.end_cell(),
+            "script" | "style" => self.skipped = self.skipped.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn parse(html: &str) -> Vec<Block> {
+    let mut parser = HtmlParser {
+        containers: vec![vec![]],
+        ..HtmlParser::default()
+    };
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        parser.push_text(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        // less-than sign of the text
+        if !rest[1..].starts_with(|char: char| char.is_ascii_alphabetic() || char == '/') {
+            parser.push_text("<");
+            rest = &rest[1..];
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        parser.tag(&rest[1..end]);
+        rest = &rest[end + 1..];
+    }
+    parser.push_text(rest);
+
+    // elements left open are closed
+    parser.end_preformatted();
+    while !parser.tables.is_empty() {
+        parser.end_table();
+    }
+    while !parser.lists.is_empty() {
+        parser.end_list();
+    }
+    parser.push_paragraph();
+    parser.containers.pop().unwrap_or_default()
+}
+
+// value of the attribute, quoted or not
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|char: char| char.is_ascii_whitespace() || char == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|char: char| char == '=' || char.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, next) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let value = &value[1..];
+                        let end = value.find(quote).unwrap_or(value.len());
+                        (&value[..end], value.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = value
+                            .find(|char: char| char.is_ascii_whitespace())
+                            .unwrap_or(value.len());
+                        value.split_at(end)
+                    }
+                };
+                rest = next;
+                Some(value)
+            }
+            None => None,
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return value.map(decode);
+        }
+    }
+}
+
+// named entities of the markup and numeric references, unknown ones are kept as they are
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .map(|end| &rest[1..end + 1])
+            .filter(|entity| entity.len() <= 8);
+        let char = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            entity => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, char) {
+            (Some(entity), Some(char)) => {
+                decoded.push(char);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, StyleBuilder,
+        position::{Quad, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::lopdf::{Document, Object};
+
+    use crate::{HtmlConverter, Renderer, RichTextStyles, new_font_cache, render::rich_text::Run};
+
+    use super::{Block, attribute, decode, parse};
+
+    fn plain(text: &str) -> Run {
+        Run {
+            text: text.into(),
+            ..Run::default()
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let blocks = parse(
+            "Intro <b>bold <i>both</i></b>\n  and <u>under</u>lined<br>next\
+             <p>See <a href=\"https://example.com/?a=1&amp;b=2\">our site</a>.</p>\
+             <ul><li>one<li>two <em>2</em></ul>\
+             <ol start=\"3\"><li><p>three</p></li></ol><!-- note <b> -->1 &lt; 2&nbsp;&#x41;",
+        );
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(vec![
+                    vec![
+                        plain("Intro "),
+                        Run {
+                            strong: true,
+                            ..plain("bold ")
+                        },
+                        Run {
+                            strong: true,
+                            emphasis: true,
+                            ..plain("both")
+                        },
+                        plain(" and "),
+                        Run {
+                            underline: true,
+                            ..plain("under")
+                        },
+                        plain("lined"),
+                    ],
+                    vec![plain("next")],
+                ]),
+                Block::Paragraph(vec![vec![
+                    plain("See "),
+                    Run {
+                        link: Some("https://example.com/?a=1&b=2".into()),
+                        ..plain("our site")
+                    },
+                    plain("."),
+                ]]),
+                Block::List(
+                    None,
+                    vec![
+                        vec![Block::Paragraph(vec![vec![plain("one")]])],
+                        vec![Block::Paragraph(vec![vec![
+                            plain("two "),
+                            Run {
+                                emphasis: true,
+                                ..plain("2")
+                            },
+                        ]])],
+                    ]
+                ),
+                Block::List(
+                    Some(3),
+                    vec![vec![Block::Paragraph(vec![vec![plain("three")]])]]
+                ),
+                Block::Paragraph(vec![vec![plain("1 < 2\u{a0}A")]]),
+            ]
+        );
+
+        assert_eq!(parse("  <p> </p><script>x</script>"), vec![]);
+    }
+
+    #[test]
+    fn elements() {
+        let blocks = parse(
+            "<h2>Price <i>list</i></h2><pre>\nlet a = 1;\n  <b>a</b> &lt; 2\n</pre>\
+             <table><tr><th>Item</th><th>Price</th></tr>\
+             <tr><td><p>Green</p><p>tea</p><td>3</table>",
+        );
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading(
+                    2,
+                    vec![vec![
+                        plain("Price "),
+                        Run {
+                            emphasis: true,
+                            ..plain("list")
+                        },
+                    ]]
+                ),
+                Block::Preformatted(vec!["let a = 1;".into(), "  a < 2".into()]),
+                Block::Table(
+                    vec![vec![plain("Item")], vec![plain("Price")]],
+                    vec![vec![vec![plain("Green tea")], vec![plain("3")]]]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(attribute(" id=x HREF='a b' /", "href"), Some("a b".into()));
+        assert_eq!(attribute(" checked href=/x", "href"), Some("/x".into()));
+        assert_eq!(attribute(" checked", "href"), None);
+        assert_eq!(decode("&amp;&unknown;&#65;&"), "&&unknown;A&");
+    }
+
+    #[test]
+    fn html() {
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+        let renderer = Renderer::new(
+            "Html",
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts.clone(),
+        );
+
+        let style = StyleBuilder::default().with_font(Font::new("LatoReg", Pt(10.0), None));
+        let converter =
+            HtmlConverter::new(RichTextStyles::new(style.clone()).with_marks(renderer.marks()));
+        let layout = converter.convert("<p>Visit <a href=\"https://example.com\">us</a></p>");
+        let pdf = renderer.render_layout(Box::new(layout)).unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let uris = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .filter_map(|dict| dict.get(b"A").ok())
+            .filter_map(|action| action.as_dict().ok())
+            .filter_map(|action| action.get(b"URI").ok())
+            .filter_map(|uri| match uri {
+                Object::String(uri, _) => Some(uri.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(uris, vec![b"https://example.com".to_vec()]);
+
+        // the link would be lost without the marks
+        let renderer = Renderer::new(
+            "Html",
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        let converter = HtmlConverter::new(RichTextStyles::new(style));
+        let layout = converter.convert("<p>Visit <a href=\"https://example.com\">us</a></p>");
+        assert!(renderer.render_layout(Box::new(layout)).is_err());
+    }
+}
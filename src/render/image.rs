@@ -0,0 +1,492 @@
+use layout::Error;
+use printpdf::{ColorBits, ColorSpace, ImageFilter, ImageXObject, Px, lopdf::Object};
+
+const MM_PER_INCH: f32 = 25.4;
+const DEFAULT_DPI: f32 = 300.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageFit {
+    #[default]
+    Contain,
+    Cover,
+    Stretch,
+    ActualDpi,
+}
+
+impl ImageFit {
+    pub(crate) fn place(
+        &self,
+        box_width: f32,
+        box_height: f32,
+        natural_width: f32,
+        natural_height: f32,
+    ) -> ImagePlacement {
+        let (width, height) = match self {
+            ImageFit::Stretch => (box_width, box_height),
+            ImageFit::ActualDpi => (natural_width, natural_height),
+            ImageFit::Contain | ImageFit::Cover => {
+                let h_scale = box_width / natural_width;
+                let v_scale = box_height / natural_height;
+                let scale = if *self == ImageFit::Contain {
+                    h_scale.min(v_scale)
+                } else {
+                    h_scale.max(v_scale)
+                };
+                (natural_width * scale, natural_height * scale)
+            }
+        };
+
+        let (x, y) = match self {
+            ImageFit::ActualDpi => (0.0, 0.0),
+            _ => ((box_width - width) / 2.0, (box_height - height) / 2.0),
+        };
+
+        ImagePlacement {
+            x,
+            y,
+            width,
+            height,
+            clip: width > box_width || height > box_height,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+}
+
+impl ImageColorSpace {
+    fn components(&self) -> usize {
+        match self {
+            ImageColorSpace::Gray => 1,
+            ImageColorSpace::Rgb => 3,
+            ImageColorSpace::Cmyk => 4,
+        }
+    }
+}
+
+impl From<ImageColorSpace> for ColorSpace {
+    fn from(color_space: ImageColorSpace) -> Self {
+        match color_space {
+            ImageColorSpace::Gray => ColorSpace::Greyscale,
+            ImageColorSpace::Rgb => ColorSpace::Rgb,
+            ImageColorSpace::Cmyk => ColorSpace::Cmyk,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageEncoding {
+    Raw,
+    Dct,
+}
+
+#[derive(Clone, Debug)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    color_space: ImageColorSpace,
+    encoding: ImageEncoding,
+    data: Vec<u8>,
+    fit: ImageFit,
+    dpi: f32,
+    max_dpi: Option<f32>,
+    inverted: bool,
+}
+
+impl Image {
+    // 8 bits per component, rows top to bottom, no padding
+    pub fn from_raw(
+        width: usize,
+        height: usize,
+        color_space: ImageColorSpace,
+        data: Vec<u8>,
+    ) -> Result<Self, Error> {
+        if width == 0 || height == 0 || data.len() != width * height * color_space.components() {
+            return Err(Error::PdfWrite(
+                format!("Image data do not match {width}x{height} {color_space:?}").into(),
+            ));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            color_space,
+            encoding: ImageEncoding::Raw,
+            data,
+            fit: ImageFit::default(),
+            dpi: DEFAULT_DPI,
+            max_dpi: None,
+            inverted: false,
+        })
+    }
+
+    // compressed stream is embedded as is (DCTDecode), it is never re-encoded
+    pub fn from_jpeg(data: Vec<u8>) -> Result<Self, Error> {
+        let header = jpeg_header(&data)
+            .ok_or_else(|| Error::PdfWrite("Image is not a baseline or progressive JPEG".into()))?;
+
+        let color_space = match header.components {
+            1 => ImageColorSpace::Gray,
+            3 => ImageColorSpace::Rgb,
+            4 => ImageColorSpace::Cmyk,
+            _ => {
+                return Err(Error::PdfWrite(
+                    format!(
+                        "JPEG with {} components is not supported",
+                        header.components
+                    )
+                    .into(),
+                ));
+            }
+        };
+
+        Ok(Self {
+            width: header.width,
+            height: header.height,
+            color_space,
+            encoding: ImageEncoding::Dct,
+            data,
+            fit: ImageFit::default(),
+            dpi: DEFAULT_DPI,
+            max_dpi: None,
+            // Adobe applications write CMYK JPEGs with inverted samples
+            inverted: header.adobe && color_space == ImageColorSpace::Cmyk,
+        })
+    }
+
+    pub fn with_fit(mut self, fit: ImageFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    // a resolution that is not positive falls back to the default
+    pub fn with_dpi(mut self, dpi: f32) -> Self {
+        self.dpi = if valid_dpi(dpi) {
+            dpi
+        } else {
+            tracing::warn!(
+                "Image resolution {} dpi is invalid, {} dpi used",
+                dpi,
+                DEFAULT_DPI
+            );
+            DEFAULT_DPI
+        };
+        self
+    }
+
+    // the image is not downsampled by a limit that is not positive
+    pub fn with_max_dpi(mut self, max_dpi: f32) -> Self {
+        self.max_dpi = if valid_dpi(max_dpi) {
+            Some(max_dpi)
+        } else {
+            tracing::warn!("Image resolution limit {} dpi is invalid, ignored", max_dpi);
+            None
+        };
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn fit(&self) -> ImageFit {
+        self.fit
+    }
+
+    fn natural_size(&self) -> (f32, f32) {
+        (
+            self.width as f32 / self.dpi * MM_PER_INCH,
+            self.height as f32 / self.dpi * MM_PER_INCH,
+        )
+    }
+
+    pub(crate) fn place(&self, box_width: f32, box_height: f32) -> ImagePlacement {
+        let (natural_width, natural_height) = self.natural_size();
+        self.fit
+            .place(box_width, box_height, natural_width, natural_height)
+    }
+
+    pub(crate) fn to_xobject(&self, placement: &ImagePlacement) -> ImageXObject {
+        let (width, height, data) = match self.max_dpi {
+            Some(_) if self.encoding == ImageEncoding::Dct => {
+                tracing::debug!("JPEG image is not downsampled, it is embedded as is");
+                (self.width, self.height, self.data.clone())
+            }
+            Some(max_dpi) => {
+                let target_width = pixels_at(placement.width, max_dpi).min(self.width);
+                let target_height = pixels_at(placement.height, max_dpi).min(self.height);
+                if target_width < self.width || target_height < self.height {
+                    let data = self.downsample(target_width, target_height);
+                    (target_width, target_height, data)
+                } else {
+                    (self.width, self.height, self.data.clone())
+                }
+            }
+            None => (self.width, self.height, self.data.clone()),
+        };
+
+        ImageXObject {
+            width: Px(width),
+            height: Px(height),
+            color_space: self.color_space.into(),
+            bits_per_component: ColorBits::Bit8,
+            interpolate: true,
+            image_data: data,
+            image_filter: match self.encoding {
+                ImageEncoding::Raw => None,
+                ImageEncoding::Dct => Some(ImageFilter::DCT),
+            },
+            smask: None,
+            clipping_bbox: None,
+        }
+    }
+
+    // printpdf has no decode array on image XObjects, it is set on the embedded stream
+    pub(crate) fn decode(&self) -> Option<Vec<Object>> {
+        self.inverted.then(|| {
+            (0..self.color_space.components())
+                .flat_map(|_| [Object::Integer(1), Object::Integer(0)])
+                .collect()
+        })
+    }
+
+    // box filter, each target pixel averages the source pixels it covers
+    fn downsample(&self, target_width: usize, target_height: usize) -> Vec<u8> {
+        let components = self.color_space.components();
+        let mut target = Vec::with_capacity(target_width * target_height * components);
+
+        for target_y in 0..target_height {
+            let y_from = target_y * self.height / target_height;
+            let y_to = ((target_y + 1) * self.height / target_height).max(y_from + 1);
+
+            for target_x in 0..target_width {
+                let x_from = target_x * self.width / target_width;
+                let x_to = ((target_x + 1) * self.width / target_width).max(x_from + 1);
+
+                let count = ((y_to - y_from) * (x_to - x_from)) as u32;
+                for component in 0..components {
+                    let mut sum = 0u32;
+                    for y in y_from..y_to {
+                        for x in x_from..x_to {
+                            sum += self.data[(y * self.width + x) * components + component] as u32;
+                        }
+                    }
+                    target.push(((sum + count / 2) / count) as u8);
+                }
+            }
+        }
+
+        target
+    }
+}
+
+struct JpegHeader {
+    width: usize,
+    height: usize,
+    components: u8,
+    adobe: bool,
+}
+
+// dimensions from the first SOFn segment, APP14 segments before it mark Adobe JPEGs
+fn jpeg_header(data: &[u8]) -> Option<JpegHeader> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut adobe = false;
+    let mut position = 2;
+    while position + 4 <= data.len() {
+        if data[position] != 0xFF {
+            return None;
+        }
+        let marker = data[position + 1];
+        if marker == 0xFF {
+            // fill byte
+            position += 1;
+            continue;
+        }
+
+        let length = u16::from_be_bytes([data[position + 2], data[position + 3]]) as usize;
+        if marker == 0xEE {
+            adobe |= data
+                .get(position + 4..position + 2 + length)
+                .is_some_and(|segment| segment.starts_with(b"Adobe"));
+        }
+        let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof {
+            let segment = data.get(position + 4..position + 2 + length)?;
+            if segment.len() < 6 {
+                return None;
+            }
+            let height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+            let width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+            let components = segment[5];
+            return (width > 0 && height > 0).then_some(JpegHeader {
+                width,
+                height,
+                components,
+                adobe,
+            });
+        }
+
+        position += 2 + length;
+    }
+
+    None
+}
+
+fn valid_dpi(dpi: f32) -> bool {
+    dpi.is_finite() && dpi > 0.0
+}
+
+fn pixels_at(length: f32, dpi: f32) -> usize {
+    // tolerate f32 noise, 400.00003 px is still 400 px
+    ((length / MM_PER_INCH * dpi - 0.001).ceil() as usize).max(1)
+}
+
+// Image rectangle relative to the top left corner of its box, in mm
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ImagePlacement {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub clip: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::ImageFilter;
+
+    use super::{Image, ImageColorSpace, ImageFit, ImagePlacement};
+
+    fn image(width: usize, height: usize) -> Image {
+        Image::from_raw(
+            width,
+            height,
+            ImageColorSpace::Gray,
+            vec![128; width * height],
+        )
+        .unwrap()
+        .with_dpi(254.0)
+    }
+
+    fn assert_placement(placement: &ImagePlacement, expected: (f32, f32, f32, f32)) {
+        let actual = (placement.x, placement.y, placement.width, placement.height);
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3
+                && (actual.1 - expected.1).abs() < 1e-3
+                && (actual.2 - expected.2).abs() < 1e-3
+                && (actual.3 - expected.3).abs() < 1e-3,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn fit() {
+        // 300x200 px at 254 dpi = 30x20 mm
+        let placement = image(300, 200)
+            .with_fit(ImageFit::Contain)
+            .place(40.0, 40.0);
+        assert_placement(&placement, (0.0, 6.667, 40.0, 26.667));
+        assert!(!placement.clip);
+
+        let placement = image(300, 200).with_fit(ImageFit::Cover).place(40.0, 40.0);
+        assert_placement(&placement, (-10.0, 0.0, 60.0, 40.0));
+        assert!(placement.clip);
+
+        let placement = image(300, 200)
+            .with_fit(ImageFit::Stretch)
+            .place(40.0, 10.0);
+        assert_placement(&placement, (0.0, 0.0, 40.0, 10.0));
+        assert!(!placement.clip);
+
+        let placement = image(300, 200)
+            .with_fit(ImageFit::ActualDpi)
+            .place(40.0, 10.0);
+        assert_placement(&placement, (0.0, 0.0, 30.0, 20.0));
+        assert!(placement.clip);
+    }
+
+    #[test]
+    fn downsample() {
+        // 3000 px photo in a 40 mm box, 254 dpi limit = 400 px
+        let image = image(3000, 2000).with_max_dpi(254.0);
+        let placement = image.place(40.0, 40.0);
+        let xobject = image.to_xobject(&placement);
+
+        assert_eq!(xobject.width.0, 400);
+        assert_eq!(xobject.height.0, 267);
+        assert_eq!(xobject.image_data.len(), 400 * 267);
+        assert!(xobject.image_data.iter().all(|value| *value == 128));
+    }
+
+    #[test]
+    fn invalid_dpi() {
+        // 300 px at the default 300 dpi = 25.4 mm
+        for dpi in [0.0, -72.0, f32::NAN, f32::INFINITY] {
+            let image = image(300, 200).with_dpi(dpi).with_max_dpi(dpi);
+            let placement = image.with_fit(ImageFit::ActualDpi).place(40.0, 40.0);
+            assert_placement(&placement, (0.0, 0.0, 25.4, 16.933));
+        }
+
+        let image = image(300, 200).with_max_dpi(0.0);
+        let xobject = image.to_xobject(&image.place(40.0, 40.0));
+        assert_eq!(xobject.width.0, 300);
+    }
+
+    #[test]
+    fn jpeg() {
+        // SOI, APP0 stub, SOF0 640x480 with 3 components, EOI
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0xE0, 0x02, 0x80, 0x03]);
+        jpeg.extend([0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01]);
+        jpeg.extend([0xFF, 0xD9]);
+
+        let image = Image::from_jpeg(jpeg.clone()).unwrap().with_max_dpi(72.0);
+        assert_eq!((image.width(), image.height()), (640, 480));
+
+        let xobject = image.to_xobject(&image.place(10.0, 10.0));
+        assert_eq!(xobject.width.0, 640);
+        assert!(matches!(xobject.image_filter, Some(ImageFilter::DCT)));
+        assert_eq!(xobject.image_data, jpeg);
+        assert!(image.decode().is_none());
+
+        assert!(Image::from_jpeg(vec![0x89, 0x50, 0x4E, 0x47]).is_err());
+    }
+
+    #[test]
+    fn adobe_cmyk_jpeg() {
+        // SOI, APP14 Adobe with transform 0, SOF0 64x48 with 4 components, EOI
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E];
+        jpeg.extend(b"Adobe");
+        jpeg.extend([0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x14, 0x08, 0x00, 0x30, 0x00, 0x40, 0x04]);
+        jpeg.extend([
+            0x01, 0x11, 0x00, 0x02, 0x11, 0x00, 0x03, 0x11, 0x00, 0x04, 0x11, 0x00,
+        ]);
+        jpeg.extend([0xFF, 0xD9]);
+
+        let image = Image::from_jpeg(jpeg.clone()).unwrap();
+        assert_eq!((image.width(), image.height()), (64, 48));
+        let decode = image
+            .decode()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(decode, vec![1, 0, 1, 0, 1, 0, 1, 0]);
+
+        // CMYK of other applications is not inverted
+        let plain = [&jpeg[..2], &jpeg[18..]].concat();
+        assert!(Image::from_jpeg(plain).unwrap().decode().is_none());
+    }
+}
@@ -0,0 +1,662 @@
+use layout::{
+    Error,
+    position::Size,
+    unit::{Mm, Unit},
+};
+use printpdf::lopdf::{
+    Dictionary, Document, Object, ObjectId, Stream,
+    content::{Content, Operation},
+};
+
+use super::{
+    from_unit,
+    resources::{catalog, pdf_error},
+    stationery::{page_attribute, page_box},
+};
+
+// distance of cut marks from the pages and their length, in millimeters
+const CUT_MARK_OFFSET: f64 = 2.0;
+const CUT_MARK_LENGTH: f64 = 5.0;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Arrangement {
+    // pages follow each other by rows from the top left corner
+    Grid { columns: usize, rows: usize },
+    // pairs of pages of saddle-stitched booklets, sheets folded in the middle
+    Booklet { rotated_back: bool },
+}
+
+// logical pages of the document are placed onto physical sheets, e.g. tickets or labels
+// printed several on a sheet, or pages of booklets; imposed documents are meant for
+// printing, so links, outlines, labels and structure of logical pages are dropped
+#[derive(Clone, Debug, PartialEq)]
+pub struct Imposition {
+    arrangement: Arrangement,
+    sheet_size: Option<Size>,
+    gap: Unit,
+    cut_marks: bool,
+}
+
+// logical page on a sheet, position of the bottom left corner of its cell is in points;
+// pages smaller than cells are centered in them
+struct Placement {
+    page: usize,
+    x: f32,
+    y: f32,
+    scale: f32,
+    // turned upside down
+    rotated: bool,
+}
+
+struct Sheet {
+    placements: Vec<Placement>,
+    // lines from and to, in points
+    cut_marks: Vec<[f32; 4]>,
+}
+
+impl Imposition {
+    // e.g. 2 by 1 for 2-up, 2 by 2 for 4-up
+    pub fn n_up(columns: usize, rows: usize) -> Self {
+        Self {
+            arrangement: Arrangement::Grid {
+                columns: columns.max(1),
+                rows: rows.max(1),
+            },
+            sheet_size: None,
+            gap: Unit::zero(),
+            cut_marks: false,
+        }
+    }
+
+    // sides of sheets have two pages next to each other, the document is padded by blank
+    // pages to a multiple of four; sheets are printed on both sides, stacked and folded,
+    // e.g. A5 brochures on A4 sheets
+    pub fn booklet() -> Self {
+        Self {
+            arrangement: Arrangement::Booklet { rotated_back: true },
+            sheet_size: None,
+            gap: Unit::zero(),
+            cut_marks: false,
+        }
+    }
+
+    // back sides of booklet sheets are turned upside down, for printers turning sheets
+    // over their long edge; on by default
+    pub fn with_rotated_back(mut self, rotated_back: bool) -> Self {
+        if let Arrangement::Booklet {
+            rotated_back: current,
+        } = &mut self.arrangement
+        {
+            *current = rotated_back;
+        }
+        self
+    }
+
+    // pages are centered on sheets and scaled down if they do not fit; sheets just fit
+    // the pages by default
+    pub fn with_sheet_size(mut self, sheet_size: Size) -> Self {
+        self.sheet_size = Some(sheet_size);
+        self
+    }
+
+    // space between pages of a sheet
+    pub fn with_gap(mut self, gap: impl Into<Unit>) -> Self {
+        self.gap = gap.into();
+        self
+    }
+
+    // marks where the sheet is cut, out of the pages
+    pub fn with_cut_marks(mut self, cut_marks: bool) -> Self {
+        self.cut_marks = cut_marks;
+        self
+    }
+
+    // pages of landscape rotation, numbered from 0, are placed as they are displayed;
+    // cells of sheets fit the largest page
+    pub(crate) fn write(&self, document: &mut Document, rotated: &[usize]) -> Result<(), Error> {
+        let pages = document.get_pages().into_values().collect::<Vec<_>>();
+        if pages.is_empty() {
+            return Ok(());
+        }
+
+        let forms = pages
+            .iter()
+            .enumerate()
+            .map(|(page, page_id)| page_form(document, *page_id, rotated.contains(&page)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let cell = forms
+            .iter()
+            .fold((0.0f32, 0.0f32), |(width, height), (_, page_box)| {
+                (
+                    width.max(page_box[2] - page_box[0]),
+                    height.max(page_box[3] - page_box[1]),
+                )
+            });
+        let (sheet_size, sheets) = match self.arrangement {
+            Arrangement::Grid { columns, rows } => self.grid(pages.len(), columns, rows, cell),
+            Arrangement::Booklet { rotated_back } => {
+                self.booklet_sheets(pages.len(), rotated_back, cell)
+            }
+        };
+        write_sheets(document, &forms, cell, sheet_size, sheets)
+    }
+
+    fn grid(
+        &self,
+        pages: usize,
+        columns: usize,
+        rows: usize,
+        (width, height): (f32, f32),
+    ) -> ((f32, f32), Vec<Sheet>) {
+        let gap = pt(self.gap);
+        let margin = match self.cut_marks {
+            true => pt(Mm(CUT_MARK_OFFSET + CUT_MARK_LENGTH).into()),
+            false => 0.0,
+        };
+        let grid_width = columns as f32 * width + (columns - 1) as f32 * gap;
+        let grid_height = rows as f32 * height + (rows - 1) as f32 * gap;
+        let sheet_size = match &self.sheet_size {
+            Some(size) => (pt(size.base_width()), pt(size.base_height())),
+            None => (grid_width + 2.0 * margin, grid_height + 2.0 * margin),
+        };
+        let scale = ((sheet_size.0 - 2.0 * margin) / grid_width)
+            .min((sheet_size.1 - 2.0 * margin) / grid_height)
+            .min(1.0);
+        let left = (sheet_size.0 - scale * grid_width) / 2.0;
+        let bottom = (sheet_size.1 - scale * grid_height) / 2.0;
+        let cell = |column: usize, row: usize| {
+            (
+                left + scale * column as f32 * (width + gap),
+                bottom + scale * (rows - 1 - row) as f32 * (height + gap),
+            )
+        };
+
+        let mut cut_marks = vec![];
+        if self.cut_marks {
+            let mut xs = vec![];
+            for column in 0..columns {
+                let (x, _) = cell(column, 0);
+                xs.extend([x, x + scale * width]);
+            }
+            let mut ys = vec![];
+            for row in 0..rows {
+                let (_, y) = cell(0, row);
+                ys.extend([y, y + scale * height]);
+            }
+            xs.dedup();
+            ys.dedup();
+            let (right, top) = (left + scale * grid_width, bottom + scale * grid_height);
+            cut_marks.extend(
+                xs.iter()
+                    .flat_map(|x| [[*x, bottom, *x, 0.0], [*x, top, *x, sheet_size.1]]),
+            );
+            cut_marks.extend(
+                ys.iter()
+                    .flat_map(|y| [[left, *y, 0.0, *y], [right, *y, sheet_size.0, *y]]),
+            );
+            let (offset, length) = (
+                pt(Mm(CUT_MARK_OFFSET).into()),
+                pt(Mm(CUT_MARK_LENGTH).into()),
+            );
+            for mark in cut_marks.iter_mut() {
+                shorten(mark, offset, length);
+            }
+        }
+
+        let per_sheet = columns * rows;
+        let sheets = (0..pages)
+            .step_by(per_sheet)
+            .map(|first| Sheet {
+                placements: (first..pages.min(first + per_sheet))
+                    .map(|page| {
+                        let index = page - first;
+                        let (x, y) = cell(index % columns, index / columns);
+                        Placement {
+                            page,
+                            x,
+                            y,
+                            scale,
+                            rotated: false,
+                        }
+                    })
+                    .collect(),
+                cut_marks: cut_marks.clone(),
+            })
+            .collect();
+        (sheet_size, sheets)
+    }
+
+    // the outer sheet has the last and the first page on the front, the second and the
+    // last but one on the back, following sheets go to the middle of the booklet
+    fn booklet_sheets(
+        &self,
+        pages: usize,
+        rotated_back: bool,
+        (width, height): (f32, f32),
+    ) -> ((f32, f32), Vec<Sheet>) {
+        let sheet_size = match &self.sheet_size {
+            Some(size) => (pt(size.base_width()), pt(size.base_height())),
+            None => (2.0 * width, height),
+        };
+        let scale = (sheet_size.0 / (2.0 * width))
+            .min(sheet_size.1 / height)
+            .min(1.0);
+        let x = sheet_size.0 / 2.0 - scale * width;
+        let y = (sheet_size.1 - scale * height) / 2.0;
+        let placement = |page: usize, column: usize, rotated: bool| {
+            (page < pages).then_some(Placement {
+                page,
+                x: x + column as f32 * scale * width,
+                y,
+                scale,
+                rotated,
+            })
+        };
+
+        let total = pages.div_ceil(4) * 4;
+        let mut sheets = vec![];
+        for sheet in 0..total / 4 {
+            let front = [total - 1 - 2 * sheet, 2 * sheet];
+            let mut back = [2 * sheet + 1, total - 2 - 2 * sheet];
+            // pages swap their sides when the side is upside down
+            if rotated_back {
+                back.reverse();
+            }
+            sheets.push(Sheet {
+                placements: front
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(column, page)| placement(page, column, false))
+                    .collect(),
+                cut_marks: vec![],
+            });
+            sheets.push(Sheet {
+                placements: back
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(column, page)| placement(page, column, rotated_back))
+                    .collect(),
+                cut_marks: vec![],
+            });
+        }
+        (sheet_size, sheets)
+    }
+}
+
+fn pt(unit: Unit) -> f32 {
+    from_unit(unit).into_pt().0
+}
+
+// the mark starts at the offset from its start towards its end and has the length
+fn shorten(mark: &mut [f32; 4], offset: f32, length: f32) {
+    let (dx, dy) = (mark[2] - mark[0], mark[3] - mark[1]);
+    let distance = dx.hypot(dy);
+    if distance == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / distance, dy / distance);
+    let (x, y) = (mark[0], mark[1]);
+    *mark = [
+        x + ux * offset,
+        y + uy * offset,
+        x + ux * (offset + length),
+        y + uy * (offset + length),
+    ];
+}
+
+// form xobject of the page and the box of the page as it is displayed; rotated pages are
+// turned clockwise by the matrix of the form, as viewers do by their rotation
+fn page_form(
+    document: &mut Document,
+    page_id: ObjectId,
+    rotated: bool,
+) -> Result<(ObjectId, [f32; 4]), Error> {
+    let content = document.get_page_content(page_id).map_err(pdf_error)?;
+    let Some(media_box) = page_box(document, page_id) else {
+        return Err(Error::PdfWrite("Imposed page has no media box".into()));
+    };
+    let resources =
+        page_attribute(document, page_id, b"Resources").unwrap_or(Dictionary::new().into());
+
+    let mut form = Dictionary::new();
+    form.set("Type", Object::Name(b"XObject".to_vec()));
+    form.set("Subtype", Object::Name(b"Form".to_vec()));
+    form.set("BBox", media_box.map(Object::Real).to_vec());
+    form.set("Resources", resources);
+    let [left, bottom, right, top] = media_box;
+    let displayed_box = match rotated {
+        true => {
+            form.set(
+                "Matrix",
+                [0.0, -1.0, 1.0, 0.0, 0.0, 0.0].map(Object::Real).to_vec(),
+            );
+            [bottom, -right, top, -left]
+        }
+        false => media_box,
+    };
+    Ok((
+        document.add_object(Stream::new(form, content)),
+        displayed_box,
+    ))
+}
+
+// sheets replace pages of the document
+fn write_sheets(
+    document: &mut Document,
+    forms: &[(ObjectId, [f32; 4])],
+    (cell_width, cell_height): (f32, f32),
+    (sheet_width, sheet_height): (f32, f32),
+    sheets: Vec<Sheet>,
+) -> Result<(), Error> {
+    let pages_id = catalog(document)?
+        .get(b"Pages")
+        .and_then(Object::as_reference)
+        .map_err(pdf_error)?;
+
+    let mut kids = vec![];
+    for sheet in sheets {
+        let mut xobjects = Dictionary::new();
+        let mut operations = vec![];
+        for (index, placement) in sheet.placements.iter().enumerate() {
+            let (form_id, [left, bottom, right, top]) = forms[placement.page];
+            let name = format!("PRPage{index}");
+            xobjects.set(name.as_bytes(), Object::Reference(form_id));
+            let scale = placement.scale;
+            let x = placement.x + scale * (cell_width - (right - left)) / 2.0;
+            let y = placement.y + scale * (cell_height - (top - bottom)) / 2.0;
+            let matrix = match placement.rotated {
+                true => [-scale, 0.0, 0.0, -scale, x + scale * right, y + scale * top],
+                false => [scale, 0.0, 0.0, scale, x - scale * left, y - scale * bottom],
+            };
+            operations.extend([
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.map(Object::Real).to_vec()),
+                Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+                Operation::new("Q", vec![]),
+            ]);
+        }
+        if !sheet.cut_marks.is_empty() {
+            operations.extend([
+                Operation::new("q", vec![]),
+                Operation::new("G", vec![Object::Real(0.0)]),
+                Operation::new("w", vec![Object::Real(0.25)]),
+            ]);
+            for [x1, y1, x2, y2] in sheet.cut_marks {
+                operations.extend([
+                    Operation::new("m", vec![Object::Real(x1), Object::Real(y1)]),
+                    Operation::new("l", vec![Object::Real(x2), Object::Real(y2)]),
+                ]);
+            }
+            operations.extend([Operation::new("S", vec![]), Operation::new("Q", vec![])]);
+        }
+        let content = Content { operations }.encode().map_err(pdf_error)?;
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+
+        let mut resources = Dictionary::new();
+        resources.set("XObject", xobjects);
+        let mut page = Dictionary::new();
+        page.set("Type", Object::Name(b"Page".to_vec()));
+        page.set("Parent", Object::Reference(pages_id));
+        page.set(
+            "MediaBox",
+            [0.0, 0.0, sheet_width, sheet_height]
+                .map(Object::Real)
+                .to_vec(),
+        );
+        page.set("Resources", resources);
+        page.set("Contents", Object::Reference(content_id));
+        kids.push(Object::Reference(document.add_object(page)));
+    }
+
+    let count = kids.len();
+    let pages = document
+        .get_object_mut(pages_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    pages.set("Kids", kids);
+    pages.set("Count", count as i64);
+
+    // they refer to logical pages; sheets are not tagged, so neither is the document
+    let catalog = catalog(document)?;
+    for key in [
+        "Outlines",
+        "PageLabels",
+        "OpenAction",
+        "StructTreeRoot",
+        "MarkInfo",
+    ] {
+        catalog.remove(key.as_bytes());
+    }
+    if catalog
+        .get(b"PageMode")
+        .and_then(Object::as_name)
+        .is_ok_and(|mode| mode == b"UseOutlines")
+    {
+        catalog.remove(b"PageMode");
+    }
+    document.prune_objects();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object},
+    };
+
+    use crate::{Imposition, RenderContext, new_font_cache};
+
+    fn impose(pages: usize, imposition: Imposition) -> Document {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(105.0), printpdf::Mm(148.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(105.0), Mm(148.0)),
+            new_font_cache(),
+        )
+        .with_imposition(imposition);
+        for page in 0..pages {
+            if page > 0 {
+                layout::RenderContext::new_page(&mut rctx, None);
+            }
+            rctx.rect(
+                &Offset::new(Mm(0.0), Mm(0.0)),
+                &Size::fixed(Mm(85.0), Mm(128.0)),
+                Some(&layout::Rgba::from((13, 71, 161, 1.0))),
+                None,
+            );
+        }
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_imposition.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+        Document::load_mem(&pdf).unwrap()
+    }
+
+    fn sheets(document: &Document) -> Vec<(Vec<f32>, usize)> {
+        document
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let media_box = document
+                    .get_dictionary(page_id)
+                    .and_then(|page| page.get(b"MediaBox"))
+                    .and_then(Object::as_array)
+                    .unwrap()
+                    .iter()
+                    .map(|value| value.as_float().unwrap().round())
+                    .collect();
+                let content = document.get_and_decode_page_content(page_id).unwrap();
+                let placed = content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == "Do")
+                    .count();
+                (media_box, placed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn n_up() {
+        let document = impose(5, Imposition::n_up(2, 2));
+        assert_eq!(
+            sheets(&document),
+            vec![
+                (vec![0.0, 0.0, 595.0, 839.0], 4),
+                (vec![0.0, 0.0, 595.0, 839.0], 1)
+            ]
+        );
+
+        let document = impose(
+            2,
+            Imposition::n_up(2, 1)
+                .with_sheet_size(Size::fixed(Mm(297.0), Mm(210.0)))
+                .with_cut_marks(true),
+        );
+        assert_eq!(sheets(&document), vec![(vec![0.0, 0.0, 842.0, 595.0], 2)]);
+    }
+
+    #[test]
+    fn mixed_pages() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(148.0), printpdf::Mm(210.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(148.0), Mm(210.0)),
+            new_font_cache(),
+        )
+        .with_landscape_rotation(true)
+        .with_pdf_ua(true)
+        .with_imposition(Imposition::n_up(2, 1));
+        // the second page is landscape, stored upright and rotated
+        rctx.set_page_setup(
+            &Quad::square(Mm(10.0)),
+            &Size::fixed(Mm(210.0), Mm(148.0)),
+            false,
+        );
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_mixed_pages.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+
+        // cells fit the width of the landscape page and the height of the portrait one
+        assert_eq!(sheets(&document), vec![(vec![0.0, 0.0, 1191.0, 595.0], 2)]);
+        let page_id = document.get_pages()[&1];
+        let page = document.get_dictionary(page_id).unwrap();
+        assert!(page.get(b"Rotate").is_err());
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let offsets = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "cm")
+            .map(|matrix| {
+                let operand = |index: usize| matrix.operands[index].as_float().unwrap().round();
+                (operand(4), operand(5))
+            })
+            .collect::<Vec<_>>();
+        // pages are centered in their cells, the landscape one is moved up by the box of
+        // its form turned upright
+        assert_eq!(offsets, vec![(88.0, 0.0), (595.0, 507.0)]);
+        let forms = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| stream.dict.get(b"Matrix").is_ok())
+            .count();
+        assert_eq!(forms, 1);
+
+        // sheets are not tagged
+        let catalog = document.catalog().unwrap();
+        assert!(catalog.get(b"StructTreeRoot").is_err());
+        assert!(catalog.get(b"MarkInfo").is_err());
+        let metadata = catalog
+            .get(b"Metadata")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_object(id))
+            .and_then(Object::as_stream)
+            .map(|stream| String::from_utf8_lossy(&stream.content).to_string())
+            .unwrap_or_default();
+        assert!(!metadata.contains("pdfuaid"));
+    }
+
+    #[test]
+    fn booklet() {
+        let document = impose(6, Imposition::booklet());
+        let sides = sheets(&document);
+        assert_eq!(
+            sides.iter().map(|(_, placed)| *placed).collect::<Vec<_>>(),
+            vec![1, 1, 2, 2]
+        );
+        assert_eq!(sides[0].0, vec![0.0, 0.0, 595.0, 420.0]);
+
+        // back sides are upside down
+        let scales = document
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(page_id).unwrap();
+                let matrix = content
+                    .operations
+                    .iter()
+                    .find(|operation| operation.operator == "cm")
+                    .unwrap();
+                matrix.operands[0].as_float().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(scales, vec![1.0, -1.0, 1.0, -1.0]);
+
+        // pages from 0 on sides of sheets, left to right, the last two are blank; pages of
+        // back sides swap when the side is upside down
+        let order = |rotated_back: bool| {
+            let (_, sheets) = Imposition::booklet().booklet_sheets(6, rotated_back, (298.0, 420.0));
+            sheets
+                .iter()
+                .map(|sheet| {
+                    sheet
+                        .placements
+                        .iter()
+                        .map(|placement| (placement.page, placement.x.round()))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            order(false),
+            vec![
+                vec![(0, 298.0)],
+                vec![(1, 0.0)],
+                vec![(5, 0.0), (2, 298.0)],
+                vec![(3, 0.0), (4, 298.0)],
+            ]
+        );
+        assert_eq!(
+            order(true),
+            vec![
+                vec![(0, 298.0)],
+                vec![(1, 298.0)],
+                vec![(5, 0.0), (2, 298.0)],
+                vec![(4, 0.0), (3, 298.0)],
+            ]
+        );
+    }
+}
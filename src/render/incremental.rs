@@ -0,0 +1,290 @@
+use layout::Error;
+use printpdf::lopdf::{Document, IncrementalDocument, Object};
+
+use super::{
+    RenderContext, Signature,
+    merge::inherited,
+    resources::{catalog, pdf_error},
+};
+
+// update appended to an existing document, e.g. a signature page or an addendum; bytes of
+// the document are kept as they are, so signatures made before stay valid, only new and
+// changed objects are written after them
+pub struct IncrementalUpdate {
+    pdf: Vec<u8>,
+    original: Document,
+    document: Document,
+    signature: Option<Signature>,
+}
+
+impl IncrementalUpdate {
+    // the document must not be encrypted
+    pub fn new(pdf: &[u8]) -> Result<Self, Error> {
+        let original = Document::load_mem(pdf).map_err(pdf_error)?;
+        if original.is_encrypted() {
+            return Err(Error::PdfWrite(
+                "Encrypted documents can not be updated".into(),
+            ));
+        }
+        Ok(Self {
+            pdf: pdf.to_vec(),
+            document: original.clone(),
+            original,
+            signature: None,
+        })
+    }
+
+    pub fn add_context(&mut self, rctx: RenderContext) -> Result<(), Error> {
+        self.add_pdf(&rctx.save_to_bytes()?)
+    }
+
+    // pages are appended, form fields on them join the form of the document; its catalog,
+    // outline and structure are dropped
+    pub fn add_pdf(&mut self, pdf: &[u8]) -> Result<(), Error> {
+        let mut added = Document::load_mem(pdf).map_err(pdf_error)?;
+        if added.is_encrypted() {
+            return Err(Error::PdfWrite(
+                "Encrypted documents can not be appended".into(),
+            ));
+        }
+        added.renumber_objects_with(self.document.max_id + 1);
+        self.document.max_id = added.max_id;
+
+        let pages_id = catalog(&mut self.document)?
+            .get(b"Pages")
+            .and_then(Object::as_reference)
+            .map_err(pdf_error)?;
+        let mut kids = vec![];
+        for page_id in added.get_pages().into_values() {
+            let inherited = inherited(&added, page_id);
+            let page = added
+                .get_object_mut(page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?;
+            for (key, value) in inherited {
+                page.set(key, value);
+            }
+            page.set("Parent", Object::Reference(pages_id));
+            kids.push(Object::Reference(page_id));
+        }
+        let form = catalog(&mut added)?.get(b"AcroForm").cloned().ok();
+        self.document.objects.append(&mut added.objects);
+
+        let pages = self
+            .document
+            .get_object_mut(pages_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?;
+        let mut all_kids = pages
+            .get(b"Kids")
+            .and_then(Object::as_array)
+            .cloned()
+            .unwrap_or_default();
+        all_kids.extend(kids);
+        pages.set("Kids", all_kids);
+        let count = self.document.get_pages().len();
+        self.document
+            .get_object_mut(pages_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?
+            .set("Count", count as i64);
+
+        if let Some(form) = form {
+            append_form(&mut self.document, form)?;
+        }
+        Ok(())
+    }
+
+    // the signature field is either in the document or on the appended pages
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    pub fn save_to_bytes(mut self) -> Result<Vec<u8>, Error> {
+        if let Some(signature) = &self.signature {
+            signature.prepare(&mut self.document)?;
+        }
+        // catalogs and page trees of appended documents are not written
+        self.document.prune_objects();
+
+        let mut update = IncrementalDocument::create_from(self.pdf, self.original);
+        update.new_document.max_id = self.document.max_id;
+        for (id, object) in self.document.objects {
+            let changed = match update.get_prev_documents().get_object(id) {
+                Ok(original) => changed(original, &object),
+                Err(_) => true,
+            };
+            if changed {
+                update.new_document.objects.insert(id, object);
+            }
+        }
+
+        let mut pdf = vec![];
+        update.save_to(&mut pdf).map_err(pdf_error)?;
+        match self.signature {
+            Some(signature) => signature.sign(pdf),
+            None => Ok(pdf),
+        }
+    }
+}
+
+// debug output of streams does not include their content
+fn changed(original: &Object, object: &Object) -> bool {
+    match (original, object) {
+        (Object::Stream(original), Object::Stream(stream)) => {
+            original.content != stream.content
+                || format!("{:?}", original.dict) != format!("{:?}", stream.dict)
+        }
+        (original, object) => format!("{original:?}") != format!("{object:?}"),
+    }
+}
+
+// fields of the appended form are added to the form of the document, which takes the
+// appended one when it has none
+fn append_form(document: &mut Document, form: Object) -> Result<(), Error> {
+    let form = match form {
+        Object::Reference(id) => document.get_dictionary(id).map_err(pdf_error)?.clone(),
+        Object::Dictionary(form) => form,
+        _ => return Ok(()),
+    };
+    let form_id = match catalog(document)?.get(b"AcroForm").cloned() {
+        Ok(Object::Reference(id)) => id,
+        // signing expects the form to be indirect
+        Ok(existing) => {
+            let form_id = document.add_object(existing);
+            catalog(document)?.set("AcroForm", Object::Reference(form_id));
+            form_id
+        }
+        Err(_) => {
+            let form_id = document.add_object(form);
+            catalog(document)?.set("AcroForm", Object::Reference(form_id));
+            return Ok(());
+        }
+    };
+
+    let fields = form
+        .get(b"Fields")
+        .and_then(Object::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let existing = document
+        .get_object_mut(form_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    let mut all_fields = existing
+        .get(b"Fields")
+        .and_then(Object::as_array)
+        .cloned()
+        .unwrap_or_default();
+    all_fields.extend(fields);
+    existing.set("Fields", all_fields);
+    for (key, value) in form.iter() {
+        if !existing.has(key) {
+            existing.set(key.clone(), value.clone());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{
+        position::{Offset, Size},
+        unit::Mm,
+    };
+    use printpdf::lopdf::{Document, Object, ObjectId};
+
+    use crate::{FormField, IncrementalUpdate, RenderContext, Signature};
+
+    use super::super::test_layouts::render_context;
+
+    fn form_context(signature_field: bool) -> RenderContext {
+        let mut rctx = render_context("Test", 210.0, 1);
+        if signature_field {
+            rctx.form_field(
+                &Offset::zero(),
+                &Size::fixed(Mm(80.0), Mm(20.0)),
+                &FormField::signature("approval"),
+            );
+        }
+        rctx
+    }
+
+    // objects written by the update, after the original document
+    fn updated(pdf: &[u8], original: &[u8]) -> Vec<ObjectId> {
+        String::from_utf8_lossy(&pdf[original.len()..])
+            .lines()
+            .filter_map(|line| line.strip_suffix(" obj"))
+            .filter_map(|line| {
+                let (id, generation) = line.split_once(' ')?;
+                Some((id.parse().ok()?, generation.parse().ok()?))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn incremental_update() {
+        let original_pdf = form_context(false).save_to_bytes().unwrap();
+        let mut update = IncrementalUpdate::new(&original_pdf).unwrap();
+        update.add_context(form_context(true)).unwrap();
+        let pdf = update
+            .with_signature(Signature::new("approval", |_| Ok(vec![0x30, 0x00])))
+            .save_to_bytes()
+            .unwrap();
+        BufWriter::new(File::create("test_incremental_update.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        // the original document is kept as it is
+        assert!(pdf.starts_with(&original_pdf));
+        let original = Document::load_mem(&original_pdf).unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        assert_eq!(document.get_pages().len(), 2);
+        let update = String::from_utf8_lossy(&pdf[original_pdf.len()..]);
+        assert!(update.contains(&format!("/Prev {}", original.xref_start)));
+
+        // of the original objects, the page tree and the catalog, which gets the form, change
+        let catalog_id = original
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .unwrap();
+        let pages_id = original
+            .catalog()
+            .unwrap()
+            .get(b"Pages")
+            .and_then(Object::as_reference)
+            .unwrap();
+        let mut changed = updated(&pdf, &original_pdf)
+            .into_iter()
+            .filter(|id| original.objects.contains_key(id))
+            .collect::<Vec<_>>();
+        changed.sort();
+        let mut expected = vec![catalog_id, pages_id];
+        expected.sort();
+        assert_eq!(changed, expected);
+
+        let form = document
+            .catalog()
+            .unwrap()
+            .get(b"AcroForm")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        assert_eq!(form.get(b"SigFlags").unwrap().as_i64().unwrap(), 3);
+        let signature = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .find(|dictionary| dictionary.get(b"ByteRange").is_ok())
+            .unwrap();
+        let contents = signature.get(b"Contents").unwrap().as_str().unwrap();
+        assert_eq!(&contents[..2], &[0x30, 0x00]);
+    }
+}
@@ -0,0 +1,303 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object};
+
+use super::resources::{catalog, pdf_error};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zoom {
+    FitPage,
+    FitWidth,
+    // 1.0 is the actual size
+    Scale(f32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageLayout {
+    SinglePage,
+    OneColumn,
+    // odd pages on the left
+    TwoColumnLeft,
+    TwoColumnRight,
+    // two pages at a time, odd pages on the left
+    TwoPageLeft,
+    TwoPageRight,
+}
+
+impl PageLayout {
+    fn name(self) -> &'static [u8] {
+        match self {
+            PageLayout::SinglePage => b"SinglePage",
+            PageLayout::OneColumn => b"OneColumn",
+            PageLayout::TwoColumnLeft => b"TwoColumnLeft",
+            PageLayout::TwoColumnRight => b"TwoColumnRight",
+            PageLayout::TwoPageLeft => b"TwoPageLeft",
+            PageLayout::TwoPageRight => b"TwoPageRight",
+        }
+    }
+}
+
+// how viewers show the document when it is opened, viewers may ignore any of it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InitialView {
+    page: Option<usize>,
+    destination: Option<String>,
+    zoom: Option<Zoom>,
+    page_layout: Option<PageLayout>,
+    hide_toolbar: bool,
+    hide_menubar: bool,
+    hide_window_ui: bool,
+    fit_window: bool,
+    center_window: bool,
+    full_screen: bool,
+}
+
+impl InitialView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // pages are numbered from 1
+    pub fn with_page(mut self, page: usize) -> Self {
+        self.page = Some(page.max(1));
+        self
+    }
+
+    // anchor or named destination, e.g. of the summary section; it wins over the page
+    pub fn with_destination(mut self, name: impl Into<String>) -> Self {
+        self.destination = Some(name.into());
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: Zoom) -> Self {
+        self.zoom = Some(zoom);
+        self
+    }
+
+    pub fn with_page_layout(mut self, page_layout: PageLayout) -> Self {
+        self.page_layout = Some(page_layout);
+        self
+    }
+
+    pub fn with_hide_toolbar(mut self, hide_toolbar: bool) -> Self {
+        self.hide_toolbar = hide_toolbar;
+        self
+    }
+
+    pub fn with_hide_menubar(mut self, hide_menubar: bool) -> Self {
+        self.hide_menubar = hide_menubar;
+        self
+    }
+
+    // scroll bars, navigation controls and the like
+    pub fn with_hide_window_ui(mut self, hide_window_ui: bool) -> Self {
+        self.hide_window_ui = hide_window_ui;
+        self
+    }
+
+    // the window is resized to the first page shown
+    pub fn with_fit_window(mut self, fit_window: bool) -> Self {
+        self.fit_window = fit_window;
+        self
+    }
+
+    pub fn with_center_window(mut self, center_window: bool) -> Self {
+        self.center_window = center_window;
+        self
+    }
+
+    // neither menu bar, window controls nor any other window is shown, e.g. for kiosks
+    pub fn with_full_screen(mut self, full_screen: bool) -> Self {
+        self.full_screen = full_screen;
+        self
+    }
+
+    // preferences already set, e.g. of tagged documents, are kept; destinations of anchors
+    // are resolved by their names
+    pub(crate) fn write(
+        &self,
+        document: &mut Document,
+        anchor_destination: impl Fn(&str) -> Option<Vec<Object>>,
+    ) -> Result<(), Error> {
+        let anchored = self.destination.as_ref().and_then(|name| {
+            let destination = anchor_destination(name);
+            if destination.is_none() {
+                tracing::warn!("Initial view refers to missing destination {}.", name);
+            }
+            destination
+        });
+        let open_action = match (anchored, self.page, self.zoom) {
+            // the anchor is at the top left corner of the view
+            (Some(destination), _, zoom) => {
+                let mut destination = destination.into_iter();
+                let page = destination.next();
+                let left = destination.nth(1);
+                let top = destination.next();
+                page.map(|page| view(page, left, top, zoom))
+            }
+            (None, None, None) => None,
+            (None, page, zoom) => {
+                let pages = document.get_pages();
+                let page = page.unwrap_or(1).min(pages.len());
+                let page_id = pages.get(&(page as u32)).copied();
+                page_id.map(|page_id| view(Object::Reference(page_id), None, None, zoom))
+            }
+        };
+
+        let catalog = catalog(document)?;
+        if let Some(destination) = open_action {
+            catalog.set("OpenAction", destination);
+        }
+        if let Some(page_layout) = self.page_layout {
+            catalog.set("PageLayout", Object::Name(page_layout.name().to_vec()));
+        }
+        if self.full_screen {
+            // mode after leaving the full screen, e.g. with the outline shown
+            if let Ok(page_mode) = catalog.get(b"PageMode").cloned() {
+                catalog.set("NonFullScreenPageMode", page_mode);
+            }
+            catalog.set("PageMode", Object::Name(b"FullScreen".to_vec()));
+        }
+
+        let flags = [
+            ("HideToolbar", self.hide_toolbar),
+            ("HideMenubar", self.hide_menubar),
+            ("HideWindowUI", self.hide_window_ui),
+            ("FitWindow", self.fit_window),
+            ("CenterWindow", self.center_window),
+        ];
+        if flags.iter().any(|(_, flag)| *flag) {
+            if !catalog.has(b"ViewerPreferences") {
+                catalog.set("ViewerPreferences", Dictionary::new());
+            }
+            let preferences = catalog
+                .get_mut(b"ViewerPreferences")
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?;
+            for (key, flag) in flags {
+                if flag {
+                    preferences.set(key, true);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// coordinates are kept by viewers unless set
+fn view(
+    page: Object,
+    left: Option<Object>,
+    top: Option<Object>,
+    zoom: Option<Zoom>,
+) -> Vec<Object> {
+    let left = left.unwrap_or(Object::Null);
+    let top = top.unwrap_or(Object::Null);
+    let mut destination = vec![page];
+    destination.extend(match zoom {
+        None => vec![Object::Name(b"XYZ".to_vec()), left, top, Object::Null],
+        Some(Zoom::FitPage) => vec![Object::Name(b"Fit".to_vec())],
+        Some(Zoom::FitWidth) => vec![Object::Name(b"FitH".to_vec()), top],
+        Some(Zoom::Scale(scale)) => vec![
+            Object::Name(b"XYZ".to_vec()),
+            left,
+            top,
+            Object::Real(scale),
+        ],
+    });
+    destination
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object},
+    };
+
+    use crate::{InitialView, PageLayout, RenderContext, Zoom, new_font_cache};
+
+    #[test]
+    fn initial_view() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_section_outline(true)
+        .with_initial_view(
+            InitialView::new()
+                .with_page(2)
+                .with_zoom(Zoom::FitWidth)
+                .with_page_layout(PageLayout::TwoPageLeft)
+                .with_hide_toolbar(true)
+                .with_full_screen(true),
+        );
+        rctx.mark_section("Introduction", 1, &Offset::new(Mm(0.0), Mm(0.0)));
+        layout::RenderContext::new_page(&mut rctx, None);
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let catalog = document.catalog().unwrap();
+        let name = |key: &[u8]| catalog.get(key).unwrap().as_name().unwrap().to_vec();
+        assert_eq!(name(b"PageLayout"), b"TwoPageLeft");
+        assert_eq!(name(b"PageMode"), b"FullScreen");
+        assert_eq!(name(b"NonFullScreenPageMode"), b"UseOutlines");
+
+        let destination = catalog.get(b"OpenAction").unwrap().as_array().unwrap();
+        let page_id = *document.get_pages().get(&2).unwrap();
+        assert_eq!(destination[0].as_reference().unwrap(), page_id);
+        assert_eq!(destination[1].as_name().unwrap(), b"FitH");
+
+        let preferences = catalog
+            .get(b"ViewerPreferences")
+            .and_then(Object::as_dict)
+            .unwrap();
+        assert!(preferences.get(b"HideToolbar").unwrap().as_bool().unwrap());
+        assert!(preferences.get(b"HideMenubar").is_err());
+    }
+
+    #[test]
+    fn initial_view_destination() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_initial_view(
+            InitialView::new()
+                .with_page(1)
+                .with_destination("summary")
+                .with_zoom(Zoom::Scale(1.5)),
+        );
+        layout::RenderContext::new_page(&mut rctx, None);
+        rctx.destination("summary", &Offset::zero());
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let catalog = document.catalog().unwrap();
+        let destination = catalog.get(b"OpenAction").unwrap().as_array().unwrap();
+        let page_id = *document.get_pages().get(&2).unwrap();
+        assert_eq!(destination[0].as_reference().unwrap(), page_id);
+        assert_eq!(destination[1].as_name().unwrap(), b"XYZ");
+        // top left corner of the content of the second page
+        assert!((destination[2].as_float().unwrap() - 28.3465).abs() < 1e-2);
+        assert!((destination[3].as_float().unwrap() - 813.5434).abs() < 1e-2);
+        assert_eq!(destination[4].as_float().unwrap(), 1.5);
+    }
+}
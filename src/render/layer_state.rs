@@ -0,0 +1,161 @@
+use layout::{Rgba, Stroke};
+use printpdf::{IndirectFontRef, PdfLayerReference, TextRenderingMode};
+use smol_str::SmolStr;
+
+use super::{ColorModel, stroke::stroke_thickness};
+
+// graphics state set by text painting, operators which would not change it are skipped;
+// None is an unknown value, e.g. after switching back to a layer used before
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct LayerState {
+    font: Option<(SmolStr, f32)>,
+    fill_color: Option<Rgba>,
+    outline: Option<(Rgba, f32)>,
+    text_scaling: Option<f32>,
+    character_spacing: Option<f32>,
+    rendering_mode: Option<i64>,
+}
+
+impl LayerState {
+    // defaults of a new content stream
+    pub(crate) fn initial() -> Self {
+        Self {
+            font: None,
+            fill_color: Some(Rgba::black()),
+            outline: Some((Rgba::black(), 1.0)),
+            text_scaling: Some(100.0),
+            character_spacing: Some(0.0),
+            rendering_mode: Some(TextRenderingMode::Fill.into()),
+        }
+    }
+
+    // font is identified by the name it was added to the font cache with
+    pub(crate) fn set_font(
+        &mut self,
+        layer: &PdfLayerReference,
+        name: &SmolStr,
+        font_ref: &IndirectFontRef,
+        size: f32,
+    ) {
+        let font = Some((name.clone(), size));
+        if self.font != font {
+            layer.set_font(font_ref, size);
+            self.font = font;
+        }
+    }
+
+    pub(crate) fn set_fill_color(
+        &mut self,
+        layer: &PdfLayerReference,
+        color_model: ColorModel,
+        color: &Rgba,
+    ) {
+        if self.fill_color.as_ref() != Some(color) {
+            layer.set_fill_color(color_model.color(color));
+            self.fill_color = Some(*color);
+        }
+    }
+
+    pub(crate) fn set_outline(
+        &mut self,
+        layer: &PdfLayerReference,
+        color_model: ColorModel,
+        stroke: &Stroke,
+    ) {
+        let (color, thickness) = (*stroke.color(), stroke_thickness(stroke));
+        let (current_color, current_thickness) = match self.outline {
+            Some((color, thickness)) => (Some(color), Some(thickness)),
+            None => (None, None),
+        };
+        if current_color != Some(color) {
+            layer.set_outline_color(color_model.color(&color));
+        }
+        if current_thickness != Some(thickness) {
+            layer.set_outline_thickness(thickness);
+        }
+        self.outline = Some((color, thickness));
+    }
+
+    pub(crate) fn set_text_scaling(&mut self, layer: &PdfLayerReference, scaling: f32) {
+        if self.text_scaling != Some(scaling) {
+            layer.set_text_scaling(scaling);
+            self.text_scaling = Some(scaling);
+        }
+    }
+
+    pub(crate) fn character_spacing(&self) -> Option<f32> {
+        self.character_spacing
+    }
+
+    pub(crate) fn set_character_spacing(&mut self, layer: &PdfLayerReference, spacing: f32) {
+        if self.character_spacing != Some(spacing) {
+            layer.set_character_spacing(spacing);
+            self.character_spacing = Some(spacing);
+        }
+    }
+
+    pub(crate) fn set_rendering_mode(
+        &mut self,
+        layer: &PdfLayerReference,
+        mode: TextRenderingMode,
+    ) {
+        let mode_value = i64::from(mode);
+        if self.rendering_mode != Some(mode_value) {
+            layer.set_text_rendering_mode(mode);
+            self.rendering_mode = Some(mode_value);
+        }
+    }
+
+    // outline changed out of the tracking, e.g. for debug output
+    pub(crate) fn forget_outline(&mut self) {
+        self.outline = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{Rgba, Stroke, unit::Pt};
+    use printpdf::{BuiltinFont, Mm, PdfDocument, TextRenderingMode};
+    use smol_str::SmolStr;
+
+    use crate::ColorModel;
+
+    use super::LayerState;
+
+    #[test]
+    fn redundant_operators() {
+        let (document, page, layer) = PdfDocument::new("Test", Mm(210.0), Mm(297.0), "default");
+        let layer = document.get_page(page).get_layer(layer);
+        let font = document.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        let name = SmolStr::new("Helvetica");
+        let blue = Rgba::from((13, 71, 161, 1.0));
+
+        let mut state = LayerState::initial();
+        for _ in 0..3 {
+            state.set_fill_color(&layer, ColorModel::Rgb, &Rgba::black());
+            state.set_font(&layer, &name, &font, 12.0);
+            state.set_text_scaling(&layer, 100.0);
+            state.set_character_spacing(&layer, 0.0);
+            state.set_rendering_mode(&layer, TextRenderingMode::Fill);
+        }
+        for _ in 0..2 {
+            state.set_fill_color(&layer, ColorModel::Rgb, &blue);
+            state.set_font(&layer, &name, &font, 10.0);
+            state.set_outline(&layer, ColorModel::Rgb, &Stroke::new(blue, Pt(0.5)));
+        }
+        state.forget_outline();
+        state.set_outline(&layer, ColorModel::Rgb, &Stroke::new(blue, Pt(0.5)));
+
+        let pdf = document.save_to_bytes().unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let operators = content
+            .operations
+            .iter()
+            .map(|operation| operation.operator.as_str())
+            .filter(|operator| !matches!(*operator, "q" | "Q" | "BDC" | "EMC"))
+            .collect::<Vec<_>>();
+        assert_eq!(operators, vec!["Tf", "rg", "Tf", "RG", "w", "RG", "w"]);
+    }
+}
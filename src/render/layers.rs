@@ -0,0 +1,222 @@
+use layout::Error;
+use printpdf::lopdf::{
+    Document, Object, ObjectId,
+    content::{Content, Operation},
+};
+
+use super::resources::{catalog, pdf_error};
+
+fn layer_name(document: &Document, id: ObjectId) -> Option<Vec<u8>> {
+    let layer = document.get_dictionary(id).ok()?;
+    layer
+        .get(b"Name")
+        .and_then(Object::as_str)
+        .ok()
+        .map(<[u8]>::to_vec)
+}
+
+// printpdf adds a separate optional content group for every layer of every page,
+// groups with the same name are merged, so viewers toggle the layer on all pages at once
+pub(crate) fn merge_layers(document: &mut Document) -> Result<(), Error> {
+    let Ok(properties) = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"OCProperties"))
+        .and_then(Object::as_dict)
+    else {
+        return Ok(());
+    };
+    let layers = properties
+        .get(b"OCGs")
+        .and_then(Object::as_array)
+        .map_err(pdf_error)?
+        .iter()
+        .filter_map(|layer| layer.as_reference().ok())
+        .collect::<Vec<_>>();
+
+    let mut names: Vec<(Vec<u8>, ObjectId)> = vec![];
+    let mut replacements = vec![];
+    for id in layers {
+        let name = layer_name(document, id).unwrap_or_default();
+        match names.iter().find(|(existing, _)| *existing == name) {
+            Some((_, merged)) => replacements.push((id, *merged)),
+            None => names.push((name, id)),
+        }
+    }
+    if replacements.is_empty() {
+        return Ok(());
+    }
+
+    for page_id in document.get_pages().into_values() {
+        let resources_id = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Resources"))
+            .and_then(Object::as_reference);
+        let Ok(resources_id) = resources_id else {
+            continue;
+        };
+        let Ok(page_layers) = document
+            .get_object_mut(resources_id)
+            .and_then(Object::as_dict_mut)
+            .and_then(|resources| resources.get_mut(b"Properties"))
+            .and_then(Object::as_dict_mut)
+        else {
+            continue;
+        };
+        for (_, layer) in page_layers.iter_mut() {
+            if let Object::Reference(id) = layer
+                && let Some((_, merged)) = replacements.iter().find(|(from, _)| from == id)
+            {
+                *id = *merged;
+            }
+        }
+    }
+
+    for (id, _) in replacements.iter() {
+        document.objects.remove(id);
+    }
+
+    let layers = names
+        .into_iter()
+        .map(|(_, id)| Object::Reference(id))
+        .collect::<Vec<_>>();
+    let properties = catalog(document)?
+        .get_mut(b"OCProperties")
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    properties.set("OCGs", layers.clone());
+    if let Ok(configuration) = properties.get_mut(b"D").and_then(Object::as_dict_mut) {
+        configuration.set("Order", layers.clone());
+        configuration.set("ON", layers);
+    }
+    Ok(())
+}
+
+// optional content is removed, contents of layers are kept as plain marked content, e.g.
+// for profiles that predate optional content
+pub(crate) fn flatten_layers(document: &mut Document) -> Result<(), Error> {
+    let Some(properties) = catalog(document)?.remove(b"OCProperties") else {
+        return Ok(());
+    };
+    let layers = properties
+        .as_dict()
+        .and_then(|properties| properties.get(b"OCGs"))
+        .and_then(Object::as_array)
+        .map(|layers| {
+            layers
+                .iter()
+                .filter_map(|layer| layer.as_reference().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    for page_id in document.get_pages().into_values() {
+        let mut content = document
+            .get_and_decode_page_content(page_id)
+            .map_err(pdf_error)?;
+        for operation in content.operations.iter_mut() {
+            if operation.operator == "BDC"
+                && operation
+                    .operands
+                    .first()
+                    .and_then(|tag| tag.as_name().ok())
+                    == Some(b"OC")
+            {
+                operation.operator = "BMC".into();
+                operation.operands.truncate(1);
+            }
+        }
+        let content = content.encode().map_err(pdf_error)?;
+        document
+            .change_page_content(page_id, content)
+            .map_err(pdf_error)?;
+
+        let resources_id = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Resources"))
+            .and_then(Object::as_reference);
+        if let Ok(resources) = resources_id
+            .and_then(|id| document.get_object_mut(id))
+            .and_then(Object::as_dict_mut)
+        {
+            resources.remove(b"Properties");
+        }
+    }
+
+    for id in layers {
+        document.objects.remove(&id);
+    }
+    Ok(())
+}
+
+// layer of the marked content starting with the operation, printpdf wraps the content
+// of every layer in /OC /MCn BDC ... EMC
+pub(crate) fn marked_layer(
+    document: &Document,
+    page_id: ObjectId,
+    operation: &Operation,
+) -> Option<Vec<u8>> {
+    let [Object::Name(tag), Object::Name(property)] = operation.operands.as_slice() else {
+        return None;
+    };
+    if operation.operator != "BDC" || tag != b"OC" {
+        return None;
+    }
+    let id = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Resources"))
+        .and_then(Object::as_reference)
+        .and_then(|id| document.get_dictionary(id))
+        .and_then(|resources| resources.get(b"Properties"))
+        .and_then(Object::as_dict)
+        .and_then(|properties| properties.get(property))
+        .and_then(Object::as_reference)
+        .ok()?;
+    layer_name(document, id)
+}
+
+// content of page layers is reordered by their z-index, layers of the same z-index keep
+// the order they were created in
+pub(crate) fn stack_layers(
+    document: &mut Document,
+    z_index: impl Fn(&[u8]) -> i32,
+) -> Result<(), Error> {
+    for page_id in document.get_pages().into_values() {
+        let content = document
+            .get_and_decode_page_content(page_id)
+            .map_err(pdf_error)?;
+
+        // operations out of layers are kept in place with the z-index of the page content
+        let mut blocks: Vec<(i32, Vec<Operation>)> = vec![];
+        let mut depth = 0usize;
+        for operation in content.operations {
+            if depth == 0 {
+                let z_index = marked_layer(document, page_id, &operation)
+                    .map(|name| z_index(&name))
+                    .unwrap_or(0);
+                blocks.push((z_index, vec![]));
+            }
+            match operation.operator.as_str() {
+                "BDC" | "BMC" => depth += 1,
+                "EMC" => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if let Some((_, operations)) = blocks.last_mut() {
+                operations.push(operation);
+            }
+        }
+
+        if blocks.is_sorted_by_key(|(z_index, _)| *z_index) {
+            continue;
+        }
+        blocks.sort_by_key(|(z_index, _)| *z_index);
+        let operations: Vec<_> = blocks
+            .into_iter()
+            .flat_map(|(_, operations)| operations)
+            .collect();
+        let content = Content { operations }.encode().map_err(pdf_error)?;
+        document
+            .change_page_content(page_id, content)
+            .map_err(pdf_error)?;
+    }
+    Ok(())
+}
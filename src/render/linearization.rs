@@ -0,0 +1,640 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use layout::Error;
+use printpdf::lopdf::{Document, Object, ObjectId, xref::XrefType};
+
+use super::{merge::replace_references, resources::pdf_error};
+
+// the previous cross reference offset of the first page trailer is known once the file is
+// laid out, it is written padded, so the length of the trailer does not change
+const PLACEHOLDER: i64 = 9_999_999_999;
+
+// objects of the parts of the linearized file, in the order they are written
+struct Sections {
+    catalog: ObjectId,
+    // the page object first, everything the first page needs, including shared objects
+    first_page: Vec<ObjectId>,
+    // page objects first, of pages after the first one
+    pages: Vec<Vec<ObjectId>>,
+    // objects of more pages, which are not in the first page section
+    shared: Vec<ObjectId>,
+    // e.g. the page tree, outlines or structure
+    other: Vec<ObjectId>,
+    // shared objects pages after the first one refer to
+    page_shared: Vec<Vec<ObjectId>>,
+}
+
+impl Sections {
+    fn new(document: &Document, pages: &[ObjectId]) -> Result<Self, Error> {
+        let catalog = document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(pdf_error)?;
+        // traversal of objects of a page stops at other pages and nodes of the page tree
+        let mut tree = document
+            .objects
+            .iter()
+            .filter(|(_, object)| {
+                object
+                    .as_dict()
+                    .and_then(|dictionary| dictionary.get(b"Type"))
+                    .and_then(Object::as_name)
+                    .is_ok_and(|kind| kind == b"Pages")
+            })
+            .map(|(id, _)| *id)
+            .collect::<HashSet<_>>();
+        tree.extend(pages.iter().copied());
+        tree.insert(catalog);
+
+        let reached = pages
+            .iter()
+            .map(|page| reachable(document, *page, &tree))
+            .collect::<Vec<_>>();
+        let mut counts = HashMap::<ObjectId, usize>::new();
+        for id in reached.iter().flatten() {
+            *counts.entry(*id).or_default() += 1;
+        }
+        let is_shared = |id: &ObjectId| counts[id] > 1;
+
+        let first_page = [pages[0]]
+            .into_iter()
+            .chain(reached[0].iter().filter(|id| !is_shared(id)).copied())
+            .chain(reached[0].iter().filter(|id| is_shared(id)).copied())
+            .collect::<Vec<_>>();
+        let in_first_page = first_page.iter().copied().collect::<HashSet<_>>();
+        let mut shared = vec![];
+        let mut page_objects = vec![];
+        let mut page_shared = vec![];
+        for (page, reached) in pages.iter().zip(reached.iter()).skip(1) {
+            page_objects.push(
+                [*page]
+                    .into_iter()
+                    .chain(reached.iter().filter(|id| !is_shared(id)).copied())
+                    .collect(),
+            );
+            page_shared.push(reached.iter().filter(|id| is_shared(id)).copied().collect());
+            for id in reached.iter().filter(|id| is_shared(id)) {
+                if !in_first_page.contains(id) && !shared.contains(id) {
+                    shared.push(*id);
+                }
+            }
+        }
+
+        let written = [catalog]
+            .iter()
+            .chain(first_page.iter())
+            .chain(page_objects.iter().flatten())
+            .chain(shared.iter())
+            .copied()
+            .collect::<HashSet<_>>();
+        let other = document
+            .objects
+            .keys()
+            .filter(|id| !written.contains(id))
+            .copied()
+            .collect();
+
+        Ok(Self {
+            catalog,
+            first_page,
+            pages: page_objects,
+            shared,
+            other,
+            page_shared,
+        })
+    }
+
+    // objects after the first page section are numbered first, so the cross reference
+    // section of the first page covers the last numbers; the linearization dictionary and
+    // the hint stream are numbered before the catalog
+    fn renumbered(&self) -> HashMap<ObjectId, ObjectId> {
+        let rest = self.rest().count() as u32;
+        self.rest()
+            .enumerate()
+            .map(|(index, id)| (id, (index as u32 + 1, 0)))
+            .chain([(self.catalog, (rest + 2, 0))])
+            .chain(
+                self.first_page
+                    .iter()
+                    .enumerate()
+                    .map(|(index, id)| (*id, (rest + 4 + index as u32, 0))),
+            )
+            .collect()
+    }
+
+    fn rest(&self) -> impl Iterator<Item = ObjectId> + '_ {
+        self.pages
+            .iter()
+            .flatten()
+            .chain(self.shared.iter())
+            .chain(self.other.iter())
+            .copied()
+    }
+
+    fn map(&self, renumbered: &HashMap<ObjectId, ObjectId>) -> Self {
+        let map = |ids: &[ObjectId]| ids.iter().map(|id| renumbered[id]).collect::<Vec<_>>();
+        Self {
+            catalog: renumbered[&self.catalog],
+            first_page: map(&self.first_page),
+            pages: self.pages.iter().map(|ids| map(ids)).collect(),
+            shared: map(&self.shared),
+            other: map(&self.other),
+            page_shared: self.page_shared.iter().map(|ids| map(ids)).collect(),
+        }
+    }
+}
+
+// objects referred to by the object, except the tree and its parents, in the order found
+fn reachable(document: &Document, id: ObjectId, tree: &HashSet<ObjectId>) -> Vec<ObjectId> {
+    fn visit(
+        document: &Document,
+        object: &Object,
+        tree: &HashSet<ObjectId>,
+        reached: &mut Vec<ObjectId>,
+        visited: &mut HashSet<ObjectId>,
+    ) {
+        let dictionary = match object {
+            Object::Reference(id) => {
+                if tree.contains(id) || !visited.insert(*id) {
+                    return;
+                }
+                let Ok(object) = document.get_object(*id) else {
+                    return;
+                };
+                reached.push(*id);
+                visit(document, object, tree, reached, visited);
+                return;
+            }
+            Object::Array(array) => {
+                for value in array.iter() {
+                    visit(document, value, tree, reached, visited);
+                }
+                return;
+            }
+            Object::Dictionary(dictionary) => dictionary,
+            Object::Stream(stream) => &stream.dict,
+            _ => return,
+        };
+        for (key, value) in dictionary.iter() {
+            if key != b"Parent" {
+                visit(document, value, tree, reached, visited);
+            }
+        }
+    }
+
+    let mut reached = vec![];
+    if let Ok(object) = document.get_object(id) {
+        visit(document, object, tree, &mut reached, &mut HashSet::new());
+    }
+    reached
+}
+
+// offsets of objects and the trailer of the document as saved by lopdf
+struct Saved {
+    header: usize,
+    objects: BTreeMap<u32, (usize, usize)>,
+    trailer: (usize, usize),
+}
+
+impl Saved {
+    fn parse(pdf: &[u8]) -> Option<Self> {
+        let find = |from: usize, pattern: &[u8]| {
+            pdf[from..]
+                .windows(pattern.len())
+                .position(|window| window == pattern)
+                .map(|position| from + position)
+        };
+        let number = |from: usize| {
+            let digits = pdf[from..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            std::str::from_utf8(&pdf[from..from + digits])
+                .ok()?
+                .parse::<usize>()
+                .ok()
+                .map(|value| (value, from + digits))
+        };
+
+        let startxref = pdf
+            .windows(b"startxref".len())
+            .rposition(|window| window == b"startxref")?;
+        let (xref, _) = number(startxref + b"startxref\n".len())?;
+        let trailer = find(xref, b"trailer\n")?;
+
+        let mut offsets = BTreeMap::new();
+        let mut position = xref + b"xref\n".len();
+        while position < trailer {
+            let (first, end) = number(position)?;
+            let (count, end) = number(end + 1)?;
+            position = end + 1;
+            for index in 0..count {
+                let entry = &pdf[position..position + 20];
+                if entry[17] == b'n' {
+                    let (offset, _) = number(position)?;
+                    offsets.insert((first + index) as u32, offset);
+                }
+                position += 20;
+            }
+        }
+
+        let mut objects = BTreeMap::new();
+        let mut starts = offsets.iter().peekable();
+        while let Some((id, start)) = starts.next() {
+            let end = starts.peek().map(|(_, end)| **end).unwrap_or(xref);
+            objects.insert(*id, (*start, end));
+        }
+        Some(Self {
+            header: *offsets.values().min()?,
+            objects,
+            trailer: (trailer + b"trailer\n".len(), startxref - 1),
+        })
+    }
+
+    fn object<'a>(&self, pdf: &'a [u8], id: ObjectId) -> &'a [u8] {
+        let (start, end) = self.objects[&id.0];
+        &pdf[start..end]
+    }
+}
+
+// the catalog, hint stream and objects of the first page are written first, so viewers
+// display it before the rest of the document is loaded; encrypted documents are not
+// supported, object numbers are a part of their keys
+pub(crate) fn linearize(document: &mut Document) -> Result<Vec<u8>, Error> {
+    let pages = document.get_pages().into_values().collect::<Vec<_>>();
+    if pages.is_empty() {
+        return Err(Error::PdfWrite("Linearized document has no pages".into()));
+    }
+    // lopdf does not write cross reference and object streams, e.g. of the loaded document
+    document.objects.retain(|_, object| {
+        !object
+            .type_name()
+            .is_ok_and(|name| ["ObjStm", "XRef", "Linearized"].contains(&name))
+    });
+    let sections = Sections::new(document, &pages)?;
+    let renumbered = sections.renumbered();
+    let sections = sections.map(&renumbered);
+
+    for (id, mut object) in std::mem::take(&mut document.objects) {
+        replace_references(&mut object, &renumbered);
+        document.objects.insert(renumbered[&id], object);
+    }
+    let mut trailer = std::mem::take(&mut document.trailer);
+    for (_, value) in trailer.iter_mut() {
+        replace_references(value, &renumbered);
+    }
+    for key in [
+        &b"Prev"[..],
+        b"XRefStm",
+        b"Type",
+        b"W",
+        b"Index",
+        b"Filter",
+        b"Length",
+    ] {
+        trailer.remove(key);
+    }
+    trailer.set("Prev", PLACEHOLDER);
+    document.trailer = trailer;
+    // numbers of the linearization dictionary and the hint stream are left free
+    let size = renumbered.len() as u32 + 3;
+    document.max_id = size - 1;
+    document.reference_table.cross_reference_type = XrefType::CrossReferenceTable;
+
+    let mut pdf = vec![];
+    document.save_to(&mut pdf).map_err(pdf_error)?;
+    let saved = Saved::parse(&pdf)
+        .ok_or_else(|| Error::PdfWrite("Saved document can not be linearized".into()))?;
+    Ok(assemble(&pdf, &saved, &sections, pages.len(), size))
+}
+
+fn assemble(pdf: &[u8], saved: &Saved, sections: &Sections, pages: usize, size: u32) -> Vec<u8> {
+    let rest = sections.rest().count() as u32;
+    let linearization_id = rest + 1;
+    let hint_id = rest + 3;
+    let lengths = saved
+        .objects
+        .iter()
+        .map(|(id, (start, end))| ((*id, 0), end - start))
+        .collect::<HashMap<_, _>>();
+
+    let header = [&pdf[..saved.header], b"%\xE2\xE3\xCF\xD3\n"].concat();
+    let linearization = |[length, hint_offset, hint_length, end, main_xref]: [usize; 5]| {
+        format!(
+            "{linearization_id} 0 obj\n<< /Linearized 1 /L {length:010} /H [ {hint_offset:010} \
+             {hint_length:010} ] /O {} /E {end:010} /N {pages} /T {main_xref:010} >>\nendobj\n",
+            sections.first_page[0].0
+        )
+        .into_bytes()
+    };
+    let first_xref = |offsets: &[usize], prev: usize| {
+        let mut xref = format!("xref\n{linearization_id} {}\n", offsets.len());
+        for offset in offsets {
+            xref.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+        let trailer = String::from_utf8_lossy(&pdf[saved.trailer.0..saved.trailer.1])
+            .replace(&PLACEHOLDER.to_string(), &format!("{prev:010}"));
+        format!("{xref}trailer\n{trailer}\nstartxref\n0\n%%EOF\n").into_bytes()
+    };
+    let first_xref_offset = header.len() + linearization([0; 5]).len();
+    let first_entries = (size - linearization_id) as usize;
+    let prefix = first_xref_offset + first_xref(&vec![0; first_entries], 0).len();
+
+    // offsets in hint tables are as if the hint stream was not written
+    let mut offsets = HashMap::new();
+    let mut end = prefix;
+    for id in [sections.catalog]
+        .into_iter()
+        .chain(sections.first_page.iter().copied())
+        .chain(sections.rest())
+    {
+        offsets.insert(id, end);
+        end += lengths[&id];
+    }
+    let (data, shared_table) = hint_tables(sections, &offsets, &lengths);
+    let mut hint = format!(
+        "{hint_id} 0 obj\n<< /Length {} /S {shared_table} >>\nstream\n",
+        data.len()
+    )
+    .into_bytes();
+    hint.extend(data);
+    hint.extend(b"\nendstream\nendobj\n");
+
+    let hint_offset = prefix + lengths[&sections.catalog];
+    let offset = |id: &ObjectId| match *id == sections.catalog {
+        true => offsets[id],
+        false => offsets[id] + hint.len(),
+    };
+    let first_page_end = hint_offset + hint.len() + length(&sections.first_page, &lengths);
+    let main_xref = end + hint.len();
+    let mut main = format!("xref\n0 {}", rest + 1);
+    let main_entries = main_xref + main.len();
+    main.push_str("\n0000000000 65535 f \n");
+    for id in 1..=rest {
+        main.push_str(&format!("{:010} 00000 n \n", offset(&(id, 0))));
+    }
+    main.push_str(&format!(
+        "trailer\n<< /Size {} >>\nstartxref\n{first_xref_offset}\n%%EOF\n",
+        rest + 1
+    ));
+    let total = main_xref + main.len();
+
+    let first_offsets = [header.len(), prefix, hint_offset]
+        .into_iter()
+        .chain(sections.first_page.iter().map(offset))
+        .collect::<Vec<_>>();
+    let mut linearized = header;
+    linearized.extend(linearization([
+        total,
+        hint_offset,
+        hint.len(),
+        first_page_end,
+        main_entries,
+    ]));
+    linearized.extend(first_xref(&first_offsets, main_xref));
+    linearized.extend_from_slice(saved.object(pdf, sections.catalog));
+    linearized.extend(hint);
+    for id in sections.first_page.iter().copied().chain(sections.rest()) {
+        linearized.extend_from_slice(saved.object(pdf, id));
+    }
+    linearized.extend(main.into_bytes());
+    linearized
+}
+
+fn length(ids: &[ObjectId], lengths: &HashMap<ObjectId, usize>) -> usize {
+    ids.iter().map(|id| lengths[id]).sum()
+}
+
+fn bits(value: usize) -> u32 {
+    usize::BITS - value.leading_zeros()
+}
+
+// values of hint tables are packed by bits, from the most significant one
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: usize, bits: u32) {
+        for bit in (0..bits).rev() {
+            if self.used == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+            }
+            self.used = (self.used + 1) % 8;
+        }
+    }
+
+    // items of entries start at a byte boundary
+    fn flush(&mut self) {
+        self.used = 0;
+    }
+}
+
+// page offset and shared object hint tables, and the offset of the latter; shared object
+// groups are single objects, the first page ones are all objects of its section
+fn hint_tables(
+    sections: &Sections,
+    offsets: &HashMap<ObjectId, usize>,
+    lengths: &HashMap<ObjectId, usize>,
+) -> (Vec<u8>, usize) {
+    let pages = [&sections.first_page]
+        .into_iter()
+        .chain(sections.pages.iter())
+        .collect::<Vec<_>>();
+    let identifier = |id: &ObjectId| match sections.first_page.iter().position(|first| first == id)
+    {
+        Some(index) => index,
+        None => {
+            let index = sections.shared.iter().position(|shared| shared == id);
+            sections.first_page.len() + index.unwrap_or_default()
+        }
+    };
+    let identifiers = [vec![]]
+        .into_iter()
+        .chain(
+            sections
+                .page_shared
+                .iter()
+                .map(|ids| ids.iter().map(identifier).collect::<Vec<_>>()),
+        )
+        .collect::<Vec<_>>();
+    let objects = pages.iter().map(|ids| ids.len()).collect::<Vec<_>>();
+    let page_lengths = pages
+        .iter()
+        .map(|ids| length(ids, lengths))
+        .collect::<Vec<_>>();
+    let (least_objects, most_objects) = min_max(&objects);
+    let (least_length, most_length) = min_max(&page_lengths);
+    let length_bits = bits(most_length - least_length);
+    let shared_bits = bits(identifiers.iter().map(Vec::len).max().unwrap_or_default());
+    let identifier_bits = bits(
+        identifiers
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or_default(),
+    );
+
+    let mut writer = BitWriter::default();
+    writer.write(least_objects, 32);
+    writer.write(offsets[&sections.first_page[0]], 32);
+    writer.write(bits(most_objects - least_objects) as usize, 16);
+    writer.write(least_length, 32);
+    writer.write(length_bits as usize, 16);
+    // content streams are not located, their offsets are 0 and lengths those of pages
+    writer.write(0, 32);
+    writer.write(0, 16);
+    writer.write(least_length, 32);
+    writer.write(length_bits as usize, 16);
+    writer.write(shared_bits as usize, 16);
+    writer.write(identifier_bits as usize, 16);
+    writer.write(0, 16);
+    writer.write(1, 16);
+    let mut column = |values: &mut dyn Iterator<Item = usize>, bits: u32| {
+        for value in values {
+            writer.write(value, bits);
+        }
+        writer.flush();
+    };
+    column(
+        &mut objects.iter().map(|count| count - least_objects),
+        bits(most_objects - least_objects),
+    );
+    column(
+        &mut page_lengths.iter().map(|length| length - least_length),
+        length_bits,
+    );
+    column(&mut identifiers.iter().map(Vec::len), shared_bits);
+    column(&mut identifiers.iter().flatten().copied(), identifier_bits);
+    column(&mut identifiers.iter().flatten().map(|_| 0), 0);
+    column(&mut page_lengths.iter().map(|_| 0), 0);
+    column(
+        &mut page_lengths.iter().map(|length| length - least_length),
+        length_bits,
+    );
+    let shared_table = writer.bytes.len();
+
+    let groups = sections
+        .first_page
+        .iter()
+        .chain(sections.shared.iter())
+        .map(|id| lengths[id])
+        .collect::<Vec<_>>();
+    let (least_group, most_group) = min_max(&groups);
+    let group_bits = bits(most_group - least_group);
+    let (first_shared, first_shared_offset) = match sections.shared.first() {
+        Some(id) => (id.0 as usize, offsets[id]),
+        None => (0, 0),
+    };
+    writer.write(first_shared, 32);
+    writer.write(first_shared_offset, 32);
+    writer.write(sections.first_page.len(), 32);
+    writer.write(groups.len(), 32);
+    writer.write(0, 16);
+    writer.write(least_group, 32);
+    writer.write(group_bits as usize, 16);
+    for group in groups.iter() {
+        writer.write(group - least_group, group_bits);
+    }
+    writer.flush();
+    // no group has a signature
+    for _ in groups.iter() {
+        writer.write(0, 1);
+    }
+    writer.flush();
+
+    (writer.bytes, shared_table)
+}
+
+fn min_max(values: &[usize]) -> (usize, usize) {
+    let least = values.iter().copied().min().unwrap_or_default();
+    let most = values.iter().copied().max().unwrap_or_default();
+    (least, most)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{PdfDocument, lopdf::Document};
+
+    use crate::{Image, ImageColorSpace, RenderContext, new_font_cache};
+
+    // value of the key in the linearization dictionary
+    fn value(pdf: &[u8], key: &str) -> usize {
+        let dictionary = String::from_utf8_lossy(&pdf[..200]);
+        let start = dictionary.find(&format!("/{key} ")).unwrap() + key.len() + 2;
+        dictionary[start..]
+            .trim_start_matches("[ ")
+            .split(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn linearization() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_linearization(true);
+        let image = Image::from_raw(2, 2, ImageColorSpace::Gray, vec![0, 255, 255, 0]).unwrap();
+        for page in 0..3 {
+            if page > 0 {
+                layout::RenderContext::new_page(&mut rctx, None);
+            }
+            rctx.image(&Offset::zero(), &Size::fixed(Mm(20.0), Mm(20.0)), &image);
+        }
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_linearization.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let dictionary = String::from_utf8_lossy(&pdf[..200]);
+        assert!(dictionary.contains(" 0 obj\n<< /Linearized 1 "));
+        assert_eq!(value(&pdf, "L"), pdf.len());
+        assert_eq!(value(&pdf, "N"), 3);
+        let hint = value(&pdf, "H");
+        assert!(String::from_utf8_lossy(&pdf[hint..hint + 20]).contains(" 0 obj\n<< /Length "));
+        // the main cross reference table starts by the free entry
+        let main_xref = value(&pdf, "T");
+        assert_eq!(&pdf[main_xref..main_xref + 21], b"\n0000000000 65535 f \n");
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 3);
+        // the first page is the object the linearization dictionary points to
+        assert_eq!(pages[&1].0 as usize, value(&pdf, "O"));
+        let first_page = format!("{} 0 obj", pages[&1].0);
+        let position = dictionary.len()
+            + pdf[dictionary.len()..]
+                .windows(first_page.len())
+                .position(|window| window == first_page.as_bytes())
+                .unwrap();
+        assert!(position < value(&pdf, "E"));
+        assert!(value(&pdf, "E") < main_xref);
+    }
+}
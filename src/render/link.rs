@@ -0,0 +1,152 @@
+use layout::{
+    Error, Layout, MeasureContext, RenderContext,
+    position::{Offset, Size},
+};
+
+use super::{LinkTarget, Mark, Marks};
+
+// box laid out for the element and the target of its link
+pub(crate) type LinkMark = (Offset, Size, LinkTarget);
+
+// links are taken over by the render context, which annotates the box of the element on
+// the page it is rendered on
+impl Marks {
+    // the whole element is clickable, e.g. a logo or a button of a form
+    pub fn link(&self, uri: impl Into<String>, layout: impl Layout + 'static) -> LinkLayout {
+        self.link_layout(LinkTarget::Uri(uri.into()), layout)
+    }
+
+    // the anchor may be defined later in the document
+    pub fn internal_link(
+        &self,
+        anchor: impl Into<String>,
+        layout: impl Layout + 'static,
+    ) -> LinkLayout {
+        self.link_layout(LinkTarget::Anchor(anchor.into()), layout)
+    }
+
+    fn link_layout(&self, target: LinkTarget, layout: impl Layout + 'static) -> LinkLayout {
+        LinkLayout {
+            layout: Box::new(layout),
+            target,
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LinkLayout {
+    layout: Box<dyn Layout>,
+    target: LinkTarget,
+    marks: Marks,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for LinkLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    // the link moves to the next page together with the element if it does not fit
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        self.marks.push(Mark::Link((
+            self.offset.clone(),
+            self.size.clone(),
+            self.target.clone(),
+        )));
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+        self.layout.render(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Layout,
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{PdfDocument, lopdf::Document};
+
+    use crate::{Marks, RenderContext, new_font_cache};
+
+    use super::super::test_layouts::Rule;
+
+    #[test]
+    fn link() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        rctx.anchor("start", &Offset::new(Mm(0.0), Mm(0.0)));
+        let size = Size::fixed(Mm(20.0), Mm(30.0));
+        let mut render = |mut layout: Box<dyn Layout>, y: f64| {
+            layout.measure(&mut rctx, size.clone()).unwrap();
+            layout
+                .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(y)), size.clone())
+                .unwrap();
+            layout.render(&mut rctx).unwrap();
+        };
+        render(
+            Box::new(marks.link("https://example.com", Rule::new(30.0))),
+            0.0,
+        );
+        // the element past the page end takes its link to the next page
+        render(
+            Box::new(marks.internal_link("start", Rule::new(30.0))),
+            260.0,
+        );
+        assert!(marks.take().is_empty());
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        let annotations = |page: u32| {
+            let page = document.get_dictionary(pages[&page]).unwrap();
+            page.get(b"Annots")
+                .and_then(|annotations| annotations.as_array())
+                .map_or(0, |annotations| annotations.len())
+        };
+        assert_eq!((annotations(1), annotations(2)), (1, 1));
+
+        // the box of the element in mm, from the bottom left corner of the page
+        let page = document.get_dictionary(pages[&1]).unwrap();
+        let annotation = page.get(b"Annots").unwrap().as_array().unwrap()[0]
+            .as_reference()
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        let rect = annotation
+            .get(b"Rect")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| (value.as_float().unwrap() * 25.4 / 72.0).round())
+            .collect::<Vec<_>>();
+        assert_eq!(rect, vec![10.0, 257.0, 30.0, 287.0]);
+    }
+}
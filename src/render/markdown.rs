@@ -0,0 +1,318 @@
+use layout::LayoutBox;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use super::{
+    RichTextStyles,
+    rich_text::{Block, Cells, Lines, Run, push_run},
+};
+
+// CommonMark subset of headings, paragraphs, lists, emphasis, tables and code blocks
+// converted into layout elements, e.g. user provided text of a report; other elements
+// are dropped, their text is kept
+pub struct MarkdownConverter {
+    styles: RichTextStyles,
+}
+
+impl MarkdownConverter {
+    pub fn new(styles: RichTextStyles) -> Self {
+        Self { styles }
+    }
+
+    pub fn convert(&self, markdown: &str) -> LayoutBox {
+        self.styles.layout(&parse(markdown))
+    }
+}
+
+#[derive(Default)]
+struct BlockParser {
+    // blocks of the document and of list items being parsed
+    containers: Vec<Vec<Block>>,
+    lists: Vec<(Option<u64>, Vec<Vec<Block>>)>,
+    lines: Lines,
+    line: Vec<Run>,
+    emphasis: usize,
+    strong: usize,
+    code_block: Option<String>,
+    table: Option<(Cells, Vec<Cells>)>,
+    row: Cells,
+}
+
+impl BlockParser {
+    fn push_run(&mut self, text: &str, code: bool) {
+        let run = Run {
+            text: text.into(),
+            emphasis: self.emphasis > 0,
+            strong: self.strong > 0,
+            code,
+            ..Run::default()
+        };
+        push_run(&mut self.line, run);
+    }
+
+    fn take_lines(&mut self) -> Lines {
+        if !self.line.is_empty() {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+        std::mem::take(&mut self.lines)
+    }
+
+    fn push_block(&mut self, block: Block) {
+        if let Some(container) = self.containers.last_mut() {
+            container.push(block);
+        }
+    }
+
+    // items of tight lists have no paragraphs, their text is ended by the item or a nested list
+    fn push_item_text(&mut self) {
+        let lines = self.take_lines();
+        if !lines.is_empty() {
+            self.push_block(Block::Paragraph(lines));
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Emphasis => self.emphasis += 1,
+            Tag::Strong => self.strong += 1,
+            Tag::List(start) => {
+                self.push_item_text();
+                self.lists.push((start, vec![]));
+            }
+            Tag::Item => self.containers.push(vec![]),
+            Tag::CodeBlock(_) => self.code_block = Some(String::new()),
+            Tag::Table(_) => self.table = Some((vec![], vec![])),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Emphasis => self.emphasis = self.emphasis.saturating_sub(1),
+            TagEnd::Strong => self.strong = self.strong.saturating_sub(1),
+            TagEnd::Paragraph => {
+                let lines = self.take_lines();
+                self.push_block(Block::Paragraph(lines));
+            }
+            TagEnd::Heading(level) => {
+                let lines = self.take_lines();
+                self.push_block(Block::Heading(level as usize, lines));
+            }
+            TagEnd::Item => {
+                self.push_item_text();
+                let item = self.containers.pop().unwrap_or_default();
+                if let Some((_, items)) = self.lists.last_mut() {
+                    items.push(item);
+                }
+            }
+            TagEnd::List(_) => {
+                if let Some((start, items)) = self.lists.pop() {
+                    self.push_block(Block::List(start, items));
+                }
+            }
+            TagEnd::CodeBlock => {
+                if let Some(code) = self.code_block.take() {
+                    let lines = code.trim_end_matches('\n').lines().map(String::from);
+                    self.push_block(Block::Preformatted(lines.collect()));
+                }
+            }
+            TagEnd::TableCell => {
+                let cell = self.take_lines().concat();
+                self.row.push(cell);
+            }
+            TagEnd::TableHead => {
+                if let Some((header, _)) = self.table.as_mut() {
+                    *header = std::mem::take(&mut self.row);
+                }
+            }
+            TagEnd::TableRow => {
+                if let Some((_, rows)) = self.table.as_mut() {
+                    rows.push(std::mem::take(&mut self.row));
+                }
+            }
+            TagEnd::Table => {
+                if let Some((header, rows)) = self.table.take() {
+                    self.push_block(Block::Table(header, rows));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn parse(markdown: &str) -> Vec<Block> {
+    let mut parser = BlockParser {
+        containers: vec![vec![]],
+        ..BlockParser::default()
+    };
+    let options = Options::ENABLE_TABLES;
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(tag) => parser.start(tag),
+            Event::End(tag) => parser.end(tag),
+            Event::Text(text) => match parser.code_block.as_mut() {
+                Some(code) => code.push_str(&text),
+                None => parser.push_run(&text, false),
+            },
+            Event::Code(code) => parser.push_run(&code, true),
+            Event::SoftBreak => parser.push_run(" ", false),
+            Event::HardBreak => {
+                let line = std::mem::take(&mut parser.line);
+                parser.lines.push(line);
+            }
+            _ => {}
+        }
+    }
+    parser.containers.pop().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, Layout, StyleBuilder,
+        position::{Quad, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::PdfDocument;
+
+    use crate::{
+        MarkdownConverter, Marks, RenderContext, Renderer, RichTextStyles, new_font_cache,
+        render::rich_text::{Block, Run, plain_text},
+    };
+
+    use super::parse;
+
+    fn plain(text: &str) -> Run {
+        run(text, false, false, false)
+    }
+
+    fn run(text: &str, emphasis: bool, strong: bool, code: bool) -> Run {
+        Run {
+            text: text.into(),
+            emphasis,
+            strong,
+            code,
+            ..Run::default()
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let blocks = parse(
+            "# Report\n\nSome *emphasized* and **strong**\ntext with `code`.\n\n\
+             - one\n- two\n\n3. three\n\n```\nlet a = 1;\nlet b = 2;\n```\n\n\
+             | Item | Price |\n|---|---|\n| Tea | 3 |\n",
+        );
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading(1, vec![vec![plain("Report")]]),
+                Block::Paragraph(vec![vec![
+                    plain("Some "),
+                    run("emphasized", true, false, false),
+                    plain(" and "),
+                    run("strong", false, true, false),
+                    plain(" text with "),
+                    run("code", false, false, true),
+                    plain("."),
+                ]]),
+                Block::List(
+                    None,
+                    vec![
+                        vec![Block::Paragraph(vec![vec![plain("one")]])],
+                        vec![Block::Paragraph(vec![vec![plain("two")]])],
+                    ]
+                ),
+                Block::List(
+                    Some(3),
+                    vec![vec![Block::Paragraph(vec![vec![plain("three")]])]]
+                ),
+                Block::Preformatted(vec!["let a = 1;".into(), "let b = 2;".into()]),
+                Block::Table(
+                    vec![vec![plain("Item")], vec![plain("Price")]],
+                    vec![vec![vec![plain("Tea")], vec![plain("3")]]]
+                ),
+            ]
+        );
+
+        assert_eq!(
+            parse("- outer\n  - inner\n\nline  \nbreak"),
+            vec![
+                Block::List(
+                    None,
+                    vec![vec![
+                        Block::Paragraph(vec![vec![plain("outer")]]),
+                        Block::List(
+                            None,
+                            vec![vec![Block::Paragraph(vec![vec![plain("inner")]])]]
+                        ),
+                    ]]
+                ),
+                Block::Paragraph(vec![vec![plain("line")], vec![plain("break")]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        let style = StyleBuilder::default().with_font(Font::new("LatoReg", Pt(10.0), None));
+        let marks = Marks::new();
+        let converter = MarkdownConverter::new(
+            RichTextStyles::new(style.clone())
+                .with_headings([style.clone()])
+                .with_block_spacing(Pt(6.0))
+                .with_marks(marks),
+        );
+        let markdown = "# Report\n\nPlain words *and a long emphasized run of words wrapped \
+                        over lines of the paragraph* end.\n\n- one\n- two\n\n\
+                        | Item | Price |\n|---|---|\n| Tea | 3 |\n";
+        let layout = converter.convert(markdown);
+
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        // runs of the paragraph wrap as one sequence
+        let Block::Paragraph(lines) = &parse(markdown)[1] else {
+            panic!("paragraph expected");
+        };
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts.clone(),
+        );
+        let mut paragraph = converter.styles.line(&lines[0], &style);
+        paragraph
+            .measure(&mut rctx, Size::fixed(Mm(40.0), Mm(100.0)))
+            .unwrap();
+        let wrapped = paragraph.lines();
+        assert!(wrapped.len() > 2);
+        let text = wrapped
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|(_, text)| text.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(text, plain_text(lines));
+        let emphasized = wrapped
+            .iter()
+            .filter(|line| line.iter().any(|(index, _)| *index == 1))
+            .count();
+        assert!(emphasized > 1);
+
+        let renderer = Renderer::new(
+            "Markdown",
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        assert!(renderer.render_layout(Box::new(layout)).is_ok());
+    }
+}
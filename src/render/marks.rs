@@ -0,0 +1,83 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use layout::unit::Unit;
+
+use super::{
+    ColumnMark, FootnoteMark, LinkMark, PageBreakOptions, SpanMark, StrokeMark, header::HeaderMark,
+    path::PathPaint, section::SectionMark,
+};
+
+#[derive(Clone, Debug)]
+pub(crate) enum Mark {
+    Span(SpanMark),
+    Path(PathPaint),
+    Footnote(FootnoteMark),
+    Link(LinkMark),
+    Column(ColumnMark),
+    PageBreak(PageBreakOptions),
+    Header(HeaderMark),
+    Stroke(StrokeMark),
+    Section(SectionMark),
+}
+
+#[derive(Debug, Default)]
+struct MarkQueue {
+    marks: RefCell<Vec<Mark>>,
+    // offsets of paragraphs laid out and heights of their lines kept with a heading
+    paragraphs: RefCell<Vec<(Unit, Unit)>>,
+    // kerning of spans being measured, the last one is the innermost
+    kerning: RefCell<Vec<bool>>,
+    wired: Cell<bool>,
+}
+
+// layouts render through the layout render context, which knows its primitives only, so
+// the rest, e.g. spans, links, paths, footnotes or sections of headings, is queued to the
+// marks and taken over by the render context in the order queued with the next primitive
+// or page break check; the options of a page break are used by the check that follows them
+#[derive(Clone, Debug, Default)]
+pub struct Marks(Rc<MarkQueue>);
+
+impl Marks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // whether a render context takes the marks, otherwise nothing queued is rendered
+    pub fn is_wired(&self) -> bool {
+        self.0.wired.get()
+    }
+
+    pub(crate) fn wire(&self) {
+        self.0.wired.set(true);
+    }
+
+    pub(crate) fn push(&self, mark: Mark) {
+        self.0.marks.borrow_mut().push(mark);
+    }
+
+    pub(crate) fn take(&self) -> Vec<Mark> {
+        std::mem::take(&mut *self.0.marks.borrow_mut())
+    }
+
+    pub(crate) fn paragraphs(&self) -> &RefCell<Vec<(Unit, Unit)>> {
+        &self.0.paragraphs
+    }
+
+    // text typeset by the measure has the kerning, unless set it is of the enclosing span
+    pub(crate) fn with_kerning<R>(&self, kerning: Option<bool>, measure: impl FnOnce() -> R) -> R {
+        let Some(kerning) = kerning else {
+            return measure();
+        };
+        self.0.kerning.borrow_mut().push(kerning);
+        let result = measure();
+        self.0.kerning.borrow_mut().pop();
+        result
+    }
+
+    pub(crate) fn kerning(&self) -> Option<bool> {
+        self.0.kerning.borrow().last().copied()
+    }
+}
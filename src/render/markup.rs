@@ -0,0 +1,88 @@
+use layout::Rgba;
+use printpdf::{Rect, lopdf::Dictionary};
+
+use super::annotations::{annotation, color_array, text_string};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MarkupKind {
+    Highlight,
+    Underline,
+    Squiggly,
+    StrikeOut,
+}
+
+// text markup annotation, shown by viewers over the marked text and listed as a comment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Markup {
+    kind: MarkupKind,
+    color: Rgba,
+    contents: Option<String>,
+    author: Option<String>,
+}
+
+impl Markup {
+    pub fn highlight(color: Rgba) -> Self {
+        Self::new(MarkupKind::Highlight, color)
+    }
+
+    pub fn underline(color: Rgba) -> Self {
+        Self::new(MarkupKind::Underline, color)
+    }
+
+    pub fn squiggly(color: Rgba) -> Self {
+        Self::new(MarkupKind::Squiggly, color)
+    }
+
+    pub fn strike_out(color: Rgba) -> Self {
+        Self::new(MarkupKind::StrikeOut, color)
+    }
+
+    fn new(kind: MarkupKind, color: Rgba) -> Self {
+        Self {
+            kind,
+            color,
+            contents: None,
+            author: None,
+        }
+    }
+
+    pub fn with_contents(mut self, contents: impl Into<String>) -> Self {
+        self.contents = Some(contents.into());
+        self
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    // corners in pt go counterclockwise from the bottom left one of the marked text
+    pub(crate) fn annotation(&self, rect: &Rect, corners: [(f32, f32); 4]) -> Dictionary {
+        let subtype = match self.kind {
+            MarkupKind::Highlight => "Highlight",
+            MarkupKind::Underline => "Underline",
+            MarkupKind::Squiggly => "Squiggly",
+            MarkupKind::StrikeOut => "StrikeOut",
+        };
+
+        let mut markup = annotation(subtype, rect);
+        // viewers expect top left, top right, bottom left, bottom right
+        let [bottom_left, bottom_right, top_right, top_left] = corners;
+        markup.set(
+            "QuadPoints",
+            [top_left, top_right, bottom_left, bottom_right]
+                .into_iter()
+                .flat_map(|(x, y)| [x.into(), y.into()])
+                .collect::<Vec<_>>(),
+        );
+        markup.set("C", color_array(&self.color));
+        if let Some(contents) = &self.contents {
+            markup.set("Contents", text_string(contents));
+        }
+        if let Some(author) = &self.author {
+            markup.set("T", text_string(author));
+        }
+        markup.set("F", 4);
+        markup
+    }
+}
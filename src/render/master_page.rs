@@ -0,0 +1,215 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, Stream, content::Content};
+
+use super::{
+    layers::marked_layer,
+    resources::pdf_error,
+    stationery::{page_box, stamp_form},
+};
+
+// the master page is rendered to the layer of a scratch page for every page size
+pub(crate) const MASTER_PAGE_LAYER: &str = "master page";
+
+// content of the master page layer of scratch pages is moved into form xobjects painted
+// under the content of pages of the same size, scratch pages are removed then
+pub(crate) fn stamp_master_pages(
+    document: &mut Document,
+    master_pages: &[(usize, Vec<usize>)],
+) -> Result<(), Error> {
+    let pages = document.get_pages();
+    let page_id = |page: usize| pages.get(&(page as u32 + 1)).copied();
+    for (master_page, stamped) in master_pages {
+        let Some(master_id) = page_id(*master_page) else {
+            return Err(Error::PdfWrite("Master page refers to missing page".into()));
+        };
+        let Some(form_id) = master_form(document, master_id)? else {
+            continue;
+        };
+        for page_id in stamped.iter().filter_map(|page| page_id(*page)) {
+            stamp_form(
+                document,
+                page_id,
+                "PRMasterPage",
+                Object::Reference(form_id),
+                (0.0, 0.0),
+            )?;
+        }
+    }
+
+    let mut scratch_pages = vec![];
+    for (master_page, _) in master_pages {
+        if let Some(master_id) = page_id(*master_page) {
+            for content_id in document.get_page_contents(master_id) {
+                document.objects.remove(&content_id);
+            }
+            scratch_pages.push(*master_page as u32 + 1);
+        }
+    }
+    document.delete_pages(&scratch_pages);
+    Ok(())
+}
+
+fn master_form(document: &mut Document, master_id: ObjectId) -> Result<Option<ObjectId>, Error> {
+    let content = document
+        .get_and_decode_page_content(master_id)
+        .map_err(pdf_error)?;
+    let mut master = vec![];
+    let mut depth = 0usize;
+    let mut in_master = false;
+    for operation in content.operations {
+        let starts = depth == 0;
+        if starts {
+            in_master = marked_layer(document, master_id, &operation)
+                .is_some_and(|name| name == MASTER_PAGE_LAYER.as_bytes());
+        }
+        match operation.operator.as_str() {
+            "BDC" | "BMC" => depth += 1,
+            "EMC" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        // the form is not optional content, the marked content of the layer is dropped
+        if in_master && !starts && depth > 0 {
+            master.push(operation);
+        }
+    }
+    if master.is_empty() {
+        return Ok(None);
+    }
+
+    let Some(media_box) = page_box(document, master_id) else {
+        return Err(Error::PdfWrite("Master page has no media box".into()));
+    };
+    // copied, the scratch page is removed
+    let resources = match document
+        .get_dictionary(master_id)
+        .and_then(|page| page.get(b"Resources"))
+    {
+        Ok(Object::Reference(id)) => document.get_dictionary(*id).cloned().map_err(pdf_error)?,
+        Ok(Object::Dictionary(resources)) => resources.clone(),
+        _ => Dictionary::new(),
+    };
+    let mut form = Dictionary::new();
+    form.set("Type", Object::Name(b"XObject".to_vec()));
+    form.set("Subtype", Object::Name(b"Form".to_vec()));
+    form.set("BBox", media_box.map(Object::Real).to_vec());
+    form.set("Resources", resources);
+    let content = Content { operations: master }.encode().map_err(pdf_error)?;
+    Ok(Some(document.add_object(Stream::new(form, content))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{
+        position::{Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object},
+    };
+
+    use crate::{RenderContext, new_font_cache, render::test_layouts::Rule};
+
+    // margin line of the master page
+    fn margin_line() -> Rule {
+        Rule::new(297.0).at(20.0)
+    }
+
+    #[test]
+    fn master_page() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        rctx.set_master_page(Box::new(margin_line())).unwrap();
+        rctx.complete_fonts().unwrap();
+        layout::RenderContext::new_page(&mut rctx, None);
+        rctx.set_page_setup(
+            &Quad::square(Mm(10.0)),
+            &Size::fixed(Mm(210.0), Mm(148.0)),
+            false,
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_master_page.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let forms = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| {
+                stream
+                    .dict
+                    .get(b"Subtype")
+                    .and_then(Object::as_name)
+                    .is_ok_and(|subtype| subtype == b"Form")
+            })
+            .collect::<Vec<_>>();
+        // one form for every page size, scratch pages are removed
+        assert_eq!(forms.len(), 2);
+        let mut heights = forms
+            .iter()
+            .map(|form| {
+                let bbox = form.dict.get(b"BBox").and_then(Object::as_array).unwrap();
+                bbox[3].as_float().unwrap().round()
+            })
+            .collect::<Vec<_>>();
+        heights.sort_by(f32::total_cmp);
+        assert_eq!(heights, [420.0, 842.0]);
+        for form in forms {
+            let operators = form
+                .decode_content()
+                .unwrap()
+                .operations
+                .into_iter()
+                .map(|operation| operation.operator)
+                .collect::<Vec<_>>();
+            assert!(operators.contains(&"m".to_string()));
+        }
+
+        assert_eq!(document.get_pages().len(), 3);
+        for page_id in document.get_pages().into_values() {
+            let content = document.get_and_decode_page_content(page_id).unwrap();
+            let operators = content
+                .operations
+                .iter()
+                .map(|operation| operation.operator.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(operators[..4], ["q", "cm", "Do", "Q"]);
+            assert!(!operators.contains(&"m"));
+        }
+    }
+
+    #[test]
+    fn fonts_not_completed() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        rctx.set_master_page(Box::new(margin_line())).unwrap();
+
+        assert!(rctx.save_to_bytes().is_err());
+    }
+}
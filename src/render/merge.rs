@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId};
+
+use super::{
+    RenderContext,
+    resources::{catalog, pdf_error},
+};
+
+// attributes pages inherit from the page tree, they are set on the pages moved to another tree
+const INHERITED: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+// pages of several documents concatenated into one, e.g. a cover, a body and appendices
+// rendered from different layouts; the catalog and information of the first document are
+// kept, outlines are concatenated, forms, structure and labels of the others are dropped
+#[derive(Default)]
+pub struct DocumentMerger {
+    documents: Vec<Document>,
+}
+
+impl DocumentMerger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_context(&mut self, rctx: RenderContext) -> Result<(), Error> {
+        self.add_pdf(&rctx.save_to_bytes()?)
+    }
+
+    // e.g. a document rendered before, it must not be encrypted
+    pub fn add_pdf(&mut self, pdf: &[u8]) -> Result<(), Error> {
+        let document = Document::load_mem(pdf).map_err(pdf_error)?;
+        if document.is_encrypted() {
+            return Err(Error::PdfWrite(
+                "Encrypted documents can not be merged".into(),
+            ));
+        }
+        self.documents.push(document);
+        Ok(())
+    }
+
+    pub fn save_to_bytes(self) -> Result<Vec<u8>, Error> {
+        let mut documents = self.documents.into_iter();
+        let Some(mut merged) = documents.next() else {
+            return Err(Error::PdfWrite("No documents to merge".into()));
+        };
+
+        let pages_id = catalog(&mut merged)?
+            .get(b"Pages")
+            .and_then(Object::as_reference)
+            .map_err(pdf_error)?;
+        let mut kids = vec![];
+        let mut outlines = vec![];
+        for mut document in documents {
+            document.renumber_objects_with(merged.max_id + 1);
+            merged.max_id = document.max_id;
+
+            for page_id in document.get_pages().into_values() {
+                let inherited = inherited(&document, page_id);
+                let page = document
+                    .get_object_mut(page_id)
+                    .and_then(Object::as_dict_mut)
+                    .map_err(pdf_error)?;
+                for (key, value) in inherited {
+                    page.set(key, value);
+                }
+                page.set("Parent", Object::Reference(pages_id));
+                kids.push(Object::Reference(page_id));
+            }
+            if let Some(range) = outline_range(&mut document)? {
+                outlines.push(range);
+            }
+            // catalog and page tree become unreferenced and are pruned
+            merged.objects.append(&mut document.objects);
+        }
+
+        let pages = merged
+            .get_object_mut(pages_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?;
+        let mut all_kids = pages
+            .get(b"Kids")
+            .and_then(Object::as_array)
+            .cloned()
+            .unwrap_or_default();
+        all_kids.extend(kids);
+        pages.set("Kids", all_kids);
+        let count = merged.get_pages().len();
+        merged
+            .get_object_mut(pages_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?
+            .set("Count", count as i64);
+
+        append_outlines(&mut merged, outlines)?;
+        unify_font_files(&mut merged);
+
+        merged.prune_objects();
+        merged.renumber_objects();
+        let mut pdf = vec![];
+        merged.save_to(&mut pdf).map_err(pdf_error)?;
+        Ok(pdf)
+    }
+}
+
+pub(crate) fn inherited(document: &Document, page_id: ObjectId) -> Vec<(&'static [u8], Object)> {
+    let Ok(page) = document.get_dictionary(page_id) else {
+        return vec![];
+    };
+    let mut inherited = vec![];
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(node) = parent.and_then(|id| document.get_dictionary(id).ok()) {
+        for key in INHERITED {
+            if !page.has(key)
+                && !inherited.iter().any(|(name, _)| *name == key)
+                && let Ok(value) = node.get(key)
+            {
+                inherited.push((key, value.clone()));
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    inherited
+}
+
+// first and last top level items of the outline of a document
+fn outline_range(document: &mut Document) -> Result<Option<(ObjectId, ObjectId)>, Error> {
+    let outlines = catalog(document)?
+        .get(b"Outlines")
+        .and_then(Object::as_reference)
+        .and_then(|id| document.get_dictionary(id));
+    let Ok(outlines) = outlines else {
+        return Ok(None);
+    };
+    let first = outlines.get(b"First").and_then(Object::as_reference);
+    let last = outlines.get(b"Last").and_then(Object::as_reference);
+    Ok(first.ok().zip(last.ok()))
+}
+
+// top level items of other documents follow those of the first one
+fn append_outlines(
+    document: &mut Document,
+    ranges: Vec<(ObjectId, ObjectId)>,
+) -> Result<(), Error> {
+    if ranges.is_empty() {
+        return Ok(());
+    }
+
+    let outlines_id = match catalog(document)?
+        .get(b"Outlines")
+        .and_then(Object::as_reference)
+    {
+        Ok(outlines_id) => outlines_id,
+        Err(_) => {
+            let mut outlines = Dictionary::new();
+            outlines.set("Type", Object::Name(b"Outlines".to_vec()));
+            let outlines_id = document.add_object(outlines);
+            let catalog = catalog(document)?;
+            catalog.set("Outlines", Object::Reference(outlines_id));
+            if !catalog.has(b"PageMode") {
+                catalog.set("PageMode", Object::Name(b"UseOutlines".to_vec()));
+            }
+            outlines_id
+        }
+    };
+    let outlines = document.get_dictionary(outlines_id).map_err(pdf_error)?;
+    let mut first = outlines.get(b"First").and_then(Object::as_reference).ok();
+    let mut last = outlines.get(b"Last").and_then(Object::as_reference).ok();
+
+    for (range_first, range_last) in ranges {
+        let mut item = Some(range_first);
+        while let Some(item_id) = item {
+            let dictionary = document
+                .get_object_mut(item_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?;
+            dictionary.set("Parent", Object::Reference(outlines_id));
+            item = match item_id == range_last {
+                true => None,
+                false => dictionary.get(b"Next").and_then(Object::as_reference).ok(),
+            };
+        }
+        if let Some(last) = last {
+            set_reference(document, last, "Next", range_first)?;
+            set_reference(document, range_first, "Prev", last)?;
+        }
+        first.get_or_insert(range_first);
+        last = Some(range_last);
+    }
+
+    let mut count = 0;
+    let mut item = first;
+    while let Some(item_id) = item {
+        count += 1;
+        item = document
+            .get_dictionary(item_id)
+            .and_then(|item| item.get(b"Next"))
+            .and_then(Object::as_reference)
+            .ok();
+    }
+    let outlines = document
+        .get_object_mut(outlines_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    if let (Some(first), Some(last)) = (first, last) {
+        outlines.set("First", Object::Reference(first));
+        outlines.set("Last", Object::Reference(last));
+    }
+    outlines.set("Count", count as i64);
+    Ok(())
+}
+
+fn set_reference(
+    document: &mut Document,
+    id: ObjectId,
+    key: &str,
+    reference: ObjectId,
+) -> Result<(), Error> {
+    document
+        .get_object_mut(id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?
+        .set(key, Object::Reference(reference));
+    Ok(())
+}
+
+// documents rendered with the same fonts embed the same font programs, one of them is kept
+fn unify_font_files(document: &mut Document) {
+    let mut font_files = HashMap::<(String, Vec<u8>), ObjectId>::new();
+    let mut replaced = HashMap::new();
+    let descriptors = document
+        .objects
+        .values()
+        .filter_map(|object| object.as_dict().ok())
+        .filter(|dictionary| {
+            dictionary
+                .get(b"Type")
+                .and_then(Object::as_name)
+                .is_ok_and(|kind| kind == b"FontDescriptor")
+        })
+        .flat_map(|descriptor| {
+            [&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+                .into_iter()
+                .filter_map(|key| descriptor.get(key).and_then(Object::as_reference).ok())
+        })
+        .collect::<Vec<_>>();
+    for id in descriptors {
+        let Ok(Object::Stream(stream)) = document.get_object(id) else {
+            continue;
+        };
+        let key = (format!("{:?}", stream.dict), stream.content.clone());
+        let kept = *font_files.entry(key).or_insert(id);
+        if kept != id {
+            replaced.insert(id, kept);
+        }
+    }
+
+    if !replaced.is_empty() {
+        for object in document.objects.values_mut() {
+            replace_references(object, &replaced);
+        }
+    }
+}
+
+pub(crate) fn replace_references(object: &mut Object, replaced: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(kept) = replaced.get(id) {
+                *id = *kept;
+            }
+        }
+        Object::Array(array) => {
+            for value in array.iter_mut() {
+                replace_references(value, replaced);
+            }
+        }
+        Object::Dictionary(dictionary) => {
+            for (_, value) in dictionary.iter_mut() {
+                replace_references(value, replaced);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                replace_references(value, replaced);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{position::Offset, unit::Mm};
+    use printpdf::lopdf::{Document, Object};
+
+    use crate::{DocumentMerger, RenderContext};
+
+    use super::super::test_layouts::render_context;
+
+    // the section is marked on the first page
+    fn section_context(title: &str, pages: usize, width: f64) -> RenderContext {
+        let mut rctx = render_context(title, width, 1).with_section_outline(true);
+        rctx.mark_section(title, 1, &Offset::new(Mm(0.0), Mm(0.0)));
+        for _ in 1..pages {
+            layout::RenderContext::new_page(&mut rctx, None);
+        }
+        rctx
+    }
+
+    #[test]
+    fn merge() {
+        let mut merger = DocumentMerger::new();
+        merger
+            .add_context(section_context("Cover", 1, 210.0))
+            .unwrap();
+        merger
+            .add_context(section_context("Body", 2, 148.0))
+            .unwrap();
+        let appendix = section_context("Appendix", 1, 210.0)
+            .save_to_bytes()
+            .unwrap();
+        merger.add_pdf(&appendix).unwrap();
+        let pdf = merger.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_merge.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let widths = document
+            .get_pages()
+            .values()
+            .map(|page_id| {
+                document
+                    .get_dictionary(*page_id)
+                    .and_then(|page| page.get(b"MediaBox"))
+                    .and_then(Object::as_array)
+                    .map(|media_box| media_box[2].as_float().unwrap().round())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(widths, vec![595.0, 420.0, 420.0, 595.0]);
+
+        let outlines = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        assert_eq!(outlines.get(b"Count").unwrap().as_i64().unwrap(), 3);
+        let mut titles = vec![];
+        let mut item = outlines.get(b"First").and_then(Object::as_reference).ok();
+        while let Some(item_id) = item {
+            let dictionary = document.get_dictionary(item_id).unwrap();
+            titles.push(dictionary.get(b"Title").unwrap().as_str().unwrap().to_vec());
+            item = dictionary.get(b"Next").and_then(Object::as_reference).ok();
+        }
+        assert_eq!(
+            titles,
+            vec![b"Cover".to_vec(), b"Body".to_vec(), b"Appendix".to_vec()]
+        );
+
+        assert!(DocumentMerger::new().save_to_bytes().is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use layout::Rgba;
+use printpdf::{
+    Rect,
+    lopdf::{Dictionary, Object},
+};
+
+use super::annotations::{annotation, color_array, text_string};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoteIcon {
+    #[default]
+    Comment,
+    Note,
+    Help,
+    Key,
+    Insert,
+    Paragraph,
+}
+
+impl NoteIcon {
+    fn name(&self) -> &'static [u8] {
+        match self {
+            NoteIcon::Comment => b"Comment",
+            NoteIcon::Note => b"Note",
+            NoteIcon::Help => b"Help",
+            NoteIcon::Key => b"Key",
+            NoteIcon::Insert => b"Insert",
+            NoteIcon::Paragraph => b"Paragraph",
+        }
+    }
+}
+
+// popup note shown by viewers as an icon at its position
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    contents: String,
+    author: Option<String>,
+    icon: NoteIcon,
+    color: Option<Rgba>,
+    open: bool,
+}
+
+impl Note {
+    pub fn new(contents: impl Into<String>) -> Self {
+        Self {
+            contents: contents.into(),
+            author: None,
+            icon: NoteIcon::default(),
+            color: None,
+            open: false,
+        }
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn with_icon(mut self, icon: NoteIcon) -> Self {
+        self.icon = icon;
+        self
+    }
+
+    pub fn with_color(mut self, color: Rgba) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    // popup is shown when the document is opened
+    pub fn with_open(mut self, open: bool) -> Self {
+        self.open = open;
+        self
+    }
+
+    pub(crate) fn annotation(&self, rect: &Rect) -> Dictionary {
+        let mut note = annotation("Text", rect);
+        note.set("Contents", text_string(&self.contents));
+        if let Some(author) = &self.author {
+            note.set("T", text_string(author));
+        }
+        note.set("Name", Object::Name(self.icon.name().to_vec()));
+        if let Some(color) = &self.color {
+            note.set("C", color_array(color));
+        }
+        note.set("Open", self.open);
+        // printable, not scaled or rotated with the page zoom
+        note.set("F", 4 | 8 | 16);
+        note
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::{HashMap, HashSet};
+
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, content::Operation};
+
+use super::{
+    merge::{inherited, replace_references},
+    precision::round_content,
+    resources::{indirect_dictionary, pdf_error},
+};
+
+// resource categories of names the content of pages refers to
+const CATEGORIES: [&[u8]; 7] = [
+    b"XObject",
+    b"ExtGState",
+    b"Font",
+    b"Shading",
+    b"Pattern",
+    b"ColorSpace",
+    b"Properties",
+];
+
+// size optimization of the saved document, e.g. of statements of thousands of pages, which
+// repeat the same images and graphics states on every page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Optimization {
+    deduplicate: bool,
+    strip_unused: bool,
+    precision: Option<u8>,
+}
+
+impl Default for Optimization {
+    fn default() -> Self {
+        Self {
+            deduplicate: true,
+            strip_unused: true,
+            precision: Some(2),
+        }
+    }
+}
+
+impl Optimization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // identical images and forms are written once, identical graphics states are shared
+    pub fn with_deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    // resources of pages their content does not refer to are removed
+    pub fn with_strip_unused(mut self, strip_unused: bool) -> Self {
+        self.strip_unused = strip_unused;
+        self
+    }
+
+    // coordinates are rounded to the decimals (in pt), none keeps them
+    pub fn with_precision(mut self, decimals: Option<u8>) -> Self {
+        self.precision = decimals;
+        self
+    }
+
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        if let Some(decimals) = self.precision {
+            round_content(document, decimals)?;
+        }
+        let groups = resource_groups(document)?;
+        if self.deduplicate {
+            deduplicate_xobjects(document);
+            merge_graphics_states(document, &groups)?;
+        }
+        if self.strip_unused {
+            strip_unused(document, &groups)?;
+        }
+        // objects left without references, e.g. duplicates, are dropped
+        document.prune_objects();
+        Ok(())
+    }
+}
+
+// pages by their resources dictionary, which is made indirect; resources inherited from
+// the page tree may be shared by other pages, they are left as they are
+fn resource_groups(document: &mut Document) -> Result<Vec<(ObjectId, Vec<ObjectId>)>, Error> {
+    let mut groups = Vec::<(ObjectId, Vec<ObjectId>)>::new();
+    let mut shared = HashSet::new();
+    for page_id in document.get_pages().into_values() {
+        let own = document
+            .get_dictionary(page_id)
+            .map_err(pdf_error)?
+            .has(b"Resources");
+        if !own {
+            if let Some((_, Object::Reference(id))) = inherited(document, page_id)
+                .into_iter()
+                .find(|(key, _)| *key == b"Resources")
+            {
+                shared.insert(id);
+            }
+            continue;
+        }
+        let resources_id = indirect_dictionary(document, page_id, b"Resources")?;
+        match groups.iter_mut().find(|(id, _)| *id == resources_id) {
+            Some((_, pages)) => pages.push(page_id),
+            None => groups.push((resources_id, vec![page_id])),
+        }
+    }
+    groups.retain(|(id, _)| !shared.contains(id));
+    Ok(groups)
+}
+
+// duplicates are replaced by the first one until none is left, so images of identical
+// soft masks become identical as well
+fn deduplicate_xobjects(document: &mut Document) {
+    loop {
+        let mut xobjects = HashMap::<(String, Vec<u8>), ObjectId>::new();
+        let mut replaced = HashMap::new();
+        for (id, object) in document.objects.iter() {
+            let Object::Stream(stream) = object else {
+                continue;
+            };
+            let xobject = stream
+                .dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .is_ok_and(|subtype| subtype == b"Image" || subtype == b"Form");
+            if !xobject {
+                continue;
+            }
+            let key = (format!("{:?}", stream.dict), stream.content.clone());
+            let kept = *xobjects.entry(key).or_insert(*id);
+            if kept != *id {
+                replaced.insert(*id, kept);
+            }
+        }
+
+        if replaced.is_empty() {
+            return;
+        }
+        for id in replaced.keys() {
+            document.objects.remove(id);
+        }
+        for object in document.objects.values_mut() {
+            replace_references(object, &replaced);
+        }
+    }
+}
+
+// graphics states are direct objects of pages, identical ones become one indirect object,
+// names of duplicates within a page are replaced in its content
+fn merge_graphics_states(
+    document: &mut Document,
+    groups: &[(ObjectId, Vec<ObjectId>)],
+) -> Result<(), Error> {
+    let mut shared = HashMap::<String, ObjectId>::new();
+    for (resources_id, pages) in groups {
+        let Some(states) = category(document, *resources_id, b"ExtGState") else {
+            continue;
+        };
+
+        let mut merged = Dictionary::new();
+        let mut kept = HashMap::<String, Vec<u8>>::new();
+        let mut renamed = HashMap::<Vec<u8>, Vec<u8>>::new();
+        for (name, state) in states.iter() {
+            let state = match state {
+                Object::Reference(id) => document.get_object(*id).map_err(pdf_error)?.clone(),
+                state => state.clone(),
+            };
+            let key = format!("{state:?}");
+            if let Some(kept) = kept.get(&key) {
+                renamed.insert(name.clone(), kept.clone());
+                continue;
+            }
+            kept.insert(key.clone(), name.clone());
+            let id = match shared.get(&key) {
+                Some(id) => *id,
+                None => {
+                    let id = document.add_object(state);
+                    *shared.entry(key).or_insert(id)
+                }
+            };
+            merged.set(name.clone(), Object::Reference(id));
+        }
+
+        set_category(document, *resources_id, b"ExtGState", merged)?;
+        if !renamed.is_empty() {
+            for page_id in pages {
+                rename_graphics_states(document, *page_id, &renamed)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rename_graphics_states(
+    document: &mut Document,
+    page_id: ObjectId,
+    renamed: &HashMap<Vec<u8>, Vec<u8>>,
+) -> Result<(), Error> {
+    let mut content = document
+        .get_and_decode_page_content(page_id)
+        .map_err(pdf_error)?;
+    for operation in content.operations.iter_mut() {
+        if operation.operator != "gs" {
+            continue;
+        }
+        let Some(Object::Name(name)) = operation.operands.first_mut() else {
+            continue;
+        };
+        if let Some(kept) = renamed.get(name) {
+            *name = kept.clone();
+        }
+    }
+    let content = content.encode().map_err(pdf_error)?;
+    document
+        .change_page_content(page_id, content)
+        .map_err(pdf_error)
+}
+
+// resources of pages sharing them are kept if any of the pages refers to them
+fn strip_unused(
+    document: &mut Document,
+    groups: &[(ObjectId, Vec<ObjectId>)],
+) -> Result<(), Error> {
+    for (resources_id, pages) in groups {
+        let mut used = HashSet::new();
+        for page_id in pages {
+            let content = document
+                .get_and_decode_page_content(*page_id)
+                .map_err(pdf_error)?;
+            used.extend(content.operations.iter().filter_map(resource_name));
+        }
+
+        for key in CATEGORIES {
+            let Some(mut resources) = category(document, *resources_id, key) else {
+                continue;
+            };
+            let unused = resources
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| !used.contains(&(key, name.clone())))
+                .collect::<Vec<_>>();
+            if unused.is_empty() {
+                continue;
+            }
+            for name in unused {
+                resources.remove(&name);
+            }
+            set_category(document, *resources_id, key, resources)?;
+        }
+    }
+    Ok(())
+}
+
+// category and name of the resource the operation refers to
+fn resource_name(operation: &Operation) -> Option<(&'static [u8], Vec<u8>)> {
+    let (category, operand): (&'static [u8], _) = match operation.operator.as_str() {
+        "Do" => (b"XObject", operation.operands.first()),
+        "gs" => (b"ExtGState", operation.operands.first()),
+        "Tf" => (b"Font", operation.operands.first()),
+        "sh" => (b"Shading", operation.operands.first()),
+        "cs" | "CS" => (b"ColorSpace", operation.operands.first()),
+        "scn" | "SCN" => (b"Pattern", operation.operands.last()),
+        "BDC" | "DP" => (b"Properties", operation.operands.get(1)),
+        _ => return None,
+    };
+    match operand {
+        Some(Object::Name(name)) => Some((category, name.clone())),
+        _ => None,
+    }
+}
+
+fn category(document: &Document, resources_id: ObjectId, key: &[u8]) -> Option<Dictionary> {
+    let category = document.get_dictionary(resources_id).ok()?.get(key).ok()?;
+    match category {
+        Object::Reference(id) => document.get_dictionary(*id).ok().cloned(),
+        Object::Dictionary(category) => Some(category.clone()),
+        _ => None,
+    }
+}
+
+// the category is set direct, the one it replaces may be shared with other pages
+fn set_category(
+    document: &mut Document,
+    resources_id: ObjectId,
+    key: &[u8],
+    category: Dictionary,
+) -> Result<(), Error> {
+    document
+        .get_object_mut(resources_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?
+        .set(key, category);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Rgba,
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object, ObjectId},
+    };
+
+    use crate::{Image, ImageColorSpace, Optimization, RenderContext, new_font_cache};
+
+    use super::category;
+
+    fn resources(document: &Document, page_id: ObjectId) -> ObjectId {
+        document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Resources")
+            .and_then(Object::as_reference)
+            .unwrap()
+    }
+
+    fn images(document: &Document) -> usize {
+        document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| {
+                stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+            })
+            .count()
+    }
+
+    #[test]
+    fn optimization() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        let image = Image::from_raw(2, 2, ImageColorSpace::Gray, vec![0, 255, 255, 0]).unwrap();
+        let size = Size::fixed(Mm(20.0), Mm(20.0));
+        for page in 0..2 {
+            if page > 0 {
+                layout::RenderContext::new_page(&mut rctx, None);
+            }
+            rctx.image(&Offset::zero(), &size, &image);
+            rctx.with_opacity(0.5, |rctx| {
+                rctx.rect(&Offset::zero(), &size, Some(&Rgba::black()), None)
+            });
+        }
+        let pdf = rctx.save_to_bytes().unwrap();
+
+        let mut document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages().into_values().collect::<Vec<_>>();
+        // resources are made indirect when the document is saved
+        let first = resources(&document, pages[0]);
+        let mut xobjects = category(&document, first, b"XObject").unwrap();
+        let (_, image_reference) = xobjects.iter().next().unwrap();
+        let image_reference = image_reference.clone();
+        xobjects.set("PRUnused", image_reference);
+        document
+            .get_object_mut(first)
+            .and_then(Object::as_dict_mut)
+            .unwrap()
+            .set("XObject", xobjects);
+        assert_eq!(images(&document), 2);
+
+        Optimization::new().write(&mut document).unwrap();
+        assert_eq!(images(&document), 1);
+        let states = pages
+            .iter()
+            .map(|page_id| {
+                let states = category(&document, resources(&document, *page_id), b"ExtGState");
+                states
+                    .unwrap()
+                    .iter()
+                    .map(|(_, state)| state.as_reference().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(states[0].len(), 1);
+        assert_eq!(states[0], states[1]);
+        let xobjects = category(&document, first, b"XObject").unwrap();
+        assert!(!xobjects.has(b"PRUnused"));
+        assert_eq!(xobjects.len(), 1);
+    }
+}
@@ -0,0 +1,296 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId};
+
+use super::{TocEntry, annotations::text_string, resources::catalog};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutlineTarget {
+    // anchor or destination set while rendering
+    Anchor(String),
+    // top of the page, pages are numbered from 1
+    Page(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutlineItem {
+    title: String,
+    target: OutlineTarget,
+    open: bool,
+    children: Vec<OutlineItem>,
+}
+
+impl OutlineItem {
+    pub fn new(title: impl Into<String>, target: OutlineTarget) -> Self {
+        Self {
+            title: title.into(),
+            target,
+            open: false,
+            children: vec![],
+        }
+    }
+
+    // children are shown when the document is opened
+    pub fn with_open(mut self, open: bool) -> Self {
+        self.open = open;
+        self
+    }
+
+    pub fn with_child(mut self, child: OutlineItem) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+// bookmarks shown by viewers next to the document
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Outline {
+    items: Vec<OutlineItem>,
+}
+
+impl Outline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_item(mut self, item: OutlineItem) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    // entries of deeper levels nest under the preceding entry of a lower level
+    pub fn from_entries(entries: &[TocEntry]) -> Self {
+        fn nest(entries: &[TocEntry], index: &mut usize, level: usize) -> Vec<OutlineItem> {
+            let mut items = vec![];
+            while let Some(entry) = entries.get(*index)
+                && entry.level > level
+            {
+                *index += 1;
+                let item = OutlineItem::new(
+                    entry.title.clone(),
+                    OutlineTarget::Anchor(entry.anchor.clone()),
+                );
+                let children = nest(entries, index, entry.level);
+                items.push(OutlineItem { children, ..item });
+            }
+            items
+        }
+
+        Self {
+            items: nest(entries, &mut 0, 0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // destinations of anchors are resolved by the caller, items of missing ones are skipped
+    pub(crate) fn write(
+        &self,
+        document: &mut Document,
+        anchor_destination: impl Fn(&str) -> Option<Vec<Object>>,
+    ) -> Result<(), Error> {
+        let pages = document.get_pages();
+        let destination = |target: &OutlineTarget| match target {
+            OutlineTarget::Anchor(name) => anchor_destination(name),
+            OutlineTarget::Page(page) => Some(vec![
+                Object::Reference(*pages.get(&(*page as u32))?),
+                Object::Name(b"Fit".to_vec()),
+            ]),
+        };
+
+        let outlines_id = document.new_object_id();
+        let (first, last, count) = write_items(document, &self.items, outlines_id, &destination)?;
+        let mut outlines = Dictionary::new();
+        outlines.set("Type", Object::Name(b"Outlines".to_vec()));
+        if let Some((first, last)) = first.zip(last) {
+            outlines.set("First", Object::Reference(first));
+            outlines.set("Last", Object::Reference(last));
+        }
+        outlines.set("Count", count as i64);
+        document.set_object(outlines_id, outlines);
+
+        // the outline panel is shown when the document is opened
+        let catalog = catalog(document)?;
+        catalog.set("Outlines", Object::Reference(outlines_id));
+        catalog.set("PageMode", Object::Name(b"UseOutlines".to_vec()));
+        Ok(())
+    }
+}
+
+type Destination<'a> = dyn Fn(&OutlineTarget) -> Option<Vec<Object>> + 'a;
+
+// siblings are linked to each other, returns ids of the first and the last one written and
+// the count of items written that are visible, i.e. not below closed ones
+fn write_items(
+    document: &mut Document,
+    items: &[OutlineItem],
+    parent_id: ObjectId,
+    destination: &Destination,
+) -> Result<(Option<ObjectId>, Option<ObjectId>, usize), Error> {
+    let mut written = vec![];
+    let mut visible = 0;
+    for item in items {
+        let Some(target) = destination(&item.target) else {
+            tracing::warn!("Outline item {} refers to missing target.", item.title);
+            continue;
+        };
+        let id = document.new_object_id();
+        let (first, last, count) = write_items(document, &item.children, id, destination)?;
+        let mut dictionary = Dictionary::new();
+        dictionary.set("Title", text_string(&item.title));
+        dictionary.set("Parent", Object::Reference(parent_id));
+        dictionary.set("Dest", target);
+        if let Some((first, last)) = first.zip(last) {
+            dictionary.set("First", Object::Reference(first));
+            dictionary.set("Last", Object::Reference(last));
+            // negative count hides the children, it is of those shown when opened
+            dictionary.set(
+                "Count",
+                match item.open {
+                    true => count as i64,
+                    false => -(count as i64),
+                },
+            );
+        }
+        visible += match item.open {
+            true => 1 + count,
+            false => 1,
+        };
+        written.push((id, dictionary));
+    }
+
+    let ids = written.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    for (index, (id, mut dictionary)) in written.into_iter().enumerate() {
+        if index > 0 {
+            dictionary.set("Prev", Object::Reference(ids[index - 1]));
+        }
+        if let Some(next) = ids.get(index + 1) {
+            dictionary.set("Next", Object::Reference(*next));
+        }
+        document.set_object(id, dictionary);
+    }
+    Ok((ids.first().copied(), ids.last().copied(), visible))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object},
+    };
+
+    use crate::{Outline, OutlineItem, OutlineTarget, RenderContext, new_font_cache};
+
+    #[test]
+    fn outline() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_section_outline(true);
+        rctx.mark_section("Introduction", 1, &Offset::new(Mm(0.0), Mm(0.0)));
+        rctx.mark_section("Scope", 2, &Offset::new(Mm(0.0), Mm(50.0)));
+        rctx.mark_section("Results", 1, &Offset::new(Mm(0.0), Mm(100.0)));
+
+        let outline = Outline::from_entries(&rctx.toc_entries());
+        assert_eq!(
+            outline,
+            Outline::new()
+                .with_item(
+                    OutlineItem::new("Introduction", OutlineTarget::Anchor("section 1".into()))
+                        .with_child(OutlineItem::new(
+                            "Scope",
+                            OutlineTarget::Anchor("section 2".into())
+                        ))
+                )
+                .with_item(OutlineItem::new(
+                    "Results",
+                    OutlineTarget::Anchor("section 3".into())
+                ))
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_outline.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let catalog = document.catalog().unwrap();
+        let outlines = catalog
+            .get(b"Outlines")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        assert_eq!(outlines.get(b"Count").unwrap().as_i64().unwrap(), 2);
+        let first = outlines
+            .get(b"First")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        assert_eq!(first.get(b"Count").unwrap().as_i64().unwrap(), -1);
+        assert!(first.get(b"First").is_ok());
+        assert!(first.get(b"Next").is_ok());
+    }
+
+    #[test]
+    fn missing_targets() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        rctx.mark_section("Introduction", 1, &Offset::new(Mm(0.0), Mm(0.0)));
+        let missing = || OutlineItem::new("Missing", OutlineTarget::Anchor("missing".into()));
+        let item = |open| {
+            OutlineItem::new("Introduction", OutlineTarget::Anchor("section 1".into()))
+                .with_open(open)
+                .with_child(OutlineItem::new("Top", OutlineTarget::Page(1)))
+                .with_child(missing())
+        };
+        rctx.set_outline(Some(
+            Outline::new()
+                .with_item(item(false))
+                .with_item(item(true))
+                .with_item(missing()),
+        ));
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let dictionary = |object: &Object| {
+            object
+                .as_reference()
+                .and_then(|id| document.get_dictionary(id))
+                .unwrap()
+        };
+        let outlines = dictionary(document.catalog().unwrap().get(b"Outlines").unwrap());
+        // items skipped are not counted
+        assert_eq!(outlines.get(b"Count").unwrap().as_i64().unwrap(), 3);
+        let closed = dictionary(outlines.get(b"First").unwrap());
+        assert_eq!(closed.get(b"Count").unwrap().as_i64().unwrap(), -1);
+        let open = dictionary(outlines.get(b"Last").unwrap());
+        assert_eq!(open.get(b"Count").unwrap().as_i64().unwrap(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+use layout::{
+    Layout,
+    position::{Offset, Size},
+    unit::{Mm, Unit},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageAnchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+// sub-layout painted over the content of every page, at the anchor of the physical page;
+// it is measured when added to the render context and laid out for each page
+#[derive(Debug)]
+pub struct Overlay {
+    layout: Box<dyn Layout>,
+    size: Size,
+    anchor: PageAnchor,
+    // distance from the page edges the overlay is anchored to
+    inset: Unit,
+    // text of the overlay has page value slots
+    page_dependent: bool,
+}
+
+impl Overlay {
+    pub fn new(layout: Box<dyn Layout>, size: Size, anchor: PageAnchor) -> Self {
+        Self {
+            layout,
+            size,
+            anchor,
+            inset: Mm(10.0).into(),
+            page_dependent: false,
+        }
+    }
+
+    pub fn with_inset(mut self, inset: impl Into<Unit>) -> Self {
+        self.inset = inset.into();
+        self
+    }
+
+    pub(crate) fn layout_mut(&mut self) -> &mut dyn Layout {
+        self.layout.as_mut()
+    }
+
+    pub(crate) fn layout(&self) -> &dyn Layout {
+        self.layout.as_ref()
+    }
+
+    pub(crate) fn set_page_dependent(&mut self, page_dependent: bool) {
+        self.page_dependent = page_dependent;
+    }
+
+    pub(crate) fn page_dependent(&self) -> bool {
+        self.page_dependent
+    }
+
+    pub(crate) fn size(&self) -> &Size {
+        &self.size
+    }
+
+    // top left corner of the overlay on the page
+    pub(crate) fn offset(&self, page_size: &Size) -> Offset {
+        anchor_offset(self.anchor, self.inset, page_size, &self.size)
+    }
+}
+
+// top left corner of a box of the size anchored to the page, inset from its edges
+pub(crate) fn anchor_offset(
+    anchor: PageAnchor,
+    inset: Unit,
+    page_size: &Size,
+    size: &Size,
+) -> Offset {
+    let free_width = page_size.base_width() - size.base_width();
+    let free_height = page_size.base_height() - size.base_height();
+    let half = |unit: Unit| Unit::from(Mm(Mm::from(unit).0 / 2.0));
+
+    let x = match anchor {
+        PageAnchor::TopLeft | PageAnchor::CenterLeft | PageAnchor::BottomLeft => inset,
+        PageAnchor::TopCenter | PageAnchor::Center | PageAnchor::BottomCenter => half(free_width),
+        _ => free_width - inset,
+    };
+    let y = match anchor {
+        PageAnchor::TopLeft | PageAnchor::TopCenter | PageAnchor::TopRight => inset,
+        PageAnchor::CenterLeft | PageAnchor::Center | PageAnchor::CenterRight => half(free_height),
+        _ => free_height - inset,
+    };
+    Offset::new(x, y)
+}
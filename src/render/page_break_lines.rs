@@ -0,0 +1,358 @@
+use layout::{
+    Error, Layout, MeasureContext, RenderContext,
+    position::{Offset, Size},
+    unit::{Mm, Unit},
+};
+
+use super::{Mark, Marks};
+
+// minimum lines of a paragraph left before a page break (orphans) and moved after it
+// (widows)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageBreakLines {
+    before: usize,
+    after: usize,
+}
+
+impl Default for PageBreakLines {
+    fn default() -> Self {
+        Self {
+            before: 2,
+            after: 2,
+        }
+    }
+}
+
+impl PageBreakLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_before(mut self, before: usize) -> Self {
+        self.before = before;
+        self
+    }
+
+    pub fn with_after(mut self, after: usize) -> Self {
+        self.after = after;
+        self
+    }
+
+    // lines left on the page when only the fitting ones of all lines fit, none if all fit
+    pub(crate) fn lines_before_break(&self, lines: usize, fitting: usize) -> Option<usize> {
+        if fitting >= lines {
+            return None;
+        }
+        let mut before = fitting;
+        if lines - before < self.after {
+            before = lines.saturating_sub(self.after);
+        }
+        if before < self.before.max(1) {
+            before = 0;
+        }
+        Some(before)
+    }
+}
+
+// options of the page break checked next, like the options of a new page in layout; the
+// content checked is lines of the height, broken by the constraints
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageBreakOptions {
+    pub lines: Option<(Unit, PageBreakLines)>,
+}
+
+pub(crate) fn line_count(height: Unit, line_height: Unit) -> usize {
+    let (height, line_height) = (Mm::from(height).0, Mm::from(line_height).0);
+    match line_height > 0.0 {
+        true => (height / line_height).round().max(1.0) as usize,
+        false => 1,
+    }
+}
+
+// options of page breaks are taken over by the render context with the page break check
+// that follows; paragraphs are known when laid out, so headings find the one below
+impl Marks {
+    // lines of the paragraph are of the same height, their count is given by the height
+    // the paragraph is laid out to
+    pub fn paragraph(
+        &self,
+        layout: impl Layout + 'static,
+        line_height: impl Into<Unit>,
+        lines: PageBreakLines,
+    ) -> ParagraphLayout {
+        ParagraphLayout {
+            layout: Box::new(layout),
+            line_height: line_height.into(),
+            lines,
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+
+    // the heading is not left alone at the bottom of a page, it is moved to the next one
+    // unless the first lines of the paragraph below it fit
+    pub fn heading(&self, layout: impl Layout + 'static) -> HeadingLayout {
+        HeadingLayout {
+            layout: Box::new(layout),
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+
+    // height kept with the content ending at the offset
+    fn kept_height(&self, offset: Unit) -> Unit {
+        let offset = Mm::from(offset).0;
+        self.paragraphs()
+            .borrow()
+            .iter()
+            .find(|(paragraph, _)| (Mm::from(*paragraph).0 - offset).abs() < 1e-6)
+            .map(|(_, height)| *height)
+            .unwrap_or_else(Unit::zero)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParagraphLayout {
+    layout: Box<dyn Layout>,
+    line_height: Unit,
+    lines: PageBreakLines,
+    marks: Marks,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for ParagraphLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        // lines left before a page break, all of short paragraphs
+        let lines = line_count(size.base_height(), self.line_height);
+        let before = self.lines.before.max(1);
+        let kept = match lines < before + self.lines.after {
+            true => lines,
+            false => before,
+        };
+        let kept = Mm(Mm::from(self.line_height).0 * kept as f64);
+        let paragraphs = self.marks.paragraphs();
+        paragraphs
+            .borrow_mut()
+            .retain(|(paragraph, _)| *paragraph != offset.y);
+        paragraphs.borrow_mut().push((offset.y, kept.into()));
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        self.marks.push(Mark::PageBreak(PageBreakOptions {
+            lines: Some((self.line_height, self.lines)),
+        }));
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+        self.layout.render(ctx)
+    }
+}
+
+#[derive(Debug)]
+pub struct HeadingLayout {
+    layout: Box<dyn Layout>,
+    marks: Marks,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for HeadingLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        let height = self.size.base_height();
+        let next_height = self.marks.kept_height(self.offset.y + height);
+        ctx.check_page_break(self.offset.y, height + next_height, false);
+        self.layout.render(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, Layout, MeasureContext, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::{PdfDocument, lopdf::Document};
+
+    use crate::{Marks, RenderContext, new_font_cache, render::test_layouts::Lines};
+
+    use super::{PageBreakLines, line_count};
+
+    // layouts of lines at the offsets, returns lines on each page
+    fn render(
+        layouts: impl FnOnce(&Marks, &Lines) -> Vec<(Box<dyn Layout>, Mm, usize)>,
+    ) -> Vec<usize> {
+        let fonts = new_font_cache();
+        fonts
+            .add(
+                "LatoReg",
+                include_bytes!("../../tests/Lato-Regular.ttf").as_ref(),
+            )
+            .unwrap();
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_marks(marks.clone());
+
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let lines = Lines::new(rctx.typeset(&style, "Line").unwrap(), 1);
+        rctx.complete_fonts().unwrap();
+
+        let mut layouts = layouts(&marks, &lines);
+        for (layout, y, count) in layouts.iter_mut() {
+            let size = Size::fixed(Mm(190.0), Mm(10.0 * *count as f64));
+            layout.measure(&mut rctx, size.clone()).unwrap();
+            layout
+                .lay_out(&mut rctx, Offset::new(Mm(0.0), *y), size)
+                .unwrap();
+        }
+        for (layout, _, _) in layouts.iter() {
+            layout.render(&mut rctx).unwrap();
+        }
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        document
+            .get_pages()
+            .values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(*page_id).unwrap();
+                content
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.operator == "BT")
+                    .count()
+            })
+            .collect()
+    }
+
+    fn lines(lines: &Lines, count: usize) -> Lines {
+        Lines::new(lines.text.clone(), count)
+    }
+
+    #[test]
+    fn paragraphs() {
+        // page ends at 277 mm, 4 of 5 lines would fit, a widow would be left
+        let pages = render(|marks, line| {
+            vec![(
+                Box::new(marks.paragraph(lines(line, 5), Mm(10.0), PageBreakLines::new())),
+                Mm(237.0),
+                5,
+            )]
+        });
+        assert_eq!(pages, vec![3, 2]);
+
+        // an orphan would be left, the paragraph moves
+        let pages = render(|marks, line| {
+            vec![(
+                Box::new(marks.paragraph(lines(line, 5), Mm(10.0), PageBreakLines::new())),
+                Mm(267.0),
+                5,
+            )]
+        });
+        assert_eq!(pages, vec![0, 5]);
+    }
+
+    #[test]
+    fn headings() {
+        // the first two lines of the paragraph do not fit below the heading
+        let pages = render(|marks, line| {
+            vec![
+                (Box::new(marks.heading(lines(line, 1))), Mm(257.0), 1),
+                (
+                    Box::new(marks.paragraph(lines(line, 5), Mm(10.0), PageBreakLines::new())),
+                    Mm(267.0),
+                    5,
+                ),
+            ]
+        });
+        assert_eq!(pages, vec![0, 6]);
+
+        let pages = render(|marks, line| {
+            vec![
+                (Box::new(marks.heading(lines(line, 1))), Mm(247.0), 1),
+                (
+                    Box::new(marks.paragraph(lines(line, 5), Mm(10.0), PageBreakLines::new())),
+                    Mm(257.0),
+                    5,
+                ),
+            ]
+        });
+        assert_eq!(pages, vec![3, 3]);
+
+        // all lines of a short paragraph are kept with the heading
+        let pages = render(|marks, line| {
+            vec![
+                (Box::new(marks.heading(lines(line, 1))), Mm(247.0), 1),
+                (
+                    Box::new(marks.paragraph(lines(line, 3), Mm(10.0), PageBreakLines::new())),
+                    Mm(257.0),
+                    3,
+                ),
+            ]
+        });
+        assert_eq!(pages, vec![0, 4]);
+    }
+
+    #[test]
+    fn lines_before_break() {
+        let lines = PageBreakLines::new();
+        assert_eq!(lines.lines_before_break(5, 5), None);
+        assert_eq!(lines.lines_before_break(5, 3), Some(3));
+        // widow moves a line with it
+        assert_eq!(lines.lines_before_break(5, 4), Some(3));
+        // orphan moves the whole paragraph
+        assert_eq!(lines.lines_before_break(5, 1), Some(0));
+        assert_eq!(lines.lines_before_break(3, 2), Some(0));
+
+        let lines = PageBreakLines::new().with_before(1).with_after(1);
+        assert_eq!(lines.lines_before_break(3, 2), Some(2));
+        assert_eq!(lines.lines_before_break(3, 0), Some(0));
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(line_count(Mm(25.0).into(), Mm(5.0).into()), 5);
+        assert_eq!(line_count(Mm(24.9).into(), Mm(5.0).into()), 5);
+        assert_eq!(line_count(Mm(2.0).into(), Mm(5.0).into()), 1);
+        assert_eq!(line_count(Mm(20.0).into(), Mm(0.0).into()), 1);
+    }
+}
@@ -0,0 +1,113 @@
+use layout::{
+    Error, Layout, MeasureContext, Rgba, Stroke, Style,
+    position::{Offset, Quad, Size},
+    unit::Unit,
+};
+
+use super::RenderContext;
+
+pub(crate) type PageCallback = Box<dyn FnMut(&mut PageDecorator, usize)>;
+pub(crate) type PageAddedCallback = Box<dyn FnMut(&mut PageDecorator, &PageInfo)>;
+
+// page a callback runs for, pages are numbered from 0; the margin is the one content of
+// the page is laid out within
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageInfo {
+    pub index: usize,
+    pub size: Size,
+    pub margin: Quad,
+}
+
+// drawing handle of page callbacks, positions are relative to the physical page, so headers
+// and footers can be drawn into the margins; text is typeset when the page is finished,
+// its glyphs have to be reserved before fonts are completed
+pub struct PageDecorator<'a> {
+    context: &'a mut RenderContext,
+    // margin of the page, the context has none while decorating
+    page_margin: Quad,
+    content_offset: Offset,
+}
+
+impl<'a> PageDecorator<'a> {
+    pub(crate) fn new(
+        context: &'a mut RenderContext,
+        page_margin: Quad,
+        content_offset: Offset,
+    ) -> Self {
+        Self {
+            context,
+            page_margin,
+            content_offset,
+        }
+    }
+
+    pub fn page_size(&self) -> &Size {
+        self.context.page_size()
+    }
+
+    pub fn page_margin(&self) -> &Quad {
+        &self.page_margin
+    }
+
+    // top left corner of the content of the page, it differs from the one of the margin
+    // on even pages of mirrored margins and by the binding offset
+    pub fn content_offset(&self) -> &Offset {
+        &self.content_offset
+    }
+
+    // title of the first section marked on the page, or of the one continuing on it
+    pub fn first_section(&self) -> Option<&str> {
+        self.context.section(true)
+    }
+
+    // title of the last section marked on the page, or of the one continuing on it
+    pub fn last_section(&self) -> Option<&str> {
+        self.context.section(false)
+    }
+
+    pub fn line(&mut self, from: &Offset, to: &Offset, stroke: &Stroke) {
+        layout::RenderContext::line(self.context, from, to, stroke);
+    }
+
+    pub fn rect(
+        &mut self,
+        position: &Offset,
+        size: &Size,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        self.context.rect(position, size, fill, stroke);
+    }
+
+    pub fn rounded_rect(
+        &mut self,
+        position: &Offset,
+        size: &Size,
+        radius: impl Into<Unit>,
+        fill: Option<&Rgba>,
+        stroke: Option<&Stroke>,
+    ) {
+        self.context
+            .rounded_rect(position, size, radius, fill, stroke);
+    }
+
+    // position is the top left corner of the text; fonts are completed before pages are
+    // decorated, so glyphs of the text have to be reserved, otherwise an error is returned
+    pub fn text(&mut self, position: &Offset, style: &Style, text: &str) -> Result<(), Error> {
+        let text = self.context.typeset(style, text)?;
+        layout::RenderContext::text(self.context, position, style, &text, false);
+        Ok(())
+    }
+
+    // e.g. a header with the text aligned by fills
+    pub fn layout(
+        &mut self,
+        layout: &mut dyn Layout,
+        position: &Offset,
+        size: &Size,
+    ) -> Result<(), Error> {
+        layout.measure(self.context, size.clone())?;
+        layout.lay_out(self.context, position.clone(), size.clone())?;
+        layout.render(self.context)
+    }
+}
@@ -0,0 +1,207 @@
+use layout::{
+    NewPageOptions,
+    position::{Offset, Quad, Size},
+    unit::{Mm, Unit},
+};
+
+use super::from_unit;
+
+// in pt from the top left corner of the page
+pub(crate) type PagePoint = (f32, f32);
+
+// content kept together is not broken, None is returned within it; otherwise returns
+// whether the content is checked against the page end, content reserved is kept together
+// if it fits into the page
+pub(crate) fn reserve_page_break(
+    reservations: &mut Vec<bool>,
+    content_height: Unit,
+    page_height: Unit,
+    reserve_content_height: bool,
+) -> Option<bool> {
+    if Some(&true) == reservations.last() {
+        if reserve_content_height {
+            // keep stack symmetrical to pop(), which is unconditional
+            reservations.push(true);
+        }
+        return None;
+    }
+    if !reserve_content_height {
+        return Some(true);
+    }
+    let fits_into_page = content_height <= page_height;
+    reservations.push(fits_into_page);
+    Some(fits_into_page)
+}
+
+// render contexts breaking lines by pages
+pub(crate) trait LinePages {
+    fn page_end(&self) -> Option<Unit>;
+
+    // true if the content starts a new page
+    fn break_page(&mut self, content_offset: Unit, content_height: Unit) -> bool;
+}
+
+// lines reaching past the page end are split, the rest continues on the next page; parts
+// are painted on the page they are on
+pub(crate) fn split_line<P: LinePages>(
+    pages: &mut P,
+    from: &Offset,
+    to: &Offset,
+    mut paint: impl FnMut(&mut P, &Offset, &Offset),
+) {
+    let (mut from, to) = match from.y <= to.y {
+        true => (from.clone(), to.clone()),
+        false => (to.clone(), from.clone()),
+    };
+    pages.break_page(from.y, Unit::zero());
+
+    while let Some(page_end) = pages.page_end()
+        && to.y > page_end
+    {
+        let mm = |unit: Unit| Mm::from(unit).0;
+        let ratio = (mm(page_end) - mm(from.y)) / (mm(to.y) - mm(from.y));
+        let split = Offset::new(Mm(mm(from.x) + ratio * (mm(to.x) - mm(from.x))), page_end);
+        if page_end > from.y {
+            paint(pages, &from, &split);
+        }
+        // no break within a region kept together, the line overflows
+        if !pages.break_page(page_end, to.y - page_end) {
+            break;
+        }
+        from = split;
+    }
+
+    paint(pages, &from, &to);
+}
+
+// page breaks of render contexts other than the PDF one, they break pages by the helpers
+// the PDF one does; pages are counted from 0, the context adds its pages as the flow
+// reaches them
+pub(crate) struct PageFlow {
+    page_margin: Quad,
+    page_size: Size,
+    page_start: Option<Offset>,
+    page_end: Option<Offset>,
+    page_break_reservations: Vec<bool>,
+    page: usize,
+}
+
+impl PageFlow {
+    pub(crate) fn new(page_margin: Quad, page_size: Size) -> Self {
+        Self {
+            page_margin,
+            page_size,
+            page_start: None,
+            page_end: None,
+            page_break_reservations: vec![],
+            page: 0,
+        }
+    }
+
+    pub(crate) fn page(&self) -> usize {
+        self.page
+    }
+
+    pub(crate) fn page_size(&self) -> &Size {
+        &self.page_size
+    }
+
+    pub(crate) fn page_point(&self, content_position: &Offset) -> PagePoint {
+        let content_position = match &self.page_start {
+            Some(page_start) => content_position - page_start,
+            None => content_position.clone(),
+        };
+        let position = self.page_margin.offset(&content_position);
+        (pt(position.x), pt(position.y))
+    }
+
+    pub(crate) fn check_page_break(
+        &mut self,
+        content_offset: Unit,
+        content_height: Unit,
+        reserve_content_height: bool,
+    ) -> bool {
+        let Some(check_page_break) = reserve_page_break(
+            &mut self.page_break_reservations,
+            content_height,
+            self.page_size.base_height() - self.page_margin.height(),
+            reserve_content_height,
+        ) else {
+            return false;
+        };
+
+        let mut new_page = false;
+        if let Some(page_end) = &self.page_end
+            && check_page_break
+            && content_offset + content_height > page_end.y
+        {
+            self.new_page(None);
+            new_page = true;
+        }
+
+        if self.page_start.is_none() {
+            self.set_page_offsets(content_offset);
+        }
+
+        new_page
+    }
+
+    pub(crate) fn release_page_break_reservation(&mut self) {
+        if self.page_break_reservations.pop().is_none() {
+            tracing::warn!("Page break reservation released when not defined.");
+        }
+    }
+
+    pub(crate) fn new_page(&mut self, options: Option<NewPageOptions>) {
+        if let Some(options) = options {
+            if let Some(margin) = options.margin {
+                self.page_margin = margin;
+            }
+            if let Some(size) = options.size {
+                self.page_size = size;
+            }
+        }
+
+        self.page_start = None;
+        self.page_end = None;
+        self.page += 1;
+    }
+
+    // returns the parts of the line and their pages
+    pub(crate) fn line(
+        &mut self,
+        from: &Offset,
+        to: &Offset,
+    ) -> Vec<(usize, PagePoint, PagePoint)> {
+        let mut parts = vec![];
+        split_line(self, from, to, |flow, from, to| {
+            parts.push((flow.page, flow.page_point(from), flow.page_point(to)));
+        });
+        parts
+    }
+
+    fn set_page_offsets(&mut self, content_offset: Unit) {
+        let page_start = Offset::new(Unit::zero(), content_offset);
+
+        let mut page_end = page_start.clone();
+        page_end.x_advance(self.page_size.base_width() - self.page_margin.width());
+        page_end.y_advance(self.page_size.base_height() - self.page_margin.height());
+
+        self.page_start = Some(page_start);
+        self.page_end = Some(page_end);
+    }
+}
+
+impl LinePages for PageFlow {
+    fn page_end(&self) -> Option<Unit> {
+        self.page_end.as_ref().map(|page_end| page_end.y)
+    }
+
+    fn break_page(&mut self, content_offset: Unit, content_height: Unit) -> bool {
+        self.check_page_break(content_offset, content_height, false)
+    }
+}
+
+pub(crate) fn pt(unit: Unit) -> f32 {
+    from_unit(unit).into_pt().0
+}
@@ -0,0 +1,166 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object};
+
+use super::{annotations::text_string, resources::catalog};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageLabelStyle {
+    #[default]
+    Decimal,
+    UpperRoman,
+    LowerRoman,
+    UpperLetters,
+    LowerLetters,
+    // the label is the prefix only
+    None,
+}
+
+// page number viewers show for pages of a range, e.g. i, ii, iii of the front matter
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageLabel {
+    style: PageLabelStyle,
+    prefix: Option<String>,
+    start: usize,
+}
+
+impl Default for PageLabel {
+    fn default() -> Self {
+        Self::new(PageLabelStyle::Decimal)
+    }
+}
+
+impl PageLabel {
+    pub fn new(style: PageLabelStyle) -> Self {
+        Self {
+            style,
+            prefix: None,
+            start: 1,
+        }
+    }
+
+    // e.g. A- of appendix pages A-1, A-2
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    // number of the first page of the range
+    pub fn with_start(mut self, start: usize) -> Self {
+        self.start = start.max(1);
+        self
+    }
+
+    fn dictionary(&self) -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        let style: Option<&[u8]> = match self.style {
+            PageLabelStyle::Decimal => Some(b"D"),
+            PageLabelStyle::UpperRoman => Some(b"R"),
+            PageLabelStyle::LowerRoman => Some(b"r"),
+            PageLabelStyle::UpperLetters => Some(b"A"),
+            PageLabelStyle::LowerLetters => Some(b"a"),
+            PageLabelStyle::None => None,
+        };
+        if let Some(style) = style {
+            dictionary.set("S", Object::Name(style.to_vec()));
+        }
+        if let Some(prefix) = &self.prefix {
+            dictionary.set("P", text_string(prefix));
+        }
+        if self.start != 1 {
+            dictionary.set("St", self.start as i64);
+        }
+        dictionary
+    }
+}
+
+// ranges start on pages numbered from 0, the first one has to start on the first page, so
+// decimal labels are used until the first range set
+pub(crate) fn write_page_labels(
+    document: &mut Document,
+    labels: &[(usize, PageLabel)],
+) -> Result<(), Error> {
+    let mut labels = labels.to_vec();
+    labels.sort_by_key(|(page, _)| *page);
+    if labels.first().is_some_and(|(page, _)| *page > 0) {
+        labels.insert(0, (0, PageLabel::default()));
+    }
+
+    let mut numbers = vec![];
+    for (page, label) in labels {
+        numbers.push(Object::Integer(page as i64));
+        numbers.push(Object::Dictionary(label.dictionary()));
+    }
+    let mut tree = Dictionary::new();
+    tree.set("Nums", numbers);
+    let tree_id = document.add_object(tree);
+    catalog(document)?.set("PageLabels", Object::Reference(tree_id));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        position::{Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object},
+    };
+
+    use crate::{PageLabel, PageLabelStyle, RenderContext, new_font_cache};
+
+    #[test]
+    fn page_labels() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        layout::RenderContext::new_page(&mut rctx, None);
+        rctx.set_page_label(PageLabel::new(PageLabelStyle::LowerRoman));
+        layout::RenderContext::new_page(&mut rctx, None);
+        layout::RenderContext::new_page(&mut rctx, None);
+        rctx.set_page_label(PageLabel::new(PageLabelStyle::Decimal).with_prefix("A-"));
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let labels = document
+            .catalog()
+            .unwrap()
+            .get(b"PageLabels")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        let numbers = labels.get(b"Nums").unwrap().as_array().unwrap();
+        let ranges = numbers
+            .chunks(2)
+            .map(|range| {
+                let label = range[1].as_dict().unwrap();
+                (
+                    range[0].as_i64().unwrap(),
+                    label.get(b"S").unwrap().as_name().unwrap().to_vec(),
+                    label
+                        .get(b"P")
+                        .and_then(Object::as_str)
+                        .ok()
+                        .map(<[u8]>::to_vec),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, b"D".to_vec(), None),
+                (1, b"r".to_vec(), None),
+                (3, b"D".to_vec(), Some(b"A-".to_vec())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use layout::{
+    Style,
+    unit::{Mm, Unit},
+};
+
+use super::PageAnchor;
+
+// page numbers painted on every page; %p in the format is the page number, %P the page
+// count, which is known from the first pass of two-pass rendering, ? otherwise
+#[derive(Clone)]
+pub struct PageNumbering {
+    format: String,
+    style: Arc<Style>,
+    anchor: PageAnchor,
+    inset: Unit,
+    start: usize,
+}
+
+impl PageNumbering {
+    pub fn new(format: impl Into<String>, style: impl Into<Arc<Style>>) -> Self {
+        Self {
+            format: format.into(),
+            style: style.into(),
+            anchor: PageAnchor::BottomCenter,
+            inset: Mm(10.0).into(),
+            start: 1,
+        }
+    }
+
+    pub fn with_anchor(mut self, anchor: PageAnchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    // distance from the page edges the number is anchored to
+    pub fn with_inset(mut self, inset: impl Into<Unit>) -> Self {
+        self.inset = inset.into();
+        self
+    }
+
+    // number of the first page
+    pub fn with_start(mut self, start: usize) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub(crate) fn style(&self) -> &Arc<Style> {
+        &self.style
+    }
+
+    pub(crate) fn anchor(&self) -> PageAnchor {
+        self.anchor
+    }
+
+    pub(crate) fn inset(&self) -> Unit {
+        self.inset
+    }
+
+    pub(crate) fn start(&self) -> usize {
+        self.start
+    }
+
+    // chars of the format, the numbers are made of reserved digits
+    pub(crate) fn literal_chars(&self) -> String {
+        self.format.replace("%p", "").replace("%P", "")
+    }
+
+    pub(crate) fn format(&self, number: usize, pages: Option<usize>) -> String {
+        let pages = pages.map(|pages| pages.to_string());
+        self.format
+            .replace("%P", pages.as_deref().unwrap_or("?"))
+            .replace("%p", &number.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::Style;
+
+    use super::PageNumbering;
+
+    #[test]
+    fn format() {
+        let numbering = PageNumbering::new("%p / %P", Style::new_default());
+        assert_eq!(numbering.format(3, Some(12)), "3 / 12");
+        assert_eq!(numbering.format(3, None), "3 / ?");
+
+        let numbering = PageNumbering::new("– %p –", Style::new_default());
+        assert_eq!(numbering.format(10, None), "– 10 –");
+        assert_eq!(numbering.literal_chars(), "–  –");
+    }
+}
@@ -0,0 +1,137 @@
+use layout::{Layout, position::Quad};
+
+use super::{PageDecorator, page_decorator::PageCallback};
+
+// setup of pages of a kind, e.g. the first page of a letter with the address block; unset
+// parts are kept from the document
+#[derive(Default)]
+pub struct PageTemplate {
+    margin: Option<Quad>,
+    background: Option<Box<dyn Layout>>,
+    decoration: Option<PageCallback>,
+}
+
+impl PageTemplate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_margin(mut self, margin: Quad) -> Self {
+        self.margin = Some(margin);
+        self
+    }
+
+    // laid out over the whole page and painted before its content
+    pub fn with_background(mut self, layout: impl Layout + 'static) -> Self {
+        self.background = Some(Box::new(layout));
+        self
+    }
+
+    // runs when the page is finished, after page callbacks, e.g. to draw a header
+    pub fn with_decoration(
+        mut self,
+        decoration: impl FnMut(&mut PageDecorator, usize) + 'static,
+    ) -> Self {
+        self.decoration = Some(Box::new(decoration));
+        self
+    }
+
+    pub(crate) fn margin(&self) -> Option<&Quad> {
+        self.margin.as_ref()
+    }
+
+    pub(crate) fn background_mut(&mut self) -> Option<&mut Box<dyn Layout>> {
+        self.background.as_mut()
+    }
+
+    pub(crate) fn decoration_mut(&mut self) -> Option<&mut PageCallback> {
+        self.decoration.as_mut()
+    }
+}
+
+// templates of the first page and of odd and even pages after it; the first page uses
+// the odd one unless set, pages without template use the setup of the document
+#[derive(Default)]
+pub struct PageTemplates {
+    first: Option<PageTemplate>,
+    odd: Option<PageTemplate>,
+    even: Option<PageTemplate>,
+}
+
+impl PageTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_first(mut self, template: PageTemplate) -> Self {
+        self.first = Some(template);
+        self
+    }
+
+    pub fn with_odd(mut self, template: PageTemplate) -> Self {
+        self.odd = Some(template);
+        self
+    }
+
+    pub fn with_even(mut self, template: PageTemplate) -> Self {
+        self.even = Some(template);
+        self
+    }
+
+    // pages are numbered from 0, odd pages are those of odd page numbers
+    pub(crate) fn template(&self, page: usize) -> Option<&PageTemplate> {
+        match page {
+            0 if self.first.is_some() => self.first.as_ref(),
+            page if page % 2 == 0 => self.odd.as_ref(),
+            _ => self.even.as_ref(),
+        }
+    }
+
+    pub(crate) fn template_mut(&mut self, page: usize) -> Option<&mut PageTemplate> {
+        match page {
+            0 if self.first.is_some() => self.first.as_mut(),
+            page if page % 2 == 0 => self.odd.as_mut(),
+            _ => self.even.as_mut(),
+        }
+    }
+
+    pub(crate) fn templates_mut(&mut self) -> impl Iterator<Item = &mut PageTemplate> {
+        self.first
+            .iter_mut()
+            .chain(self.odd.iter_mut())
+            .chain(self.even.iter_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{position::Quad, unit::Mm};
+
+    use super::{PageTemplate, PageTemplates};
+
+    fn margin(templates: &PageTemplates, page: usize) -> Option<Quad> {
+        templates
+            .template(page)
+            .and_then(PageTemplate::margin)
+            .cloned()
+    }
+
+    #[test]
+    fn templates() {
+        let templates = PageTemplates::new()
+            .with_first(PageTemplate::new().with_margin(Quad::square(Mm(40.0))))
+            .with_odd(PageTemplate::new().with_margin(Quad::square(Mm(10.0))))
+            .with_even(PageTemplate::new());
+
+        assert_eq!(margin(&templates, 0), Some(Quad::square(Mm(40.0))));
+        assert_eq!(margin(&templates, 1), None);
+        assert_eq!(margin(&templates, 2), Some(Quad::square(Mm(10.0))));
+        assert!(templates.template(3).is_some());
+
+        // the first page is odd unless set
+        let templates =
+            PageTemplates::new().with_odd(PageTemplate::new().with_margin(Quad::square(Mm(5.0))));
+        assert_eq!(margin(&templates, 0), Some(Quad::square(Mm(5.0))));
+        assert!(templates.template(1).is_none());
+    }
+}
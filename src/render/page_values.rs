@@ -0,0 +1,247 @@
+use layout::TextPosition;
+use smol_str::SmolStr;
+
+// page dependent values known once the document is paginated, e.g. by the first pass
+// of two-pass rendering; pages are numbered from 1
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageValues {
+    pages: usize,
+    anchors: Vec<(String, usize)>,
+}
+
+impl PageValues {
+    pub fn new(pages: usize) -> Self {
+        Self {
+            pages,
+            anchors: vec![],
+        }
+    }
+
+    pub fn with_anchor(mut self, name: impl Into<String>, page: usize) -> Self {
+        self.anchors.push((name.into(), page));
+        self
+    }
+
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    // e.g. when pages are inserted before the content
+    pub(crate) fn shifted(self, pages: usize) -> Self {
+        Self {
+            pages: self.pages + pages,
+            anchors: self
+                .anchors
+                .into_iter()
+                .map(|(name, page)| (name, page + pages))
+                .collect(),
+        }
+    }
+
+    pub fn anchor_page(&self, name: &str) -> Option<usize> {
+        self.anchors
+            .iter()
+            .find(|(anchor, _)| anchor == name)
+            .map(|(_, page)| *page)
+    }
+}
+
+// chars the substituted values are made of, glyphs are reserved for them
+pub(crate) const PAGE_VALUE_CHARS: &str = "0123456789?";
+
+// text with the {page} slot is typeset again by the page it is rendered to, its runs are
+// found by the font and glyphs typeset when it was measured, kerning of the run is kept
+#[derive(Debug, Default)]
+pub(crate) struct PageSlotRuns(Vec<(SmolStr, Vec<u16>, String, Option<bool>)>);
+
+impl PageSlotRuns {
+    pub(crate) fn record(
+        &mut self,
+        font: &str,
+        position: &TextPosition,
+        text: &str,
+        kerning: Option<bool>,
+    ) {
+        if self.find(font, position).is_none() {
+            self.0
+                .push((font.into(), glyphs(position), text.to_string(), kerning));
+        }
+    }
+
+    pub(crate) fn find(&self, font: &str, position: &TextPosition) -> Option<(&str, Option<bool>)> {
+        let glyphs = glyphs(position);
+        self.0
+            .iter()
+            .find(|(run_font, run_glyphs, _, _)| run_font == font && *run_glyphs == glyphs)
+            .map(|(_, _, text, kerning)| (text.as_str(), *kerning))
+    }
+}
+
+fn glyphs(position: &TextPosition) -> Vec<u16> {
+    position
+        .positions
+        .iter()
+        .map(|position| position.glyph_index)
+        .collect()
+}
+
+pub(crate) fn has_page_slot(text: &str) -> bool {
+    text.contains("{page}")
+}
+
+// values not known yet while text is measured, e.g. the current page or cross references
+// in the first pass of two-pass rendering, take the width of digits of the page count, so
+// the text breaks alike in both passes; two digits are reserved if the count is not known
+fn reserved_digits(values: Option<&PageValues>) -> String {
+    let digits = values.map_or(2, |values| values.pages.to_string().len());
+    "0".repeat(digits)
+}
+
+// {page} is the current page, {pages} the page count and {page:name} the page of the anchor;
+// {ref:name} is the page of the anchor as well, it differs in values not known yet only,
+// which are substituted by reserved digits instead of ?, as is the current page before it
+// is rendered; anchors missing in known values are substituted by ?; None is returned for
+// text without slots
+pub(crate) fn substitute_page_values(
+    text: &str,
+    page: Option<usize>,
+    values: Option<&PageValues>,
+) -> Option<String> {
+    let unknown = || "?".to_string();
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut substituted = false;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        // the slot is the innermost one, e.g. of "{ {page}"
+        let start = start + rest[start..end].rfind('{').unwrap_or_default();
+        let value = match &rest[start + 1..end] {
+            "page" => Some(
+                page.map(|page| page.to_string())
+                    .unwrap_or_else(|| reserved_digits(values)),
+            ),
+            "pages" => Some(
+                values
+                    .map(|values| values.pages.to_string())
+                    .unwrap_or_else(unknown),
+            ),
+            slot => match slot.split_once(':') {
+                Some((kind @ ("page" | "ref"), name)) => Some(match values {
+                    Some(values) => values
+                        .anchor_page(name)
+                        .map(|page| page.to_string())
+                        .unwrap_or_else(unknown),
+                    None if kind == "ref" => reserved_digits(values),
+                    None => unknown(),
+                }),
+                _ => None,
+            },
+        };
+        match value {
+            Some(value) => {
+                result.push_str(&rest[..start]);
+                result.push_str(&value);
+                substituted = true;
+            }
+            None => result.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    substituted.then_some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{GlyphPosition, TextPosition, unit::Em};
+
+    use super::{PageSlotRuns, PageValues, substitute_page_values};
+
+    #[test]
+    fn substitute() {
+        let values = PageValues::new(12).with_anchor("summary", 7);
+
+        assert_eq!(
+            substitute_page_values("Page 3", Some(3), Some(&values)),
+            None
+        );
+        assert_eq!(
+            substitute_page_values("Page {page} of {pages}", Some(3), Some(&values)).as_deref(),
+            Some("Page 3 of 12")
+        );
+        assert_eq!(
+            substitute_page_values(
+                "see page {page:summary}, {page:missing}",
+                Some(3),
+                Some(&values)
+            )
+            .as_deref(),
+            Some("see page 7, ?")
+        );
+        assert_eq!(
+            substitute_page_values("{page}/{pages}", Some(1), None).as_deref(),
+            Some("1/?")
+        );
+        assert_eq!(
+            substitute_page_values("{pagination} {page", Some(1), None),
+            None
+        );
+
+        assert_eq!(
+            substitute_page_values("see page {ref:summary}", Some(3), Some(&values)).as_deref(),
+            Some("see page 7")
+        );
+        // digits are reserved for references resolved by the second pass
+        assert_eq!(
+            substitute_page_values("see page {ref:summary} of {pages}", Some(3), None).as_deref(),
+            Some("see page 00 of ?")
+        );
+        // the second pass knows all anchors
+        assert_eq!(
+            substitute_page_values("see page {ref:missing}", Some(3), Some(&values)).as_deref(),
+            Some("see page ?")
+        );
+        assert_eq!(substitute_page_values("{reference}", Some(1), None), None);
+        assert_eq!(
+            substitute_page_values("{ {page}", Some(1), None).as_deref(),
+            Some("{ 1")
+        );
+        // the current page is not known until the text is rendered, digits of the page
+        // count are reserved
+        assert_eq!(
+            substitute_page_values("Page {page}", None, Some(&values)).as_deref(),
+            Some("Page 00")
+        );
+        assert_eq!(
+            substitute_page_values("Page {page}", None, Some(&PageValues::new(120))).as_deref(),
+            Some("Page 000")
+        );
+    }
+
+    #[test]
+    fn slot_runs() {
+        let run = |glyphs: &[u16]| TextPosition {
+            width: Em(1.0),
+            height: Em(1.2),
+            depth: Em(-0.2),
+            positions: glyphs
+                .iter()
+                .map(|glyph| GlyphPosition::new(None, *glyph, Em(0.5), Em(0.0), Em(0.0), Em(0.0)))
+                .collect(),
+        };
+
+        // the first run of the glyphs wins, with its kerning
+        let mut runs = PageSlotRuns::default();
+        runs.record("Lato", &run(&[1, 2]), "Page {page}", Some(false));
+        runs.record("Lato", &run(&[1, 2]), "{page}", None);
+        assert_eq!(
+            runs.find("Lato", &run(&[1, 2])),
+            Some(("Page {page}", Some(false)))
+        );
+        assert_eq!(runs.find("Lato", &run(&[1])), None);
+        assert_eq!(runs.find("Roboto", &run(&[1, 2])), None);
+    }
+}
@@ -0,0 +1,35 @@
+use super::{PageValues, TocEntry};
+
+// pages of a layout broken without rendering it, pages are numbered from 1
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PaginationReport {
+    page_values: PageValues,
+    sections: Vec<TocEntry>,
+}
+
+impl PaginationReport {
+    pub(crate) fn new(page_values: PageValues, sections: Vec<TocEntry>) -> Self {
+        Self {
+            page_values,
+            sections,
+        }
+    }
+
+    pub fn pages(&self) -> usize {
+        self.page_values.pages()
+    }
+
+    // sections marked by headings and their pages
+    pub fn sections(&self) -> &[TocEntry] {
+        &self.sections
+    }
+
+    pub fn anchor_page(&self, name: &str) -> Option<usize> {
+        self.page_values.anchor_page(name)
+    }
+
+    // e.g. values of slots of the rendering that follows
+    pub fn page_values(&self) -> &PageValues {
+        &self.page_values
+    }
+}
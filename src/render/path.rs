@@ -0,0 +1,208 @@
+use layout::{
+    Rgba, Stroke,
+    position::{Offset, Size},
+    unit::Unit,
+};
+use printpdf::lopdf::{Object, content::Operation};
+
+use super::{Mark, Marks};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    MoveTo(Offset),
+    LineTo(Offset),
+    CurveTo(Offset, Offset, Offset),
+    Close,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+// Segments are in content coordinates, same as offsets of laid out elements
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+    fill_rule: FillRule,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    pub fn rect(content_position: &Offset, size: &Size) -> Self {
+        let bottom_right = content_position + size;
+        Self::new().polygon([
+            content_position.clone(),
+            Offset::new(bottom_right.x, content_position.y),
+            bottom_right.clone(),
+            Offset::new(content_position.x, bottom_right.y),
+        ])
+    }
+
+    // closed subpath, add more of them for shapes with holes
+    pub fn polygon(mut self, points: impl IntoIterator<Item = Offset>) -> Self {
+        let mut points = points.into_iter();
+        if let Some(first) = points.next() {
+            self.segments.push(PathSegment::MoveTo(first));
+            self.segments.extend(points.map(PathSegment::LineTo));
+            self.segments.push(PathSegment::Close);
+        }
+        self
+    }
+
+    pub fn move_to(mut self, to: Offset) -> Self {
+        self.segments.push(PathSegment::MoveTo(to));
+        self
+    }
+
+    pub fn line_to(mut self, to: Offset) -> Self {
+        self.segments.push(PathSegment::LineTo(to));
+        self
+    }
+
+    pub fn curve_to(mut self, control_1: Offset, control_2: Offset, to: Offset) -> Self {
+        self.segments
+            .push(PathSegment::CurveTo(control_1, control_2, to));
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.segments.push(PathSegment::Close);
+        self
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    pub(crate) fn paint_operator(&self, fill: bool, stroke: bool) -> Option<&'static str> {
+        match (fill, stroke, self.fill_rule) {
+            (true, true, FillRule::NonZero) => Some("B"),
+            (true, true, FillRule::EvenOdd) => Some("B*"),
+            (true, false, FillRule::NonZero) => Some("f"),
+            (true, false, FillRule::EvenOdd) => Some("f*"),
+            (false, true, _) => Some("S"),
+            (false, false, _) => None,
+        }
+    }
+
+    pub(crate) fn clip_operator(&self) -> &'static str {
+        match self.fill_rule {
+            FillRule::NonZero => "W",
+            FillRule::EvenOdd => "W*",
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    fn points(&self) -> impl Iterator<Item = &Offset> {
+        self.segments.iter().flat_map(|segment| match segment {
+            PathSegment::MoveTo(to) | PathSegment::LineTo(to) => vec![to],
+            PathSegment::CurveTo(control_1, control_2, to) => vec![control_1, control_2, to],
+            PathSegment::Close => vec![],
+        })
+    }
+
+    // vertical extent (top, bottom) in content coordinates
+    pub(crate) fn v_extent(&self) -> Option<(Unit, Unit)> {
+        self.points().fold(None, |extent, point| match extent {
+            None => Some((point.y, point.y)),
+            Some((top, bottom)) => Some((
+                if point.y < top { point.y } else { top },
+                if point.y > bottom { point.y } else { bottom },
+            )),
+        })
+    }
+
+    // construction operators only, painting operator is up to the caller
+    pub(crate) fn operations<F>(&self, page_point: F) -> Vec<Operation>
+    where
+        F: Fn(&Offset) -> (f32, f32),
+    {
+        let coordinates = |points: &[&Offset]| {
+            points
+                .iter()
+                .flat_map(|point| {
+                    let (x, y) = page_point(point);
+                    [Object::Real(x), Object::Real(y)]
+                })
+                .collect::<Vec<_>>()
+        };
+
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::MoveTo(to) => Operation::new("m", coordinates(&[to])),
+                PathSegment::LineTo(to) => Operation::new("l", coordinates(&[to])),
+                PathSegment::CurveTo(control_1, control_2, to) => {
+                    Operation::new("c", coordinates(&[control_1, control_2, to]))
+                }
+                PathSegment::Close => Operation::new("h", vec![]),
+            })
+            .collect()
+    }
+}
+
+pub(crate) type PathPaint = (Path, Option<Rgba>, Option<Stroke>);
+
+// paths are painted by the render context before anything else of the layout is rendered
+impl Marks {
+    pub fn paint(&self, path: Path, fill: Option<Rgba>, stroke: Option<Stroke>) {
+        self.push(Mark::Path((path, fill, stroke)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{position::Offset, unit::Mm};
+
+    use super::{FillRule, Path};
+
+    fn square(from: f64, to: f64) -> Vec<Offset> {
+        vec![
+            Offset::new(Mm(from), Mm(from)),
+            Offset::new(Mm(to), Mm(from)),
+            Offset::new(Mm(to), Mm(to)),
+            Offset::new(Mm(from), Mm(to)),
+        ]
+    }
+
+    #[test]
+    fn donut() {
+        let path = Path::new()
+            .polygon(square(0.0, 30.0))
+            .polygon(square(10.0, 20.0))
+            .with_fill_rule(FillRule::EvenOdd);
+
+        let operations = path.operations(|_| (0.0, 0.0));
+        let operators = operations
+            .iter()
+            .map(|operation| operation.operator.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            operators,
+            ["m", "l", "l", "l", "h", "m", "l", "l", "l", "h"]
+        );
+        assert_eq!(path.paint_operator(true, false), Some("f*"));
+        assert_eq!(path.paint_operator(true, true), Some("B*"));
+        assert_eq!(path.paint_operator(false, true), Some("S"));
+        assert_eq!(path.paint_operator(false, false), None);
+    }
+}
@@ -0,0 +1,233 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+
+use super::{
+    ColorModel, XmpMetadata,
+    pdf_version::transparent,
+    resources::{catalog, visit_dictionaries},
+};
+
+const PDFA_ID_NAMESPACE: &str = "http://www.aiim.org/pdfa/ns/id/";
+
+// archival profiles; the b conformance requires reproducible appearance, the u one in
+// addition text extractable as unicode, part 3 allows embedded files of any format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PdfALevel {
+    A1b,
+    A2b,
+    A2u,
+    A3b,
+    A3u,
+}
+
+impl PdfALevel {
+    pub(crate) fn part(self) -> u8 {
+        match self {
+            PdfALevel::A1b => 1,
+            PdfALevel::A2b | PdfALevel::A2u => 2,
+            PdfALevel::A3b | PdfALevel::A3u => 3,
+        }
+    }
+
+    fn conformance(self) -> &'static str {
+        match self {
+            PdfALevel::A1b | PdfALevel::A2b | PdfALevel::A3b => "B",
+            PdfALevel::A2u | PdfALevel::A3u => "U",
+        }
+    }
+
+    // part 1 is based on pdf 1.4, which has neither transparency nor optional content
+    pub(crate) fn allows_transparency(self) -> bool {
+        self.part() > 1
+    }
+
+    pub(crate) fn allows_optional_content(self) -> bool {
+        self.part() > 1
+    }
+
+    // identification schema the profile is recognized by
+    pub(crate) fn identify(self, xmp_metadata: XmpMetadata) -> XmpMetadata {
+        xmp_metadata
+            .with_property("pdfaid", PDFA_ID_NAMESPACE, "part", self.part().to_string())
+            .with_property(
+                "pdfaid",
+                PDFA_ID_NAMESPACE,
+                "conformance",
+                self.conformance(),
+            )
+    }
+
+    pub(crate) fn name(self) -> String {
+        format!("PDF/A-{}{}", self.part(), self.conformance().to_lowercase())
+    }
+}
+
+// colors of the document are rendered as if printed on the device the profile describes
+pub(crate) fn write_output_intent(
+    document: &mut Document,
+    profile_id: ObjectId,
+    color_model: ColorModel,
+) -> Result<(), Error> {
+    let condition = match color_model {
+        ColorModel::Rgb => "RGB",
+        ColorModel::Cmyk => "CMYK",
+        ColorModel::Gray => "Gray",
+    };
+    let mut intent = Dictionary::new();
+    intent.set("Type", Object::Name(b"OutputIntent".to_vec()));
+    intent.set("S", Object::Name(b"GTS_PDFA1".to_vec()));
+    intent.set(
+        "OutputConditionIdentifier",
+        Object::String(condition.as_bytes().to_vec(), StringFormat::Literal),
+    );
+    intent.set("DestOutputProfile", Object::Reference(profile_id));
+    catalog(document)?.set("OutputIntents", vec![Object::Dictionary(intent)]);
+    Ok(())
+}
+
+// annotations have to be printed as they are shown
+pub(crate) fn print_annotations(document: &mut Document) {
+    for object in document.objects.values_mut() {
+        if let Object::Dictionary(dictionary) = object
+            && dictionary
+                .get(b"Type")
+                .and_then(Object::as_name)
+                .is_ok_and(|name| name == b"Annot")
+        {
+            let flags = dictionary
+                .get(b"F")
+                .and_then(Object::as_i64)
+                .unwrap_or_default();
+            // print on, hidden, invisible and no view off
+            dictionary.set("F", (flags | 4) & !(1 | 2 | 32));
+        }
+    }
+}
+
+// features the profile forbids, the document is not written if there is any
+pub(crate) fn validate(document: &Document, level: PdfALevel) -> Result<(), Error> {
+    let mut violations = vec![];
+    let mut violation = |violation: String| {
+        if !violations.contains(&violation) {
+            violations.push(violation);
+        }
+    };
+
+    if !level.allows_optional_content() && document.version.as_str() > "1.4" {
+        violation(format!("PDF {}", document.version));
+    }
+    if document.trailer.has(b"Encrypt") {
+        violation("encryption".into());
+    }
+    if let Ok(catalog) = document.catalog() {
+        if catalog.has(b"OCProperties") && !level.allows_optional_content() {
+            violation("optional content".into());
+        }
+        let need_appearances = catalog
+            .get(b"AcroForm")
+            .and_then(|form| document.dereference(form))
+            .and_then(|(_, form)| form.as_dict())
+            .and_then(|form| form.get(b"NeedAppearances"))
+            .and_then(Object::as_bool);
+        if need_appearances.unwrap_or(false) {
+            violation("form fields without appearances".into());
+        }
+    }
+
+    for object in document.objects.values() {
+        visit_dictionaries(object, &mut |dictionary| {
+            let name = |key: &[u8]| dictionary.get(key).and_then(Object::as_name).ok();
+            if name(b"Type") == Some(b"Font")
+                && let Some(font) = font_violation(document, dictionary)
+            {
+                violation(font);
+            }
+            if let Some(action) = name(b"S")
+                && [
+                    &b"JavaScript"[..],
+                    b"Launch",
+                    b"Sound",
+                    b"Movie",
+                    b"ImportData",
+                ]
+                .contains(&action)
+            {
+                violation(format!("{} action", String::from_utf8_lossy(action)));
+            }
+            if !level.allows_transparency() && transparent(dictionary) {
+                violation("transparency".into());
+            }
+        });
+    }
+
+    match violations.is_empty() {
+        true => Ok(()),
+        false => Err(Error::PdfWrite(
+            format!("{} forbids {}", level.name(), violations.join(", ")).into(),
+        )),
+    }
+}
+
+// composite fonts need a unicode map, their descendants and simple fonts embedded programs
+fn font_violation(document: &Document, font: &Dictionary) -> Option<String> {
+    let base_font = font
+        .get(b"BaseFont")
+        .and_then(Object::as_name)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_default();
+    match font.get(b"Subtype").and_then(Object::as_name).ok()? {
+        b"Type0" => match font.has(b"ToUnicode") {
+            true => None,
+            false => Some(format!("font {base_font} without unicode map")),
+        },
+        b"Type3" => None,
+        _ => {
+            let embedded = font
+                .get(b"FontDescriptor")
+                .and_then(|descriptor| document.dereference(descriptor))
+                .and_then(|(_, descriptor)| descriptor.as_dict())
+                .is_ok_and(|descriptor| {
+                    [&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+                        .into_iter()
+                        .any(|key| descriptor.has(key))
+                });
+            match embedded {
+                true => None,
+                false => Some(format!("font {base_font} not embedded")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::Error;
+    use printpdf::lopdf::{Dictionary, Document, Object};
+
+    use super::{PdfALevel, validate};
+
+    #[test]
+    fn transparency() {
+        let mut document = Document::with_version("1.4");
+        let mut state = Dictionary::new();
+        state.set("Type", Object::Name(b"ExtGState".to_vec()));
+        state.set("ca", 0.5);
+        document.add_object(state);
+
+        assert!(validate(&document, PdfALevel::A2b).is_ok());
+        let Err(Error::PdfWrite(message)) = validate(&document, PdfALevel::A1b) else {
+            panic!("transparency is not reported");
+        };
+        assert_eq!(message, "PDF/A-1b forbids transparency");
+
+        let mut font = Dictionary::new();
+        font.set("Type", Object::Name(b"Font".to_vec()));
+        font.set("Subtype", Object::Name(b"Type1".to_vec()));
+        font.set("BaseFont", Object::Name(b"Helvetica".to_vec()));
+        document.add_object(font);
+        let Err(Error::PdfWrite(message)) = validate(&document, PdfALevel::A2b) else {
+            panic!("font is not reported");
+        };
+        assert_eq!(message, "PDF/A-2b forbids font Helvetica not embedded");
+    }
+}
@@ -0,0 +1,74 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object};
+
+use super::{layers::flatten_layers, resources::visit_dictionaries};
+
+// version written to the header of the document; features the version lacks are left
+// out where the content stays the same, e.g. layers, otherwise saving fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PdfVersion {
+    V1_3,
+    V1_4,
+    V1_5,
+    V1_6,
+    V1_7,
+}
+
+impl PdfVersion {
+    pub(crate) fn header(self) -> &'static str {
+        match self {
+            PdfVersion::V1_3 => "1.3",
+            PdfVersion::V1_4 => "1.4",
+            PdfVersion::V1_5 => "1.5",
+            PdfVersion::V1_6 => "1.6",
+            PdfVersion::V1_7 => "1.7",
+        }
+    }
+
+    pub(crate) fn allows_transparency(self) -> bool {
+        self >= PdfVersion::V1_4
+    }
+
+    pub(crate) fn allows_optional_content(self) -> bool {
+        self >= PdfVersion::V1_5
+    }
+
+    pub(crate) fn apply(self, document: &mut Document) -> Result<(), Error> {
+        if !self.allows_optional_content() {
+            flatten_layers(document)?;
+        }
+        if !self.allows_transparency() {
+            let mut transparency = false;
+            for object in document.objects.values() {
+                visit_dictionaries(object, &mut |dictionary| {
+                    transparency |= transparent(dictionary);
+                });
+            }
+            if transparency {
+                return Err(Error::PdfWrite(
+                    format!("PDF {} does not support transparency", self.header()).into(),
+                ));
+            }
+        }
+        document.version = self.header().to_string();
+        Ok(())
+    }
+}
+
+// graphics states with opacity, soft masks or blend modes, images with soft masks, and
+// transparency groups
+pub(crate) fn transparent(dictionary: &Dictionary) -> bool {
+    let name = |key: &[u8]| dictionary.get(key).and_then(Object::as_name).ok();
+    let opacity = [&b"CA"[..], b"ca"].into_iter().any(|key| {
+        dictionary
+            .get(key)
+            .and_then(Object::as_float)
+            .is_ok_and(|alpha| alpha < 1.0)
+    });
+    let soft_mask = dictionary
+        .get(b"SMask")
+        .is_ok_and(|mask| !mask.as_name().is_ok_and(|name| name == b"None"));
+    let blend_mode = name(b"BM").is_some_and(|mode| mode != b"Normal" && mode != b"Compatible");
+    let group = name(b"S") == Some(b"Transparency");
+    opacity || soft_mask || blend_mode || group
+}
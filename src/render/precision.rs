@@ -0,0 +1,76 @@
+use layout::Error;
+use printpdf::lopdf::{Document, Object, content::Operation};
+
+use super::resources::pdf_error;
+
+pub(crate) fn round(value: f64, decimals: Option<u8>) -> f64 {
+    match decimals {
+        Some(decimals) => {
+            let scale = 10f64.powi(decimals as i32);
+            (value * scale).round() / scale
+        }
+        None => value,
+    }
+}
+
+// values are kept f64 up to the operand, the difference of rounded values is rounded again
+// so that f64 noise does not get written
+pub(crate) fn real(value: f64, decimals: Option<u8>) -> Object {
+    Object::Real(round(value, decimals) as f32)
+}
+
+// an operand is rounded as written, not as its binary f32 expansion, e.g. 0.45 is 0.5 with
+// one decimal, whereas 0.45f32 widened to f64 is 0.4499999...
+fn written(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+// operands which are coordinates, colors and scaling of matrices are kept as they are
+fn coordinates(operation: &Operation) -> std::ops::Range<usize> {
+    match operation.operator.as_str() {
+        "m" | "l" | "c" | "v" | "y" | "re" | "Td" | "TD" => 0..operation.operands.len(),
+        "cm" | "Tm" => 4..operation.operands.len(),
+        _ => 0..0,
+    }
+}
+
+// coordinates of all content streams are rounded to the decimals
+pub(crate) fn round_content(document: &mut Document, decimals: u8) -> Result<(), Error> {
+    for page_id in document.get_pages().into_values() {
+        let mut content = document
+            .get_and_decode_page_content(page_id)
+            .map_err(pdf_error)?;
+        for operation in content.operations.iter_mut() {
+            let range = coordinates(operation);
+            for operand in operation.operands[range].iter_mut() {
+                if let Object::Real(value) = operand {
+                    *operand = real(written(*value), Some(decimals));
+                }
+            }
+        }
+        let content = content.encode().map_err(pdf_error)?;
+        document
+            .change_page_content(page_id, content)
+            .map_err(pdf_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{round, written};
+
+    #[test]
+    fn decimals() {
+        assert_eq!(round(12.3456, Some(2)), 12.35);
+        assert_eq!(round(12.3456, Some(0)), 12.0);
+        assert_eq!(round(12.3456, None), 12.3456);
+    }
+
+    #[test]
+    fn written_values() {
+        assert_eq!(round(0.45f32 as f64, Some(1)), 0.4);
+        assert_eq!(round(written(0.45), Some(1)), 0.5);
+        assert_eq!(round(written(841.8898), Some(2)), 841.89);
+    }
+}
@@ -0,0 +1,271 @@
+use layout::{
+    Error,
+    unit::{Mm, Unit},
+};
+use printpdf::lopdf::{
+    Dictionary, Document, Object, ObjectId, Stream,
+    content::{Content, Operation},
+};
+
+use super::{
+    from_unit,
+    resources::{indirect_dictionary, pdf_error},
+};
+
+// Printer's marks are painted outside of the page (the trim box), the media box
+// is enlarged to hold them, content coordinates do not change
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintMarks {
+    bleed: Unit,
+    // distance of marks from the trim box
+    offset: Unit,
+    length: Unit,
+    registration_marks: bool,
+    color_bars: bool,
+}
+
+impl Default for PrintMarks {
+    fn default() -> Self {
+        Self {
+            bleed: Mm(3.0).into(),
+            offset: Mm(3.0).into(),
+            length: Mm(6.0).into(),
+            registration_marks: false,
+            color_bars: false,
+        }
+    }
+}
+
+impl PrintMarks {
+    // crop marks only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // marks are moved away so they never reach into the bleed
+    pub fn with_bleed(mut self, bleed: impl Into<Unit>) -> Self {
+        self.bleed = bleed.into();
+        self
+    }
+
+    pub fn with_registration_marks(mut self, registration_marks: bool) -> Self {
+        self.registration_marks = registration_marks;
+        self
+    }
+
+    pub fn with_color_bars(mut self, color_bars: bool) -> Self {
+        self.color_bars = color_bars;
+        self
+    }
+
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let pt = |unit: Unit| from_unit(unit).into_pt().0;
+        let bleed = pt(self.bleed);
+        let offset = pt(self.offset).max(bleed);
+        let length = pt(self.length);
+        let margin = offset + length;
+
+        let registration_id = document.add_object(registration_color_space());
+
+        for page_id in document.get_pages().into_values() {
+            let Some([left, bottom, right, top]) = media_box(document, page_id) else {
+                tracing::warn!("Page has no media box, print marks skipped.");
+                continue;
+            };
+            let (width, height) = (right - left, top - bottom);
+
+            let resources_id = indirect_dictionary(document, page_id, b"Resources")?;
+            let color_spaces_id = indirect_dictionary(document, resources_id, b"ColorSpace")?;
+            document
+                .get_object_mut(color_spaces_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?
+                .set("PRAll", Object::Reference(registration_id));
+
+            let page = document
+                .get_object_mut(page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?;
+            let boxed =
+                |grow: f32| reals(vec![left - grow, bottom - grow, right + grow, top + grow]);
+            page.set("MediaBox", boxed(margin));
+            page.set("BleedBox", boxed(bleed));
+            page.set("TrimBox", boxed(0.0));
+            page.remove(b"CropBox");
+
+            let mut operations = vec![operation("Q", vec![])];
+            operations.extend(self.operations((left, bottom), (width, height), offset, length));
+            append_content(document, page_id, operations)?;
+        }
+        Ok(())
+    }
+
+    // page content is isolated by q/Q, marks are painted in the untransformed space
+    fn operations(
+        &self,
+        origin: (f32, f32),
+        (width, height): (f32, f32),
+        offset: f32,
+        length: f32,
+    ) -> Vec<Operation> {
+        let mut operations = vec![
+            operation("q", vec![]),
+            operation(
+                "cm",
+                [1.0, 0.0, 0.0, 1.0, origin.0, origin.1]
+                    .map(Object::Real)
+                    .to_vec(),
+            ),
+            operation("CS", vec![Object::Name(b"PRAll".to_vec())]),
+            operation("SCN", vec![Object::Real(1.0)]),
+            operation("w", vec![Object::Real(0.25)]),
+        ];
+
+        let reach = offset + length;
+        for (x, y, sx, sy) in [
+            (0.0, 0.0, -1.0, -1.0),
+            (width, 0.0, 1.0, -1.0),
+            (width, height, 1.0, 1.0),
+            (0.0, height, -1.0, 1.0),
+        ] {
+            operations.extend(line((x, y + sy * offset), (x, y + sy * reach)));
+            operations.extend(line((x + sx * offset, y), (x + sx * reach, y)));
+        }
+
+        if self.registration_marks {
+            let radius = length / 4.0;
+            let middle = offset + length / 2.0;
+            for (x, y) in [
+                (width / 2.0, -middle),
+                (width / 2.0, height + middle),
+                (-middle, height / 2.0),
+                (width + middle, height / 2.0),
+            ] {
+                operations.extend(line((x - length / 2.0, y), (x + length / 2.0, y)));
+                operations.extend(line((x, y - length / 2.0), (x, y + length / 2.0)));
+                operations.extend(circle(x, y, radius));
+            }
+        }
+        operations.push(operation("S", vec![]));
+
+        if self.color_bars {
+            let size = length * 0.8;
+            let y = height + offset;
+            for (index, cmyk) in COLOR_BARS.iter().enumerate() {
+                let x = reach + index as f32 * size;
+                operations.push(operation("k", cmyk.map(Object::Real).to_vec()));
+                operations.push(operation(
+                    "re",
+                    vec![x.into(), y.into(), size.into(), size.into()],
+                ));
+                operations.push(operation("f", vec![]));
+            }
+        }
+
+        operations.push(operation("Q", vec![]));
+        operations
+    }
+}
+
+const COLOR_BARS: [[f32; 4]; 10] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+    [1.0, 1.0, 0.0, 0.0],
+    [1.0, 0.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 0.25],
+    [0.0, 0.0, 0.0, 0.5],
+    [0.0, 0.0, 0.0, 0.75],
+];
+
+// registration color prints on all separations
+fn registration_color_space() -> Object {
+    let mut function = Dictionary::new();
+    function.set("FunctionType", 2);
+    function.set("Domain", reals(vec![0.0, 1.0]));
+    function.set("C0", reals(vec![0.0; 4]));
+    function.set("C1", reals(vec![1.0; 4]));
+    function.set("N", 1);
+
+    Object::Array(vec![
+        Object::Name(b"Separation".to_vec()),
+        Object::Name(b"All".to_vec()),
+        Object::Name(b"DeviceCMYK".to_vec()),
+        Object::Dictionary(function),
+    ])
+}
+
+fn line(from: (f32, f32), to: (f32, f32)) -> [Operation; 2] {
+    [
+        operation("m", vec![from.0.into(), from.1.into()]),
+        operation("l", vec![to.0.into(), to.1.into()]),
+    ]
+}
+
+// four bezier quarters
+fn circle(x: f32, y: f32, radius: f32) -> Vec<Operation> {
+    let k = radius * 0.552_284_8;
+    let curve = |points: [f32; 6]| operation("c", points.map(Object::Real).to_vec());
+    vec![
+        operation("m", vec![(x + radius).into(), y.into()]),
+        curve([x + radius, y + k, x + k, y + radius, x, y + radius]),
+        curve([x - k, y + radius, x - radius, y + k, x - radius, y]),
+        curve([x - radius, y - k, x - k, y - radius, x, y - radius]),
+        curve([x + k, y - radius, x + radius, y - k, x + radius, y]),
+    ]
+}
+
+fn media_box(document: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let media_box = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"MediaBox"))
+        .and_then(Object::as_array)
+        .ok()?;
+    let values = media_box
+        .iter()
+        .map(|value| value.as_float().ok())
+        .collect::<Option<Vec<_>>>()?;
+    values.try_into().ok()
+}
+
+// existing content is wrapped in q, the operations are expected to restore it first
+fn append_content(
+    document: &mut Document,
+    page_id: ObjectId,
+    operations: Vec<Operation>,
+) -> Result<(), Error> {
+    let mut stream = |operations| -> Result<Object, Error> {
+        // streams are concatenated, the preceding one may not end with a whitespace
+        let mut content = vec![b'\n'];
+        content.extend(Content { operations }.encode().map_err(pdf_error)?);
+        Ok(Object::Reference(
+            document.add_object(Stream::new(Dictionary::new(), content)),
+        ))
+    };
+    let prefix = stream(vec![operation("q", vec![])])?;
+    let suffix = stream(operations)?;
+
+    let page = document
+        .get_object_mut(page_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    let mut contents = match page.get(b"Contents") {
+        Ok(Object::Array(contents)) => contents.clone(),
+        Ok(contents) => vec![contents.clone()],
+        Err(_) => vec![],
+    };
+    contents.insert(0, prefix);
+    contents.push(suffix);
+    page.set("Contents", contents);
+    Ok(())
+}
+
+fn operation(operator: &str, operands: Vec<Object>) -> Operation {
+    Operation::new(operator, operands)
+}
+
+fn reals(values: Vec<f32>) -> Object {
+    Object::Array(values.into_iter().map(Object::Real).collect())
+}
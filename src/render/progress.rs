@@ -0,0 +1,75 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use layout::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderPhase {
+    Measure,
+    LayOut,
+    Render,
+    Save,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderProgress {
+    // reported when the phase starts
+    Phase(RenderPhase),
+    // pages of the document so far, reported when a page is added
+    Pages(usize),
+}
+
+// rendering fails when it is cancelled, or by the error of a phase
+#[derive(Debug)]
+pub enum RenderError {
+    Cancelled,
+    Render(Error),
+}
+
+impl From<Error> for RenderError {
+    fn from(error: Error) -> Self {
+        RenderError::Render(error)
+    }
+}
+
+// e.g. for callers of the renderer failing by layout errors
+impl From<RenderError> for Error {
+    fn from(error: RenderError) -> Self {
+        match error {
+            RenderError::Cancelled => Error::PdfWrite("Rendering cancelled".into()),
+            RenderError::Render(error) => error,
+        }
+    }
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::Cancelled => write!(f, "Rendering cancelled"),
+            RenderError::Render(error) => write!(f, "{error:?}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+// shared by clones, rendering can be cancelled from another thread; cancelled rendering
+// adds no more pages and fails at the end of the current phase
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,150 @@
+use layout::unit::Unit;
+
+use super::{ColorModel, Compression, IccProfile, Optimization, PrintMarks};
+
+// switches of rendering in one place, applied by RendererBuilder or Renderer::with_options
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    // layout is dumped by tracing before and after each of the phases
+    pub(crate) debug_input: bool,
+    pub(crate) debug_measured: bool,
+    pub(crate) debug_laid_out: bool,
+    pub(crate) debug_frame: bool,
+    pub(crate) debug_page_breaks: bool,
+    pub(crate) debug_text_metrics: bool,
+    pub(crate) kerning: bool,
+    pub(crate) text_outlines: bool,
+    pub(crate) color_model: ColorModel,
+    pub(crate) icc_profiles: Vec<IccProfile>,
+    pub(crate) precision: Option<u8>,
+    pub(crate) compression: Option<Compression>,
+    pub(crate) optimization: Option<Optimization>,
+    pub(crate) linearization: bool,
+    pub(crate) print_marks: Option<PrintMarks>,
+    pub(crate) landscape_rotation: bool,
+    pub(crate) mirrored_margins: bool,
+    pub(crate) binding_offset: Unit,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            debug_input: false,
+            debug_measured: false,
+            debug_laid_out: false,
+            debug_frame: false,
+            debug_page_breaks: false,
+            debug_text_metrics: false,
+            kerning: true,
+            text_outlines: false,
+            color_model: ColorModel::default(),
+            icc_profiles: vec![],
+            precision: None,
+            compression: None,
+            optimization: None,
+            linearization: false,
+            print_marks: None,
+            landscape_rotation: false,
+            mirrored_margins: false,
+            binding_offset: Unit::zero(),
+        }
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_debug_input(mut self, debug_input: bool) -> Self {
+        self.debug_input = debug_input;
+        self
+    }
+
+    pub fn with_debug_measured(mut self, debug_measured: bool) -> Self {
+        self.debug_measured = debug_measured;
+        self
+    }
+
+    pub fn with_debug_laid_out(mut self, debug_laid_out: bool) -> Self {
+        self.debug_laid_out = debug_laid_out;
+        self
+    }
+
+    pub fn with_debug_frame(mut self, debug_frame: bool) -> Self {
+        self.debug_frame = debug_frame;
+        self
+    }
+
+    pub fn with_debug_page_breaks(mut self, debug_page_breaks: bool) -> Self {
+        self.debug_page_breaks = debug_page_breaks;
+        self
+    }
+
+    pub fn with_debug_text_metrics(mut self, debug_text_metrics: bool) -> Self {
+        self.debug_text_metrics = debug_text_metrics;
+        self
+    }
+
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.kerning = kerning;
+        self
+    }
+
+    pub fn with_text_outlines(mut self, text_outlines: bool) -> Self {
+        self.text_outlines = text_outlines;
+        self
+    }
+
+    pub fn with_color_model(mut self, color_model: ColorModel) -> Self {
+        self.color_model = color_model;
+        self
+    }
+
+    // one profile per color model, the last one added is used
+    pub fn with_icc_profile(mut self, icc_profile: IccProfile) -> Self {
+        self.icc_profiles.push(icc_profile);
+        self
+    }
+
+    pub fn with_precision(mut self, decimals: u8) -> Self {
+        self.precision = Some(decimals);
+        self
+    }
+
+    // e.g. Compression::None to read content streams when debugging
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn with_optimization(mut self, optimization: Optimization) -> Self {
+        self.optimization = Some(optimization);
+        self
+    }
+
+    pub fn with_linearization(mut self, linearization: bool) -> Self {
+        self.linearization = linearization;
+        self
+    }
+
+    pub fn with_print_marks(mut self, print_marks: PrintMarks) -> Self {
+        self.print_marks = Some(print_marks);
+        self
+    }
+
+    pub fn with_landscape_rotation(mut self, landscape_rotation: bool) -> Self {
+        self.landscape_rotation = landscape_rotation;
+        self
+    }
+
+    pub fn with_mirrored_margins(mut self, mirrored_margins: bool) -> Self {
+        self.mirrored_margins = mirrored_margins;
+        self
+    }
+
+    pub fn with_binding_offset(mut self, binding_offset: impl Into<Unit>) -> Self {
+        self.binding_offset = binding_offset.into();
+        self
+    }
+}
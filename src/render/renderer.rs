@@ -1,16 +1,145 @@
+use std::time::{Duration, Instant};
+
 use layout::{
     Error, Layout,
     position::{Offset, Quad, Size},
+    unit::{Mm, Unit},
 };
-use printpdf::PdfDocument;
+use printpdf::{OffsetDateTime, PdfDocument};
+use smol_str::ToSmolStr;
 
-use crate::{RenderContext, font::FontCache};
+#[cfg(feature = "encryption")]
+use crate::Encryption;
+use crate::{
+    Background, CancellationToken, ColorModel, Compression, DocumentSection, IccProfile,
+    Imposition, InitialView, Marks, Optimization, Outline, Overlay, PageDecorator, PageInfo,
+    PageLabel, PageNumbering, PageTemplates, PaginationReport, PdfALevel, PdfVersion, PrintMarks,
+    RenderContext, RenderError, RenderOptions, RenderPhase, RenderProgress, RenderStats, Signature,
+    Stationery, StructureMarks, TableOfContents, TocEntry, XmpMetadata, font::FontCache,
+};
 
 use super::from_unit;
 
+// page setup, document info, fonts and options of a renderer, A4 pages without margins
+// unless set
+pub struct RendererBuilder {
+    title: String,
+    author: Option<String>,
+    subject: Option<String>,
+    keywords: Vec<String>,
+    creator: Option<String>,
+    creation_date: Option<OffsetDateTime>,
+    modification_date: Option<OffsetDateTime>,
+    page_margin: Quad,
+    page_size: Size,
+    fonts: FontCache,
+    options: RenderOptions,
+}
+
+impl RendererBuilder {
+    pub fn new(fonts: FontCache) -> Self {
+        Self {
+            title: String::new(),
+            author: None,
+            subject: None,
+            keywords: vec![],
+            creator: None,
+            creation_date: None,
+            modification_date: None,
+            page_margin: Quad::empty(),
+            page_size: Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub fn with_keywords(mut self, keywords: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = Some(creator.into());
+        self
+    }
+
+    pub fn with_creation_date(mut self, date: OffsetDateTime) -> Self {
+        self.creation_date = Some(date);
+        self
+    }
+
+    pub fn with_modification_date(mut self, date: OffsetDateTime) -> Self {
+        self.modification_date = Some(date);
+        self
+    }
+
+    pub fn with_page_margin(mut self, page_margin: Quad) -> Self {
+        self.page_margin = page_margin;
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: Size) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> Renderer {
+        let mut renderer = Renderer::new(&self.title, self.page_margin, self.page_size, self.fonts)
+            .with_options(self.options);
+        if let Some(author) = self.author {
+            renderer = renderer.with_author(author);
+        }
+        if let Some(subject) = self.subject {
+            renderer = renderer.with_subject(subject);
+        }
+        if !self.keywords.is_empty() {
+            renderer = renderer.with_keywords(self.keywords);
+        }
+        if let Some(creator) = self.creator {
+            renderer = renderer.with_creator(creator);
+        }
+        if let Some(date) = self.creation_date {
+            renderer = renderer.with_creation_date(date);
+        }
+        if let Some(date) = self.modification_date {
+            renderer = renderer.with_modification_date(date);
+        }
+        renderer
+    }
+}
+
 pub struct Renderer {
     context: RenderContext,
-    content_size: Size,
+    options: RenderOptions,
+    page_margin: Quad,
+    page_size: Size,
+    structure_marks: StructureMarks,
+    marks: Marks,
+    page_templates: Option<PageTemplates>,
+    toc: Option<TableOfContents>,
+    // labels of pages of the table of contents and of the body after it
+    toc_page_label: Option<PageLabel>,
+    page_label: Option<PageLabel>,
 }
 
 impl Renderer {
@@ -22,17 +151,41 @@ impl Renderer {
             "default",
         );
 
-        let mut content_size = page_size.clone();
-        page_margin.narrow(None, Some(&mut content_size));
-
-        let context = RenderContext::new(document, page, layer, page_margin, page_size, fonts);
+        let structure_marks = StructureMarks::new();
+        let marks = Marks::new();
+        let context = RenderContext::new(
+            document,
+            page,
+            layer,
+            page_margin.clone(),
+            page_size.clone(),
+            fonts,
+        )
+        .with_structure_marks(structure_marks.clone())
+        .with_marks(marks.clone());
 
         Self {
             context,
-            content_size,
+            options: RenderOptions::default(),
+            page_margin,
+            page_size,
+            structure_marks,
+            marks,
+            page_templates: None,
+            toc: None,
+            toc_page_label: None,
+            page_label: None,
         }
     }
 
+    // replaces options set before, including those set by other with_* methods, e.g. icc
+    // profiles set before are dropped and precision not set in the options is cleared
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.context = self.context.with_options(&options);
+        self.options = options;
+        self
+    }
+
     pub fn with_debug_frame(mut self, debug_frame: bool) -> Self {
         self.context = self.context.with_debug_frame(debug_frame);
         self
@@ -43,53 +196,553 @@ impl Renderer {
         self
     }
 
+    pub fn with_debug_text_metrics(mut self, debug_text_metrics: bool) -> Self {
+        self.context = self.context.with_debug_text_metrics(debug_text_metrics);
+        self
+    }
+
+    pub fn with_text_outlines(mut self, text_outlines: bool) -> Self {
+        self.context = self.context.with_text_outlines(text_outlines);
+        self
+    }
+
+    pub fn with_color_model(mut self, color_model: ColorModel) -> Self {
+        self.context = self.context.with_color_model(color_model);
+        self
+    }
+
+    pub fn with_icc_profile(mut self, icc_profile: IccProfile) -> Self {
+        self.context = self.context.with_icc_profile(icc_profile);
+        self
+    }
+
+    pub fn with_precision(mut self, decimals: u8) -> Self {
+        self.context = self.context.with_precision(decimals);
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.context = self.context.with_compression(compression);
+        self
+    }
+
+    // e.g. for statements of thousands of pages
+    pub fn with_optimization(mut self, optimization: Optimization) -> Self {
+        self.context = self.context.with_optimization(optimization);
+        self
+    }
+
+    // e.g. for documents downloaded by viewers in browsers
+    pub fn with_linearization(mut self, linearization: bool) -> Self {
+        self.context = self.context.with_linearization(linearization);
+        self
+    }
+
+    pub fn with_print_marks(mut self, print_marks: PrintMarks) -> Self {
+        self.context = self.context.with_print_marks(print_marks);
+        self
+    }
+
+    pub fn with_landscape_rotation(mut self, landscape_rotation: bool) -> Self {
+        self.context = self.context.with_landscape_rotation(landscape_rotation);
+        self
+    }
+
+    pub fn with_mirrored_margins(mut self, mirrored_margins: bool) -> Self {
+        self.context = self.context.with_mirrored_margins(mirrored_margins);
+        self
+    }
+
+    // options keep the offset for the content size
+    pub fn with_binding_offset(mut self, binding_offset: impl Into<Unit>) -> Self {
+        let binding_offset = binding_offset.into();
+        self.context = self.context.with_binding_offset(binding_offset);
+        self.options.binding_offset = binding_offset;
+        self
+    }
+
+    // content is narrowed by the margin and by the binding offset
+    fn content_size(&self, page_margin: &Quad, page_size: &Size) -> Size {
+        let mut content_size = page_size.clone();
+        page_margin.narrow(None, Some(&mut content_size));
+        Size::fixed(
+            content_size.base_width() - self.options.binding_offset,
+            content_size.base_height(),
+        )
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.context = self.context.with_author(author);
+        self
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.context = self.context.with_subject(subject);
+        self
+    }
+
+    pub fn with_keywords(mut self, keywords: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.context = self.context.with_keywords(keywords);
+        self
+    }
+
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.context = self.context.with_creator(creator);
+        self
+    }
+
+    pub fn with_creation_date(mut self, date: OffsetDateTime) -> Self {
+        self.context = self.context.with_creation_date(date);
+        self
+    }
+
+    pub fn with_modification_date(mut self, date: OffsetDateTime) -> Self {
+        self.context = self.context.with_modification_date(date);
+        self
+    }
+
+    pub fn with_xmp_metadata(mut self, xmp_metadata: XmpMetadata) -> Self {
+        self.context = self.context.with_xmp_metadata(xmp_metadata);
+        self
+    }
+
+    // archival output, the icc profile of the color model is required
+    pub fn with_pdf_a(mut self, level: PdfALevel) -> Self {
+        self.context = self.context.with_pdf_a(level);
+        self
+    }
+
+    pub fn with_pdf_version(mut self, version: PdfVersion) -> Self {
+        self.context = self.context.with_pdf_version(version);
+        self
+    }
+
+    // e.g. documents opened only by the user password, which can not be printed
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption(mut self, encryption: Encryption) -> Self {
+        self.context = self.context.with_encryption(encryption);
+        self
+    }
+
+    // accessible documents also need the language and alternate text of figures
+    pub fn with_tagged_pdf(mut self, tagged: bool) -> Self {
+        self.context = self.context.with_tagged_pdf(tagged);
+        self
+    }
+
+    pub fn with_pdf_ua(mut self, pdf_ua: bool) -> Self {
+        self.context = self.context.with_pdf_ua(pdf_ua);
+        self
+    }
+
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.context = self.context.with_language(language);
+        self
+    }
+
+    // e.g. full screen without toolbars for kiosks
+    pub fn with_initial_view(mut self, initial_view: InitialView) -> Self {
+        self.context = self.context.with_initial_view(initial_view);
+        self
+    }
+
+    // pre-designed pages, e.g. letter paper, the layout is rendered onto
+    pub fn with_stationery(mut self, stationery: Stationery) -> Self {
+        self.context = self.context.with_stationery(stationery);
+        self
+    }
+
+    // e.g. the full-bleed decoration of certificates
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.context = self.context.with_background(background);
+        self
+    }
+
+    // e.g. 4-up for labels
+    pub fn with_imposition(mut self, imposition: Imposition) -> Self {
+        self.context = self.context.with_imposition(imposition);
+        self
+    }
+
+    // the layout has to place the signature form field the signature refers to
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.context = self.context.with_signature(signature);
+        self
+    }
+
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.context = self.context.with_kerning(kerning);
+        self
+    }
+
+    pub fn with_font_kerning(mut self, font_name: impl ToSmolStr, kerning: bool) -> Self {
+        self.context = self.context.with_font_kerning(font_name, kerning);
+        self
+    }
+
+    // phases and pages added are reported while rendering
+    pub fn with_progress(mut self, progress: impl Fn(RenderProgress) + 'static) -> Self {
+        self.context = self.context.with_progress(progress);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.context = self.context.with_cancellation(cancellation);
+        self
+    }
+
+    pub fn reserve_glyphs(
+        &mut self,
+        font_name: &str,
+        chars: impl IntoIterator<Item = char>,
+    ) -> Result<(), Error> {
+        self.context.reserve_glyphs(font_name, chars)
+    }
+
+    pub fn add_overlay(&mut self, overlay: Overlay) -> Result<(), Error> {
+        self.context.add_overlay(overlay)
+    }
+
+    pub fn set_master_page(&mut self, layout: Box<dyn Layout>) -> Result<(), Error> {
+        self.context.set_master_page(layout)
+    }
+
+    // templates are set to the context when rendering starts, margins are checked then
+    pub fn with_page_templates(mut self, templates: PageTemplates) -> Self {
+        self.page_templates = Some(templates);
+        self
+    }
+
+    pub fn set_page_numbering(&mut self, numbering: Option<PageNumbering>) -> Result<(), Error> {
+        self.context.set_page_numbering(numbering)
+    }
+
+    // elements made by the marks tag content of layouts rendered by the renderer
+    pub fn structure_marks(&self) -> StructureMarks {
+        self.structure_marks.clone()
+    }
+
+    // spans, paths, footnotes, columns, paragraphs and headers made by the marks are taken
+    // over with layouts rendered by the renderer
+    pub fn marks(&self) -> Marks {
+        self.marks.clone()
+    }
+
+    // two-pass rendering prepends the table of contents, its entries are the sections
+    // marked in the first pass
+    pub fn with_table_of_contents(mut self, toc: TableOfContents) -> Self {
+        self.toc = Some(toc);
+        self
+    }
+
+    // e.g. lower roman numbers of the front matter
+    pub fn with_toc_page_label(mut self, label: PageLabel) -> Self {
+        self.toc_page_label = Some(label);
+        self
+    }
+
+    // pages of the body are labelled from its first page, after the table of contents
+    pub fn with_page_label(mut self, label: PageLabel) -> Self {
+        self.page_label = Some(label);
+        self
+    }
+
+    pub fn with_outline(mut self, outline: Outline) -> Self {
+        self.context.set_outline(Some(outline));
+        self
+    }
+
+    // bookmarks of sections marked by headings, nested by their levels
+    pub fn with_section_outline(mut self, section_outline: bool) -> Self {
+        self.context = self.context.with_section_outline(section_outline);
+        self
+    }
+
+    // e.g. headers and footers, pages created by page breaks are decorated as well
+    pub fn on_page(&mut self, callback: impl FnMut(&mut PageDecorator, usize) + 'static) {
+        self.context.on_page(callback);
+    }
+
+    // e.g. serial numbers or security microtext, drawn when pages are added
+    pub fn on_page_added(&mut self, callback: impl FnMut(&mut PageDecorator, &PageInfo) + 'static) {
+        self.context.on_page_added(callback);
+    }
+
     pub fn render(
         mut self,
-        mut layout: Box<dyn Layout>,
+        layout: Box<dyn Layout>,
         debug_input: bool,
         debug_measured: bool,
         debug_laid_out: bool,
     ) -> Result<Vec<u8>, Error> {
-        if debug_input {
-            tracing::debug!("INPUT\n{:#?}", layout);
+        self.options = self
+            .options
+            .with_debug_input(debug_input)
+            .with_debug_measured(debug_measured)
+            .with_debug_laid_out(debug_laid_out);
+        self.render_layout(layout)
+    }
+
+    // debug output is driven by the options
+    pub fn render_layout(self, layout: Box<dyn Layout>) -> Result<Vec<u8>, Error> {
+        self.render_with_stats(layout).map(|(pdf, _)| pdf)
+    }
+
+    pub fn render_with_stats(
+        self,
+        layout: Box<dyn Layout>,
+    ) -> Result<(Vec<u8>, RenderStats), Error> {
+        self.render_sections_with_stats(vec![DocumentSection::from_box(layout)])
+    }
+
+    // sections are concatenated into one document, each starts on a new page
+    pub fn render_sections(self, sections: Vec<DocumentSection>) -> Result<Vec<u8>, Error> {
+        self.render_sections_with_stats(sections)
+            .map(|(pdf, _)| pdf)
+    }
+
+    pub fn render_sections_with_stats(
+        self,
+        sections: Vec<DocumentSection>,
+    ) -> Result<(Vec<u8>, RenderStats), Error> {
+        self.save_sections(sections).map_err(Error::from)
+    }
+
+    // cancelled rendering fails by its own error, not by an error of a phase
+    pub fn render_cancellable(self, layout: Box<dyn Layout>) -> Result<Vec<u8>, RenderError> {
+        self.render_sections_cancellable(vec![DocumentSection::from_box(layout)])
+    }
+
+    pub fn render_sections_cancellable(
+        self,
+        sections: Vec<DocumentSection>,
+    ) -> Result<Vec<u8>, RenderError> {
+        self.save_sections(sections).map(|(pdf, _)| pdf)
+    }
+
+    fn save_sections(
+        mut self,
+        sections: Vec<DocumentSection>,
+    ) -> Result<(Vec<u8>, RenderStats), RenderError> {
+        let mut phases = self.render_pages(sections)?;
+
+        // saving consumes the context, stats are collected before
+        self.start_phase(RenderPhase::Save)?;
+        let mut stats = self.context.stats();
+        let started = Instant::now();
+        let pdf = self.context.save_to_bytes()?;
+        phases.push((RenderPhase::Save, started.elapsed()));
+        stats.phases = phases;
+
+        Ok((pdf, stats))
+    }
+
+    // pages are broken as if the layout was rendered, nothing is painted and no pdf is
+    // written, e.g. to price a print job before it is rendered
+    pub fn paginate(self, layout: Box<dyn Layout>) -> Result<PaginationReport, Error> {
+        self.paginate_sections(vec![DocumentSection::from_box(layout)])
+    }
+
+    pub fn paginate_sections(
+        mut self,
+        sections: Vec<DocumentSection>,
+    ) -> Result<PaginationReport, Error> {
+        self.context = self.context.with_dry_run(true);
+        self.render_pages(sections)?;
+        Ok(PaginationReport::new(
+            self.context.page_values(),
+            self.context.toc_entries(),
+        ))
+    }
+
+    fn render_pages(
+        &mut self,
+        mut sections: Vec<DocumentSection>,
+    ) -> Result<Vec<(RenderPhase, Duration)>, RenderError> {
+        let mut phases = vec![];
+        if let Some(templates) = self.page_templates.take() {
+            self.context.set_page_templates(templates)?;
         }
 
-        layout.measure(&mut self.context, self.content_size.clone())?;
+        let setups = sections
+            .iter()
+            .map(|section| {
+                let page_margin = section.page_margin(&self.page_margin);
+                let page_size = section.page_size(&self.page_size);
+                let content_size = self.content_size(&page_margin, &page_size);
+                (page_margin, page_size, content_size)
+            })
+            .collect::<Vec<_>>();
 
-        if debug_measured {
-            tracing::debug!("MEASURED\n{:#?}", layout);
+        if self.options.debug_input {
+            for section in sections.iter() {
+                tracing::debug!("INPUT\n{:#?}", section.layout);
+            }
         }
 
-        layout.lay_out(&mut self.context, Offset::zero(), self.content_size.clone())?;
+        self.start_phase(RenderPhase::Measure)?;
+        let started = Instant::now();
+        for (section, (_, _, content_size)) in sections.iter_mut().zip(setups.iter()) {
+            section
+                .layout
+                .measure(&mut self.context, content_size.clone())?;
+        }
+        if let Some(toc) = self.toc.as_mut().filter(|toc| !toc.entries().is_empty()) {
+            toc.typeset(&mut self.context)?;
+        }
+        phases.push((RenderPhase::Measure, started.elapsed()));
 
-        if debug_laid_out {
-            tracing::debug!("LAID OUT\n{:#?}", layout);
+        if self.options.debug_measured {
+            for section in sections.iter() {
+                tracing::debug!("MEASURED\n{:#?}", section.layout);
+            }
         }
 
+        self.start_phase(RenderPhase::LayOut)?;
+        let started = Instant::now();
+        for (section, (_, _, content_size)) in sections.iter_mut().zip(setups.iter()) {
+            section
+                .layout
+                .lay_out(&mut self.context, Offset::zero(), content_size.clone())?;
+        }
+        phases.push((RenderPhase::LayOut, started.elapsed()));
+
+        if self.options.debug_laid_out {
+            for section in sections.iter() {
+                tracing::debug!("LAID OUT\n{:#?}", section.layout);
+            }
+        }
+
+        self.start_phase(RenderPhase::Render)?;
+        let started = Instant::now();
+        // numbering is switched between sections after fonts are completed
+        for section in sections.iter() {
+            if let Some(Some(numbering)) = &section.page_numbering {
+                self.context.reserve_page_numbering(numbering)?;
+            }
+        }
         self.context.complete_fonts()?;
-        layout.render(&mut self.context)?;
+        if let Some(toc) = self.toc.as_ref().filter(|toc| !toc.entries().is_empty()) {
+            if let Some(label) = self.toc_page_label.clone() {
+                self.context.set_page_label(label);
+            }
+            let width = self
+                .content_size(&self.page_margin, &self.page_size)
+                .base_width();
+            self.context.table_of_contents(&Offset::zero(), width, toc);
+            layout::RenderContext::new_page(&mut self.context, None);
+        }
+        if let Some(label) = self.page_label.clone() {
+            self.context.set_page_label(label);
+        }
 
-        let pdf = self.context.save_to_bytes()?;
+        let document_numbering = self.context.switch_page_numbering(None);
+        for (index, (section, (page_margin, page_size, _))) in
+            sections.iter_mut().zip(setups.iter()).enumerate()
+        {
+            // pages of the previous section are decorated by its callbacks and numbering, the
+            // first section starts on the page already open
+            if index > 0 || section.has_page_setup() {
+                self.context
+                    .set_page_setup(page_margin, page_size, index == 0);
+            }
+            self.context
+                .set_section_page_callbacks(std::mem::take(&mut section.page_callbacks));
+            let numbering = match section.page_numbering.take() {
+                Some(numbering) => {
+                    if let Some(numbering) = &numbering {
+                        self.context.restart_page_numbering(numbering.start());
+                    }
+                    numbering
+                }
+                None => document_numbering.clone(),
+            };
+            self.context.switch_page_numbering(numbering);
+            section.layout.render(&mut self.context)?;
+        }
+        phases.push((RenderPhase::Render, started.elapsed()));
+        Ok(phases)
+    }
+
+    // the renderer and the layout are built for each pass, both passes are set up the same
+    // way, e.g. by templates, numbering or the master page; the first one paginates the
+    // layout and records page values, the second one substitutes them into slots of typeset
+    // text, e.g. {pages} or {ref:name}, the page of the anchor
+    pub fn render_two_pass(
+        renderer: impl Fn() -> Result<Renderer, Error>,
+        layout: impl Fn(&Renderer) -> Box<dyn Layout>,
+    ) -> Result<Vec<u8>, Error> {
+        let first = renderer()?;
+        let first_layout = layout(&first);
+        let report = first.paginate(first_layout)?;
+
+        // the body is moved by pages of the table of contents, which are counted by
+        // paginating it alone; the body starts on the page that follows it
+        let mut toc_pass = renderer()?;
+        let toc_pages = match toc_pass.toc.as_mut() {
+            Some(toc) => {
+                toc.set_entries(report.sections().to_vec());
+                toc_pass
+                    .paginate_sections(vec![])?
+                    .pages()
+                    .saturating_sub(1)
+            }
+            None => 0,
+        };
+
+        let mut second = renderer()?;
+        let mut page_values = report.page_values().clone();
+        if let Some(toc) = second.toc.as_mut() {
+            toc.set_entries(report.sections().to_vec());
+            let entries = toc
+                .entries()
+                .iter()
+                .cloned()
+                .map(|entry| TocEntry {
+                    page: entry.page + toc_pages,
+                    ..entry
+                })
+                .collect();
+            toc.set_entries(entries);
+            page_values = page_values.shifted(toc_pages);
+        }
 
-        Ok(pdf)
+        second.context.set_page_values(Some(page_values));
+        let second_layout = layout(&second);
+        second.render_layout(second_layout)
+    }
+
+    fn start_phase(&self, phase: RenderPhase) -> Result<(), RenderError> {
+        if self.context.is_cancelled() {
+            return Err(RenderError::Cancelled);
+        }
+        self.context.report(RenderProgress::Phase(phase));
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
+        cell::RefCell,
         fs::File,
         io::{BufWriter, Write},
+        rc::Rc,
     };
 
     use layout::{
-        Axis, Border, Features, Font, LayoutBox, Rgba, Stroke, StyleBuilder, Text, hbox, hfill,
+        Axis, Border, Error, Features, Font, LayoutBox, Rgba, Stroke, StyleBuilder, Text, hbox,
+        hfill,
         position::{Quad, Size},
         unit::{Mm, Pt},
         vbox, vfill,
     };
 
-    use crate::{Renderer, new_font_cache};
+    use crate::{
+        CancellationToken, ColorModel, DocumentSection, Marks, Overlay, PageAnchor, PageTemplate,
+        PageTemplates, RenderError, RenderOptions, RenderPhase, RenderProgress, Renderer,
+        RendererBuilder, TableOfContents, new_font_cache, render::test_layouts::Rule,
+    };
 
     #[test]
     fn h_center() {
@@ -275,4 +928,341 @@ mod tests {
             .write_all(&pdf)
             .unwrap();
     }
+
+    #[test]
+    fn overlay() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let mut renderer = Renderer::new(
+            "Text",
+            Quad::square(Mm(20.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+
+        let style = StyleBuilder::default().with_font(Font::new(
+            "LatoReg",
+            Pt(10.0),
+            Some(Features::default()),
+        ));
+
+        let badge = hbox()
+            .axis_size(Mm(40.0))
+            .child(hfill(1))
+            .child(Text::new("CONFIDENTIAL").style(style.clone()));
+        renderer
+            .add_overlay(Overlay::new(
+                Box::new(badge),
+                Size::fixed(Mm(40.0), Mm(6.0)),
+                PageAnchor::TopRight,
+            ))
+            .unwrap();
+
+        let outer = hbox()
+            .axis_size(Mm(170.0))
+            .child(Text::new("Žáňa Nováková jr.").style(style));
+
+        let pdf = renderer
+            .render(Box::new(outer), false, false, false)
+            .unwrap();
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let content = document.get_and_decode_page_content(page_id).unwrap();
+        let texts = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "BT")
+            .count();
+        assert_eq!(texts, 2);
+
+        BufWriter::new(File::create("test_overlay.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn builder() {
+        let renderer = RendererBuilder::new(new_font_cache())
+            .with_title("Builder")
+            .with_author("Jana Nováková")
+            .with_keywords(["builder", "options"])
+            .with_page_margin(Quad::square(Mm(20.0)))
+            .with_page_size(Size::fixed(Mm(148.0), Mm(210.0)))
+            .with_options(
+                RenderOptions::new()
+                    .with_debug_frame(true)
+                    .with_color_model(ColorModel::Cmyk),
+            )
+            .build();
+        assert!(renderer.options.debug_frame);
+
+        let outer = LayoutBox::new(Axis::Vertical)
+            .style(StyleBuilder::new().with_border(Border::h(Stroke::new(Rgba::black(), Pt(1.0)))));
+        let pdf = renderer.render_layout(Box::new(outer)).unwrap();
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let (_, page_id) = document.get_pages().into_iter().next().unwrap();
+        let media_box = document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"MediaBox"))
+            .and_then(|media_box| media_box.as_array())
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap().round())
+            .collect::<Vec<_>>();
+        assert_eq!(media_box, vec![0.0, 0.0, 420.0, 595.0]);
+        let info = document
+            .trailer
+            .get(b"Info")
+            .and_then(|info| info.as_reference())
+            .and_then(|info| document.get_dictionary(info))
+            .unwrap();
+        assert_eq!(
+            info.get(b"Author")
+                .and_then(|author| author.as_str())
+                .unwrap(),
+            "Jana Nováková".as_bytes()
+        );
+
+        BufWriter::new(File::create("test_builder.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn progress() {
+        let reported = Rc::new(RefCell::new(vec![]));
+        let renderer = RendererBuilder::new(new_font_cache())
+            .build()
+            .with_progress({
+                let reported = reported.clone();
+                move |progress| reported.borrow_mut().push(progress)
+            });
+
+        renderer
+            .render_layout(Box::new(LayoutBox::new(Axis::Vertical)))
+            .unwrap();
+        assert_eq!(
+            *reported.borrow(),
+            [
+                RenderPhase::Measure,
+                RenderPhase::LayOut,
+                RenderPhase::Render,
+                RenderPhase::Save
+            ]
+            .map(RenderProgress::Phase)
+        );
+
+        let cancellation = CancellationToken::new();
+        let renderer = RendererBuilder::new(new_font_cache())
+            .build()
+            .with_cancellation(cancellation.clone());
+        cancellation.cancel();
+        assert!(matches!(
+            renderer.render_cancellable(Box::new(LayoutBox::new(Axis::Vertical))),
+            Err(RenderError::Cancelled)
+        ));
+
+        // the error of the api kept for callers of layout errors
+        let renderer = RendererBuilder::new(new_font_cache())
+            .build()
+            .with_cancellation(cancellation);
+        let Err(Error::PdfWrite(message)) =
+            renderer.render_layout(Box::new(LayoutBox::new(Axis::Vertical)))
+        else {
+            panic!("cancelled rendering does not fail");
+        };
+        assert_eq!(message, "Rendering cancelled");
+    }
+
+    #[test]
+    fn stats() {
+        let (pdf, stats) = RendererBuilder::new(new_font_cache())
+            .build()
+            .render_with_stats(Box::new(LayoutBox::new(Axis::Vertical)))
+            .unwrap();
+        assert!(!pdf.is_empty());
+        assert_eq!(stats.pages, 1);
+        assert!(stats.fonts.is_empty());
+        assert_eq!((stats.images, stats.image_bytes), (0, 0));
+        assert_eq!(
+            stats
+                .phases
+                .iter()
+                .map(|(phase, _)| *phase)
+                .collect::<Vec<_>>(),
+            [
+                RenderPhase::Measure,
+                RenderPhase::LayOut,
+                RenderPhase::Render,
+                RenderPhase::Save
+            ]
+        );
+        assert!(stats.total_time() >= stats.phases[0].1);
+    }
+
+    #[test]
+    fn table_of_contents() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let style = StyleBuilder::default().with_font(Font::new(
+            "LatoReg",
+            Pt(10.0),
+            Some(Features::default()),
+        ));
+
+        let renderer = || {
+            Ok(RendererBuilder::new(fonts.clone())
+                .with_page_margin(Quad::square(Mm(10.0)))
+                .build()
+                .with_table_of_contents(TableOfContents::new(style.clone().build())))
+        };
+
+        let pdf = Renderer::render_two_pass(renderer, |renderer| {
+            let marks = renderer.marks();
+            let heading =
+                |title: &str| marks.section(title, 1, Text::new(title).style(style.clone()));
+            Box::new(
+                vbox()
+                    .child(heading("Introduction"))
+                    .child(vbox().axis_size(Mm(150.0)))
+                    .child(vbox().axis_size(Mm(150.0)))
+                    .child(heading("Results")),
+            )
+        })
+        .unwrap();
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 3);
+        let links = document
+            .get_dictionary(pages[&1])
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| annots.as_array())
+            .map(|annots| annots.len())
+            .unwrap();
+        assert_eq!(links, 2);
+
+        BufWriter::new(File::create("test_table_of_contents.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn page_templates() {
+        let renderer = || {
+            RendererBuilder::new(new_font_cache())
+                .with_page_margin(Quad::square(Mm(10.0)))
+                .build()
+        };
+        let layout = || Box::new(vbox().child(vbox().axis_size(Mm(100.0))));
+
+        let templates =
+            PageTemplates::new().with_odd(PageTemplate::new().with_margin(Quad::square(Mm(10.0))));
+        let pdf = renderer()
+            .with_page_templates(templates)
+            .render_layout(layout());
+        assert!(pdf.is_ok());
+
+        // narrower content of the template is rejected
+        let templates =
+            PageTemplates::new().with_odd(PageTemplate::new().with_margin(Quad::square(Mm(30.0))));
+        let pdf = renderer()
+            .with_page_templates(templates)
+            .render_layout(layout());
+        assert!(pdf.is_err());
+    }
+
+    #[test]
+    fn sections() {
+        let decorated = Rc::new(RefCell::new(vec![]));
+        let renderer = RendererBuilder::new(new_font_cache())
+            .with_page_margin(Quad::square(Mm(10.0)))
+            .with_page_size(Size::fixed(Mm(210.0), Mm(297.0)))
+            .build();
+
+        let pdf = renderer
+            .render_sections(vec![
+                DocumentSection::new(vbox().child(vbox().axis_size(Mm(100.0)))),
+                DocumentSection::new(vbox().child(vbox().axis_size(Mm(100.0))))
+                    .with_page_size(Size::fixed(Mm(148.0), Mm(210.0)))
+                    .with_page_margin(Quad::square(Mm(20.0))),
+                DocumentSection::new(vbox().child(vbox().axis_size(Mm(100.0))))
+                    .with_landscape(true)
+                    .with_page_callback({
+                        let decorated = decorated.clone();
+                        move |_, page| decorated.borrow_mut().push(page)
+                    }),
+            ])
+            .unwrap();
+
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        let media_boxes = document
+            .get_pages()
+            .values()
+            .map(|page_id| {
+                document
+                    .get_dictionary(*page_id)
+                    .and_then(|page| page.get(b"MediaBox"))
+                    .and_then(|media_box| media_box.as_array())
+                    .unwrap()
+                    .iter()
+                    .map(|value| value.as_float().unwrap().round())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            media_boxes,
+            vec![
+                vec![0.0, 0.0, 595.0, 842.0],
+                vec![0.0, 0.0, 420.0, 595.0],
+                vec![0.0, 0.0, 842.0, 595.0],
+            ]
+        );
+        assert_eq!(*decorated.borrow(), vec![3]);
+
+        BufWriter::new(File::create("test_sections.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn paginate() {
+        let renderer = || {
+            RendererBuilder::new(new_font_cache())
+                .with_page_margin(Quad::square(Mm(10.0)))
+                .build()
+        };
+        let sections = |marks: &Marks| {
+            vec![
+                DocumentSection::new(Rule::new(600.0)),
+                DocumentSection::new(marks.section("Appendix", 1, Rule::new(10.0))),
+            ]
+        };
+
+        let paginated = renderer();
+        let marks = paginated.marks();
+        let report = paginated.paginate_sections(sections(&marks)).unwrap();
+        assert_eq!(report.pages(), 4);
+        let entries = report
+            .sections()
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.page))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![("Appendix", 4)]);
+        assert_eq!(report.anchor_page("section 1"), Some(4));
+
+        let rendered = renderer();
+        let marks = rendered.marks();
+        let pdf = rendered.render_sections(sections(&marks)).unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&pdf).unwrap();
+        assert_eq!(document.get_pages().len(), report.pages());
+    }
 }
@@ -0,0 +1,180 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId};
+
+// resources printpdf has no public API for, added to the saved document
+struct PageResource {
+    page: usize,
+    category: &'static str,
+    key: Option<String>,
+    name: String,
+    object: Object,
+}
+
+#[derive(Default)]
+pub(crate) struct PageResources {
+    resources: Vec<PageResource>,
+}
+
+impl PageResources {
+    // names are prefixed so they never clash with the ones printpdf generates
+    pub(crate) fn add(
+        &mut self,
+        page: usize,
+        category: &'static str,
+        object: impl Into<Object>,
+    ) -> String {
+        self.push(page, category, None, object.into())
+    }
+
+    // resources with the same key are added only once per page
+    pub(crate) fn add_shared(
+        &mut self,
+        page: usize,
+        category: &'static str,
+        key: String,
+        object: impl FnOnce() -> Object,
+    ) -> String {
+        let existing = self.resources.iter().find(|resource| {
+            resource.page == page
+                && resource.category == category
+                && resource.key.as_ref() == Some(&key)
+        });
+        match existing {
+            Some(resource) => resource.name.clone(),
+            None => self.push(page, category, Some(key), object()),
+        }
+    }
+
+    fn push(
+        &mut self,
+        page: usize,
+        category: &'static str,
+        key: Option<String>,
+        object: Object,
+    ) -> String {
+        let name = format!("PR{}{}", category, self.resources.len());
+        self.resources.push(PageResource {
+            page,
+            category,
+            key,
+            name: name.clone(),
+            object,
+        });
+        name
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let pages = document.get_pages();
+        for resource in self.resources.iter() {
+            let Some(page_id) = pages.get(&(resource.page as u32 + 1)) else {
+                tracing::warn!("Resource {} refers to missing page.", resource.name);
+                continue;
+            };
+
+            let resources_id = indirect_dictionary(document, *page_id, b"Resources")?;
+            let category_id =
+                indirect_dictionary(document, resources_id, resource.category.as_bytes())?;
+
+            let object = indirect_streams(document, resource.object.clone());
+            document
+                .get_object_mut(category_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?
+                .set(resource.name.as_bytes(), object);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn pdf_error(error: impl ToString) -> Error {
+    Error::PdfWrite(error.to_string().into())
+}
+
+pub(crate) fn catalog(document: &mut Document) -> Result<&mut Dictionary, Error> {
+    let catalog_id = document
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(pdf_error)?;
+    document
+        .get_object_mut(catalog_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)
+}
+
+// streams can not be direct objects, they are added to the document and referenced
+pub(crate) fn indirect_streams(document: &mut Document, object: Object) -> Object {
+    match object {
+        Object::Stream(mut stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                *value = indirect_streams(document, std::mem::replace(value, Object::Null));
+            }
+            Object::Reference(document.add_object(stream))
+        }
+        Object::Dictionary(mut dictionary) => {
+            for (_, value) in dictionary.iter_mut() {
+                *value = indirect_streams(document, std::mem::replace(value, Object::Null));
+            }
+            Object::Dictionary(dictionary)
+        }
+        Object::Array(array) => Object::Array(
+            array
+                .into_iter()
+                .map(|value| indirect_streams(document, value))
+                .collect(),
+        ),
+        object => object,
+    }
+}
+
+// dictionaries of the object and all nested in it, of streams as well
+pub(crate) fn visit_dictionaries(object: &Object, visit: &mut impl FnMut(&Dictionary)) {
+    let dictionary = match object {
+        Object::Dictionary(dictionary) => dictionary,
+        Object::Stream(stream) => &stream.dict,
+        Object::Array(array) => {
+            array
+                .iter()
+                .for_each(|object| visit_dictionaries(object, visit));
+            return;
+        }
+        _ => return,
+    };
+    visit(dictionary);
+    for (_, object) in dictionary.iter() {
+        visit_dictionaries(object, visit);
+    }
+}
+
+// makes parent[key] an indirect dictionary, so it can be modified in place
+pub(crate) fn indirect_dictionary(
+    document: &mut Document,
+    parent: ObjectId,
+    key: &[u8],
+) -> Result<ObjectId, Error> {
+    let current = document
+        .get_object(parent)
+        .and_then(Object::as_dict)
+        .map_err(pdf_error)?
+        .get(key)
+        .ok()
+        .cloned();
+
+    let id = match current {
+        Some(Object::Reference(id)) => return Ok(id),
+        Some(Object::Dictionary(dictionary)) => document.add_object(dictionary),
+        _ => document.add_object(Dictionary::new()),
+    };
+
+    document
+        .get_object_mut(parent)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?
+        .set(key, Object::Reference(id));
+
+    Ok(id)
+}
@@ -0,0 +1,456 @@
+use layout::{
+    Error, Layout, LayoutBox, MeasureContext, RenderContext, StyleBuilder, Text, hbox, hfill,
+    position::{Offset, Size},
+    unit::{Mm, Pt, Unit},
+    vbox,
+};
+
+use super::{Marks, Span, TextDecoration};
+
+// text of the same style within a block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Run {
+    pub(crate) text: String,
+    pub(crate) emphasis: bool,
+    pub(crate) strong: bool,
+    pub(crate) code: bool,
+    pub(crate) underline: bool,
+    pub(crate) link: Option<String>,
+}
+
+impl Run {
+    fn same_style(&self, other: &Run) -> bool {
+        (
+            self.emphasis,
+            self.strong,
+            self.code,
+            self.underline,
+            &self.link,
+        ) == (
+            other.emphasis,
+            other.strong,
+            other.code,
+            other.underline,
+            &other.link,
+        )
+    }
+}
+
+// the run is joined to the last one of the line, if they are of the same style
+pub(crate) fn push_run(line: &mut Vec<Run>, run: Run) {
+    match line.last_mut() {
+        Some(last) if last.same_style(&run) => last.text.push_str(&run.text),
+        _ => line.push(run),
+    }
+}
+
+// runs of lines broken explicitly, lines are broken further by the layout
+pub(crate) type Lines = Vec<Vec<Run>>;
+
+pub(crate) type Cells = Vec<Vec<Run>>;
+
+// blocks of rich text converted from a markup, e.g. markdown or HTML
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Block {
+    Heading(usize, Lines),
+    Paragraph(Lines),
+    // numbered from the start, bulleted if none
+    List(Option<u64>, Vec<Vec<Block>>),
+    // lines kept as they are, e.g. a code block
+    Preformatted(Vec<String>),
+    // header cells and cells of rows
+    Table(Cells, Vec<Cells>),
+}
+
+// styles of elements of rich text, those not set are the style of text; headings of
+// levels without own style get the last one set
+#[derive(Clone)]
+pub struct RichTextStyles {
+    text: StyleBuilder,
+    emphasis: Option<StyleBuilder>,
+    strong: Option<StyleBuilder>,
+    strong_emphasis: Option<StyleBuilder>,
+    code: Option<StyleBuilder>,
+    code_block: Option<StyleBuilder>,
+    headings: Vec<StyleBuilder>,
+    table_header: Option<StyleBuilder>,
+    link: Option<StyleBuilder>,
+    bullet: String,
+    list_indent: Unit,
+    block_spacing: Option<Unit>,
+    marks: Option<Marks>,
+}
+
+impl RichTextStyles {
+    pub fn new(text: StyleBuilder) -> Self {
+        Self {
+            text,
+            emphasis: None,
+            strong: None,
+            strong_emphasis: None,
+            code: None,
+            code_block: None,
+            headings: vec![],
+            table_header: None,
+            link: None,
+            bullet: "\u{2022}".into(),
+            list_indent: Mm(6.0).into(),
+            block_spacing: None,
+            marks: None,
+        }
+    }
+
+    // e.g. an italic font
+    pub fn with_emphasis(mut self, style: StyleBuilder) -> Self {
+        self.emphasis = Some(style);
+        self
+    }
+
+    pub fn with_strong(mut self, style: StyleBuilder) -> Self {
+        self.strong = Some(style);
+        self
+    }
+
+    // strong is used unless set
+    pub fn with_strong_emphasis(mut self, style: StyleBuilder) -> Self {
+        self.strong_emphasis = Some(style);
+        self
+    }
+
+    // inline code
+    pub fn with_code(mut self, style: StyleBuilder) -> Self {
+        self.code = Some(style);
+        self
+    }
+
+    // the style of the box of the block and of its lines, inline code is used unless set
+    pub fn with_code_block(mut self, style: StyleBuilder) -> Self {
+        self.code_block = Some(style);
+        self
+    }
+
+    // styles of headings from level 1
+    pub fn with_headings(mut self, styles: impl IntoIterator<Item = StyleBuilder>) -> Self {
+        self.headings = styles.into_iter().collect();
+        self
+    }
+
+    pub fn with_table_header(mut self, style: StyleBuilder) -> Self {
+        self.table_header = Some(style);
+        self
+    }
+
+    // e.g. a color of links, it takes precedence over emphasis
+    pub fn with_link(mut self, style: StyleBuilder) -> Self {
+        self.link = Some(style);
+        self
+    }
+
+    pub fn with_bullet(mut self, bullet: impl Into<String>) -> Self {
+        self.bullet = bullet.into();
+        self
+    }
+
+    // width of bullets and numbers of list items
+    pub fn with_list_indent(mut self, indent: impl Into<Unit>) -> Self {
+        self.list_indent = indent.into();
+        self
+    }
+
+    // vertical space between blocks
+    pub fn with_block_spacing(mut self, spacing: impl Into<Unit>) -> Self {
+        self.block_spacing = Some(spacing.into());
+        self
+    }
+
+    // links and underlines are painted through spans of the marks, text having them is
+    // not measured unless the marks are passed to the render context; headings mark their
+    // sections, e.g. for the table of contents
+    pub fn with_marks(mut self, marks: Marks) -> Self {
+        self.marks = Some(marks);
+        self
+    }
+
+    fn heading(&self, level: usize) -> &StyleBuilder {
+        self.headings
+            .get(level.saturating_sub(1))
+            .or(self.headings.last())
+            .unwrap_or(&self.text)
+    }
+
+    fn run_style(&self, run: &Run, block: &StyleBuilder) -> StyleBuilder {
+        let style = match run {
+            Run { code: true, .. } => self.code.as_ref(),
+            Run { link: Some(_), .. } if self.link.is_some() => self.link.as_ref(),
+            Run {
+                strong: true,
+                emphasis: true,
+                ..
+            } => self.strong_emphasis.as_ref().or(self.strong.as_ref()),
+            Run { strong: true, .. } => self.strong.as_ref(),
+            Run { emphasis: true, .. } => self.emphasis.as_ref(),
+            _ => None,
+        };
+        style.unwrap_or(block).clone()
+    }
+
+    pub(crate) fn layout(&self, blocks: &[Block]) -> LayoutBox {
+        let mut layout = vbox();
+        for (index, block) in blocks.iter().enumerate() {
+            if index > 0
+                && let Some(spacing) = self.block_spacing
+            {
+                layout = layout.child(vbox().axis_size(spacing));
+            }
+            layout = self.add_block(layout, block);
+        }
+        layout
+    }
+
+    fn add_block(&self, parent: LayoutBox, block: &Block) -> LayoutBox {
+        match block {
+            Block::Heading(level, lines) => {
+                let heading = self.lines(lines, self.heading(*level));
+                match &self.marks {
+                    Some(marks) => parent.child(marks.section(plain_text(lines), *level, heading)),
+                    None => parent.child(heading),
+                }
+            }
+            Block::Paragraph(lines) => parent.child(self.lines(lines, &self.text)),
+            Block::List(start, items) => {
+                let mut list = vbox();
+                for (index, item) in items.iter().enumerate() {
+                    let marker = match start {
+                        Some(start) => format!("{}.", start + index as u64),
+                        None => self.bullet.clone(),
+                    };
+                    let marker = hbox()
+                        .axis_size(self.list_indent)
+                        .child(Text::new(&marker).style(self.text.clone()));
+                    list = list.child(hbox().child(marker).child(self.layout(item)));
+                }
+                parent.child(list)
+            }
+            Block::Preformatted(lines) => {
+                let style = self.code_block.as_ref().or(self.code.as_ref());
+                let style = style.unwrap_or(&self.text);
+                let mut code = vbox().style(style.clone());
+                for line in lines {
+                    code = code.child(Text::new(line).style(style.clone()));
+                }
+                parent.child(code)
+            }
+            Block::Table(header, rows) => {
+                let header_style = self.table_header.as_ref().unwrap_or(&self.text);
+                let mut table = vec![];
+                if !header.is_empty() {
+                    table.push(self.table_row(header, header_style));
+                }
+                for row in rows {
+                    table.push(self.table_row(row, &self.text));
+                }
+                parent.child(RichTable {
+                    rows: table,
+                    body: None,
+                })
+            }
+        }
+    }
+
+    fn table_row(&self, cells: &[Vec<Run>], style: &StyleBuilder) -> Vec<RunsLayout> {
+        cells.iter().map(|cell| self.line(cell, style)).collect()
+    }
+
+    fn lines(&self, lines: &Lines, style: &StyleBuilder) -> LayoutBox {
+        lines
+            .iter()
+            .fold(vbox(), |layout, line| layout.child(self.line(line, style)))
+    }
+
+    pub(crate) fn line(&self, runs: &[Run], style: &StyleBuilder) -> RunsLayout {
+        RunsLayout {
+            runs: runs
+                .iter()
+                .map(|run| (run.clone(), self.run_style(run, style)))
+                .collect(),
+            marks: self.marks.clone(),
+            lines: vec![],
+            body: None,
+        }
+    }
+}
+
+// runs wrapped as one sequence, lines are broken between words of any of the runs when
+// the width is known
+#[derive(Debug)]
+pub(crate) struct RunsLayout {
+    runs: Vec<(Run, StyleBuilder)>,
+    marks: Option<Marks>,
+    // parts of runs by lines, with the index of the run
+    lines: Vec<Vec<(usize, String)>>,
+    body: Option<LayoutBox>,
+}
+
+impl RunsLayout {
+    fn break_lines(
+        &self,
+        ctx: &mut dyn MeasureContext,
+        width: f64,
+    ) -> Result<Vec<Vec<(usize, String)>>, Error> {
+        let mut lines = vec![];
+        let mut line: Vec<(usize, String)> = vec![];
+        let mut line_width = 0.0;
+        for (index, (run, style)) in self.runs.iter().enumerate() {
+            let style = style.clone().build();
+            let font = style.font().merge(ctx.style().font());
+            let font_size = font.size().map_or(0.0, |size| *size);
+            for word in run.text.split_inclusive(' ') {
+                let word_width = ctx.typeset(&style, word)?.width.0 * font_size;
+                // the space after the last word of a line is not painted
+                let fits =
+                    line_width + ctx.typeset(&style, word.trim_end())?.width.0 * font_size <= width;
+                if !fits && !line.is_empty() {
+                    lines.push(trim_line(std::mem::take(&mut line)));
+                    line_width = 0.0;
+                }
+                line_width += word_width;
+                match line.last_mut() {
+                    Some((last, text)) if *last == index => text.push_str(word),
+                    _ => line.push((index, word.into())),
+                }
+            }
+        }
+        lines.push(line);
+        Ok(lines)
+    }
+
+    fn line_box(&self, parts: &[(usize, String)]) -> LayoutBox {
+        parts.iter().fold(hbox(), |line, (index, part)| {
+            let (run, style) = &self.runs[*index];
+            let text = Text::new(part).style(style.clone());
+            let mut span = Span::new();
+            if let Some(uri) = &run.link {
+                span = span.with_link(uri);
+            }
+            if run.underline {
+                span = span.with_decoration(TextDecoration::empty().underline());
+            }
+            match &self.marks {
+                Some(marks) if !span.is_empty() => line.child(marks.span(span, text)),
+                _ => line.child(text),
+            }
+        })
+    }
+
+    #[cfg(all(test, feature = "markdown"))]
+    pub(crate) fn lines(&self) -> &[Vec<(usize, String)>] {
+        &self.lines
+    }
+}
+
+fn trim_line(mut line: Vec<(usize, String)>) -> Vec<(usize, String)> {
+    if let Some((_, text)) = line.last_mut() {
+        text.truncate(text.trim_end().len());
+    }
+    line
+}
+
+impl Layout for RunsLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        let spans = self
+            .runs
+            .iter()
+            .any(|(run, _)| run.link.is_some() || run.underline);
+        if spans && !self.marks.as_ref().is_some_and(Marks::is_wired) {
+            return Err(Error::PdfWrite(
+                "Rich text marks are not passed to the render context".into(),
+            ));
+        }
+        self.lines = self.break_lines(ctx, Pt::from(size.base_width()).0)?;
+        let mut body = match self.lines.as_slice() {
+            [line] => self.line_box(line),
+            lines => lines
+                .iter()
+                .fold(vbox(), |body, line| body.child(self.line_box(line))),
+        };
+        body.measure(ctx, size)?;
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        match self.body.as_mut() {
+            Some(body) => body.lay_out(ctx, offset, size),
+            None => Ok(()),
+        }
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        match self.body.as_ref() {
+            Some(body) => body.render(ctx),
+            None => Ok(()),
+        }
+    }
+}
+
+// columns share the width of the table equally, rows are made when it is known
+#[derive(Debug)]
+struct RichTable {
+    rows: Vec<Vec<RunsLayout>>,
+    body: Option<LayoutBox>,
+}
+
+impl RichTable {
+    fn column_width(&self, width: Unit) -> Unit {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or_default();
+        Mm(Mm::from(width).0 / columns.max(1) as f64).into()
+    }
+}
+
+impl Layout for RichTable {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        let width = self.column_width(size.base_width());
+        let mut body = vbox();
+        for cells in std::mem::take(&mut self.rows) {
+            let row = cells.into_iter().fold(hbox(), |row, cell| {
+                row.child(hbox().axis_size(width).child(cell).child(hfill(1)))
+            });
+            body = body.child(row);
+        }
+        body.measure(ctx, size)?;
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        match self.body.as_mut() {
+            Some(body) => body.lay_out(ctx, offset, size),
+            None => Ok(()),
+        }
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        match self.body.as_ref() {
+            Some(body) => body.render(ctx),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn plain_text(lines: &Lines) -> String {
+    lines
+        .iter()
+        .map(|line| line.iter().map(|run| run.text.as_str()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,202 @@
+use layout::{
+    Error, Layout, MeasureContext, RenderContext,
+    position::{Offset, Size},
+    unit::Unit,
+};
+
+use super::{Mark, Marks};
+
+// section of a heading at the offset, numbered ones are part of the outline
+#[derive(Clone, Debug)]
+pub(crate) struct SectionMark {
+    pub(crate) title: String,
+    pub(crate) level: usize,
+    pub(crate) numbered: bool,
+    pub(crate) offset: Unit,
+}
+
+// headings mark their sections through the marks, the render context takes them over
+// when the heading checks its page break, so running headers and the table of contents
+// get the page the heading is moved to
+impl Marks {
+    // layout of the heading of a section, levels start at 1
+    pub fn section(
+        &self,
+        title: impl Into<String>,
+        level: usize,
+        layout: impl Layout + 'static,
+    ) -> SectionHeading {
+        SectionHeading {
+            layout: Box::new(layout),
+            title: title.into(),
+            level,
+            numbered: false,
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SectionHeading {
+    layout: Box<dyn Layout>,
+    title: String,
+    level: usize,
+    numbered: bool,
+    marks: Marks,
+    offset: Offset,
+    size: Size,
+}
+
+impl SectionHeading {
+    // hierarchical mark of the heading, e.g. 1.2, gives its level, 2, and precedes its
+    // title; headings marked so build the outline without setting it up
+    pub fn mark(mut self, mark: &str) -> Self {
+        let level = mark.split('.').filter(|part| !part.is_empty()).count();
+        self.title = format!("{} {}", mark.trim_end_matches('.'), self.title);
+        self.level = level.max(1);
+        self.numbered = true;
+        self
+    }
+}
+
+impl Layout for SectionHeading {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    // the heading is moved to the next page before it is marked, if it does not fit
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        self.marks.push(Mark::Section(SectionMark {
+            title: self.title.clone(),
+            level: self.level,
+            numbered: self.numbered,
+            offset: self.offset.y,
+        }));
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+        self.layout.render(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Axis, Layout, LayoutBox,
+        position::{Offset, Quad, Size},
+        unit::Mm,
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object, ObjectId},
+    };
+
+    use crate::{Marks, RenderContext, new_font_cache};
+
+    #[test]
+    fn marks() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let mut heading = marks.section("Results", 2, LayoutBox::new(Axis::Vertical));
+        let size = Size::fixed(Mm(190.0), Mm(10.0));
+        heading.measure(&mut rctx, size.clone()).unwrap();
+        heading
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(100.0)), size)
+            .unwrap();
+        heading.render(&mut rctx).unwrap();
+
+        let entries = rctx.toc_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].title.as_str(), entries[0].level, entries[0].page),
+            ("Results", 2, 1)
+        );
+        assert_eq!(entries[0].anchor, "section 1");
+        assert!(marks.take().is_empty());
+    }
+
+    #[test]
+    fn hierarchical_marks() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let size = Size::fixed(Mm(190.0), Mm(10.0));
+        for (mark, title, y) in [
+            ("1", "Introduction", 0.0),
+            ("1.1", "Scope", 20.0),
+            ("1.2.", "Terms", 40.0),
+            ("2", "Results", 60.0),
+        ] {
+            let mut heading = marks
+                .section(title, 1, LayoutBox::new(Axis::Vertical))
+                .mark(mark);
+            heading.measure(&mut rctx, size.clone()).unwrap();
+            heading
+                .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(y)), size.clone())
+                .unwrap();
+            heading.render(&mut rctx).unwrap();
+        }
+        let entries = rctx.toc_entries();
+        let levels = entries.iter().map(|entry| entry.level).collect::<Vec<_>>();
+        assert_eq!(levels, [1, 2, 2, 1]);
+        assert_eq!(entries[2].title, "1.2 Terms");
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let item = |id: ObjectId| document.get_dictionary(id).unwrap();
+        let reference =
+            |id: ObjectId, key: &[u8]| item(id).get(key).and_then(Object::as_reference).unwrap();
+        let title = |id: ObjectId| item(id).get(b"Title").unwrap().as_str().unwrap().to_vec();
+        let outlines = document
+            .catalog()
+            .unwrap()
+            .get(b"Outlines")
+            .and_then(Object::as_reference)
+            .unwrap();
+
+        let introduction = reference(outlines, b"First");
+        assert_eq!(title(introduction), b"1 Introduction");
+        let scope = reference(introduction, b"First");
+        assert_eq!(title(scope), b"1.1 Scope");
+        assert_eq!(title(reference(scope, b"Next")), b"1.2 Terms");
+        assert_eq!(reference(introduction, b"Last"), reference(scope, b"Next"));
+        let results = reference(outlines, b"Last");
+        assert_eq!(title(results), b"2 Results");
+        assert_eq!(reference(introduction, b"Next"), results);
+        assert!(item(introduction).get(b"Dest").is_ok());
+    }
+}
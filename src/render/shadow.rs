@@ -0,0 +1,128 @@
+use layout::{
+    Rgba,
+    position::{Offset, Size},
+    unit::{Mm, Unit},
+};
+
+// blur is approximated by translucent layers of growing size
+const SHADOW_LAYERS: usize = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shadow {
+    color: Rgba,
+    offset: Offset,
+    blur: Unit,
+}
+
+impl Shadow {
+    // alpha of the color is the opacity of the shadow
+    pub fn new(color: Rgba) -> Self {
+        Self {
+            color,
+            offset: Offset::zero(),
+            blur: Unit::zero(),
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Offset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_blur(mut self, blur: impl Into<Unit>) -> Self {
+        self.blur = blur.into();
+        self
+    }
+
+    pub fn color(&self) -> &Rgba {
+        &self.color
+    }
+
+    fn layer_count(&self) -> usize {
+        if self.blur.is_zero() {
+            1
+        } else {
+            SHADOW_LAYERS
+        }
+    }
+
+    // overlapping layers sum up to the shadow opacity
+    pub(crate) fn layer_alpha(&self) -> f32 {
+        let alpha = self.color.into_rgba().3;
+        1.0 - (1.0 - alpha).powf(1.0 / self.layer_count() as f32)
+    }
+
+    // (position, size, radius) of layers, blur is centered on the box edge
+    pub(crate) fn layers(
+        &self,
+        content_position: &Offset,
+        size: &Size,
+        radius: Unit,
+    ) -> Vec<(Offset, Size, Unit)> {
+        let count = self.layer_count();
+        let blur = Mm::from(self.blur).0;
+
+        (0..count)
+            .map(|layer| {
+                let spread = if count > 1 {
+                    Unit::from(Mm(blur * (0.5 - layer as f64 / (count - 1) as f64)))
+                } else {
+                    Unit::zero()
+                };
+
+                let position = Offset::new(
+                    content_position.x + self.offset.x - spread,
+                    content_position.y + self.offset.y - spread,
+                );
+                let size = Size::fixed(
+                    size.base_width() + spread + spread,
+                    size.base_height() + spread + spread,
+                );
+                let radius = radius + spread;
+                let radius = if radius < Unit::zero() {
+                    Unit::zero()
+                } else {
+                    radius
+                };
+
+                (position, size, radius)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Rgba,
+        position::{Offset, Size},
+        unit::{Mm, Unit},
+    };
+
+    use super::Shadow;
+
+    #[test]
+    fn layers() {
+        let position = Offset::new(Mm(10.0), Mm(10.0));
+        let size = Size::fixed(Mm(50.0), Mm(20.0));
+
+        let shadow = Shadow::new(Rgba::from((0, 0, 0, 0.5)));
+        let layers = shadow.layers(&position, &size, Unit::zero());
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].0, position);
+        assert!((shadow.layer_alpha() - 0.5).abs() < 1e-6);
+
+        let shadow = shadow
+            .with_offset(Offset::new(Mm(2.0), Mm(2.0)))
+            .with_blur(Mm(4.0));
+        let layers = shadow.layers(&position, &size, Unit::from(Mm(1.0)));
+        assert_eq!(layers.len(), 6);
+        assert_eq!(layers[0].0, Offset::new(Mm(10.0), Mm(10.0)));
+        assert_eq!(layers[0].1.base_width(), Unit::from(Mm(54.0)));
+        assert_eq!(layers[0].1.base_height(), Unit::from(Mm(24.0)));
+        assert_eq!(layers[5].2, Unit::zero());
+
+        let covered = 1.0 - (1.0 - shadow.layer_alpha()).powi(6);
+        assert!((covered - 0.5).abs() < 1e-6);
+    }
+}
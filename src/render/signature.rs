@@ -0,0 +1,356 @@
+use layout::Error;
+use printpdf::{
+    OffsetDateTime,
+    lopdf::{Dictionary, Document, Object, StringFormat},
+};
+
+use super::{
+    annotations::text_string,
+    resources::{catalog, pdf_error},
+};
+
+// replaced by the actual byte range, the digits leave room for any offset
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SignatureFormat {
+    // adbe.pkcs7.detached
+    #[default]
+    Pkcs7,
+    // ETSI.CAdES.detached, as required by PAdES
+    Cades,
+}
+
+impl SignatureFormat {
+    fn sub_filter(self) -> &'static [u8] {
+        match self {
+            SignatureFormat::Pkcs7 => b"adbe.pkcs7.detached",
+            SignatureFormat::Cades => b"ETSI.CAdES.detached",
+        }
+    }
+}
+
+type Signer = Box<dyn FnOnce(&[u8]) -> Result<Vec<u8>, Error>>;
+
+// signs the document into its signature form field; the signer gets the bytes of the
+// document without the signature contents and returns a DER encoded detached signature
+pub struct Signature {
+    field: String,
+    signer: Signer,
+    format: SignatureFormat,
+    reserved_size: usize,
+    name: Option<String>,
+    reason: Option<String>,
+    location: Option<String>,
+    contact_info: Option<String>,
+    signing_time: Option<OffsetDateTime>,
+}
+
+impl Signature {
+    pub fn new(
+        field: impl Into<String>,
+        signer: impl FnOnce(&[u8]) -> Result<Vec<u8>, Error> + 'static,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            signer: Box::new(signer),
+            format: SignatureFormat::default(),
+            reserved_size: 8192,
+            name: None,
+            reason: None,
+            location: None,
+            contact_info: None,
+            signing_time: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: SignatureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    // in bytes, has to fit the signature with certificates and timestamps
+    pub fn with_reserved_size(mut self, reserved_size: usize) -> Self {
+        self.reserved_size = reserved_size;
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    pub fn with_contact_info(mut self, contact_info: impl Into<String>) -> Self {
+        self.contact_info = Some(contact_info.into());
+        self
+    }
+
+    // the signer usually includes the time in the signature itself
+    pub fn with_signing_time(mut self, signing_time: OffsetDateTime) -> Self {
+        self.signing_time = Some(signing_time);
+        self
+    }
+
+    // signature dictionary with placeholders becomes the value of the field
+    pub(crate) fn prepare(&self, document: &mut Document) -> Result<(), Error> {
+        let name = text_string(&self.field);
+        let name = name.as_str().map_err(pdf_error)?;
+        let field_id = document
+            .objects
+            .iter()
+            .find(|(_, object)| {
+                object.as_dict().is_ok_and(|field| {
+                    field
+                        .get(b"FT")
+                        .and_then(Object::as_name)
+                        .is_ok_and(|kind| kind == b"Sig")
+                        && field
+                            .get(b"T")
+                            .and_then(Object::as_str)
+                            .is_ok_and(|title| title == name)
+                })
+            })
+            .map(|(id, _)| *id)
+            .ok_or_else(|| {
+                Error::PdfWrite(format!("Signature field {} not found", self.field).into())
+            })?;
+
+        let mut signature = Dictionary::new();
+        signature.set("Type", Object::Name(b"Sig".to_vec()));
+        signature.set("Filter", Object::Name(b"Adobe.PPKLite".to_vec()));
+        signature.set("SubFilter", Object::Name(self.format.sub_filter().to_vec()));
+        signature.set(
+            "ByteRange",
+            vec![
+                Object::Integer(0),
+                Object::Integer(BYTE_RANGE_PLACEHOLDER),
+                Object::Integer(BYTE_RANGE_PLACEHOLDER),
+                Object::Integer(BYTE_RANGE_PLACEHOLDER),
+            ],
+        );
+        signature.set(
+            "Contents",
+            Object::String(vec![0; self.reserved_size], StringFormat::Hexadecimal),
+        );
+        for (key, value) in [
+            ("Name", &self.name),
+            ("Reason", &self.reason),
+            ("Location", &self.location),
+            ("ContactInfo", &self.contact_info),
+        ] {
+            if let Some(value) = value {
+                signature.set(key, text_string(value));
+            }
+        }
+        if let Some(signing_time) = self.signing_time {
+            signature.set("M", Object::string_literal(pdf_date(signing_time)));
+        }
+        let signature_id = document.add_object(signature);
+
+        document
+            .get_object_mut(field_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?
+            .set("V", Object::Reference(signature_id));
+
+        // signatures exist and the document is changed by incremental updates only
+        let form_id = catalog(document)?
+            .get(b"AcroForm")
+            .and_then(Object::as_reference)
+            .map_err(pdf_error)?;
+        document
+            .get_object_mut(form_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?
+            .set("SigFlags", 3);
+        Ok(())
+    }
+
+    // fills in the placeholders of the saved document
+    pub(crate) fn sign(self, mut pdf: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let contents = [
+            b"<".to_vec(),
+            vec![b'0'; 2 * self.reserved_size],
+            b">".to_vec(),
+        ]
+        .concat();
+        let placeholder = format!(
+            "[0 {BYTE_RANGE_PLACEHOLDER} {BYTE_RANGE_PLACEHOLDER} {BYTE_RANGE_PLACEHOLDER}]"
+        );
+        let (Some(contents_start), Some(range_start)) =
+            (find(&pdf, &contents), find(&pdf, placeholder.as_bytes()))
+        else {
+            return Err(Error::PdfWrite("Signature placeholders not found".into()));
+        };
+
+        let contents_end = contents_start + contents.len();
+        let byte_range = format!(
+            "[0 {} {} {}]",
+            contents_start,
+            contents_end,
+            pdf.len() - contents_end
+        );
+        // padded, so no offset moves
+        let byte_range = format!("{byte_range:<width$}", width = placeholder.len());
+        pdf[range_start..range_start + placeholder.len()].copy_from_slice(byte_range.as_bytes());
+
+        let signed = [&pdf[..contents_start], &pdf[contents_end..]].concat();
+        let signature = (self.signer)(&signed)?;
+        if signature.len() > self.reserved_size {
+            return Err(Error::PdfWrite(
+                format!(
+                    "Signature of {} bytes exceeds the reserved {} bytes",
+                    signature.len(),
+                    self.reserved_size
+                )
+                .into(),
+            ));
+        }
+        let hex = signature
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<String>();
+        pdf[contents_start + 1..contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+        Ok(pdf)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// D:YYYYMMDDHHmmSS+HH'mm'
+fn pdf_date(date: OffsetDateTime) -> String {
+    let offset = date.offset();
+    let sign = match offset.is_negative() {
+        true => '-',
+        false => '+',
+    };
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}{}{:02}'{:02}'",
+        date.year(),
+        date.month() as u8,
+        date.day(),
+        date.hour(),
+        date.minute(),
+        date.second(),
+        sign,
+        offset.whole_hours().abs(),
+        offset.minutes_past_hour().abs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        fs::File,
+        io::{BufWriter, Write},
+        rc::Rc,
+    };
+
+    use layout::{
+        Error,
+        position::{Offset, Size},
+        unit::Mm,
+    };
+    use printpdf::lopdf::{Document, Object};
+
+    use crate::{FormField, RenderContext, Signature, SignatureFormat};
+
+    use super::super::test_layouts::render_context;
+
+    fn signed_context() -> RenderContext {
+        let mut rctx = render_context("Test", 210.0, 1);
+        rctx.form_field(
+            &Offset::new(Mm(0.0), Mm(0.0)),
+            &Size::fixed(Mm(80.0), Mm(20.0)),
+            &FormField::signature("approval"),
+        );
+        rctx
+    }
+
+    #[test]
+    fn signature() {
+        let signed = Rc::new(RefCell::new(vec![]));
+        let signer = {
+            let signed = signed.clone();
+            move |data: &[u8]| {
+                *signed.borrow_mut() = data.to_vec();
+                Ok(vec![0x30, 0x03, 0x02, 0x01, 0x2A])
+            }
+        };
+        let pdf = signed_context()
+            .with_signature(
+                Signature::new("approval", signer)
+                    .with_format(SignatureFormat::Cades)
+                    .with_reason("Approved")
+                    .with_reserved_size(64),
+            )
+            .save_to_bytes()
+            .unwrap();
+        BufWriter::new(File::create("test_signature.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let signature = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .find(|dictionary| dictionary.get(b"ByteRange").is_ok())
+            .unwrap();
+        assert_eq!(
+            signature.get(b"SubFilter").unwrap().as_name().unwrap(),
+            b"ETSI.CAdES.detached"
+        );
+        let contents = signature.get(b"Contents").unwrap().as_str().unwrap();
+        assert_eq!(contents.len(), 64);
+        assert_eq!(&contents[..5], &[0x30, 0x03, 0x02, 0x01, 0x2A]);
+
+        let byte_range = signature
+            .get(b"ByteRange")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|offset| offset.as_i64().unwrap() as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(byte_range[0], 0);
+        assert_eq!(byte_range[2] + byte_range[3], pdf.len());
+        assert_eq!(&pdf[byte_range[1]..byte_range[1] + 1], b"<");
+        assert_eq!(&pdf[byte_range[2] - 1..byte_range[2]], b">");
+        assert_eq!(
+            *signed.borrow(),
+            [&pdf[..byte_range[1]], &pdf[byte_range[2]..]].concat()
+        );
+
+        let catalog = document.catalog().unwrap();
+        let form = catalog
+            .get(b"AcroForm")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .unwrap();
+        assert_eq!(form.get(b"SigFlags").unwrap().as_i64().unwrap(), 3);
+
+        let Err(Error::PdfWrite(message)) = signed_context()
+            .with_signature(Signature::new("missing", |_| Ok(vec![])))
+            .save_to_bytes()
+        else {
+            panic!("missing field is not reported");
+        };
+        assert_eq!(message, "Signature field missing not found");
+    }
+}
@@ -0,0 +1,134 @@
+use layout::{
+    Error,
+    position::{Offset, Size},
+};
+use printpdf::lopdf::{
+    Dictionary, Object, Stream,
+    content::{Content, Operation},
+};
+
+use super::{ColorModel, Gradient, Image, from_unit, resources::pdf_error};
+
+#[derive(Clone, Debug)]
+enum SoftMaskSource {
+    Gradient(Gradient),
+    Image {
+        content_position: Offset,
+        size: Size,
+        image: Image,
+    },
+}
+
+// Luminosity of the source sets opacity of the masked content, white is opaque
+#[derive(Clone, Debug)]
+pub struct SoftMask {
+    source: SoftMaskSource,
+}
+
+impl SoftMask {
+    pub fn gradient(gradient: Gradient) -> Self {
+        Self {
+            source: SoftMaskSource::Gradient(gradient),
+        }
+    }
+
+    // outside of the placed image the content is masked out
+    pub fn image(content_position: Offset, size: Size, image: Image) -> Self {
+        Self {
+            source: SoftMaskSource::Image {
+                content_position,
+                size,
+                image,
+            },
+        }
+    }
+
+    pub(crate) fn graphics_state<F>(
+        &self,
+        page_point: F,
+        page_width: f32,
+        page_height: f32,
+    ) -> Result<Dictionary, Error>
+    where
+        F: Fn(&Offset) -> (f32, f32),
+    {
+        let mut resources = Dictionary::new();
+        let operations = match &self.source {
+            SoftMaskSource::Gradient(gradient) => {
+                let Some(shading) = gradient.shading(page_point, ColorModel::Gray) else {
+                    return Err(Error::PdfWrite("Soft mask gradient has no stops".into()));
+                };
+                let mut shadings = Dictionary::new();
+                shadings.set("Sh0", shading);
+                resources.set("Shading", shadings);
+
+                vec![Operation::new("sh", vec![Object::Name(b"Sh0".to_vec())])]
+            }
+            SoftMaskSource::Image {
+                content_position,
+                size,
+                image,
+            } => {
+                let width = from_unit(size.base_width());
+                let height = from_unit(size.base_height());
+                let placement = image.place(width.0, height.0);
+                let xobject = image.to_xobject(&placement);
+
+                let (left, top) = page_point(content_position);
+                let to_pt = |mm: f32| printpdf::Mm(mm).into_pt().0;
+                let matrix = [
+                    to_pt(placement.width),
+                    0.0,
+                    0.0,
+                    to_pt(placement.height),
+                    left + to_pt(placement.x),
+                    top - to_pt(placement.y + placement.height),
+                ];
+
+                let mut xobjects = Dictionary::new();
+                xobjects.set("Im0", Stream::from(xobject));
+                resources.set("XObject", xobjects);
+
+                vec![
+                    Operation::new("q", vec![]),
+                    Operation::new("cm", matrix.into_iter().map(Object::Real).collect()),
+                    Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]),
+                    Operation::new("Q", vec![]),
+                ]
+            }
+        };
+
+        let content = Content { operations }.encode().map_err(pdf_error)?;
+
+        let mut group = Dictionary::new();
+        group.set("Type", Object::Name(b"Group".to_vec()));
+        group.set("S", Object::Name(b"Transparency".to_vec()));
+        group.set("CS", Object::Name(b"DeviceGray".to_vec()));
+
+        // the mask form covers the whole page, outside of it nothing is painted
+        let mut form = Dictionary::new();
+        form.set("Type", Object::Name(b"XObject".to_vec()));
+        form.set("Subtype", Object::Name(b"Form".to_vec()));
+        form.set(
+            "BBox",
+            vec![
+                Object::Real(0.0),
+                Object::Real(0.0),
+                Object::Real(page_width),
+                Object::Real(page_height),
+            ],
+        );
+        form.set("Group", group);
+        form.set("Resources", resources);
+
+        let mut mask = Dictionary::new();
+        mask.set("Type", Object::Name(b"Mask".to_vec()));
+        mask.set("S", Object::Name(b"Luminosity".to_vec()));
+        mask.set("G", Stream::new(form, content));
+
+        let mut state = Dictionary::new();
+        state.set("Type", Object::Name(b"ExtGState".to_vec()));
+        state.set("SMask", mask);
+        Ok(state)
+    }
+}
@@ -0,0 +1,227 @@
+use layout::{
+    Error, Layout, MeasureContext, RenderContext,
+    position::{Offset, Size},
+};
+
+use super::{LinkTarget, Mark, Marks, TextDecoration};
+
+// inline state of text, e.g. a link or an underline of a run of rich text; unset parts
+// are kept from the enclosing span
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Span {
+    pub(crate) link: Option<LinkTarget>,
+    pub(crate) decoration: Option<TextDecoration>,
+    pub(crate) kerning: Option<bool>,
+}
+
+impl Span {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_link(mut self, uri: impl Into<String>) -> Self {
+        self.link = Some(LinkTarget::Uri(uri.into()));
+        self
+    }
+
+    // the anchor may be defined later in the document
+    pub fn with_internal_link(mut self, anchor: impl Into<String>) -> Self {
+        self.link = Some(LinkTarget::Anchor(anchor.into()));
+        self
+    }
+
+    pub fn with_decoration(mut self, decoration: TextDecoration) -> Self {
+        self.decoration = Some(decoration);
+        self
+    }
+
+    // kerning of the fonts set to the render context is overridden, e.g. turned off for
+    // code listings aligned as monospaced
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.kerning = Some(kerning);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.link.is_none() && self.decoration.is_none() && self.kerning.is_none()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum SpanMark {
+    Begin(Span),
+    End,
+}
+
+// spans are taken over by the render context before anything else of the layout is
+// rendered
+impl Marks {
+    // text the layout renders gets the state of the span
+    pub fn span(&self, span: Span, layout: impl Layout + 'static) -> SpanLayout {
+        SpanLayout {
+            layout: Box::new(layout),
+            span,
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SpanLayout {
+    layout: Box<dyn Layout>,
+    span: Span,
+    marks: Marks,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for SpanLayout {
+    // text is typeset when measured, so the kerning is set by then
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.marks
+            .with_kerning(self.span.kerning, || self.layout.measure(ctx, size))
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    // the end is taken over with the next mark, before anything else is rendered
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        self.marks
+            .push(Mark::Span(SpanMark::Begin(self.span.clone())));
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+        self.layout.render(ctx)?;
+        self.marks.push(Mark::Span(SpanMark::End));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use layout::{
+        Axis, Error, Font, Layout, LayoutBox, MeasureContext, RenderContext as _, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::PdfDocument;
+
+    use crate::{Marks, RenderContext, Span, TextDecoration, new_font_cache};
+
+    use super::{Mark, SpanMark};
+
+    #[test]
+    fn spans() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_marks(marks.clone());
+
+        let span = Span::new()
+            .with_link("https://example.com")
+            .with_decoration(TextDecoration::empty().underline());
+        assert!(!span.is_empty());
+        let mut layout = marks.span(span, LayoutBox::new(Axis::Vertical));
+        let size = Size::fixed(Mm(190.0), Mm(10.0));
+        layout.measure(&mut rctx, size.clone()).unwrap();
+        layout
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(100.0)), size)
+            .unwrap();
+        layout.render(&mut rctx).unwrap();
+
+        // the end is left for the next mark
+        let queued = marks.take();
+        assert!(matches!(queued.as_slice(), [Mark::Span(SpanMark::End)]));
+        queued.into_iter().for_each(|mark| marks.push(mark));
+        rctx.check_page_break(Mm(120.0).into(), Mm(0.0).into(), false);
+        assert!(marks.take().is_empty());
+    }
+
+    // width of the word typeset when measured
+    #[derive(Debug)]
+    struct Word(Rc<Cell<f64>>);
+
+    impl Layout for Word {
+        fn measure(&mut self, ctx: &mut dyn MeasureContext, _: Size) -> Result<(), Error> {
+            let style = StyleBuilder::default()
+                .with_font(Font::new("LatoReg", Pt(12.0), None))
+                .build();
+            self.0.set(ctx.typeset(&style, "AVAVA")?.width.0);
+            Ok(())
+        }
+
+        fn lay_out(&mut self, _: &mut dyn MeasureContext, _: Offset, _: Size) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn render(&self, _: &mut dyn layout::RenderContext) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn kerning() {
+        let fonts = new_font_cache();
+        fonts
+            .add(
+                "LatoReg",
+                include_bytes!("../../tests/Lato-Regular.ttf").as_ref(),
+            )
+            .unwrap();
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_marks(marks.clone());
+
+        let width = |span: Option<Span>, rctx: &mut RenderContext| {
+            let measured = Rc::new(Cell::new(0.0));
+            let mut word = Word(measured.clone());
+            let size = Size::fixed(Mm(190.0), Mm(10.0));
+            match span {
+                Some(span) => marks.span(span, word).measure(rctx, size),
+                None => word.measure(rctx, size),
+            }
+            .unwrap();
+            measured.get()
+        };
+
+        // the kerning of the span is left when its text is measured
+        let kerned = width(None, &mut rctx);
+        let unkerned = width(Some(Span::new().with_kerning(false)), &mut rctx);
+        assert!(kerned < unkerned);
+        assert_eq!(width(None, &mut rctx), kerned);
+        assert_eq!(
+            width(Some(Span::new().with_kerning(true)), &mut rctx),
+            kerned
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use layout::Error;
+use printpdf::lopdf::{
+    Dictionary, Document, Object, ObjectId, Stream,
+    content::{Content, Operation},
+};
+
+use super::{
+    merge::{inherited, replace_references},
+    resources::{indirect_dictionary, pdf_error},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StationeryRepeat {
+    // pages after the last one of the stationery get its last page, e.g. a letterhead
+    // followed by continuation pages
+    #[default]
+    Last,
+    // pages of the stationery are repeated
+    Cycle,
+    // pages after the last one of the stationery get none
+    Once,
+}
+
+// pages of an existing document painted under the rendered content, e.g. pre-designed
+// letter paper filled by a layout; they are placed at the origin of the pages unscaled
+#[derive(Clone, Debug)]
+pub struct Stationery {
+    document: Document,
+    repeat: StationeryRepeat,
+}
+
+impl Stationery {
+    pub fn new(pdf: &[u8]) -> Result<Self, Error> {
+        let document = Document::load_mem(pdf).map_err(pdf_error)?;
+        if document.is_encrypted() {
+            return Err(Error::PdfWrite("Stationery must not be encrypted".into()));
+        }
+        if document.get_pages().is_empty() {
+            return Err(Error::PdfWrite("Stationery has no pages".into()));
+        }
+        Ok(Self {
+            document,
+            repeat: StationeryRepeat::default(),
+        })
+    }
+
+    pub fn with_repeat(mut self, repeat: StationeryRepeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    fn source_page(&self, page: usize, pages: usize) -> Option<usize> {
+        match self.repeat {
+            StationeryRepeat::Last => Some(page.min(pages - 1)),
+            StationeryRepeat::Cycle => Some(page % pages),
+            StationeryRepeat::Once => (page < pages).then_some(page),
+        }
+    }
+
+    // pages of the stationery become form xobjects drawn first on the pages
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let mut stationery = self.document.clone();
+        let mut forms = vec![];
+        let mut origins = vec![];
+        for page_id in stationery.get_pages().into_values() {
+            let content = stationery.get_page_content(page_id).map_err(pdf_error)?;
+            let Some(media_box) = page_box(&stationery, page_id) else {
+                return Err(Error::PdfWrite("Stationery page has no media box".into()));
+            };
+            let resources = page_attribute(&stationery, page_id, b"Resources")
+                .unwrap_or(Dictionary::new().into());
+
+            let mut form = Dictionary::new();
+            form.set("Type", Object::Name(b"XObject".to_vec()));
+            form.set("Subtype", Object::Name(b"Form".to_vec()));
+            form.set("BBox", media_box.map(Object::Real).to_vec());
+            form.set("Resources", resources);
+            forms.push(Object::Reference(
+                stationery.add_object(Stream::new(form, content)),
+            ));
+            origins.push((media_box[0], media_box[1]));
+        }
+
+        // only objects the forms use are kept, they follow objects of the document
+        let mut trailer = Dictionary::new();
+        trailer.set("Forms", forms);
+        stationery.trailer = trailer;
+        stationery.prune_objects();
+        let replaced = stationery
+            .objects
+            .keys()
+            .enumerate()
+            .map(|(index, id)| (*id, (document.max_id + 1 + index as u32, 0)))
+            .collect::<HashMap<_, _>>();
+        for (id, mut object) in std::mem::take(&mut stationery.objects) {
+            replace_references(&mut object, &replaced);
+            document.objects.insert(replaced[&id], object);
+        }
+        document.max_id += replaced.len() as u32;
+        let mut forms = stationery
+            .trailer
+            .get(b"Forms")
+            .and_then(Object::as_array)
+            .cloned()
+            .map_err(pdf_error)?;
+        for form in forms.iter_mut() {
+            replace_references(form, &replaced);
+        }
+
+        for (page, page_id) in document.get_pages().into_values().enumerate() {
+            let Some(source) = self.source_page(page, forms.len()) else {
+                continue;
+            };
+            let (left, bottom) = page_box(document, page_id)
+                .map(|media_box| (media_box[0], media_box[1]))
+                .unwrap_or_default();
+            let (source_left, source_bottom) = origins[source];
+            stamp_form(
+                document,
+                page_id,
+                "PRStationery",
+                forms[source].clone(),
+                (left - source_left, bottom - source_bottom),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// the form is painted under the content of the page, moved by the translation
+pub(crate) fn stamp_form(
+    document: &mut Document,
+    page_id: ObjectId,
+    name: &str,
+    form: Object,
+    (x, y): (f32, f32),
+) -> Result<(), Error> {
+    stamp_xobject(document, page_id, name, form, [1.0, 0.0, 0.0, 1.0, x, y])
+}
+
+// the xobject is painted under the content of the page, transformed by the matrix
+pub(crate) fn stamp_xobject(
+    document: &mut Document,
+    page_id: ObjectId,
+    name: &str,
+    xobject: Object,
+    matrix: [f32; 6],
+) -> Result<(), Error> {
+    let resources_id = indirect_dictionary(document, page_id, b"Resources")?;
+    let xobjects_id = indirect_dictionary(document, resources_id, b"XObject")?;
+    document
+        .get_object_mut(xobjects_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?
+        .set(name, xobject);
+
+    let operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("cm", matrix.map(Object::Real).to_vec()),
+        Operation::new("Do", vec![Object::Name(name.as_bytes().to_vec())]),
+        Operation::new("Q", vec![]),
+    ];
+    let mut content = Content { operations }.encode().map_err(pdf_error)?;
+    // streams are concatenated, the following one may not start with a whitespace
+    content.push(b'\n');
+    let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+
+    let page = document
+        .get_object_mut(page_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    let mut contents = match page.get(b"Contents") {
+        Ok(Object::Array(contents)) => contents.clone(),
+        Ok(contents) => vec![contents.clone()],
+        Err(_) => vec![],
+    };
+    contents.insert(0, Object::Reference(content_id));
+    page.set("Contents", contents);
+    Ok(())
+}
+
+// own or inherited from the page tree
+pub(crate) fn page_attribute(document: &Document, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+    let page = document.get_dictionary(page_id).ok()?;
+    match page.get(key) {
+        Ok(value) => Some(value.clone()),
+        Err(_) => inherited(document, page_id)
+            .into_iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value),
+    }
+}
+
+pub(crate) fn page_box(document: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let media_box = match page_attribute(document, page_id, b"MediaBox")? {
+        Object::Reference(id) => document.get_object(id).ok()?.clone(),
+        media_box => media_box,
+    };
+    let values = media_box
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|value| value.as_float().ok())
+        .collect::<Option<Vec<_>>>()?;
+    values.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use printpdf::lopdf::Document;
+
+    use crate::{Stationery, StationeryRepeat};
+
+    use super::super::test_layouts::render_context;
+
+    fn stamped(document: &Document) -> Vec<bool> {
+        document
+            .get_pages()
+            .into_values()
+            .map(|page_id| {
+                let content = document.get_and_decode_page_content(page_id).unwrap();
+                content
+                    .operations
+                    .iter()
+                    .any(|operation| operation.operator == "Do")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stationery() {
+        let letterhead = render_context("Test", 210.0, 2).save_to_bytes().unwrap();
+
+        let pdf = render_context("Test", 210.0, 3)
+            .with_stationery(Stationery::new(&letterhead).unwrap())
+            .save_to_bytes()
+            .unwrap();
+        BufWriter::new(File::create("test_stationery.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        assert_eq!(stamped(&document), vec![true, true, true]);
+
+        let pdf = render_context("Test", 210.0, 3)
+            .with_stationery(
+                Stationery::new(&letterhead)
+                    .unwrap()
+                    .with_repeat(StationeryRepeat::Once),
+            )
+            .save_to_bytes()
+            .unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        assert_eq!(stamped(&document), vec![true, true, false]);
+        assert!(Stationery::new(b"%PDF-1.7").is_err());
+    }
+}
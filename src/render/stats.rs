@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use smol_str::SmolStr;
+
+use super::RenderPhase;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontStats {
+    pub name: SmolStr,
+    // glyphs of the subset, including .notdef
+    pub glyphs: usize,
+    // bytes of the embedded subset, 0 if the font is not embedded
+    pub subset_size: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub pages: usize,
+    pub fonts: Vec<FontStats>,
+    // images painted, an image painted several times is counted each time
+    pub images: usize,
+    // uncompressed bytes of the image data
+    pub image_bytes: usize,
+    pub phases: Vec<(RenderPhase, Duration)>,
+}
+
+impl RenderStats {
+    pub fn total_time(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+}
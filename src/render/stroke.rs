@@ -0,0 +1,197 @@
+use layout::{
+    Error, Layout, MeasureContext, RenderContext, Stroke,
+    position::{Offset, Size},
+    unit::{Pt, Unit},
+};
+use printpdf::lopdf::{Object, content::Operation};
+
+use super::{Mark, Marks, from_unit};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dash {
+    pattern: Vec<Unit>,
+    phase: Unit,
+    round_caps: bool,
+}
+
+impl Dash {
+    // alternating on/off lengths, negative ones are zero; a pattern of zero lengths only
+    // is invalid in PDF, it is solid
+    pub fn new(pattern: impl IntoIterator<Item = impl Into<Unit>>, phase: impl Into<Unit>) -> Self {
+        let mut pattern = pattern
+            .into_iter()
+            .map(|length| match length.into() {
+                length if length > Unit::zero() => length,
+                _ => Unit::zero(),
+            })
+            .collect::<Vec<_>>();
+        if pattern.iter().all(|length| *length == Unit::zero()) {
+            pattern.clear();
+        }
+        Self {
+            pattern,
+            phase: phase.into(),
+            round_caps: false,
+        }
+    }
+
+    pub fn dashed(on: impl Into<Unit>, off: impl Into<Unit>) -> Self {
+        Self::new([on.into(), off.into()], Unit::zero())
+    }
+
+    // zero length dashes with round caps render as dots of the stroke thickness
+    pub fn dotted(gap: impl Into<Unit>) -> Self {
+        Self {
+            round_caps: true,
+            ..Self::new([Unit::zero(), gap.into()], Unit::zero())
+        }
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.pattern.is_empty()
+    }
+
+    pub(crate) fn operations(&self) -> Vec<Operation> {
+        let pattern = self
+            .pattern
+            .iter()
+            .map(|length| Object::Real(from_unit(*length).into_pt().0))
+            .collect();
+        let phase = Object::Real(from_unit(self.phase).into_pt().0);
+
+        let mut operations = vec![Operation::new("d", vec![Object::Array(pattern), phase])];
+        if self.round_caps {
+            operations.push(Operation::new("J", vec![Object::Integer(1)]));
+        }
+        operations
+    }
+}
+
+// thickness in the PDF user space, converted the same way as coordinates
+pub(crate) fn stroke_thickness(stroke: &Stroke) -> f32 {
+    from_unit(stroke.thickness().into()).into_pt().0
+}
+
+// style of layout borders and other strokes; dashes and dots are derived from the stroke
+// thickness, double rules are two thirds of it apart, each a third of it thick
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum StrokeStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+    Double,
+    Pattern(Dash),
+}
+
+impl StrokeStyle {
+    // none for solid strokes, including those too thin for dashes of the thickness
+    pub(crate) fn dash(&self, thickness: Pt) -> Option<Dash> {
+        let dash = match self {
+            Self::Dashed => Dash::dashed(Pt(thickness.0 * 3.0), Pt(thickness.0 * 2.0)),
+            Self::Dotted => Dash::dotted(Pt(thickness.0 * 2.0)),
+            Self::Pattern(dash) => dash.clone(),
+            Self::Solid | Self::Double => return None,
+        };
+        (!dash.is_solid()).then_some(dash)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum StrokeMark {
+    Begin(StrokeStyle),
+    End,
+}
+
+// borders, lines and paths of the layout are stroked in the style, layouts nested in it
+// are stroked in their own one
+impl Marks {
+    pub fn stroke(&self, style: StrokeStyle, layout: impl Layout + 'static) -> StrokeLayout {
+        StrokeLayout {
+            layout: Box::new(layout),
+            style,
+            marks: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StrokeLayout {
+    layout: Box<dyn Layout>,
+    style: StrokeStyle,
+    marks: Marks,
+}
+
+impl Layout for StrokeLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    // the style is taken over with the first primitive of the layout, the end with the
+    // first one after it
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        self.marks
+            .push(Mark::Stroke(StrokeMark::Begin(self.style.clone())));
+        self.layout.render(ctx)?;
+        self.marks.push(Mark::Stroke(StrokeMark::End));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Rgba, Stroke,
+        unit::{Em, Mm, Pt},
+    };
+
+    use super::{Dash, StrokeStyle, stroke_thickness};
+
+    #[test]
+    fn thickness() {
+        let thickness = |thickness: Pt| stroke_thickness(&Stroke::new(Rgba::black(), thickness));
+        let close = |left: f32, right: f32| (left - right).abs() < 1e-4;
+
+        assert!(close(thickness(Pt(0.5)), 0.5));
+        assert!(close(thickness(Mm(25.4).into()), 72.0));
+        assert!(close(thickness(Mm(0.5).into()), 1.417_323));
+        // relative to the font size
+        assert!(close(thickness(Em(0.1) * Pt(12.0)), 1.2));
+    }
+
+    #[test]
+    fn stroke_style() {
+        assert_eq!(StrokeStyle::Solid.dash(Pt(1.0)), None);
+        assert_eq!(StrokeStyle::Double.dash(Pt(1.0)), None);
+        assert_eq!(
+            StrokeStyle::Dashed.dash(Pt(0.5)),
+            Some(Dash::dashed(Pt(1.5), Pt(1.0)))
+        );
+        assert_eq!(
+            StrokeStyle::Dotted.dash(Pt(2.0)),
+            Some(Dash::dotted(Pt(4.0)))
+        );
+
+        // zero lengths only are solid, negative ones are zero
+        assert!(Dash::new([Pt(0.0), Pt(-1.0)], Pt(0.0)).is_solid());
+        assert_eq!(
+            Dash::new([Pt(2.0), Pt(-1.0)], Pt(0.0)),
+            Dash::new([Pt(2.0), Pt(0.0)], Pt(0.0))
+        );
+        assert_eq!(StrokeStyle::Dashed.dash(Pt(0.0)), None);
+        assert_eq!(StrokeStyle::Dotted.dash(Pt(0.0)), None);
+        assert_eq!(
+            StrokeStyle::Pattern(Dash::new([Pt(0.0)], Pt(1.0))).dash(Pt(1.0)),
+            None
+        );
+    }
+}
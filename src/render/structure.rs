@@ -0,0 +1,646 @@
+use std::{cell::RefCell, rc::Rc};
+
+use layout::{
+    Error, Layout, MeasureContext, RenderContext,
+    position::{Offset, Size},
+};
+use printpdf::lopdf::{
+    Dictionary, Document, Object, ObjectId,
+    content::{Content, Operation},
+};
+
+use super::{
+    XmpMetadata,
+    annotations::text_string,
+    resources::{catalog, pdf_error},
+};
+
+const PDFUA_ID_NAMESPACE: &str = "http://www.aiim.org/pdfua/ns/id/";
+
+// standard structure types, content of a figure is tagged as a whole and described by
+// its alternate text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StructureRole {
+    Part,
+    Section,
+    // levels start at 1, deeper ones are tagged as H6
+    Heading(usize),
+    Paragraph,
+    Span,
+    List,
+    ListItem,
+    Label,
+    ListBody,
+    Table,
+    TableRow,
+    TableHeader,
+    TableCell,
+    Figure,
+    Caption,
+    Note,
+}
+
+impl StructureRole {
+    fn tag(self) -> String {
+        match self {
+            StructureRole::Part => "Part".into(),
+            StructureRole::Section => "Sect".into(),
+            StructureRole::Heading(level) => format!("H{}", level.clamp(1, 6)),
+            StructureRole::Paragraph => "P".into(),
+            StructureRole::Span => "Span".into(),
+            StructureRole::List => "L".into(),
+            StructureRole::ListItem => "LI".into(),
+            StructureRole::Label => "Lbl".into(),
+            StructureRole::ListBody => "LBody".into(),
+            StructureRole::Table => "Table".into(),
+            StructureRole::TableRow => "TR".into(),
+            StructureRole::TableHeader => "TH".into(),
+            StructureRole::TableCell => "TD".into(),
+            StructureRole::Figure => "Figure".into(),
+            StructureRole::Caption => "Caption".into(),
+            StructureRole::Note => "Note".into(),
+        }
+    }
+
+    // element of text rendered directly in a grouping element
+    fn implicit_child(self) -> Option<StructureRole> {
+        match self {
+            StructureRole::Part
+            | StructureRole::Section
+            | StructureRole::List
+            | StructureRole::Table => Some(StructureRole::Paragraph),
+            StructureRole::ListItem => Some(StructureRole::ListBody),
+            StructureRole::TableRow => Some(StructureRole::TableCell),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructureElement {
+    role: StructureRole,
+    alt_text: Option<String>,
+    language: Option<String>,
+}
+
+impl StructureElement {
+    pub fn new(role: StructureRole) -> Self {
+        Self {
+            role,
+            alt_text: None,
+            language: None,
+        }
+    }
+
+    // read instead of the content, figures require it
+    pub fn with_alt_text(mut self, alt_text: impl Into<String>) -> Self {
+        self.alt_text = Some(alt_text.into());
+        self
+    }
+
+    // e.g. en-US, if it differs from the language of the document
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub(crate) fn is_figure(&self) -> bool {
+        self.role == StructureRole::Figure
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum StructureMark {
+    Begin(StructureElement),
+    End,
+}
+
+// layouts render through the layout render context, so their elements are queued and
+// taken over by the render context before anything else of the layout is rendered
+#[derive(Clone, Debug, Default)]
+pub struct StructureMarks(Rc<RefCell<Vec<StructureMark>>>);
+
+impl StructureMarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // content the layout renders is tagged as the element, or as its descendants
+    pub fn element(
+        &self,
+        element: StructureElement,
+        layout: impl Layout + 'static,
+    ) -> StructureLayout {
+        StructureLayout {
+            layout: Box::new(layout),
+            element,
+            marks: self.clone(),
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+
+    pub(crate) fn take(&self) -> Vec<StructureMark> {
+        std::mem::take(&mut *self.0.borrow_mut())
+    }
+}
+
+#[derive(Debug)]
+pub struct StructureLayout {
+    layout: Box<dyn Layout>,
+    element: StructureElement,
+    marks: StructureMarks,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for StructureLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    // the end is taken over with the next mark, or when the document is saved
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        self.marks
+            .0
+            .borrow_mut()
+            .push(StructureMark::Begin(self.element.clone()));
+        ctx.check_page_break(self.offset.y, self.size.base_height(), false);
+        self.layout.render(ctx)?;
+        self.marks.0.borrow_mut().push(StructureMark::End);
+        Ok(())
+    }
+}
+
+enum Kid {
+    Element(usize),
+    // marked content of the page, pages are numbered from 0
+    Content(usize, i64),
+}
+
+struct Node {
+    element: StructureElement,
+    parent: usize,
+    kids: Vec<Kid>,
+}
+
+// structure tree of a tagged document, the document element is the root
+pub(crate) struct StructureTree {
+    nodes: Vec<Node>,
+    open: Vec<usize>,
+    // element of text which is not tagged by any, kept until the structure changes
+    implicit: Option<usize>,
+    heading: Option<usize>,
+    // next marked content identifier of pages
+    mcids: Vec<i64>,
+}
+
+impl StructureTree {
+    // the root is written as the document element, it groups like a part
+    pub(crate) fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                element: StructureElement::new(StructureRole::Part),
+                parent: 0,
+                kids: vec![],
+            }],
+            open: vec![0],
+            implicit: None,
+            heading: None,
+            mcids: vec![],
+        }
+    }
+
+    pub(crate) fn begin(&mut self, element: StructureElement) {
+        let index = self.add(element);
+        self.open.push(index);
+    }
+
+    pub(crate) fn end(&mut self) {
+        self.implicit = None;
+        if self.open.len() > 1 {
+            self.open.pop();
+        } else {
+            tracing::warn!("Structure element ended when not begun.");
+        }
+    }
+
+    // next text is the heading of a section, levels start at 1
+    pub(crate) fn heading(&mut self, level: usize) {
+        self.implicit = None;
+        self.heading = Some(level);
+    }
+
+    // figures are read by their alternate text, their content is not
+    pub(crate) fn has_figures_without_alt_text(&self) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| node.element.is_figure() && node.element.alt_text.is_none())
+    }
+
+    // content of figures is marked by the figure
+    pub(crate) fn in_figure(&self) -> bool {
+        self.open
+            .iter()
+            .any(|index| self.nodes[*index].element.is_figure())
+    }
+
+    // marks content of the element begun last, returns its tag and identifier
+    pub(crate) fn mark_element(&mut self, page: usize) -> (String, i64) {
+        let index = *self.open.last().unwrap_or(&0);
+        self.mark(index, page)
+    }
+
+    // marks text, which goes to an implicit element unless the open one holds text
+    pub(crate) fn mark_text(&mut self, page: usize) -> (String, i64) {
+        let current = *self.open.last().unwrap_or(&0);
+        let role = self.nodes[current].element.role;
+        let index = match (self.implicit, self.heading.take()) {
+            (_, Some(level)) => self.add_implicit(StructureRole::Heading(level)),
+            (Some(implicit), None) => implicit,
+            (None, None) => match role.implicit_child() {
+                Some(role) => self.add_implicit(role),
+                None => current,
+            },
+        };
+        self.mark(index, page)
+    }
+
+    fn add(&mut self, element: StructureElement) -> usize {
+        self.implicit = None;
+        let parent = *self.open.last().unwrap_or(&0);
+        self.nodes.push(Node {
+            element,
+            parent,
+            kids: vec![],
+        });
+        let index = self.nodes.len() - 1;
+        self.nodes[parent].kids.push(Kid::Element(index));
+        index
+    }
+
+    fn add_implicit(&mut self, role: StructureRole) -> usize {
+        let index = self.add(StructureElement::new(role));
+        self.implicit = Some(index);
+        index
+    }
+
+    fn mark(&mut self, index: usize, page: usize) -> (String, i64) {
+        if self.mcids.len() <= page {
+            self.mcids.resize(page + 1, 0);
+        }
+        let mcid = self.mcids[page];
+        self.mcids[page] += 1;
+        self.nodes[index].kids.push(Kid::Content(page, mcid));
+        (self.nodes[index].element.role.tag(), mcid)
+    }
+
+    // structure tree with the parent tree of marked content; content not marked is
+    // marked as artifact, e.g. page decorations and debug output
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let pages = document.get_pages();
+        let page_id = |page: usize| pages.get(&(page as u32 + 1)).copied();
+
+        let root_id = document.new_object_id();
+        let ids = self
+            .nodes
+            .iter()
+            .map(|_| document.new_object_id())
+            .collect::<Vec<_>>();
+        let mut parents: Vec<Vec<Object>> = vec![vec![]; self.mcids.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut kids = vec![];
+            for kid in node.kids.iter() {
+                match kid {
+                    Kid::Element(kid) => kids.push(Object::Reference(ids[*kid])),
+                    Kid::Content(page, mcid) => {
+                        let Some(page_id) = page_id(*page) else {
+                            continue;
+                        };
+                        let mut reference = Dictionary::new();
+                        reference.set("Type", Object::Name(b"MCR".to_vec()));
+                        reference.set("Pg", Object::Reference(page_id));
+                        reference.set("MCID", *mcid);
+                        kids.push(Object::Dictionary(reference));
+
+                        let parent = &mut parents[*page];
+                        parent.resize(*mcid as usize + 1, Object::Null);
+                        parent[*mcid as usize] = Object::Reference(ids[index]);
+                    }
+                }
+            }
+
+            let mut dictionary = Dictionary::new();
+            dictionary.set("Type", Object::Name(b"StructElem".to_vec()));
+            let tag = match index {
+                0 => "Document".to_string(),
+                _ => node.element.role.tag(),
+            };
+            dictionary.set("S", Object::Name(tag.into_bytes()));
+            dictionary.set(
+                "P",
+                Object::Reference(match index {
+                    0 => root_id,
+                    _ => ids[node.parent],
+                }),
+            );
+            dictionary.set("K", kids);
+            if let Some(alt_text) = &node.element.alt_text {
+                dictionary.set("Alt", text_string(alt_text));
+            }
+            if let Some(language) = &node.element.language {
+                dictionary.set("Lang", text_string(language));
+            }
+            document.set_object(ids[index], dictionary);
+        }
+
+        let mut numbers = vec![];
+        for (page, parent) in parents.into_iter().enumerate() {
+            let Some(page_id) = page_id(page).filter(|_| !parent.is_empty()) else {
+                continue;
+            };
+            numbers.push(Object::Integer(page as i64));
+            numbers.push(Object::Reference(document.add_object(parent)));
+            document
+                .get_object_mut(page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?
+                .set("StructParents", page as i64);
+        }
+        for page_id in pages.values() {
+            // annotations are visited in the order of the structure
+            document
+                .get_object_mut(*page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(pdf_error)?
+                .set("Tabs", Object::Name(b"S".to_vec()));
+        }
+        let mut parent_tree = Dictionary::new();
+        parent_tree.set("Nums", numbers);
+
+        let mut root = Dictionary::new();
+        root.set("Type", Object::Name(b"StructTreeRoot".to_vec()));
+        root.set("K", Object::Reference(ids[0]));
+        root.set("ParentTree", parent_tree);
+        root.set("ParentTreeNextKey", pages.len() as i64);
+        document.set_object(root_id, root);
+
+        let mut mark_info = Dictionary::new();
+        mark_info.set("Marked", true);
+        let catalog = catalog(document)?;
+        catalog.set("StructTreeRoot", Object::Reference(root_id));
+        catalog.set("MarkInfo", mark_info);
+        match catalog
+            .get_mut(b"ViewerPreferences")
+            .and_then(Object::as_dict_mut)
+        {
+            Ok(preferences) => preferences.set("DisplayDocTitle", true),
+            Err(_) => {
+                let mut preferences = Dictionary::new();
+                preferences.set("DisplayDocTitle", true);
+                catalog.set("ViewerPreferences", preferences);
+            }
+        }
+
+        for page_id in pages.into_values() {
+            mark_artifacts(document, page_id)?;
+        }
+        Ok(())
+    }
+}
+
+// identification of the accessibility profile the tagged document follows
+pub(crate) fn identify_pdf_ua(xmp_metadata: XmpMetadata) -> XmpMetadata {
+    xmp_metadata.with_property("pdfuaid", PDFUA_ID_NAMESPACE, "part", "1")
+}
+
+pub(crate) fn marked_content(tag: &str, mcid: i64) -> Operation {
+    let mut properties = Dictionary::new();
+    properties.set("MCID", mcid);
+    Operation::new(
+        "BDC",
+        vec![
+            Object::Name(tag.as_bytes().to_vec()),
+            Object::Dictionary(properties),
+        ],
+    )
+}
+
+// runs of operations outside of any tagged marked content, sequences of optional content
+// do not tag their content
+fn mark_artifacts(document: &mut Document, page_id: ObjectId) -> Result<(), Error> {
+    let content = document
+        .get_and_decode_page_content(page_id)
+        .map_err(pdf_error)?;
+
+    let mut operations = vec![];
+    let mut run = vec![];
+    let mut tagged = vec![];
+    let flush = |operations: &mut Vec<Operation>, run: &mut Vec<Operation>| {
+        if !run.is_empty() {
+            operations.push(Operation::new(
+                "BMC",
+                vec![Object::Name(b"Artifact".to_vec())],
+            ));
+            operations.append(run);
+            operations.push(Operation::new("EMC", vec![]));
+        }
+    };
+    for operation in content.operations {
+        match operation.operator.as_str() {
+            "BDC" | "BMC" => {
+                flush(&mut operations, &mut run);
+                let tag = operation
+                    .operands
+                    .first()
+                    .and_then(|tag| tag.as_name().ok());
+                tagged.push(tag != Some(b"OC"));
+                operations.push(operation);
+            }
+            "EMC" => {
+                flush(&mut operations, &mut run);
+                tagged.pop();
+                operations.push(operation);
+            }
+            _ if tagged.iter().any(|tagged| *tagged) => operations.push(operation),
+            _ => run.push(operation),
+        }
+    }
+    flush(&mut operations, &mut run);
+
+    let content = Content { operations }.encode().map_err(pdf_error)?;
+    document
+        .change_page_content(page_id, content)
+        .map_err(pdf_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    use layout::{
+        Axis, Layout, LayoutBox, Rgba, Stroke,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt},
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Dictionary, Document, Object},
+    };
+
+    use crate::{RenderContext, StructureElement, StructureMarks, StructureRole, new_font_cache};
+
+    #[test]
+    fn structure() {
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+
+        let marks = StructureMarks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        )
+        .with_tagged_pdf(true)
+        .with_language("en-US")
+        .with_structure_marks(marks.clone());
+
+        let mut section = marks.element(
+            StructureElement::new(StructureRole::Section),
+            LayoutBox::new(Axis::Vertical),
+        );
+        let size = Size::fixed(Mm(190.0), Mm(10.0));
+        section.measure(&mut rctx, size.clone()).unwrap();
+        section
+            .lay_out(&mut rctx, Offset::new(Mm(0.0), Mm(0.0)), size)
+            .unwrap();
+        section.render(&mut rctx).unwrap();
+
+        let stroke = Stroke::new(Rgba::black(), Pt(1.0));
+        rctx.with_structure(
+            StructureElement::new(StructureRole::Figure).with_alt_text("Rising trend"),
+            |rctx| {
+                layout::RenderContext::line(
+                    rctx,
+                    &Offset::new(Mm(0.0), Mm(50.0)),
+                    &Offset::new(Mm(50.0), Mm(20.0)),
+                    &stroke,
+                );
+            },
+        );
+        // not tagged, so it is an artifact
+        layout::RenderContext::line(
+            &mut rctx,
+            &Offset::new(Mm(0.0), Mm(60.0)),
+            &Offset::new(Mm(190.0), Mm(60.0)),
+            &stroke,
+        );
+
+        let pdf = rctx.save_to_bytes().unwrap();
+        BufWriter::new(File::create("test_structure.pdf").unwrap())
+            .write_all(&pdf)
+            .unwrap();
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let dictionary = |object: &Object| {
+            object
+                .as_reference()
+                .and_then(|id| document.get_dictionary(id))
+                .unwrap()
+        };
+        let tag = |element: &Dictionary| element.get(b"S").unwrap().as_name().unwrap().to_vec();
+
+        let catalog = document.catalog().unwrap();
+        assert_eq!(catalog.get(b"Lang").unwrap().as_str().unwrap(), b"en-US");
+        let mark_info = catalog.get(b"MarkInfo").unwrap().as_dict().unwrap();
+        assert!(mark_info.get(b"Marked").unwrap().as_bool().unwrap());
+        let root = dictionary(catalog.get(b"StructTreeRoot").unwrap());
+        let document_element = dictionary(root.get(b"K").unwrap());
+        assert_eq!(tag(document_element), b"Document");
+
+        let kids = document_element.get(b"K").unwrap().as_array().unwrap();
+        assert_eq!(kids.len(), 2);
+        assert_eq!(tag(dictionary(&kids[0])), b"Sect");
+        let figure = dictionary(&kids[1]);
+        assert_eq!(tag(figure), b"Figure");
+        assert_eq!(
+            figure.get(b"Alt").unwrap().as_str().unwrap(),
+            b"Rising trend"
+        );
+        let content = figure.get(b"K").unwrap().as_array().unwrap()[0]
+            .as_dict()
+            .unwrap();
+        assert_eq!(content.get(b"MCID").unwrap().as_i64().unwrap(), 0);
+
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let page = document.get_dictionary(page_id).unwrap();
+        assert_eq!(page.get(b"StructParents").unwrap().as_i64().unwrap(), 0);
+        let operations = document
+            .get_and_decode_page_content(page_id)
+            .unwrap()
+            .operations;
+        let marked = operations
+            .iter()
+            .filter(|operation| operation.operator == "BDC" || operation.operator == "BMC")
+            .map(|operation| operation.operands[0].as_name().unwrap().to_vec())
+            .collect::<Vec<_>>();
+        assert!(marked.contains(&b"Figure".to_vec()));
+        assert!(marked.contains(&b"Artifact".to_vec()));
+    }
+
+    #[test]
+    fn pdf_ua() {
+        let render = |pdf_ua: bool, language: bool, figure: StructureElement| {
+            let (document, page, layer) =
+                PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+            let mut rctx = RenderContext::new(
+                document,
+                page,
+                layer,
+                Quad::square(Mm(10.0)),
+                Size::fixed(Mm(210.0), Mm(297.0)),
+                new_font_cache(),
+            )
+            .with_tagged_pdf(true)
+            .with_pdf_ua(pdf_ua);
+            if language {
+                rctx = rctx.with_language("en-US");
+            }
+            rctx.with_structure(figure, |rctx| {
+                layout::RenderContext::line(
+                    rctx,
+                    &Offset::new(Mm(0.0), Mm(50.0)),
+                    &Offset::new(Mm(50.0), Mm(20.0)),
+                    &Stroke::new(Rgba::black(), Pt(1.0)),
+                );
+            });
+            rctx.save_to_bytes()
+                .map(|pdf| String::from_utf8_lossy(&pdf).contains("pdfuaid"))
+        };
+        let figure = || StructureElement::new(StructureRole::Figure);
+        let described = || figure().with_alt_text("Rising trend");
+
+        // tagging alone does not claim the conformance
+        assert!(!render(false, false, figure()).unwrap());
+        assert!(render(true, true, described()).unwrap());
+        assert!(render(true, false, described()).is_err());
+        assert!(render(true, true, figure()).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use std::{fmt::Write, sync::Arc};
+
+use layout::{
+    Error, NewPageOptions, Rgba, Stroke, Style, TextPosition,
+    position::{Offset, Quad, Size},
+    unit::{FillPerMille, Unit},
+};
+use smol_str::ToSmolStr;
+
+use crate::font::{FontCache, OutlineSegment};
+
+use super::{
+    RenderFonts,
+    page_flow::{PageFlow, PagePoint, pt},
+    stroke::stroke_thickness,
+};
+
+struct SvgPage {
+    width: f32,
+    height: f32,
+    content: String,
+}
+
+impl SvgPage {
+    fn new(size: &Size) -> Self {
+        Self {
+            width: pt(size.base_width()),
+            height: pt(size.base_height()),
+            content: String::new(),
+        }
+    }
+
+    fn into_svg(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}pt\" height=\"{h}pt\" viewBox=\"0 0 {w} {h}\">\n{}</svg>\n",
+            self.content,
+            w = number(self.width),
+            h = number(self.height),
+        )
+    }
+}
+
+// preview of a layout, one SVG document per page in pt; text is shaped by the fonts the
+// PDF is rendered with and painted by outlines of its glyphs, so no fonts are needed to
+// view them; pages and lines are broken by the helpers of the PDF render context, content
+// flowed by it, e.g. columns, footnotes or repeated headers, is not flowed in previews
+pub struct SvgRenderContext {
+    fonts: RenderFonts,
+    style: Arc<Style>,
+    flow: PageFlow,
+    pages: Vec<SvgPage>,
+}
+
+impl SvgRenderContext {
+    pub fn new(page_margin: Quad, page_size: Size, fonts: FontCache) -> Self {
+        Self {
+            fonts: RenderFonts::new(fonts),
+            style: Style::new_default(),
+            pages: vec![SvgPage::new(&page_size)],
+            flow: PageFlow::new(page_margin, page_size),
+        }
+    }
+
+    // the same as of the renderer of the PDF, text would be shaped differently otherwise
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.fonts.set_kerning(kerning);
+        self
+    }
+
+    pub fn with_font_kerning(mut self, font_name: impl ToSmolStr, kerning: bool) -> Self {
+        self.fonts.set_font_kerning(font_name, kerning);
+        self
+    }
+
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn into_svg_pages(self) -> Vec<String> {
+        self.pages.into_iter().map(SvgPage::into_svg).collect()
+    }
+
+    // pages broken by the flow, the size of each is the one it starts with
+    fn add_pages(&mut self) {
+        while self.pages.len() <= self.flow.page() {
+            self.pages.push(SvgPage::new(self.flow.page_size()));
+        }
+    }
+
+    fn page(&mut self) -> &mut String {
+        &mut self.pages.last_mut().unwrap().content
+    }
+
+    fn paint_line(
+        &mut self,
+        page: usize,
+        (x1, y1): PagePoint,
+        (x2, y2): PagePoint,
+        stroke: &Stroke,
+    ) {
+        let line = format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"{}/>\n",
+            number(x1),
+            number(y1),
+            number(x2),
+            number(y2),
+            color(stroke.color()),
+            number(stroke_thickness(stroke)),
+            opacity("stroke-opacity", stroke.color()),
+        );
+        self.pages[page].content.push_str(&line);
+    }
+
+    // glyph outlines are in em, with y up
+    fn paint_text(&mut self, origin: (f32, f32), style: &Style, text: &TextPosition) {
+        let font = style.font().merge(self.style.font());
+        let font_name = font.name().unwrap();
+        let font_size = font.size().unwrap();
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0);
+        let (h_scale, v_scale) = ((*font_size * font_scaling) as f32, *font_size as f32);
+
+        let mut data = String::new();
+        let mut pen = (0.0, 0.0);
+        for position in text.positions.iter() {
+            let glyph_origin = (
+                pen.0 + *(position.h_offset * font_size * font_scaling),
+                pen.1 + *(position.v_offset * font_size),
+            );
+            let point = |x: f32, y: f32| {
+                format!(
+                    "{} {}",
+                    number(origin.0 + glyph_origin.0 as f32 + x * h_scale),
+                    number(origin.1 - glyph_origin.1 as f32 - y * v_scale),
+                )
+            };
+            for segment in self
+                .fonts
+                .glyph_outline(font_name, position.glyph_index)
+                .unwrap_or_default()
+            {
+                let _ = match segment {
+                    OutlineSegment::MoveTo(x, y) => write!(data, "M{}", point(x, y)),
+                    OutlineSegment::LineTo(x, y) => write!(data, "L{}", point(x, y)),
+                    OutlineSegment::CurveTo([x1, y1, x2, y2, x, y]) => {
+                        write!(data, "C{} {} {}", point(x1, y1), point(x2, y2), point(x, y))
+                    }
+                    OutlineSegment::Close => write!(data, "Z"),
+                };
+            }
+
+            pen = (
+                pen.0 + *(position.h_advance * font_size * font_scaling),
+                pen.1 + *(position.v_advance * font_size),
+            );
+        }
+        if data.is_empty() {
+            return;
+        }
+
+        let fill = style.color().cloned().unwrap_or(Rgba::black());
+        let path = format!(
+            "<path d=\"{data}\" fill=\"{}\"{}/>\n",
+            color(&fill),
+            opacity("fill-opacity", &fill),
+        );
+        self.page().push_str(&path);
+    }
+}
+
+impl layout::MeasureContext for SvgRenderContext {
+    fn style(&self) -> &Style {
+        self.style.as_ref()
+    }
+
+    fn typeset(&mut self, style: &Style, text: &str) -> Result<TextPosition, Error> {
+        let font = style.font().merge(self.style.font());
+        if let Some(name) = font.name()
+            && font.size().is_some()
+        {
+            self.fonts
+                .typeset(name, text, &font.features().cloned().unwrap_or_default())
+        } else {
+            Err(Error::UnknownFont("Font name or size is undefined".into()))
+        }
+    }
+}
+
+impl layout::RenderContext for SvgRenderContext {
+    fn debug_frame(&mut self, _content_position: &Offset, _size: &Size) {}
+
+    fn check_page_break(&mut self, offset: Unit, height: Unit, reserve_height: bool) -> bool {
+        let new_page = self.flow.check_page_break(offset, height, reserve_height);
+        self.add_pages();
+        new_page
+    }
+
+    fn release_page_break_reservation(&mut self) {
+        self.flow.release_page_break_reservation();
+    }
+
+    fn new_page(&mut self, options: Option<NewPageOptions>) {
+        self.flow.new_page(options);
+        self.add_pages();
+    }
+
+    fn line(&mut self, from: &Offset, to: &Offset, stroke: &Stroke) {
+        let parts = self.flow.line(from, to);
+        self.add_pages();
+        for (page, from, to) in parts {
+            self.paint_line(page, from, to, stroke);
+        }
+    }
+
+    fn text(
+        &mut self,
+        content_position: &Offset,
+        style: &Style,
+        text: &TextPosition,
+        position_is_baseline: bool,
+    ) {
+        if text.positions.is_empty() {
+            return;
+        }
+
+        let font = style.font().merge(self.style.font());
+        if font.name().is_none() || font.size().is_none() {
+            tracing::warn!("Try to typeset text without defined font");
+            return;
+        }
+        let font_size = font.size().unwrap();
+
+        layout::RenderContext::check_page_break(
+            self,
+            content_position.y,
+            (text.height * font_size).into(),
+            false,
+        );
+
+        let (x, mut y) = self.flow.page_point(content_position);
+        if !position_is_baseline {
+            y += pt((text.ascent() * font_size).into());
+        }
+        self.paint_text((x, y), style, text);
+    }
+}
+
+// two decimals are below the resolution of any preview
+pub(crate) fn number(value: f32) -> String {
+    let value = format!("{value:.2}");
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    match value {
+        "-0" => "0".into(),
+        value => value.into(),
+    }
+}
+
+pub(crate) fn color(color: &Rgba) -> String {
+    let (red, green, blue, _) = color.into_rgba();
+    let byte = |component: f32| (component * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(red), byte(green), byte(blue))
+}
+
+fn opacity(attribute: &str, color: &Rgba) -> String {
+    let (_, _, _, alpha) = color.into_rgba();
+    match alpha < 1.0 {
+        true => format!(" {attribute}=\"{}\"", number(alpha)),
+        false => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, Layout, MeasureContext, RenderContext, Rgba, Stroke, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt},
+    };
+
+    use crate::{SvgRenderContext, new_font_cache, render::test_layouts::Rule};
+
+    #[test]
+    fn svg_pages() {
+        let mut ctx = SvgRenderContext::new(
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            new_font_cache(),
+        );
+        Rule::new(400.0)
+            .at(0.0)
+            .with_stroke(Stroke::new(Rgba::from((255, 0, 0, 0.5)), Pt(1.0)))
+            .render(&mut ctx)
+            .unwrap();
+        assert_eq!(ctx.pages(), 2);
+
+        let pages = ctx.into_svg_pages();
+        for page in pages.iter() {
+            assert!(page.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+            assert!(page.contains("viewBox=\"0 0 595.28 841.89\""));
+            assert_eq!(page.matches("<line ").count(), 1);
+            assert!(page.contains("stroke=\"#ff0000\""));
+            assert!(page.contains("stroke-opacity=\"0.5\""));
+        }
+        // the rule continues from the top margin of the next page
+        assert!(pages[1].contains("y1=\"28.35\""));
+    }
+
+    #[test]
+    fn svg_text() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let mut ctx = SvgRenderContext::new(
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .with_color(Rgba::from((13, 71, 161, 1.0)))
+            .build();
+        let text = ctx.typeset(&style, "Preview").unwrap();
+        ctx.text(&Offset::new(Mm(0.0), Mm(0.0)), &style, &text, false);
+
+        let pages = ctx.into_svg_pages();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].matches("<path ").count(), 1);
+        assert!(pages[0].contains("fill=\"#0d47a1\""));
+        assert!(pages[0].contains("d=\"M") && pages[0].contains("Z\""));
+    }
+}
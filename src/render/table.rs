@@ -0,0 +1,530 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use layout::{
+    Error, Layout, LayoutBox, MeasureContext, RenderContext, StyleBuilder, Text, hbox, hfill,
+    position::{Offset, Quad, Size},
+    unit::{Mm, Unit},
+    vbox,
+};
+
+use super::{Marks, header::SharedLayout};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnWidth {
+    Fixed(Unit),
+    // share of the width left by fixed columns
+    Weight(u32),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellAlignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug)]
+pub struct TableColumn {
+    title: String,
+    width: ColumnWidth,
+    alignment: CellAlignment,
+    style: Option<StyleBuilder>,
+}
+
+impl TableColumn {
+    // columns share the width of the table equally unless set
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            width: ColumnWidth::Weight(1),
+            alignment: CellAlignment::Left,
+            style: None,
+        }
+    }
+
+    pub fn with_width(mut self, width: impl Into<Unit>) -> Self {
+        self.width = ColumnWidth::Fixed(width.into());
+        self
+    }
+
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.width = ColumnWidth::Weight(weight);
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: CellAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    // style of cells of the column, e.g. a font with tabular figures
+    pub fn with_style(mut self, style: StyleBuilder) -> Self {
+        self.style = Some(style);
+        self
+    }
+}
+
+// table of text cells bound to rows of data, e.g. records of CSV; missing cells of a row
+// are empty, cells beyond the columns are dropped
+#[derive(Clone, Debug)]
+pub struct TableBuilder {
+    columns: Vec<TableColumn>,
+    style: StyleBuilder,
+    header_style: Option<StyleBuilder>,
+    cell_padding: Option<Quad>,
+    repeat_header: bool,
+    marks: Option<Marks>,
+    rows: Vec<Vec<String>>,
+}
+
+impl TableBuilder {
+    // the header is repeated on pages the table continues to unless set otherwise
+    pub fn new(columns: impl IntoIterator<Item = TableColumn>, style: StyleBuilder) -> Self {
+        Self {
+            columns: columns.into_iter().collect(),
+            style,
+            header_style: None,
+            cell_padding: None,
+            repeat_header: true,
+            marks: None,
+            rows: vec![],
+        }
+    }
+
+    pub fn with_header_style(mut self, style: StyleBuilder) -> Self {
+        self.header_style = Some(style);
+        self
+    }
+
+    pub fn with_cell_padding(mut self, padding: Quad) -> Self {
+        self.cell_padding = Some(padding);
+        self
+    }
+
+    pub fn with_repeated_header(mut self, repeat_header: bool) -> Self {
+        self.repeat_header = repeat_header;
+        self
+    }
+
+    // the header is repeated by the render context the marks are taken by, a table
+    // repeating its header without them is not measured
+    pub fn with_marks(mut self, marks: Marks) -> Self {
+        self.marks = Some(marks);
+        self
+    }
+
+    pub fn with_rows(
+        mut self,
+        rows: impl IntoIterator<Item = impl IntoIterator<Item = impl ToString>>,
+    ) -> Self {
+        for row in rows {
+            self.add_row(row);
+        }
+        self
+    }
+
+    pub fn add_row(&mut self, row: impl IntoIterator<Item = impl ToString>) {
+        let row = row.into_iter().take(self.columns.len());
+        self.rows.push(row.map(|cell| cell.to_string()).collect());
+    }
+
+    pub fn build(self) -> TableLayout {
+        TableLayout {
+            table: Rc::new(self),
+            body: None,
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+
+    // the header is left out if no column has a title
+    fn has_header(&self) -> bool {
+        self.columns.iter().any(|column| !column.title.is_empty())
+    }
+
+    fn repeats_header(&self) -> bool {
+        self.repeat_header && self.has_header()
+    }
+
+    fn widths(&self, width: Unit) -> Vec<Unit> {
+        let mm = |unit: Unit| Mm::from(unit).0;
+        let (fixed, weights) = self
+            .columns
+            .iter()
+            .fold((0.0, 0), |(fixed, weights), column| match column.width {
+                ColumnWidth::Fixed(width) => (fixed + mm(width), weights),
+                ColumnWidth::Weight(weight) => (fixed, weights + weight),
+            });
+        let free = (mm(width) - fixed).max(0.0);
+        self.columns
+            .iter()
+            .map(|column| match column.width {
+                ColumnWidth::Fixed(width) => width,
+                ColumnWidth::Weight(weight) => {
+                    Mm(free * weight as f64 / weights.max(1) as f64).into()
+                }
+            })
+            .collect()
+    }
+
+    fn header_row(&self, widths: &[Unit]) -> LayoutBox {
+        let style = self.header_style.as_ref().unwrap_or(&self.style);
+        self.columns
+            .iter()
+            .zip(widths)
+            .fold(hbox(), |row, (column, width)| {
+                row.child(self.cell(&column.title, *width, column.alignment, style))
+            })
+    }
+
+    fn row(&self, cells: &[String], widths: &[Unit]) -> LayoutBox {
+        self.columns
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .fold(hbox(), |row, (index, (column, width))| {
+                let text = cells.get(index).map(String::as_str).unwrap_or_default();
+                let style = column.style.as_ref().unwrap_or(&self.style);
+                row.child(self.cell(text, *width, column.alignment, style))
+            })
+    }
+
+    fn cell(
+        &self,
+        text: &str,
+        width: Unit,
+        alignment: CellAlignment,
+        style: &StyleBuilder,
+    ) -> LayoutBox {
+        let mut cell = hbox().axis_size(width);
+        if let Some(padding) = &self.cell_padding {
+            cell = cell.style(StyleBuilder::default().with_padding(padding.clone()));
+        }
+        let text = Text::new(text).style(style.clone());
+        match alignment {
+            CellAlignment::Left => cell.child(text).child(hfill(1)),
+            CellAlignment::Center => cell.child(hfill(1)).child(text).child(hfill(1)),
+            CellAlignment::Right => cell.child(hfill(1)).child(text),
+        }
+    }
+}
+
+// rows are made when the width of the table is known
+#[derive(Debug)]
+pub struct TableLayout {
+    table: Rc<TableBuilder>,
+    body: Option<LayoutBox>,
+    offset: Offset,
+    size: Size,
+}
+
+impl Layout for TableLayout {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        let widths = self.table.widths(size.base_width());
+        let repeated = match (&self.table.marks, self.table.repeats_header()) {
+            (Some(marks), true) if marks.is_wired() => {
+                let mut row: Box<dyn Layout> = Box::new(self.table.header_row(&widths));
+                row.measure(ctx, size.clone())?;
+                Some((marks.clone(), Rc::new(RefCell::new(row))))
+            }
+            (_, true) => {
+                return Err(Error::PdfWrite(
+                    "Table marks are not passed to the render context".into(),
+                ));
+            }
+            (_, false) => None,
+        };
+        let header = Rc::new(TableHeader {
+            repeated,
+            first_row_height: Cell::new(None),
+        });
+
+        let mut body = vbox();
+        if self.table.has_header() {
+            body = body.child(TableRow::new(
+                self.table.header_row(&widths),
+                RowKind::Header(header.clone()),
+            ));
+        }
+        for cells in &self.table.rows {
+            body = body.child(TableRow::new(
+                self.table.row(cells, &widths),
+                RowKind::Body(header.clone()),
+            ));
+        }
+        body.measure(ctx, size)?;
+        self.body = Some(body);
+        Ok(())
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset.clone();
+        self.size = size.clone();
+        match self.body.as_mut() {
+            Some(body) => body.lay_out(ctx, offset, size),
+            None => Ok(()),
+        }
+    }
+
+    // the header stops being repeated after the last row
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        let Some(body) = self.body.as_ref() else {
+            return Ok(());
+        };
+        body.render(ctx)?;
+        if let Some(marks) = &self.table.marks
+            && self.table.repeats_header()
+        {
+            marks.end_header();
+            ctx.check_page_break(self.offset.y + self.size.base_height(), Unit::zero(), false);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct TableHeader {
+    // the row the render context repeats on pages the table continues to
+    repeated: Option<(Marks, SharedLayout)>,
+    first_row_height: Cell<Option<Unit>>,
+}
+
+#[derive(Debug)]
+enum RowKind {
+    // kept on the page together with the first row
+    Header(Rc<TableHeader>),
+    Body(Rc<TableHeader>),
+}
+
+#[derive(Debug)]
+struct TableRow {
+    layout: LayoutBox,
+    kind: RowKind,
+    offset: Offset,
+    size: Size,
+}
+
+impl TableRow {
+    fn new(layout: LayoutBox, kind: RowKind) -> Self {
+        Self {
+            layout,
+            kind,
+            offset: Offset::zero(),
+            size: Size::fixed(0, 0),
+        }
+    }
+}
+
+impl Layout for TableRow {
+    fn measure(&mut self, ctx: &mut dyn MeasureContext, size: Size) -> Result<(), Error> {
+        self.layout.measure(ctx, size)
+    }
+
+    fn lay_out(
+        &mut self,
+        ctx: &mut dyn MeasureContext,
+        offset: Offset,
+        size: Size,
+    ) -> Result<(), Error> {
+        if let RowKind::Body(header) = &self.kind
+            && header.first_row_height.get().is_none()
+        {
+            header.first_row_height.set(Some(size.base_height()));
+        }
+        self.offset = offset.clone();
+        self.size = size.clone();
+        self.layout.lay_out(ctx, offset, size)
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        let RowKind::Header(header) = &self.kind else {
+            return self.layout.render(ctx);
+        };
+
+        let first_row_height = header.first_row_height.get().unwrap_or_default();
+        ctx.check_page_break(
+            self.offset.y,
+            self.size.base_height() + first_row_height,
+            false,
+        );
+        self.layout.render(ctx)?;
+
+        // repeated from the page after the one the header is rendered to
+        if let Some((marks, layout)) = &header.repeated {
+            marks.begin_header(layout.clone(), self.offset.clone(), self.size.clone());
+            ctx.check_page_break(self.offset.y + self.size.base_height(), Unit::zero(), false);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, Layout, MeasureContext, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt, Unit},
+    };
+    use printpdf::{
+        PdfDocument,
+        lopdf::{Document, Object},
+    };
+
+    use crate::{
+        CellAlignment, Marks, RenderContext, Renderer, TableBuilder, TableColumn, new_font_cache,
+    };
+
+    #[test]
+    fn widths() {
+        let table = TableBuilder::new(
+            [
+                TableColumn::new("Item").with_weight(2),
+                TableColumn::new("Code").with_width(Mm(30.0)),
+                TableColumn::new("Price").with_alignment(CellAlignment::Right),
+            ],
+            StyleBuilder::default(),
+        )
+        .with_rows([["Tea", "T1", "3.50", "dropped"]])
+        .with_rows([vec!["Coffee"]]);
+
+        let widths = table
+            .widths(Mm(180.0).into())
+            .into_iter()
+            .map(|width| Mm::from(width).0.round())
+            .collect::<Vec<_>>();
+        assert_eq!(widths, [100.0, 30.0, 50.0]);
+        assert_eq!(table.rows[0], ["Tea", "T1", "3.50"]);
+        assert_eq!(table.rows[1], ["Coffee"]);
+        assert!(table.has_header());
+
+        // fixed columns wider than the table leave nothing to weighted ones
+        let widths = table.widths(Mm(20.0).into());
+        assert_eq!(widths[0], Unit::zero());
+        assert!(!TableBuilder::new([TableColumn::new("")], StyleBuilder::default()).has_header());
+    }
+
+    #[test]
+    fn table() {
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let style = StyleBuilder::default().with_font(Font::new("LatoReg", Pt(10.0), None));
+        let table = TableBuilder::new(
+            [
+                TableColumn::new("Item"),
+                TableColumn::new("Price").with_alignment(CellAlignment::Right),
+            ],
+            style.clone(),
+        )
+        .with_header_style(style)
+        .with_cell_padding(Quad::square(Mm(1.0)))
+        .with_rows((1..=100).map(|index| [format!("Item {index}"), format!("{index}.00")]));
+
+        let renderer = Renderer::new(
+            "Table",
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        let table = table.with_marks(renderer.marks()).build();
+        let pdf = renderer.render_layout(Box::new(table)).unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        assert!(document.get_pages().len() > 1);
+
+        // the header is repeated unless set otherwise, it needs the marks
+        let table = TableBuilder::new([TableColumn::new("Item")], StyleBuilder::default())
+            .with_rows([["Tea"]])
+            .build();
+        let fonts = new_font_cache();
+        let renderer = Renderer::new(
+            "Table",
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        assert!(renderer.render_layout(Box::new(table)).is_err());
+        let table = TableBuilder::new([TableColumn::new("Item")], StyleBuilder::default())
+            .with_repeated_header(false)
+            .build();
+        assert!(table.table.marks.is_none() && !table.table.repeats_header());
+    }
+
+    #[test]
+    fn repeated_header() {
+        let fonts = new_font_cache();
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let (document, page, layer) =
+            PdfDocument::new("Test", printpdf::Mm(210.0), printpdf::Mm(297.0), "default");
+        let marks = Marks::new();
+        let mut rctx = RenderContext::new(
+            document,
+            page,
+            layer,
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        )
+        .with_marks(marks.clone());
+
+        let style = StyleBuilder::default().with_font(Font::new("LatoReg", Pt(10.0), None));
+        let mut table = TableBuilder::new(
+            [TableColumn::new("Name"), TableColumn::new("Price")],
+            style.clone(),
+        )
+        .with_rows((1..=100).map(|index| [format!("Row {index}"), format!("{index}.00")]))
+        .with_marks(marks)
+        .build();
+        let size = Size::fixed(Mm(190.0), Mm(277.0));
+        table.measure(&mut rctx, size.clone()).unwrap();
+        let title = rctx.typeset(&style.build(), "Name").unwrap();
+        rctx.complete_fonts().unwrap();
+        table
+            .lay_out(&mut rctx, Offset::new(Mm(10.0), Mm(10.0)), size)
+            .unwrap();
+        table.render(&mut rctx).unwrap();
+        let pdf = rctx.save_to_bytes().unwrap();
+
+        // glyphs are shown one by one, the title is found among glyphs of the page
+        let document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert!(pages.len() > 1);
+        let glyphs = title
+            .positions
+            .iter()
+            .flat_map(|position| position.glyph_index.to_be_bytes())
+            .collect::<Vec<_>>();
+        let shown = |page: u32| {
+            let content = document.get_and_decode_page_content(pages[&page]).unwrap();
+            content
+                .operations
+                .into_iter()
+                .filter(|operation| operation.operator == "Tj")
+                .flat_map(|operation| operation.operands)
+                .filter_map(|operand| match operand {
+                    Object::String(bytes, _) => Some(bytes),
+                    _ => None,
+                })
+                .flatten()
+                .collect::<Vec<_>>()
+        };
+        for page in [1, 2] {
+            assert!(
+                shown(page)
+                    .windows(glyphs.len())
+                    .any(|window| window == glyphs.as_slice())
+            );
+        }
+    }
+}
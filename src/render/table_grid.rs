@@ -0,0 +1,345 @@
+use layout::{
+    Stroke,
+    position::Offset,
+    unit::{Mm, Unit},
+};
+
+use super::{Path, context::KAPPA, stroke::stroke_thickness};
+
+// Borders of a table drawn at once, each edge shared by neighbouring cells is stroked
+// only once (collapsed), and continuous edges of the same stroke form a single line
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableGrid {
+    columns: Vec<Unit>,
+    rows: Vec<Unit>,
+    // horizontal edges, columns per each of rows + 1 lines
+    horizontal: Vec<Option<Stroke>>,
+    // vertical edges, columns + 1 per each of rows
+    vertical: Vec<Option<Stroke>>,
+    radius: Unit,
+}
+
+impl TableGrid {
+    // column widths and row heights, no borders are drawn unless set
+    pub fn new(
+        columns: impl IntoIterator<Item = impl Into<Unit>>,
+        rows: impl IntoIterator<Item = impl Into<Unit>>,
+    ) -> Self {
+        let columns = columns.into_iter().map(Into::into).collect::<Vec<_>>();
+        let rows = rows.into_iter().map(Into::into).collect::<Vec<_>>();
+        Self {
+            horizontal: vec![None; columns.len() * (rows.len() + 1)],
+            vertical: vec![None; (columns.len() + 1) * rows.len()],
+            columns,
+            rows,
+            radius: Unit::zero(),
+        }
+    }
+
+    // all edges, replaces borders set before
+    pub fn with_border(mut self, stroke: Option<Stroke>) -> Self {
+        self.horizontal.fill(stroke.clone());
+        self.vertical.fill(stroke);
+        self
+    }
+
+    pub fn with_outer_border(mut self, stroke: Stroke) -> Self {
+        let (columns, rows) = (self.columns.len(), self.rows.len());
+        for column in 0..columns {
+            collapse(&mut self.horizontal[column], &stroke);
+            collapse(&mut self.horizontal[rows * columns + column], &stroke);
+        }
+        for row in 0..rows {
+            collapse(&mut self.vertical[row * (columns + 1)], &stroke);
+            collapse(&mut self.vertical[row * (columns + 1) + columns], &stroke);
+        }
+        self
+    }
+
+    // edges shared with neighbouring cells keep the thicker stroke, the later one if equal
+    pub fn with_cell_border(mut self, row: usize, column: usize, stroke: Stroke) -> Self {
+        let columns = self.columns.len();
+        if row >= self.rows.len() || column >= columns {
+            tracing::warn!("Cell {row}, {column} is outside of the table grid.");
+            return self;
+        }
+        for edge in [row * columns + column, (row + 1) * columns + column] {
+            collapse(&mut self.horizontal[edge], &stroke);
+        }
+        for edge in [
+            row * (columns + 1) + column,
+            row * (columns + 1) + column + 1,
+        ] {
+            collapse(&mut self.vertical[edge], &stroke);
+        }
+        self
+    }
+
+    // outer corners whose both edges have the same stroke are rounded, the radius is
+    // limited by the cells in the corners
+    pub fn with_radius(mut self, radius: impl Into<Unit>) -> Self {
+        self.radius = radius.into();
+        self
+    }
+
+    pub fn height(&self) -> Unit {
+        self.rows.iter().fold(Unit::zero(), |sum, row| sum + *row)
+    }
+
+    // lines grouped by their stroke, so every stroke is set once
+    pub(crate) fn lines(&self, content_position: &Offset) -> Vec<(Stroke, Path)> {
+        let xs = positions(content_position.x, &self.columns);
+        let ys = positions(content_position.y, &self.rows);
+
+        let (columns, rows) = (self.columns.len(), self.rows.len());
+        let radius = self.corner_radius();
+        let [top_left, top_right, bottom_left, bottom_right] = self.rounded_corners(radius);
+        let inset = |rounded: bool| match rounded {
+            true => radius,
+            false => Unit::zero(),
+        };
+
+        let mut lines: Vec<(Stroke, Path)> = vec![];
+        let mut add = |stroke: &Stroke, from: Offset, to: Offset| {
+            // edges as long as the corners are left to them
+            if from == to {
+                return;
+            }
+            let path = path_of(&mut lines, stroke);
+            *path = std::mem::take(path).move_to(from).line_to(to);
+        };
+
+        for (line, y) in ys.iter().enumerate() {
+            let edges = &self.horizontal[line * columns..][..columns];
+            let (left, right) = match line {
+                0 => (top_left, top_right),
+                line if line == rows => (bottom_left, bottom_right),
+                _ => (false, false),
+            };
+            for (start, end, stroke) in runs(edges) {
+                let from = xs[start] + inset(start == 0 && left);
+                let to = xs[end] - inset(end == columns && right);
+                add(stroke, Offset::new(from, *y), Offset::new(to, *y));
+            }
+        }
+        for (line, x) in xs.iter().enumerate() {
+            let edges = (0..rows)
+                .map(|row| self.vertical[row * (columns + 1) + line].clone())
+                .collect::<Vec<_>>();
+            let (top, bottom) = match line {
+                0 => (top_left, bottom_left),
+                line if line == columns => (top_right, bottom_right),
+                _ => (false, false),
+            };
+            for (start, end, stroke) in runs(&edges) {
+                let from = ys[start] + inset(start == 0 && top);
+                let to = ys[end] - inset(end == rows && bottom);
+                add(stroke, Offset::new(*x, from), Offset::new(*x, to));
+            }
+        }
+
+        // quarter circles from the vertical edge to the horizontal one
+        let corners = [
+            (top_left, 0, 0, 1.0, 1.0),
+            (top_right, columns, 0, -1.0, 1.0),
+            (bottom_left, 0, rows, 1.0, -1.0),
+            (bottom_right, columns, rows, -1.0, -1.0),
+        ];
+        let radius = Mm::from(radius).0;
+        let handle = radius * (1.0 - KAPPA as f64);
+        for (rounded, column, row, dx, dy) in corners {
+            if !rounded {
+                continue;
+            }
+            let (x, y) = (Mm::from(xs[column]).0, Mm::from(ys[row]).0);
+            let point = |dx: f64, dy: f64| Offset::new(Mm(x + dx), Mm(y + dy));
+            let stroke = self.horizontal[row * columns + column.min(columns - 1)]
+                .as_ref()
+                .unwrap();
+            let path = path_of(&mut lines, stroke);
+            *path = std::mem::take(path)
+                .move_to(point(0.0, dy * radius))
+                .curve_to(
+                    point(0.0, dy * handle),
+                    point(dx * handle, 0.0),
+                    point(dx * radius, 0.0),
+                );
+        }
+        lines
+    }
+
+    fn corner_radius(&self) -> Unit {
+        let cells = [
+            self.columns.first(),
+            self.columns.last(),
+            self.rows.first(),
+            self.rows.last(),
+        ];
+        cells
+            .into_iter()
+            .flatten()
+            .fold(self.radius, |radius, size| match *size < radius {
+                true => *size,
+                false => radius,
+            })
+    }
+
+    fn rounded_corners(&self, radius: Unit) -> [bool; 4] {
+        let (columns, rows) = (self.columns.len(), self.rows.len());
+        if radius <= Unit::zero() || columns == 0 || rows == 0 {
+            return [false; 4];
+        }
+        let corner = |horizontal: usize, vertical: usize| {
+            self.horizontal[horizontal]
+                .as_ref()
+                .is_some_and(|stroke| self.vertical[vertical].as_ref() == Some(stroke))
+        };
+        let last_row = (rows - 1) * (columns + 1);
+        [
+            corner(0, 0),
+            corner(columns - 1, columns),
+            corner(rows * columns, last_row),
+            corner(rows * columns + columns - 1, last_row + columns),
+        ]
+    }
+}
+
+fn path_of<'a>(lines: &'a mut Vec<(Stroke, Path)>, stroke: &Stroke) -> &'a mut Path {
+    let index = match lines.iter().position(|(existing, _)| existing == stroke) {
+        Some(index) => index,
+        None => {
+            lines.push((stroke.clone(), Path::new()));
+            lines.len() - 1
+        }
+    };
+    &mut lines[index].1
+}
+
+fn collapse(edge: &mut Option<Stroke>, stroke: &Stroke) {
+    if edge
+        .as_ref()
+        .is_none_or(|existing| stroke_thickness(existing) <= stroke_thickness(stroke))
+    {
+        *edge = Some(stroke.clone());
+    }
+}
+
+fn positions(start: Unit, sizes: &[Unit]) -> Vec<Unit> {
+    let mut positions = vec![start];
+    for size in sizes {
+        positions.push(*positions.last().unwrap() + *size);
+    }
+    positions
+}
+
+// consecutive edges of the same stroke, as (start, end) indices of their positions
+fn runs(edges: &[Option<Stroke>]) -> Vec<(usize, usize, &Stroke)> {
+    let mut runs: Vec<(usize, usize, &Stroke)> = vec![];
+    for (index, edge) in edges.iter().enumerate() {
+        let Some(stroke) = edge else {
+            continue;
+        };
+        match runs.last_mut() {
+            Some((_, end, last)) if *end == index && *last == stroke => *end = index + 1,
+            _ => runs.push((index, index + 1, stroke)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Rgba, Stroke,
+        position::Offset,
+        unit::{Mm, Pt},
+    };
+
+    use crate::{Path, PathSegment, TableGrid};
+
+    use super::KAPPA;
+
+    fn subpaths(lines: &[(Stroke, Path)]) -> Vec<usize> {
+        lines
+            .iter()
+            .map(|(_, path)| {
+                path.segments()
+                    .iter()
+                    .filter(|segment| matches!(segment, PathSegment::MoveTo(_)))
+                    .count()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collapsed_edges() {
+        let thin = Stroke::new(Rgba::black(), Pt(0.5));
+        let thick = Stroke::new(Rgba::black(), Pt(2.0));
+        let position = Offset::new(Mm(0.0), Mm(0.0));
+
+        // 3 horizontal and 4 vertical lines, each one continuous
+        let grid = TableGrid::new([Mm(20.0), Mm(30.0), Mm(40.0)], [Mm(10.0), Mm(10.0)])
+            .with_border(Some(thin.clone()));
+        assert_eq!(subpaths(&grid.lines(&position)), vec![7]);
+
+        // thicker cell border splits lines around the cell, thinner one does not
+        let grid = grid
+            .with_cell_border(0, 1, thick.clone())
+            .with_cell_border(0, 2, thin.clone());
+        let lines = grid.lines(&position);
+        assert_eq!(lines[0].0, thin);
+        assert_eq!(lines[1].0, thick);
+        assert_eq!(subpaths(&lines), vec![9, 4]);
+
+        let grid = TableGrid::new([Mm(20.0), Mm(30.0)], [Mm(10.0)]).with_outer_border(thick);
+        assert_eq!(subpaths(&grid.lines(&position)), vec![4]);
+        assert_eq!(grid.height(), Mm(10.0).into());
+    }
+
+    #[test]
+    fn rounded_corners() {
+        let thin = Stroke::new(Rgba::black(), Pt(0.5));
+        let thick = Stroke::new(Rgba::black(), Pt(2.0));
+        let position = Offset::new(Mm(0.0), Mm(0.0));
+        let curves = |path: &Path| {
+            path.segments()
+                .iter()
+                .filter(|segment| matches!(segment, PathSegment::CurveTo(..)))
+                .count()
+        };
+
+        let grid = TableGrid::new([Mm(20.0), Mm(30.0)], [Mm(10.0), Mm(10.0)])
+            .with_border(Some(thin.clone()))
+            .with_radius(Mm(2.0));
+        let lines = grid.lines(&position);
+        assert_eq!(curves(&lines[0].1), 4);
+        // edges end where their corners start
+        assert_eq!(
+            lines[0].1.segments()[..2],
+            [
+                PathSegment::MoveTo(Offset::new(Mm(2.0), Mm(0.0))),
+                PathSegment::LineTo(Offset::new(Mm(48.0), Mm(0.0)))
+            ]
+        );
+
+        // corners without edges are not rounded, the radius is limited by the cells
+        let lines = TableGrid::new([Mm(20.0), Mm(30.0)], [Mm(10.0), Mm(10.0)])
+            .with_cell_border(0, 0, thick)
+            .with_radius(Mm(15.0))
+            .lines(&position);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(curves(&lines[0].1), 1);
+        assert_eq!(
+            lines[0].1.segments()[0],
+            PathSegment::MoveTo(Offset::new(Mm(10.0), Mm(0.0)))
+        );
+        assert_eq!(
+            lines[0].1.segments().last(),
+            Some(&PathSegment::CurveTo(
+                Offset::new(Mm(0.0), Mm(10.0 * (1.0 - KAPPA as f64))),
+                Offset::new(Mm(10.0 * (1.0 - KAPPA as f64)), Mm(0.0)),
+                Offset::new(Mm(10.0), Mm(0.0)),
+            ))
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use layout::{
+    Error, Font, Layout, MeasureContext, RenderContext, Rgba, Stroke, StyleBuilder, TextPosition,
+    position::{Offset, Quad, Size},
+    unit::{Mm, Pt},
+};
+use printpdf::PdfDocument;
+
+use crate::new_font_cache;
+
+// context of the pages 297 mm high with margins of 10 mm, fonts are not added
+pub(crate) fn render_context(title: &str, width: f64, pages: usize) -> crate::RenderContext {
+    let (document, page, layer) = PdfDocument::new(
+        title,
+        printpdf::Mm(width as f32),
+        printpdf::Mm(297.0),
+        "default",
+    );
+
+    let mut rctx = crate::RenderContext::new(
+        document,
+        page,
+        layer,
+        Quad::square(Mm(10.0)),
+        Size::fixed(Mm(width), Mm(297.0)),
+        new_font_cache(),
+    );
+    for _ in 1..pages {
+        RenderContext::new_page(&mut rctx, None);
+    }
+    rctx
+}
+
+// vertical rule of the height, broken by pages, 10 mm from the left of the content unless
+// placed elsewhere
+#[derive(Debug)]
+pub(crate) struct Rule {
+    x: f64,
+    height: f64,
+    stroke: Stroke,
+}
+
+impl Rule {
+    pub(crate) fn new(height: f64) -> Self {
+        Self {
+            x: 10.0,
+            height,
+            stroke: Stroke::new(Rgba::black(), Pt(0.5)),
+        }
+    }
+
+    pub(crate) fn at(mut self, x: f64) -> Self {
+        self.x = x;
+        self
+    }
+
+    pub(crate) fn with_stroke(mut self, stroke: Stroke) -> Self {
+        self.stroke = stroke;
+        self
+    }
+}
+
+impl Layout for Rule {
+    fn measure(&mut self, _: &mut dyn MeasureContext, _: Size) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn lay_out(&mut self, _: &mut dyn MeasureContext, _: Offset, _: Size) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        ctx.line(
+            &Offset::new(Mm(self.x), Mm(0.0)),
+            &Offset::new(Mm(self.x), Mm(self.height)),
+            &self.stroke,
+        );
+        Ok(())
+    }
+}
+
+// lines of the text 10 mm apart, rendered one by one in LatoReg of 12 pt
+#[derive(Debug)]
+pub(crate) struct Lines {
+    pub(crate) text: TextPosition,
+    count: usize,
+    offset: Offset,
+}
+
+impl Lines {
+    pub(crate) fn new(text: TextPosition, count: usize) -> Self {
+        Self {
+            text,
+            count,
+            offset: Offset::zero(),
+        }
+    }
+}
+
+impl Layout for Lines {
+    fn measure(&mut self, _: &mut dyn MeasureContext, _: Size) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn lay_out(
+        &mut self,
+        _: &mut dyn MeasureContext,
+        offset: Offset,
+        _: Size,
+    ) -> Result<(), Error> {
+        self.offset = offset;
+        Ok(())
+    }
+
+    fn render(&self, ctx: &mut dyn RenderContext) -> Result<(), Error> {
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        for line in 0..self.count {
+            let offset = Offset::new(self.offset.x, self.offset.y + Mm(10.0 * line as f64).into());
+            ctx.text(&offset, &style, &self.text, false);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use layout::{Stroke, unit::Em};
+use printpdf::TextRenderingMode;
+
+use crate::font::DecorationMetrics;
+
+use super::{Gradient, Image};
+
+#[derive(Clone, Debug, Default)]
+pub enum TextMode {
+    #[default]
+    Fill,
+    Stroke(Stroke),
+    FillStroke(Stroke),
+    // searchable, but not painted, e.g. over scanned pages
+    Invisible,
+    // glyphs clip content rendered after them
+    Clip,
+}
+
+impl TextMode {
+    pub(crate) fn rendering_mode(&self) -> TextRenderingMode {
+        match self {
+            Self::Fill => TextRenderingMode::Fill,
+            Self::Stroke(_) => TextRenderingMode::Stroke,
+            Self::FillStroke(_) => TextRenderingMode::FillStroke,
+            Self::Invisible => TextRenderingMode::Invisible,
+            Self::Clip => TextRenderingMode::Clip,
+        }
+    }
+
+    pub(crate) fn stroke(&self) -> Option<&Stroke> {
+        match self {
+            Self::Stroke(stroke) | Self::FillStroke(stroke) => Some(stroke),
+            _ => None,
+        }
+    }
+}
+
+// paint of glyphs instead of the fill color, it covers the box of the text
+#[derive(Clone, Debug)]
+pub enum TextFill {
+    Gradient(Gradient),
+    // placed by its fit, e.g. a texture covering the text
+    Image(Image),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextDecoration {
+    underline: bool,
+    strikethrough: bool,
+    overline: bool,
+}
+
+impl TextDecoration {
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Self {
+        self.strikethrough = true;
+        self
+    }
+
+    pub fn overline(mut self) -> Self {
+        self.overline = true;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.underline && !self.strikethrough && !self.overline
+    }
+
+    // (center, thickness) of every rule, relative to the baseline
+    pub(crate) fn rules(&self, metrics: &DecorationMetrics, ascent: Em) -> Vec<(Em, Em)> {
+        let mut rules = vec![];
+        if self.underline {
+            // post table position is the top of the underline
+            rules.push((
+                Em(metrics.underline_position.0 - metrics.underline_thickness.0 / 2.0),
+                metrics.underline_thickness,
+            ));
+        }
+        if self.strikethrough {
+            // os/2 table position is the top of the strikeout as well
+            rules.push((
+                Em(metrics.strikeout_position.0 - metrics.strikeout_thickness.0 / 2.0),
+                metrics.strikeout_thickness,
+            ));
+        }
+        if self.overline {
+            rules.push((ascent, metrics.underline_thickness));
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::unit::Em;
+
+    use crate::font::DecorationMetrics;
+
+    use super::TextDecoration;
+
+    #[test]
+    fn rules() {
+        let metrics = DecorationMetrics {
+            underline_position: Em(-0.1),
+            underline_thickness: Em(0.06),
+            strikeout_position: Em(0.3),
+            strikeout_thickness: Em(0.04),
+        };
+
+        assert!(TextDecoration::empty().rules(&metrics, Em(0.8)).is_empty());
+
+        let rules = TextDecoration::empty()
+            .underline()
+            .strikethrough()
+            .overline()
+            .rules(&metrics, Em(0.8));
+        let rules = rules
+            .iter()
+            .map(|(center, thickness)| {
+                (
+                    (center.0 * 100.0).round() as i32,
+                    (thickness.0 * 100.0).round() as i32,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rules, [(-13, 6), (28, 4), (80, 6)]);
+    }
+}
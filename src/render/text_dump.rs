@@ -0,0 +1,269 @@
+use std::{fmt::Write, sync::Arc};
+
+use layout::{
+    Error, NewPageOptions, Rgba, Stroke, Style, TextPosition,
+    position::{Offset, Quad, Size},
+    unit::{FillPerMille, Unit},
+};
+use smol_str::{SmolStr, ToSmolStr};
+
+use crate::font::FontCache;
+
+use super::{
+    RenderFonts,
+    page_flow::{PageFlow, pt},
+    svg_context::{color, number},
+};
+
+// text rendered to a page, in pt from the top left corner of the page to the start of
+// the baseline; pages are numbered from 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+    pub page: usize,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub font: SmolStr,
+    pub size: f32,
+    pub color: Rgba,
+    pub text: String,
+}
+
+// records text of a layout instead of painting it, e.g. for tests of content placement;
+// pages are broken by the helpers of the PDF render context, content flowed by it, e.g.
+// columns, footnotes or repeated headers, is not flowed
+pub struct TextRenderContext {
+    fonts: RenderFonts,
+    style: Arc<Style>,
+    flow: PageFlow,
+    // runs typeset by fonts with their text, layouts render the positions they typeset
+    typeset: Vec<(SmolStr, TextPosition, String)>,
+    runs: Vec<TextRun>,
+}
+
+impl TextRenderContext {
+    pub fn new(page_margin: Quad, page_size: Size, fonts: FontCache) -> Self {
+        Self {
+            fonts: RenderFonts::new(fonts),
+            style: Style::new_default(),
+            flow: PageFlow::new(page_margin, page_size),
+            typeset: vec![],
+            runs: vec![],
+        }
+    }
+
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.fonts.set_kerning(kerning);
+        self
+    }
+
+    pub fn pages(&self) -> usize {
+        self.flow.page() + 1
+    }
+
+    // the last run typeset of the font and positions
+    fn typeset_text(&self, font: &str, position: &TextPosition) -> Option<&str> {
+        self.typeset
+            .iter()
+            .rev()
+            .find(|(run_font, run_position, _)| run_font == font && run_position == position)
+            .map(|(_, _, text)| text.as_str())
+    }
+
+    pub fn runs(&self) -> &[TextRun] {
+        &self.runs
+    }
+
+    // the first run of the text
+    pub fn run(&self, text: &str) -> Option<&TextRun> {
+        self.runs.iter().find(|run| run.text == text)
+    }
+
+    // array of runs in the order they were rendered, colors are hex RGB
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (index, run) in self.runs.iter().enumerate() {
+            let separator = match index {
+                0 => "\n",
+                _ => ",\n",
+            };
+            let _ = write!(
+                json,
+                "{separator}  {{\"page\": {}, \"x\": {}, \"y\": {}, \"width\": {}, \"font\": \"{}\", \"size\": {}, \"color\": \"{}\", \"text\": \"{}\"}}",
+                run.page,
+                number(run.x),
+                number(run.y),
+                number(run.width),
+                escape(&run.font),
+                number(run.size),
+                color(&run.color),
+                escape(&run.text),
+            );
+        }
+        if !self.runs.is_empty() {
+            json.push('\n');
+        }
+        json.push_str("]\n");
+        json
+    }
+}
+
+impl layout::MeasureContext for TextRenderContext {
+    fn style(&self) -> &Style {
+        self.style.as_ref()
+    }
+
+    fn typeset(&mut self, style: &Style, text: &str) -> Result<TextPosition, Error> {
+        let font = style.font().merge(self.style.font());
+        if let Some(name) = font.name()
+            && font.size().is_some()
+        {
+            let position =
+                self.fonts
+                    .typeset(name, text, &font.features().cloned().unwrap_or_default())?;
+            if self.typeset_text(name, &position) != Some(text) {
+                self.typeset
+                    .push((name.to_smolstr(), position.clone(), text.to_string()));
+            }
+            Ok(position)
+        } else {
+            Err(Error::UnknownFont("Font name or size is undefined".into()))
+        }
+    }
+}
+
+impl layout::RenderContext for TextRenderContext {
+    fn debug_frame(&mut self, _content_position: &Offset, _size: &Size) {}
+
+    fn check_page_break(&mut self, offset: Unit, height: Unit, reserve_height: bool) -> bool {
+        self.flow.check_page_break(offset, height, reserve_height)
+    }
+
+    fn release_page_break_reservation(&mut self) {
+        self.flow.release_page_break_reservation();
+    }
+
+    fn new_page(&mut self, options: Option<NewPageOptions>) {
+        self.flow.new_page(options);
+    }
+
+    fn line(&mut self, from: &Offset, to: &Offset, _stroke: &Stroke) {
+        self.flow.line(from, to);
+    }
+
+    fn text(
+        &mut self,
+        content_position: &Offset,
+        style: &Style,
+        text: &TextPosition,
+        position_is_baseline: bool,
+    ) {
+        if text.positions.is_empty() {
+            return;
+        }
+
+        let font = style.font().merge(self.style.font());
+        let (Some(name), Some(font_size)) = (font.name(), font.size()) else {
+            tracing::warn!("Try to typeset text without defined font");
+            return;
+        };
+        let font_scaling = font
+            .scaling()
+            .as_ref()
+            .map(FillPerMille::scaling)
+            .unwrap_or(1.0);
+
+        self.flow
+            .check_page_break(content_position.y, (text.height * font_size).into(), false);
+
+        let (x, mut y) = self.flow.page_point(content_position);
+        if !position_is_baseline {
+            y += pt((text.ascent() * font_size).into());
+        }
+        let text_string = self
+            .typeset_text(name, text)
+            .map(str::to_string)
+            .unwrap_or_default();
+        self.runs.push(TextRun {
+            page: self.flow.page() + 1,
+            x,
+            y,
+            width: *(text.width * font_size * font_scaling) as f32,
+            font: name.to_smolstr(),
+            size: *font_size as f32,
+            color: style.color().cloned().unwrap_or(Rgba::black()),
+            text: text_string,
+        });
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if char.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", char as u32);
+            }
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        Font, MeasureContext, RenderContext, Rgba, StyleBuilder,
+        position::{Offset, Quad, Size},
+        unit::{Mm, Pt},
+    };
+
+    use crate::{TextRenderContext, new_font_cache};
+
+    use super::escape;
+
+    #[test]
+    fn text_runs() {
+        let fonts = new_font_cache();
+
+        let font_bin = include_bytes!("../../tests/Lato-Regular.ttf").as_ref();
+        fonts.add("LatoReg", font_bin).unwrap();
+
+        let mut ctx = TextRenderContext::new(
+            Quad::square(Mm(10.0)),
+            Size::fixed(Mm(210.0), Mm(297.0)),
+            fonts,
+        );
+        let style = StyleBuilder::default()
+            .with_font(Font::new("LatoReg", Pt(12.0), None))
+            .build();
+        let title = ctx.typeset(&style, "Invoice \"A\"").unwrap();
+        let total = ctx.typeset(&style, "Total").unwrap();
+        ctx.text(&Offset::new(Mm(0.0), Mm(0.0)), &style, &title, true);
+        ctx.text(&Offset::new(Mm(0.0), Mm(300.0)), &style, &total, true);
+
+        assert_eq!(ctx.pages(), 2);
+        let run = ctx.run("Invoice \"A\"").unwrap();
+        assert_eq!(
+            (run.page, run.font.as_str(), run.size),
+            (1, "LatoReg", 12.0)
+        );
+        assert!((run.x - 28.35).abs() < 0.01 && (run.y - 28.35).abs() < 0.01);
+        assert_eq!(run.color, Rgba::black());
+        assert_eq!(ctx.run("Total").unwrap().page, 2);
+
+        let json = ctx.to_json();
+        assert!(json.contains("\"text\": \"Invoice \\\"A\\\"\""));
+        assert!(json.contains("\"page\": 2"));
+    }
+
+    #[test]
+    fn escaped() {
+        assert_eq!(escape("a\"b\\c\nd\u{1}"), "a\\\"b\\\\c\\nd\\u0001");
+    }
+}
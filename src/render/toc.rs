@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use layout::{
+    Error, MeasureContext, Style, TextPosition,
+    unit::{Mm, Unit},
+};
+
+// section marked by a heading, pages are numbered from 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct TocEntry {
+    pub title: String,
+    pub level: usize,
+    pub page: usize,
+    // anchor the entry links to
+    pub anchor: String,
+}
+
+// rows of titles and page numbers joined by leaders, nested levels are indented
+pub struct TableOfContents {
+    style: Arc<Style>,
+    indent: Unit,
+    leader: char,
+    entries: Vec<TocEntry>,
+    // typeset title and page number of each entry
+    rows: Vec<(TextPosition, TextPosition)>,
+}
+
+impl TableOfContents {
+    pub fn new(style: impl Into<Arc<Style>>) -> Self {
+        Self {
+            style: style.into(),
+            indent: Mm(5.0).into(),
+            leader: '.',
+            entries: vec![],
+            rows: vec![],
+        }
+    }
+
+    pub fn with_indent(mut self, indent: impl Into<Unit>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    pub fn with_leader(mut self, leader: char) -> Self {
+        self.leader = leader;
+        self
+    }
+
+    pub fn with_entries(mut self, entries: Vec<TocEntry>) -> Self {
+        self.set_entries(entries);
+        self
+    }
+
+    pub(crate) fn set_entries(&mut self, entries: Vec<TocEntry>) {
+        self.entries = entries;
+        self.rows.clear();
+    }
+
+    pub fn entries(&self) -> &[TocEntry] {
+        &self.entries
+    }
+
+    // entries are painted after fonts are completed, so they are typeset ahead, with
+    // the leader; rows are typeset again when entries change
+    pub fn typeset(&mut self, ctx: &mut dyn MeasureContext) -> Result<(), Error> {
+        ctx.typeset(&self.style, &self.leader.to_string())?;
+        self.rows = self
+            .entries
+            .iter()
+            .map(|entry| {
+                Ok((
+                    ctx.typeset(&self.style, &entry.title)?,
+                    ctx.typeset(&self.style, &entry.page.to_string())?,
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(())
+    }
+
+    pub(crate) fn style(&self) -> &Arc<Style> {
+        &self.style
+    }
+
+    pub(crate) fn leader(&self) -> char {
+        self.leader
+    }
+
+    pub(crate) fn indent(&self, level: usize) -> Unit {
+        Mm(Mm::from(self.indent).0 * level.saturating_sub(1) as f64).into()
+    }
+
+    pub(crate) fn rows(&self) -> impl Iterator<Item = (&TocEntry, &TextPosition, &TextPosition)> {
+        self.entries
+            .iter()
+            .zip(self.rows.iter())
+            .map(|(entry, (title, page))| (entry, title, page))
+    }
+
+    // in pt
+    pub(crate) fn row_height(&self, default_style: &Style) -> f32 {
+        let font = self.style.font().merge(default_style.font());
+        font.size().map(|size| *size as f32).unwrap_or(0.0) * 1.5
+    }
+}
@@ -0,0 +1,158 @@
+use layout::position::{Offset, Size};
+
+use super::from_unit;
+
+// Transformation of content around an origin, all in content coordinates
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    origin: Offset,
+    translation: Offset,
+    scale: (f32, f32),
+    rotation: f32,
+}
+
+impl Transform {
+    pub fn around(origin: Offset) -> Self {
+        Self {
+            origin,
+            translation: Offset::zero(),
+            scale: (1.0, 1.0),
+            rotation: 0.0,
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Offset) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale_x: f32, scale_y: f32) -> Self {
+        self.scale = (scale_x, scale_y);
+        self
+    }
+
+    // counterclockwise
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    // scale and rotation around the origin, then translation, as a cm matrix
+    pub(crate) fn matrix<F>(&self, page_point: F) -> [f32; 6]
+    where
+        F: Fn(&Offset) -> (f32, f32),
+    {
+        let (origin_x, origin_y) = page_point(&self.origin);
+        let translate_x = from_unit(self.translation.x).into_pt().0;
+        // content y grows down, page y grows up
+        let translate_y = -from_unit(self.translation.y).into_pt().0;
+
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let a = cos * self.scale.0;
+        let b = sin * self.scale.0;
+        let c = -sin * self.scale.1;
+        let d = cos * self.scale.1;
+
+        [
+            a,
+            b,
+            c,
+            d,
+            origin_x + translate_x - (a * origin_x + c * origin_y),
+            origin_y + translate_y - (b * origin_x + d * origin_y),
+        ]
+    }
+}
+
+// rotation of a sub-layout which keeps it within its box
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuarterTurn {
+    // 90°, counterclockwise, text reads upwards
+    Left,
+    // 270°, text reads downwards, e.g. on a book spine
+    Right,
+}
+
+impl QuarterTurn {
+    // content laid out at the position with width and height of the box swapped
+    // is turned into the box
+    pub(crate) fn transform(&self, content_position: &Offset, size: &Size) -> Transform {
+        let translation = match self {
+            Self::Left => Offset::new(0, size.base_height()),
+            Self::Right => Offset::new(size.base_width(), 0),
+        };
+        let rotation = match self {
+            Self::Left => 90.0,
+            Self::Right => 270.0,
+        };
+        Transform::around(content_position.clone())
+            .with_translation(translation)
+            .with_rotation(rotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use layout::{
+        position::{Offset, Size},
+        unit::Mm,
+    };
+
+    use super::{QuarterTurn, Transform};
+
+    fn apply(matrix: [f32; 6], (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            matrix[0] * x + matrix[2] * y + matrix[4],
+            matrix[1] * x + matrix[3] * y + matrix[5],
+        )
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn matrix() {
+        let origin = Offset::new(Mm(0.0), Mm(0.0));
+        let page_point = |_: &Offset| (100.0, 100.0);
+
+        let matrix = Transform::around(origin.clone())
+            .with_rotation(90.0)
+            .matrix(page_point);
+        assert_near(apply(matrix, (100.0, 100.0)), (100.0, 100.0));
+        assert_near(apply(matrix, (110.0, 100.0)), (100.0, 110.0));
+
+        let matrix = Transform::around(origin.clone())
+            .with_scale(2.0, 0.5)
+            .matrix(page_point);
+        assert_near(apply(matrix, (110.0, 110.0)), (120.0, 105.0));
+
+        let matrix = Transform::around(origin)
+            .with_translation(Offset::new(Mm(10.0), Mm(10.0)))
+            .matrix(page_point);
+        assert_near(apply(matrix, (100.0, 100.0)), (128.34646, 71.65354));
+    }
+    #[test]
+    fn quarter_turn() {
+        let origin = Offset::new(Mm(0.0), Mm(0.0));
+        let size = Size::fixed(Mm(20.0), Mm(10.0));
+        let page_point = |_: &Offset| (100.0, 100.0);
+
+        // top left corner of the turned content goes to the bottom left corner of the box
+        let matrix = QuarterTurn::Left
+            .transform(&origin, &size)
+            .matrix(page_point);
+        assert_near(apply(matrix, (100.0, 100.0)), (100.0, 71.65354));
+        assert_near(apply(matrix, (128.34646, 100.0)), (100.0, 100.0));
+
+        // and to the top right one
+        let matrix = QuarterTurn::Right
+            .transform(&origin, &size)
+            .matrix(page_point);
+        assert_near(apply(matrix, (100.0, 100.0)), (156.69292, 100.0));
+        assert_near(apply(matrix, (128.34646, 100.0)), (156.69292, 71.65354));
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use layout::{Style, TextPosition, position::Size};
+
+use super::Image;
+
+pub(crate) enum WatermarkContent {
+    Text {
+        text: String,
+        style: Arc<Style>,
+        // typeset when the watermark is set on the render context
+        position: Option<TextPosition>,
+    },
+    Image {
+        image: Image,
+        size: Size,
+    },
+}
+
+// stamped centered on every page, below the page content
+pub struct Watermark {
+    content: WatermarkContent,
+    rotation: f32,
+    opacity: f32,
+}
+
+impl Watermark {
+    pub fn text(text: impl Into<String>, style: impl Into<Arc<Style>>) -> Self {
+        Self::new(WatermarkContent::Text {
+            text: text.into(),
+            style: style.into(),
+            position: None,
+        })
+    }
+
+    pub fn image(image: Image, size: Size) -> Self {
+        Self::new(WatermarkContent::Image { image, size })
+    }
+
+    fn new(content: WatermarkContent) -> Self {
+        Self {
+            content,
+            rotation: 0.0,
+            opacity: 1.0,
+        }
+    }
+
+    // degrees, counter-clockwise
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    pub(crate) fn content(&self) -> &WatermarkContent {
+        &self.content
+    }
+
+    pub(crate) fn content_mut(&mut self) -> &mut WatermarkContent {
+        &mut self.content
+    }
+
+    pub(crate) fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub(crate) fn opacity(&self) -> f32 {
+        self.opacity
+    }
+}
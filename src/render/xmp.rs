@@ -0,0 +1,264 @@
+use layout::Error;
+use printpdf::lopdf::{Dictionary, Document, Object, Stream};
+
+use super::resources::catalog;
+
+struct XmpProperty {
+    prefix: String,
+    namespace: String,
+    name: String,
+    value: String,
+}
+
+// metadata packet of the document catalog; dublin core, pdf and xmp basic properties not
+// set are taken from the document info, so both say the same
+#[derive(Default)]
+pub struct XmpMetadata {
+    title: Option<String>,
+    creators: Vec<String>,
+    description: Option<String>,
+    properties: Vec<XmpProperty>,
+}
+
+impl XmpMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // dc:title, the title of the document info otherwise
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    // dc:creator, creators are ordered, the author of the document info otherwise
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.creators.push(creator.into());
+        self
+    }
+
+    // dc:description, the subject of the document info otherwise
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    // simple property of a custom schema, properties of the same namespace share the prefix
+    pub fn with_property(
+        mut self,
+        prefix: impl Into<String>,
+        namespace: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.properties.push(XmpProperty {
+            prefix: prefix.into(),
+            namespace: namespace.into(),
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    // dc:title the packet gets, accessible documents need one
+    pub(crate) fn has_title(&self, document: &Document) -> bool {
+        self.title.as_ref().is_some_and(|title| !title.is_empty())
+            || info_entry(&document_info(document), b"Title").is_some()
+    }
+
+    pub(crate) fn packet(&self, info: &Dictionary) -> String {
+        let info_text = |key: &[u8]| info_entry(info, key);
+        let info_date = |key: &[u8]| info_text(key).and_then(|date| xmp_date(&date));
+
+        let mut dublin_core = String::new();
+        if let Some(title) = self.title.clone().or_else(|| info_text(b"Title")) {
+            dublin_core += &format!(
+                "<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>",
+                escape(&title)
+            );
+        }
+        let creators = match self.creators.is_empty() {
+            true => info_text(b"Author").into_iter().collect(),
+            false => self.creators.clone(),
+        };
+        if !creators.is_empty() {
+            dublin_core += "<dc:creator><rdf:Seq>";
+            for creator in creators {
+                dublin_core += &format!("<rdf:li>{}</rdf:li>", escape(&creator));
+            }
+            dublin_core += "</rdf:Seq></dc:creator>";
+        }
+        if let Some(description) = self.description.clone().or_else(|| info_text(b"Subject")) {
+            dublin_core += &format!(
+                "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+                escape(&description)
+            );
+        }
+        dublin_core += "<dc:format>application/pdf</dc:format>";
+
+        let mut descriptions = vec![(
+            "dc".to_string(),
+            "http://purl.org/dc/elements/1.1/".to_string(),
+            dublin_core,
+        )];
+        let pdf = [("Producer", b"Producer"), ("Keywords", b"Keywords")]
+            .into_iter()
+            .filter_map(|(name, key)| Some((name, info_text(key)?)))
+            .collect::<Vec<_>>();
+        let basic = [
+            ("CreatorTool", info_text(b"Creator")),
+            ("CreateDate", info_date(b"CreationDate")),
+            ("ModifyDate", info_date(b"ModDate")),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect::<Vec<_>>();
+        for (prefix, namespace, values) in [
+            ("pdf", "http://ns.adobe.com/pdf/1.3/", pdf),
+            ("xmp", "http://ns.adobe.com/xap/1.0/", basic),
+        ] {
+            if !values.is_empty() {
+                let properties = values
+                    .iter()
+                    .map(|(name, value)| property(prefix, name, value))
+                    .collect();
+                descriptions.push((prefix.to_string(), namespace.to_string(), properties));
+            }
+        }
+        for custom in self.properties.iter() {
+            let property = property(&custom.prefix, &custom.name, &custom.value);
+            match descriptions
+                .iter_mut()
+                .find(|(_, namespace, _)| *namespace == custom.namespace)
+            {
+                Some((_, _, properties)) => *properties += &property,
+                None => {
+                    descriptions.push((custom.prefix.clone(), custom.namespace.clone(), property))
+                }
+            }
+        }
+
+        let mut packet = String::from(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+        );
+        for (prefix, namespace, properties) in descriptions {
+            packet += &format!(
+                "<rdf:Description rdf:about=\"\" xmlns:{}=\"{}\">{}</rdf:Description>\n",
+                prefix,
+                escape(&namespace),
+                properties
+            );
+        }
+        packet += "</rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>";
+        packet
+    }
+
+    // replaces the packet printpdf writes for some conformances
+    pub(crate) fn write(&self, document: &mut Document) -> Result<(), Error> {
+        let info = document_info(document);
+
+        let mut dictionary = Dictionary::new();
+        dictionary.set("Type", Object::Name(b"Metadata".to_vec()));
+        dictionary.set("Subtype", Object::Name(b"XML".to_vec()));
+        // readers not aware of pdf find the packet only uncompressed
+        let stream =
+            Stream::new(dictionary, self.packet(&info).into_bytes()).with_compression(false);
+        let metadata_id = document.add_object(stream);
+        catalog(document)?.set("Metadata", Object::Reference(metadata_id));
+        Ok(())
+    }
+}
+
+fn document_info(document: &Document) -> Dictionary {
+    document
+        .trailer
+        .get(b"Info")
+        .and_then(Object::as_reference)
+        .and_then(|id| document.get_dictionary(id))
+        .cloned()
+        .unwrap_or_default()
+}
+
+// text of the document info entry, empty ones are not set
+fn info_entry(info: &Dictionary, key: &[u8]) -> Option<String> {
+    info.get(key)
+        .and_then(Object::as_str)
+        .map(|text| String::from_utf8_lossy(text).into_owned())
+        .ok()
+        .filter(|text| !text.is_empty())
+}
+
+fn property(prefix: &str, name: &str, value: &str) -> String {
+    format!("<{prefix}:{name}>{}</{prefix}:{name}>", escape(value))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// D:YYYYMMDDHHmmSS+HH'mm' of the document info to YYYY-MM-DDTHH:mm:SS+HH:mm
+fn xmp_date(date: &str) -> Option<String> {
+    let date = date.strip_prefix("D:")?;
+    let digits = date.get(..14)?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let zone = match date.get(14..) {
+        None | Some("") | Some("Z") => "Z".to_string(),
+        Some(zone) => {
+            let zone = zone.replace('\'', "");
+            format!("{}:{}", zone.get(..3)?, zone.get(3..5).unwrap_or("00"))
+        }
+    };
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        zone
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::lopdf::{Dictionary, Object, StringFormat};
+
+    use super::{XmpMetadata, xmp_date};
+
+    #[test]
+    fn packet() {
+        assert_eq!(
+            xmp_date("D:20240105093000+01'00'").as_deref(),
+            Some("2024-01-05T09:30:00+01:00")
+        );
+        assert_eq!(
+            xmp_date("D:20240105093000").as_deref(),
+            Some("2024-01-05T09:30:00Z")
+        );
+
+        let mut info = Dictionary::new();
+        let text = |text: &str| Object::String(text.as_bytes().to_vec(), StringFormat::Literal);
+        info.set("Title", text("Invoice 42"));
+        info.set("Author", text("Jan Novák"));
+        info.set("CreationDate", text("D:20240105093000+01'00'"));
+
+        let packet = XmpMetadata::new()
+            .with_creator("Billing & Co")
+            .with_property("dms", "http://example.com/dms/1.0/", "Category", "invoice")
+            .packet(&info);
+        assert!(packet.contains("<rdf:li xml:lang=\"x-default\">Invoice 42</rdf:li>"));
+        assert!(packet.contains("<rdf:li>Billing &amp; Co</rdf:li>"));
+        assert!(!packet.contains("Jan Novák"));
+        assert!(packet.contains("<xmp:CreateDate>2024-01-05T09:30:00+01:00</xmp:CreateDate>"));
+        assert!(packet.contains("xmlns:dms=\"http://example.com/dms/1.0/\""));
+        assert!(packet.contains("<dms:Category>invoice</dms:Category>"));
+    }
+}